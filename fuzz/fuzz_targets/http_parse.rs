@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use oxide_core::http::HttpRequest;
+
+// Anything a client can put on the wire must parse or be rejected, never panic.
+fuzz_target!(|data: &[u8]| {
+    if let Some(request) = HttpRequest::parse(data) {
+        let _ = request.content_type();
+        let _ = request.method.to_string();
+    }
+});
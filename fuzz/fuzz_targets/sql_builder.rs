@@ -0,0 +1,119 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use oxide_orm::{model, prelude::*};
+use sqlparser::{dialect::PostgreSqlDialect, parser::Parser};
+
+#[model]
+pub struct User {
+    pub id: i32,
+    pub name: String,
+    pub email: String,
+    pub age: i32,
+    pub active: bool,
+}
+
+#[derive(Debug, Arbitrary)]
+enum Value {
+    Id(i32),
+    Name(String),
+    Email(String),
+    Age(i32),
+    Active(bool),
+}
+
+#[derive(Debug, Arbitrary)]
+enum Node {
+    And(Value),
+    Or(Value),
+    AndGroup(Vec<Node>),
+    OrGroup(Vec<Node>),
+}
+
+#[derive(Debug, Arbitrary)]
+enum Statement {
+    Select(Vec<Node>),
+    Insert(Value, Vec<Value>),
+    Update(i32, Value, Vec<Value>),
+}
+
+type Query = OxideQueryBuilder<User, UserColumns>;
+
+fn apply(query: Query, node: Node, depth: usize) -> Query {
+    let columns = User::columns();
+    match node {
+        Node::And(value) => match value {
+            Value::Id(v) => query.and_where(columns.id, v),
+            Value::Name(v) => query.and_where(columns.name, v),
+            Value::Email(v) => query.and_where(columns.email, v),
+            Value::Age(v) => query.and_where(columns.age, v),
+            Value::Active(v) => query.and_where(columns.active, v),
+        },
+        Node::Or(value) => match value {
+            Value::Id(v) => query.or_where(columns.id, v),
+            Value::Name(v) => query.or_where(columns.name, v),
+            Value::Email(v) => query.or_where(columns.email, v),
+            Value::Age(v) => query.or_where(columns.age, v),
+            Value::Active(v) => query.or_where(columns.active, v),
+        },
+        // Bounded so deeply nested inputs don't overflow the stack in the harness itself.
+        Node::AndGroup(_) | Node::OrGroup(_) if depth > 16 => query,
+        Node::AndGroup(nodes) => query.and_group(|group| apply_all(group, nodes, depth + 1)),
+        Node::OrGroup(nodes) => query.or_group(|group| apply_all(group, nodes, depth + 1)),
+    }
+}
+
+fn apply_all(query: Query, nodes: Vec<Node>, depth: usize) -> Query {
+    nodes
+        .into_iter()
+        .fold(query, |query, node| apply(query, node, depth))
+}
+
+fn insert(
+    query: OxideInsertQueryBuilder<User, UserColumns>,
+    value: Value,
+) -> OxideInsertQueryBuilder<User, UserColumns> {
+    let columns = User::columns();
+    match value {
+        Value::Id(v) => query.value(columns.id, v),
+        Value::Name(v) => query.value(columns.name, v),
+        Value::Email(v) => query.value(columns.email, v),
+        Value::Age(v) => query.value(columns.age, v),
+        Value::Active(v) => query.value(columns.active, v),
+    }
+}
+
+fn update(
+    query: OxideUpdateQueryBuilder<User, UserColumns>,
+    value: Value,
+) -> OxideUpdateQueryBuilder<User, UserColumns> {
+    let columns = User::columns();
+    match value {
+        Value::Id(v) => query.set(columns.id, v),
+        Value::Name(v) => query.set(columns.name, v),
+        Value::Email(v) => query.set(columns.email, v),
+        Value::Age(v) => query.set(columns.age, v),
+        Value::Active(v) => query.set(columns.active, v),
+    }
+}
+
+// Whatever the values and condition tree, the builders must emit exactly one valid statement.
+fuzz_target!(|statement: Statement| {
+    let sql = match statement {
+        Statement::Select(nodes) => apply_all(User::query(), nodes, 0).build(),
+        Statement::Insert(first, rest) => rest
+            .into_iter()
+            .fold(insert(User::insert(), first), insert)
+            .build(),
+        Statement::Update(id, first, rest) => rest
+            .into_iter()
+            .fold(update(User::update(id), first), update)
+            .build(),
+    };
+
+    match Parser::parse_sql(&PostgreSqlDialect {}, &sql) {
+        Ok(statements) => assert_eq!(statements.len(), 1, "multiple statements: {}", sql),
+        Err(e) => panic!("invalid SQL ({}): {}", e, sql),
+    }
+});
@@ -0,0 +1,59 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use oxide_core::http::HttpRequest;
+
+const MINIMAL: &[u8] = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+const BROWSER: &[u8] = b"GET /users/42?include=posts&sort=desc HTTP/1.1\r\n\
+Host: localhost:8080\r\n\
+User-Agent: Mozilla/5.0 (X11; Linux x86_64; rv:131.0) Gecko/20100101 Firefox/131.0\r\n\
+Accept: text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8\r\n\
+Accept-Language: en-GB,en;q=0.5\r\n\
+Accept-Encoding: gzip, deflate, br\r\n\
+Connection: keep-alive\r\n\
+Cookie: session=abc123; theme=dark; locale=en\r\n\
+Cache-Control: no-cache\r\n\r\n";
+
+fn json_post() -> Vec<u8> {
+    let body = serde_json::json!({
+        "name": "Alice",
+        "email": "alice@example.com",
+        "tags": (0..64).map(|i| format!("tag-{}", i)).collect::<Vec<_>>(),
+    })
+    .to_string();
+    format!(
+        "POST /users HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    )
+    .into_bytes()
+}
+
+/// A form upload, where the body dwarfs the head it has to be split from.
+fn upload() -> Vec<u8> {
+    let body = "field=value&".repeat(64 * 1024 / 12);
+    format!(
+        "POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/x-www-form-urlencoded\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    )
+    .into_bytes()
+}
+
+fn parse(c: &mut Criterion) {
+    let json_post = json_post();
+    let upload = upload();
+    let mut group = c.benchmark_group("parser/parse");
+    for (name, request) in [
+        ("minimal", MINIMAL),
+        ("browser", BROWSER),
+        ("json_post", json_post.as_slice()),
+        ("upload", upload.as_slice()),
+    ] {
+        group.throughput(Throughput::Bytes(request.len() as u64));
+        group.bench_function(name, |b| b.iter(|| HttpRequest::parse(black_box(request))));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,46 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use oxide_core::http::BufferBuilder;
+
+fn build(c: &mut Criterion) {
+    let small_json = r#"{"id":42,"name":"Alice","active":true}"#;
+    // Large enough to cross the compression threshold.
+    let large_json = serde_json::to_string(
+        &(0..200)
+            .map(|i| serde_json::json!({ "id": i, "name": format!("user-{}", i) }))
+            .collect::<Vec<_>>(),
+    )
+    .unwrap();
+
+    let mut group = c.benchmark_group("response/build");
+    group.bench_function("text", |b| {
+        b.iter(|| BufferBuilder::ok().text(black_box("Hello, World!")).build())
+    });
+    group.bench_function("json_small", |b| {
+        b.iter(|| BufferBuilder::ok().json(black_box(small_json)).build())
+    });
+    group.bench_function("json_large", |b| {
+        b.iter(|| BufferBuilder::ok().json(black_box(&large_json)).build())
+    });
+    group.bench_function("json_large_gzip", |b| {
+        b.iter(|| {
+            BufferBuilder::ok()
+                .header("Accept-Encoding", "gzip")
+                .json(black_box(&large_json))
+                .build()
+        })
+    });
+    group.bench_function("headers", |b| {
+        b.iter(|| {
+            BufferBuilder::ok()
+                .header("Cache-Control", "no-store")
+                .header("X-Request-Id", "0b6f5c1e")
+                .header("Vary", "Accept-Encoding")
+                .text(black_box("ok"))
+                .build()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, build);
+criterion_main!(benches);
@@ -0,0 +1,44 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use oxide_core::{
+    http::{AsyncResponse, Context, OxideRes, OxideResponse, RouteManager},
+    Environment, HttpMethod,
+};
+
+fn noop(_ctx: &Context) -> AsyncResponse<'_> {
+    Box::pin(async { OxideResponse::text(OxideRes::Success, String::new()) })
+}
+
+/// A router with `count` parameterised routes spread over a handful of resources.
+fn router(count: usize) -> RouteManager {
+    let mut router = RouteManager::new();
+    for i in 0..count {
+        router.get(&format!("/resource{}/items/:id", i), noop);
+    }
+    router
+}
+
+fn find_route(c: &mut Criterion) {
+    // Keeps route registration logging out of the measurements.
+    Environment::Production.activate();
+
+    let mut group = c.benchmark_group("router/find_route");
+    for count in [1, 100, 10_000] {
+        let router = router(count);
+        let first = "/resource0/items/42".to_string();
+        let last = format!("/resource{}/items/42", count - 1);
+
+        group.bench_with_input(BenchmarkId::new("first", count), &first, |b, path| {
+            b.iter(|| router.find_route(black_box(path), HttpMethod::Get))
+        });
+        group.bench_with_input(BenchmarkId::new("last", count), &last, |b, path| {
+            b.iter(|| router.find_route(black_box(path), HttpMethod::Get))
+        });
+        group.bench_with_input(BenchmarkId::new("miss", count), "/missing", |b, path| {
+            b.iter(|| router.find_route(black_box(path), HttpMethod::Get))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, find_route);
+criterion_main!(benches);
@@ -0,0 +1,370 @@
+//! Circuit breakers for calls to downstream dependencies.
+//!
+//! A [`CircuitBreaker`] watches the outcome of the last calls to one dependency, such as a
+//! payment provider's API or a replica database. Once enough of them fail it opens and
+//! rejects calls straight away instead of letting each one wait for a timeout. After a
+//! cool-down it lets a few trial calls through (half-open), closing again if they succeed.
+//!
+//! Breakers registered with [`Server::with_circuit_breaker`](crate::Server::with_circuit_breaker)
+//! are available to handlers as `ctx.circuit_breaker(name)`. Routes declared with
+//! `.depends_on(name)` answer 503 with `Retry-After` while that breaker is open, without
+//! running their middleware or handler:
+//!
+//! ```rust,ignore
+//! server.with_circuit_breaker(CircuitBreaker::new("payments").with_open_duration(Duration::from_secs(10)));
+//! server.router.post("/checkout", checkout).depends_on("payments");
+//!
+//! #[handler]
+//! async fn checkout(ctx: &Context) -> OxideResponse {
+//!     let payments = ctx.circuit_breaker("payments").unwrap();
+//!     match payments.call(charge(&ctx.request)).await {
+//!         Ok(receipt) => OxideResponse::json(OxideRes::Created, receipt),
+//!         Err(e) => OxideResponse::error(e),
+//!     }
+//! }
+//! ```
+use std::{
+    collections::VecDeque,
+    fmt,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::{logger::LogLevel, Error, Logger};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Calls go through and their outcomes are counted.
+    Closed,
+    /// Calls are rejected until the open duration has passed.
+    Open,
+    /// A limited number of trial calls go through to decide whether to close again.
+    HalfOpen,
+}
+
+impl fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+            CircuitState::HalfOpen => "half-open",
+        })
+    }
+}
+
+/// Point-in-time view of a [`CircuitBreaker`], suitable for metrics endpoints.
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitBreakerStats {
+    pub name: String,
+    pub state: CircuitState,
+    /// Share of the calls in the window that failed, between 0 and 1.
+    pub failure_rate: f64,
+    pub calls: u64,
+    pub failures: u64,
+    /// Calls turned away while the breaker was open or out of trial calls.
+    pub rejected: u64,
+    /// How many times the breaker has opened.
+    pub opened: u64,
+}
+
+/// Why a call through a breaker failed: the breaker was open, or the call itself failed.
+#[derive(Debug)]
+pub enum CircuitError<E> {
+    Open { name: String, retry_after: Duration },
+    Failed(E),
+}
+
+impl<E: fmt::Display> fmt::Display for CircuitError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitError::Open { name, .. } => write!(f, "{} is unavailable", name),
+            CircuitError::Failed(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: Into<Error>> From<CircuitError<E>> for Error {
+    fn from(error: CircuitError<E>) -> Self {
+        match error {
+            CircuitError::Open { name, .. } => {
+                Error::ServiceUnavailable(format!("{} is unavailable", name))
+            }
+            CircuitError::Failed(e) => e.into(),
+        }
+    }
+}
+
+/// A breaker for one dependency. Clones share state, so the same breaker can be registered
+/// with the server and kept elsewhere.
+///
+/// While closed it remembers the outcome of the last `window` calls and opens when at least
+/// `minimum_calls` of them were made and the share that failed reaches the failure rate
+/// threshold. The defaults open when half of the last 20 calls failed, given at least 10,
+/// stay open for 30 seconds, then let 3 trial calls through.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    name: String,
+    failure_rate_threshold: f64,
+    minimum_calls: usize,
+    window: usize,
+    open_duration: Duration,
+    half_open_calls: u32,
+    shared: Arc<Shared>,
+    logger: Logger,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    state: Mutex<State>,
+    calls: AtomicU64,
+    failures: AtomicU64,
+    rejected: AtomicU64,
+    opened: AtomicU64,
+}
+
+#[derive(Debug)]
+struct State {
+    state: CircuitState,
+    /// `true` for each failed call in the window, oldest first. Only kept while closed.
+    outcomes: VecDeque<bool>,
+    opened_at: Option<Instant>,
+    /// Trial calls let through and how many of them succeeded, while half-open.
+    trials: u32,
+    trial_successes: u32,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            state: CircuitState::Closed,
+            outcomes: VecDeque::new(),
+            opened_at: None,
+            trials: 0,
+            trial_successes: 0,
+        }
+    }
+}
+
+impl State {
+    fn failure_rate(&self) -> f64 {
+        if self.outcomes.is_empty() {
+            return 0.0;
+        }
+        self.outcomes.iter().filter(|failed| **failed).count() as f64 / self.outcomes.len() as f64
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            failure_rate_threshold: 0.5,
+            minimum_calls: 10,
+            window: 20,
+            open_duration: Duration::from_secs(30),
+            half_open_calls: 3,
+            shared: Arc::new(Shared::default()),
+            logger: Logger::for_target(module_path!()),
+        }
+    }
+
+    /// Share of failed calls, between 0 and 1, at which the breaker opens.
+    pub fn with_failure_rate_threshold(mut self, threshold: f64) -> Self {
+        self.failure_rate_threshold = threshold.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Calls needed in the window before the failure rate is acted on.
+    pub fn with_minimum_calls(mut self, calls: usize) -> Self {
+        self.minimum_calls = calls.max(1);
+        self
+    }
+
+    /// How many of the most recent calls the failure rate is measured over.
+    pub fn with_window(mut self, calls: usize) -> Self {
+        self.window = calls.max(1);
+        self
+    }
+
+    /// How long the breaker stays open before letting trial calls through.
+    pub fn with_open_duration(mut self, duration: Duration) -> Self {
+        self.open_duration = duration;
+        self
+    }
+
+    /// Trial calls let through while half-open; all must succeed for the breaker to close.
+    pub fn with_half_open_calls(mut self, calls: u32) -> Self {
+        self.half_open_calls = calls.max(1);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.lock().state
+    }
+
+    /// How long until an open breaker lets trial calls through, or `None` unless it's open.
+    pub fn retry_after(&self) -> Option<Duration> {
+        let state = self.lock();
+        match (state.state, state.opened_at) {
+            (CircuitState::Open, Some(opened_at)) => {
+                Some(self.open_duration.saturating_sub(opened_at.elapsed()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Runs `call` unless the breaker is open, counting its outcome. A call dropped before
+    /// finishing counts as failed.
+    pub async fn call<T, E, F>(&self, call: F) -> Result<T, CircuitError<E>>
+    where
+        F: Future<Output = Result<T, E>>,
+    {
+        if !self.try_acquire() {
+            return Err(CircuitError::Open {
+                name: self.name.clone(),
+                retry_after: self.retry_after().unwrap_or_default(),
+            });
+        }
+        let pending = Pending(self);
+        let result = call.await;
+        std::mem::forget(pending);
+        self.record(result.is_err());
+        result.map_err(CircuitError::Failed)
+    }
+
+    /// Whether a call may go through now, taking one of the trial calls when half-open.
+    /// Every `true` must be followed by [`record_success`](Self::record_success) or
+    /// [`record_failure`](Self::record_failure); [`call`](Self::call) does both.
+    pub fn try_acquire(&self) -> bool {
+        let mut state = self.lock();
+        let allowed = match state.state {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen if state.trials < self.half_open_calls => {
+                state.trials += 1;
+                true
+            }
+            CircuitState::HalfOpen => false,
+        };
+        if !allowed {
+            self.shared.rejected.fetch_add(1, Ordering::Relaxed);
+        }
+        allowed
+    }
+
+    pub fn record_success(&self) {
+        self.record(false);
+    }
+
+    pub fn record_failure(&self) {
+        self.record(true);
+    }
+
+    fn record(&self, failed: bool) {
+        self.shared.calls.fetch_add(1, Ordering::Relaxed);
+        if failed {
+            self.shared.failures.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut state = self.lock();
+        match state.state {
+            CircuitState::Closed => {
+                state.outcomes.push_back(failed);
+                while state.outcomes.len() > self.window {
+                    state.outcomes.pop_front();
+                }
+                let (failure_rate, calls) = (state.failure_rate(), state.outcomes.len());
+                if calls >= self.minimum_calls && failure_rate >= self.failure_rate_threshold {
+                    let reason = format!(
+                        "{:.0}% of the last {} calls failed",
+                        failure_rate * 100.0,
+                        calls
+                    );
+                    self.open(&mut state, &reason);
+                }
+            }
+            CircuitState::HalfOpen if failed => self.open(&mut state, "a trial call failed"),
+            CircuitState::HalfOpen => {
+                state.trial_successes += 1;
+                if state.trial_successes >= self.half_open_calls {
+                    state.state = CircuitState::Closed;
+                    state.opened_at = None;
+                    self.logger.log(
+                        LogLevel::Info,
+                        &format!("Circuit breaker {} closed", self.name),
+                    );
+                }
+            }
+            // Started before the breaker opened; the outcome no longer matters.
+            CircuitState::Open => {}
+        }
+    }
+
+    fn open(&self, state: &mut State, reason: &str) {
+        state.state = CircuitState::Open;
+        state.opened_at = Some(Instant::now());
+        state.outcomes.clear();
+        self.shared.opened.fetch_add(1, Ordering::Relaxed);
+        self.logger.log(
+            LogLevel::Warning,
+            &format!(
+                "Circuit breaker {} opened for {}s: {}",
+                self.name,
+                self.open_duration.as_secs(),
+                reason
+            ),
+        );
+    }
+
+    /// The state, moving an open breaker whose open duration has passed to half-open.
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        let mut state = self
+            .shared
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if state.state == CircuitState::Open
+            && state
+                .opened_at
+                .is_some_and(|opened_at| opened_at.elapsed() >= self.open_duration)
+        {
+            state.state = CircuitState::HalfOpen;
+            state.trials = 0;
+            state.trial_successes = 0;
+        }
+        state
+    }
+
+    pub fn stats(&self) -> CircuitBreakerStats {
+        let state = self.lock();
+        CircuitBreakerStats {
+            name: self.name.clone(),
+            state: state.state,
+            failure_rate: state.failure_rate(),
+            calls: self.shared.calls.load(Ordering::Relaxed),
+            failures: self.shared.failures.load(Ordering::Relaxed),
+            rejected: self.shared.rejected.load(Ordering::Relaxed),
+            opened: self.shared.opened.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Records a failure for a call that is dropped before it finishes, so a cancelled trial
+/// call doesn't leave a half-open breaker waiting forever.
+struct Pending<'a>(&'a CircuitBreaker);
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        self.0.record_failure();
+    }
+}
@@ -0,0 +1,107 @@
+use once_cell::sync::Lazy;
+use std::{
+    env, fmt,
+    str::FromStr,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+static CURRENT: Lazy<AtomicU8> = Lazy::new(|| {
+    let value = env::var("OXIDE_ENV").or_else(|_| env::var("ENV")).ok();
+    let environment: Environment = match value.as_deref().map(str::trim) {
+        None | Some("") => Environment::default(),
+        Some(value) => value.parse().unwrap_or_else(|_| {
+            // The logger reads the environment, so it can't report this.
+            eprintln!(
+                "Unrecognized environment '{}', falling back to development; use development, test or production",
+                value
+            );
+            Environment::default()
+        }),
+    };
+    AtomicU8::new(environment as u8)
+});
+
+/// `OXIDE_ENV` when it is set to something that isn't an environment. The legacy `ENV` is
+/// left out, as shells use it for other things.
+static UNRECOGNIZED: Lazy<Option<String>> = Lazy::new(|| {
+    env::var("OXIDE_ENV")
+        .ok()
+        .filter(|value| !value.trim().is_empty() && value.parse::<Environment>().is_err())
+});
+
+/// Deployment profile, selected with `OXIDE_ENV` (`development`, `test` or `production`).
+/// Unset means development; any other value stops the server from starting.
+///
+/// Development and test get pretty logs and detailed error bodies, production is terse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Environment {
+    #[default]
+    Development = 0,
+    Test = 1,
+    Production = 2,
+}
+
+impl Environment {
+    /// The environment of the running process, read once from `OXIDE_ENV`
+    /// (falling back to the legacy `ENV` variable).
+    pub fn current() -> Self {
+        match CURRENT.load(Ordering::Relaxed) {
+            1 => Environment::Test,
+            2 => Environment::Production,
+            _ => Environment::Development,
+        }
+    }
+
+    /// The value of `OXIDE_ENV` if it names no environment. [`Config::validate`] fails with
+    /// it, so a misspelt production deploy doesn't start in development.
+    ///
+    /// [`Config::validate`]: crate::config::Config::validate
+    pub fn unrecognized() -> Option<&'static str> {
+        UNRECOGNIZED.as_deref()
+    }
+
+    /// Makes this the process-wide environment used by the logging and error layers.
+    /// Called by `Server::new` with the environment of its `Config`.
+    pub fn activate(self) {
+        CURRENT.store(self as u8, Ordering::Relaxed);
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Environment::Development => "development",
+            Environment::Test => "test",
+            Environment::Production => "production",
+        }
+    }
+
+    pub fn is_development(&self) -> bool {
+        *self == Environment::Development
+    }
+
+    pub fn is_test(&self) -> bool {
+        *self == Environment::Test
+    }
+
+    pub fn is_production(&self) -> bool {
+        *self == Environment::Production
+    }
+}
+
+impl FromStr for Environment {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "dev" | "development" => Ok(Environment::Development),
+            "test" => Ok(Environment::Test),
+            "prod" | "production" => Ok(Environment::Production),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
@@ -0,0 +1,134 @@
+use serde::de::DeserializeOwned;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use super::Environment;
+use crate::Error;
+
+/// Layered TOML configuration: `oxide.toml` overlaid with `oxide.<environment>.toml`,
+/// with environment variables taking precedence over both.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigFile {
+    table: toml::Table,
+    sources: Vec<PathBuf>,
+}
+
+impl ConfigFile {
+    pub fn load(dir: impl AsRef<Path>, environment: Environment) -> Result<Self, Error> {
+        let mut file = Self::default();
+
+        for name in [
+            "oxide.toml".to_string(),
+            format!("oxide.{}.toml", environment),
+        ] {
+            let path = dir.as_ref().join(name);
+            if !path.exists() {
+                continue;
+            }
+
+            let contents = fs::read_to_string(&path)?;
+            let table: toml::Table = contents
+                .parse()
+                .map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?;
+            merge(&mut file.table, table);
+            file.sources.push(path);
+        }
+
+        Ok(file)
+    }
+
+    /// Files that were found and merged, in the order they were applied.
+    pub fn sources(&self) -> &[PathBuf] {
+        &self.sources
+    }
+
+    pub fn table(&self) -> &toml::Table {
+        &self.table
+    }
+
+    /// Reads `key` from the files, returning `Ok(None)` if it isn't set.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, Error> {
+        match self.table.get(key) {
+            Some(value) => value.clone().try_into().map(Some).map_err(|e| {
+                Error::Config(format!(
+                    "Invalid value for '{}': {}",
+                    key,
+                    e.to_string().trim()
+                ))
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// Deserializes the `[name]` table, overlaid with `NAME_*` environment variables
+    /// (e.g. `STRIPE_API_KEY` sets `api_key` in the `stripe` section).
+    pub fn section<T: DeserializeOwned>(&self, name: &str) -> Result<T, Error> {
+        let mut table = match self.table.get(name) {
+            Some(toml::Value::Table(table)) => table.clone(),
+            Some(_) => {
+                return Err(Error::Config(format!(
+                    "Config section '{}' must be a table",
+                    name
+                )))
+            }
+            None => toml::Table::new(),
+        };
+
+        let prefix = format!("{}_", name.to_uppercase());
+        for (key, value) in env::vars() {
+            if let Some(field) = key.strip_prefix(&prefix) {
+                table.insert(field.to_lowercase(), env_value(value));
+            }
+        }
+
+        toml::Value::Table(table).try_into().map_err(|e| {
+            Error::Config(format!(
+                "Invalid config section '{}': {}",
+                name,
+                e.to_string().trim()
+            ))
+        })
+    }
+
+    /// Reads a value from the `env_key` environment variable, falling back to `key` in the files.
+    pub fn layered<T>(&self, env_key: &str, key: &str) -> Result<Option<T>, Error>
+    where
+        T: DeserializeOwned + FromStr,
+    {
+        match env::var(env_key) {
+            Ok(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| Error::Config(format!("Invalid value for {}: '{}'", env_key, value))),
+            Err(_) => self.get(key),
+        }
+    }
+}
+
+/// Environment variables are untyped, so numbers and booleans are recognised before
+/// falling back to a string.
+fn env_value(value: String) -> toml::Value {
+    if let Ok(int) = value.parse::<i64>() {
+        toml::Value::Integer(int)
+    } else if let Ok(float) = value.parse::<f64>() {
+        toml::Value::Float(float)
+    } else if let Ok(boolean) = value.parse::<bool>() {
+        toml::Value::Boolean(boolean)
+    } else {
+        toml::Value::String(value)
+    }
+}
+
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
@@ -0,0 +1,297 @@
+mod environment;
+mod file;
+
+use crate::http::CorsConfig;
+use crate::logger::{LogLevel, Logger};
+use crate::Error;
+use serde::de::DeserializeOwned;
+use std::{env, path::PathBuf, time::Duration};
+
+pub use environment::Environment;
+pub use file::ConfigFile;
+
+#[derive(Clone)]
+pub struct Config {
+    pub host: String,
+    pub port: u16,
+    pub max_request_size: usize,
+    pub max_buffered_bytes: Option<usize>,
+    pub cors: Option<CorsConfig>,
+    pub verbose_logging: bool,
+    pub record_requests: bool,
+    pub drain_timeout: Duration,
+    pub environment: Environment,
+    file: ConfigFile,
+    sections: Vec<(String, SectionValidator)>,
+}
+
+type SectionValidator = fn(&ConfigFile, &str) -> Result<(), Error>;
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 8080,
+            max_request_size: 1024 * 1024,
+            max_buffered_bytes: None,
+            cors: None,
+            verbose_logging: false,
+            record_requests: false,
+            drain_timeout: Duration::from_secs(30),
+            environment: Environment::current(),
+            file: ConfigFile::default(),
+            sections: vec![],
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ConfigBuilder {
+    host: Option<String>,
+    port: Option<u16>,
+    max_request_size: Option<usize>,
+    max_buffered_bytes: Option<usize>,
+    cors: Option<CorsConfig>,
+    verbose_logging: Option<bool>,
+    record_requests: Option<bool>,
+    drain_timeout: Option<Duration>,
+    environment: Option<Environment>,
+}
+
+impl ConfigBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn max_request_size(mut self, size: usize) -> Self {
+        self.max_request_size = Some(size);
+        self
+    }
+
+    /// Global ceiling on bytes buffered for in-flight requests and responses. Requests arriving
+    /// while it is exceeded get a 503.
+    pub fn max_buffered_bytes(mut self, bytes: usize) -> Self {
+        self.max_buffered_bytes = Some(bytes);
+        self
+    }
+
+    pub fn cors(mut self, cors: CorsConfig) -> Self {
+        self.cors = Some(cors);
+        self
+    }
+
+    /// Logs the matched route, params, middleware chain and phase timings for each request.
+    pub fn verbose_logging(mut self, verbose: bool) -> Self {
+        self.verbose_logging = Some(verbose);
+        self
+    }
+
+    /// Records recent requests and responses, browsable at `/_oxide/requests`.
+    /// Ignored in production.
+    pub fn record_requests(mut self, record: bool) -> Self {
+        self.record_requests = Some(record);
+        self
+    }
+
+    /// How long to wait for in-flight connections and background jobs when shutting down or
+    /// handing off to a new process.
+    pub fn drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = Some(timeout);
+        self
+    }
+
+    pub fn environment(mut self, environment: Environment) -> Self {
+        self.environment = Some(environment);
+        self
+    }
+
+    pub fn build(self) -> Config {
+        let default = Config::default();
+        Config {
+            host: self.host.unwrap_or(default.host),
+            port: self.port.unwrap_or(default.port),
+            max_request_size: self.max_request_size.unwrap_or(default.max_request_size),
+            max_buffered_bytes: self.max_buffered_bytes,
+            cors: self.cors,
+            verbose_logging: self.verbose_logging.unwrap_or(default.verbose_logging),
+            record_requests: self.record_requests.unwrap_or(default.record_requests),
+            drain_timeout: self.drain_timeout.unwrap_or(default.drain_timeout),
+            environment: self.environment.unwrap_or(default.environment),
+            file: default.file,
+            sections: default.sections,
+        }
+    }
+}
+
+impl From<ConfigBuilder> for Config {
+    fn from(builder: ConfigBuilder) -> Self {
+        builder.build()
+    }
+}
+
+impl Config {
+    /// Loads configuration for the current `OXIDE_ENV` profile.
+    ///
+    /// Values are layered, later sources winning: built-in defaults, `oxide.toml`,
+    /// `oxide.<environment>.toml`, then environment variables (`HOST`, `PORT`, ...).
+    /// Files are read from `OXIDE_CONFIG_DIR`, or the working directory if unset.
+    pub fn load() -> Result<Self, Error> {
+        let environment = Environment::current();
+        let dir = env::var("OXIDE_CONFIG_DIR").unwrap_or_else(|_| ".".to_string());
+        let file = ConfigFile::load(dir, environment)?;
+        let default = Config::default();
+
+        Ok(Self {
+            host: file.layered("HOST", "host")?.unwrap_or(default.host),
+            port: file.layered("PORT", "port")?.unwrap_or(default.port),
+            max_request_size: file
+                .layered("MAX_REQUEST_SIZE", "max_request_size")?
+                .unwrap_or(default.max_request_size),
+            max_buffered_bytes: file.layered("MAX_BUFFERED_BYTES", "max_buffered_bytes")?,
+            cors: None,
+            verbose_logging: file
+                .layered("VERBOSE_LOGGING", "verbose_logging")?
+                .unwrap_or(default.verbose_logging),
+            record_requests: file
+                .layered("RECORD_REQUESTS", "record_requests")?
+                .unwrap_or(default.record_requests),
+            drain_timeout: file
+                .layered("DRAIN_TIMEOUT", "drain_timeout")?
+                .map(Duration::from_secs)
+                .unwrap_or(default.drain_timeout),
+            environment,
+            file,
+            sections: vec![],
+        })
+    }
+
+    /// Reads an application-defined section, e.g. `config.section::<StripeConfig>("stripe")`,
+    /// from the `[stripe]` table of the config files overlaid with `STRIPE_*` env vars.
+    pub fn section<T: DeserializeOwned>(&self, name: &str) -> Result<T, Error> {
+        self.file.section(name)
+    }
+
+    /// Registers a section to be checked by [`Config::validate`] when the server starts,
+    /// so misconfiguration fails at boot instead of on first use.
+    pub fn register_section<T: DeserializeOwned>(&mut self, name: &str) -> &mut Self {
+        self.sections.push((name.to_string(), |file, name| {
+            file.section::<T>(name).map(|_| ())
+        }));
+        self
+    }
+
+    /// Checks every registered section and that `OXIDE_ENV` names an environment, reporting
+    /// all failures at once.
+    pub fn validate(&self) -> Result<(), Error> {
+        let environment = Environment::unrecognized().map(|value| {
+            format!(
+                "OXIDE_ENV='{}' is not an environment; use development, test or production",
+                value
+            )
+        });
+        let sections = self
+            .sections
+            .iter()
+            .filter_map(|(name, validate)| validate(&self.file, name).err())
+            .map(|e| match e {
+                Error::Config(message) => message,
+                other => other.to_string(),
+            });
+        let errors = environment.into_iter().chain(sections).collect::<Vec<_>>();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Config(errors.join("; ")))
+        }
+    }
+
+    pub fn is_development(&self) -> bool {
+        self.environment.is_development()
+    }
+
+    pub fn is_test(&self) -> bool {
+        self.environment.is_test()
+    }
+
+    pub fn is_production(&self) -> bool {
+        self.environment.is_production()
+    }
+
+    /// Config files that were merged into this config, in the order they were applied.
+    pub fn sources(&self) -> &[PathBuf] {
+        self.file.sources()
+    }
+
+    pub fn from_env() -> Self {
+        let validator = EnvValidator::new(Logger::new());
+        Self {
+            host: validator.get_var("HOST", "a string (e.g., '127.0.0.1')"),
+            port: validator.get_var_parse("PORT", "a number between 0-65535"),
+            max_request_size: validator.get_var_parse(
+                "MAX_REQUEST_SIZE",
+                "a number in bytes (e.g., 1048576 for 1MB)",
+            ),
+            max_buffered_bytes: env::var("MAX_BUFFERED_BYTES")
+                .ok()
+                .and_then(|bytes| bytes.parse().ok()),
+            cors: None,
+            verbose_logging: validator.get_var_parse_or("VERBOSE_LOGGING", false),
+            record_requests: validator.get_var_parse_or("RECORD_REQUESTS", false),
+            drain_timeout: Duration::from_secs(validator.get_var_parse_or("DRAIN_TIMEOUT", 30)),
+            environment: Environment::current(),
+            file: ConfigFile::default(),
+            sections: vec![],
+        }
+    }
+}
+
+pub struct EnvValidator {
+    logger: Logger,
+}
+
+impl EnvValidator {
+    pub fn new(logger: Logger) -> Self {
+        Self { logger }
+    }
+
+    pub fn error(&self, key: &str, type_info: &str) -> ! {
+        self.logger
+            .log(LogLevel::Warning, &format!("Set {} as {}", key, type_info));
+        self.logger
+            .panic(&format!("Missing environment variable: {}", key));
+    }
+
+    pub fn get_var(&self, key: &str, type_info: &str) -> String {
+        env::var(key).unwrap_or_else(|_| self.error(key, type_info))
+    }
+
+    pub fn get_var_parse<T: std::str::FromStr>(&self, key: &str, type_info: &str) -> T {
+        self.get_var(key, type_info)
+            .parse()
+            .unwrap_or_else(|_| self.error(key, type_info))
+    }
+
+    pub fn get_var_parse_or<T: std::str::FromStr>(&self, key: &str, default: T) -> T {
+        match env::var(key) {
+            Ok(value) => value.parse().unwrap_or_else(|_| {
+                self.logger.log(
+                    LogLevel::Warning,
+                    &format!("Invalid value for {}, using the default", key),
+                );
+                default
+            }),
+            Err(_) => default,
+        }
+    }
+}
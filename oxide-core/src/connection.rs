@@ -1,5 +1,10 @@
-use crate::http::{HttpHandler, HttpMethod, RequestResponse};
+use crate::http::{
+    BufferBuilder, Fault, HttpHandler, HttpMethod, HttpRequest, RequestResponse, Res,
+};
 use crate::logger::{LogLevel, Logger};
+#[cfg(feature = "metrics")]
+use crate::server::ResponseSizes;
+use crate::server::{ConnectionGuard, ConnectionState, MemoryBudget};
 
 use bytes::BytesMut;
 use std::io;
@@ -10,6 +15,13 @@ use tokio::{
     net::TcpStream,
 };
 
+/// Read buffers start this small and double only while a request needs more, so the many
+/// connections sending small requests hold little memory each.
+const INITIAL_READ_BUFFER: usize = 4 * 1024;
+
+/// How far buffers grow without a configured request size limit.
+const DEFAULT_READ_LIMIT: usize = 1024 * 1024;
+
 #[derive(Debug)]
 pub enum Protocol {
     Http1,
@@ -27,29 +39,81 @@ pub struct Connection {
     logger: Logger,
 
     http_handler: Arc<HttpHandler>,
+
+    memory: Option<Arc<MemoryBudget>>,
+
+    #[cfg(feature = "metrics")]
+    response_sizes: Option<Arc<ResponseSizes>>,
+
+    max_request_size: Option<usize>,
+
+    tracking: Option<ConnectionGuard>,
 }
 
 impl Connection {
     pub fn new(stream: TcpStream, http_handler: Arc<HttpHandler>) -> Result<Self, io::Error> {
         let stream = BufWriter::new(stream);
-        let buffer = BytesMut::with_capacity(1024 * 1024);
-        let logger = Logger::new();
+        let buffer = BytesMut::with_capacity(INITIAL_READ_BUFFER);
+        let logger = Logger::for_target(module_path!());
 
         Ok(Self {
             stream,
             buffer,
             logger,
             http_handler,
+            memory: None,
+            #[cfg(feature = "metrics")]
+            response_sizes: None,
+            max_request_size: None,
+            tracking: None,
         })
     }
 
+    /// Charges this connection's request and response buffers to `memory`, answering 503
+    /// instead of handling the request when the budget is exhausted.
+    pub fn with_memory_budget(mut self, memory: Arc<MemoryBudget>) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+
+    /// Records the size of each response in `response_sizes`.
+    #[cfg(feature = "metrics")]
+    pub fn with_response_sizes(mut self, response_sizes: Arc<ResponseSizes>) -> Self {
+        self.response_sizes = Some(response_sizes);
+        self
+    }
+
+    /// Rejects requests larger than `size` bytes with 413.
+    pub fn with_max_request_size(mut self, size: usize) -> Self {
+        self.max_request_size = Some(size);
+        self
+    }
+
+    /// Reports this connection's state and traffic through `guard`, which marks it finished
+    /// once the connection is dropped.
+    pub fn with_connection_guard(mut self, guard: ConnectionGuard) -> Self {
+        self.tracking = Some(guard);
+        self
+    }
+
+    fn track(&self, update: impl FnOnce(&ConnectionGuard)) {
+        if let Some(handle) = &self.tracking {
+            update(handle);
+        }
+    }
+
     pub async fn process(mut self) -> io::Result<()> {
-        if 0 == self.stream.read_buf(&mut self.buffer).await? {
+        let read = self.stream.read_buf(&mut self.buffer).await?;
+        if read == 0 {
             self.logger.log(LogLevel::Application, "Connection closed");
             return Ok(());
         }
+        self.track(|handle| {
+            handle.set_state(ConnectionState::Reading);
+            handle.add_read(read);
+        });
 
-        let first_bytes = self.peek(7);
+        let first_bytes = self.peek(8);
 
         match self.detect_protocol(first_bytes) {
             Protocol::Http1 => {
@@ -71,16 +135,45 @@ impl Connection {
         Ok(())
     }
 
+    /// Reads until the whole request is buffered, doubling the buffer as needed up to one
+    /// byte past the request size limit, so oversized requests are still detected.
+    async fn read_request(&mut self) -> io::Result<()> {
+        let limit = self.max_request_size.unwrap_or(DEFAULT_READ_LIMIT) + 1;
+        loop {
+            let complete = HttpRequest::expected_len(&self.buffer)
+                .is_some_and(|expected| self.buffer.len() >= expected);
+            if complete || self.buffer.len() >= limit {
+                return Ok(());
+            }
+            if self.buffer.len() == self.buffer.capacity() {
+                let grow = self.buffer.capacity().min(limit - self.buffer.len());
+                self.buffer.reserve(grow);
+            }
+            let read = self.stream.read_buf(&mut self.buffer).await?;
+            if read == 0 {
+                return Ok(());
+            }
+            self.track(|handle| handle.add_read(read));
+        }
+    }
+
+    /// Swaps a buffer grown for a large request back for a small one, so it isn't held while
+    /// the response is written.
+    fn shrink_buffer(&mut self) {
+        if self.buffer.capacity() > INITIAL_READ_BUFFER {
+            self.buffer = BytesMut::with_capacity(INITIAL_READ_BUFFER);
+        } else {
+            self.buffer.clear();
+        }
+    }
+
     pub async fn handle_http(&mut self) -> io::Result<()> {
         let start_time = std::time::Instant::now();
+        self.read_request().await?;
 
         let ip = self.stream.get_ref().peer_addr()?.to_string();
 
-        let request_line = std::str::from_utf8(&self.buffer)
-            .ok()
-            .and_then(|s| s.lines().next())
-            .unwrap_or("");
-
+        let request_line = HttpRequest::request_line(&self.buffer);
         let mut parts = request_line.split_whitespace();
         let method = parts
             .next()
@@ -88,20 +181,122 @@ impl Connection {
             .unwrap_or(HttpMethod::Unknown);
 
         let path = parts.next().unwrap_or("/").to_string();
+        self.track(|handle| handle.set_request(&method.to_string(), &path));
+
+        if self
+            .max_request_size
+            .is_some_and(|max| self.buffer.len() > max)
+        {
+            let response = BufferBuilder::new()
+                .status(BufferBuilder::PAYLOAD_TOO_LARGE)
+                .text("Payload Too Large")
+                .build();
+            return self.reject(response, method, path, ip, 413).await;
+        }
 
+        let mut reservation = match &self.memory {
+            Some(memory) => match memory.try_reserve(self.buffer.len()) {
+                Some(reservation) => Some(reservation),
+                None => {
+                    let stats = memory.stats();
+                    self.logger.log(
+                        LogLevel::Warning,
+                        &format!(
+                            "Memory budget exhausted ({} of {} bytes buffered, request needs {}), shedding {} {}",
+                            stats.in_use,
+                            stats.limit.unwrap_or_default(),
+                            self.buffer.len(),
+                            method,
+                            path
+                        ),
+                    );
+                    let response = BufferBuilder::new()
+                        .status(BufferBuilder::SERVICE_UNAVAILABLE)
+                        .header("Retry-After", "1")
+                        .text("Service Unavailable")
+                        .build();
+                    return self.reject(response, method, path, ip, 503).await;
+                }
+            },
+            None => None,
+        };
+
+        self.track(|handle| handle.set_state(ConnectionState::Handling));
         let response = self.http_handler.handle(&self.buffer).await;
+        self.shrink_buffer();
+        self.track(|handle| {
+            handle.set_route(response.route.as_deref());
+            handle.set_state(ConnectionState::Writing);
+        });
+        if let Some(reservation) = reservation.as_mut() {
+            reservation.add(response.buffer.len());
+        }
         let duration = start_time.elapsed();
 
+        #[cfg(feature = "metrics")]
+        if let Some(response_sizes) = &self.response_sizes {
+            response_sizes.observe(response.route.as_deref(), response.buffer.len());
+        }
         Logger::log_http(&RequestResponse {
             method,
             path,
             ip,
             status: response.status,
             duration,
+            bytes_sent: response.buffer.len(),
+            body_bytes: response.body_len(),
+            trace: response.trace,
         });
 
+        match response.fault {
+            Some(Fault::Drop) => return Ok(()),
+            Some(Fault::SlowBody { chunk, delay }) => {
+                for piece in response.buffer.chunks(chunk) {
+                    self.stream.write_all(piece).await?;
+                    self.stream.flush().await?;
+                    self.track(|handle| handle.add_written(piece.len()));
+                    tokio::time::sleep(delay).await;
+                }
+            }
+            None => {
+                self.stream.write_all(&response.buffer).await?;
+                self.stream.flush().await?;
+                self.track(|handle| handle.add_written(response.buffer.len()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Answers without dispatching to the handler, for requests refused before routing.
+    async fn reject(
+        &mut self,
+        response: Vec<u8>,
+        method: HttpMethod,
+        path: String,
+        ip: String,
+        status: u16,
+    ) -> io::Result<()> {
+        let response = Res::new(response, status);
+        #[cfg(feature = "metrics")]
+        if let Some(response_sizes) = &self.response_sizes {
+            response_sizes.observe(None, response.buffer.len());
+        }
+        Logger::log_http(&RequestResponse {
+            method,
+            path,
+            ip,
+            status,
+            duration: std::time::Duration::ZERO,
+            bytes_sent: response.buffer.len(),
+            body_bytes: response.body_len(),
+            trace: None,
+        });
+
+        self.track(|handle| handle.set_state(ConnectionState::Writing));
         self.stream.write_all(&response.buffer).await?;
-        self.stream.flush().await
+        self.stream.flush().await?;
+        self.track(|handle| handle.add_written(response.buffer.len()));
+        Ok(())
     }
 
     fn peek(&self, n: usize) -> &[u8] {
@@ -116,7 +311,9 @@ impl Connection {
         if let Some(space_pos) = bytes.iter().position(|&b| b == b' ') {
             let method = &bytes[..space_pos];
             match method {
-                b"GET" | b"POST" | b"PUT" | b"HEAD" | b"DELETE" | b"PATCH" => Protocol::Http1,
+                b"GET" | b"POST" | b"PUT" | b"HEAD" | b"DELETE" | b"PATCH" | b"OPTIONS" => {
+                    Protocol::Http1
+                }
                 b"PRI" => Protocol::Http2,
                 _ => Protocol::Unknown,
             }
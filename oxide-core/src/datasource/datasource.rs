@@ -1,10 +1,24 @@
-use crate::Error;
-use sqlx::postgres::PgQueryResult;
+use super::stream::{self, RowStream};
+use super::{Fixtures, IsolationLevel, PgDatabaseBuilder, PgTransaction};
+use crate::error_codes;
+use crate::http::RequestScope;
+use crate::logger::LogLevel;
+use crate::metering;
+use crate::retry::{retry, RetryPolicy};
+use crate::secrets::SecretString;
+use crate::{Error, Logger};
+use futures::{Stream, StreamExt, TryStreamExt};
+use sqlx::pool::PoolConnection;
 use sqlx::postgres::PgRow;
-use sqlx::PgPool;
-use sqlx::Transaction;
-use sqlx::{FromRow, Postgres};
-use tokio::time::{timeout, Duration};
+use sqlx::postgres::{PgArguments, PgConnectOptions, PgListener, PgPoolOptions, PgQueryResult};
+use sqlx::FromRow;
+use sqlx::{PgPool, Postgres};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
+use tokio::time::Duration;
 
 /// A connection pool wrapper for PostgreSQL database operations.
 ///
@@ -43,14 +57,59 @@ use tokio::time::{timeout, Duration};
 ///         .bind("Alice")
 ///         .execute(&mut *tx)
 ///         .await?;
+///     tx.after_commit(|| println!("Alice is visible to other connections now"));
 ///     tx.commit().await?;
 ///
 ///     Ok(())
 /// }
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct PgDatabase {
     pool: PgPool,
+    slow_query_threshold: Option<Duration>,
+    query_log_level: LogLevel,
+    query_hooks: Vec<QueryHook>,
+    fixtures: Option<Arc<Fixtures>>,
+    logger: Logger,
+}
+
+/// A message received from a channel [`PgDatabase::listen`] subscribed to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub channel: String,
+    pub payload: String,
+    /// The backend process of the session that sent it.
+    pub process_id: u32,
+}
+
+pub type NotificationStream = Pin<Box<dyn Stream<Item = Result<Notification, Error>> + Send>>;
+
+/// A statement run by [`PgDatabase`], passed to hooks registered with
+/// [`PgDatabase::on_query`] once it finishes.
+#[derive(Debug)]
+pub struct QueryEvent<'a> {
+    pub sql: &'a str,
+    pub duration: Duration,
+    pub error: Option<&'a Error>,
+    /// The request being handled when the query ran, if any.
+    pub scope: Option<&'a RequestScope>,
+}
+
+pub type QueryHook = Arc<dyn Fn(&QueryEvent<'_>) + Send + Sync>;
+
+/// The work [`PgDatabase::transaction`] runs in each attempt's transaction.
+pub type TransactionFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, Error>> + Send + 'a>>;
+
+impl fmt::Debug for PgDatabase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PgDatabase")
+            .field("pool", &self.pool)
+            .field("slow_query_threshold", &self.slow_query_threshold)
+            .field("query_log_level", &self.query_log_level)
+            .field("query_hooks", &self.query_hooks.len())
+            .field("fixtures", &self.fixtures)
+            .finish()
+    }
 }
 
 impl PgDatabase {
@@ -62,11 +121,101 @@ impl PgDatabase {
     /// # Returns
     /// * `Result<Self, Error>` - Connection pool or error if connection fails
     pub async fn connect(database_url: &str) -> Result<Self, Error> {
-        let pool = timeout(Duration::from_secs(5), PgPool::connect(database_url))
+        Self::builder(database_url).connect().await
+    }
+
+    /// Configures the pool's size and timeouts before connecting.
+    pub fn builder(database_url: &str) -> PgDatabaseBuilder {
+        PgDatabaseBuilder::new(database_url)
+    }
+
+    pub(super) fn from_pool(pool: PgPool) -> Self {
+        Self {
+            pool,
+            slow_query_threshold: None,
+            query_log_level: LogLevel::Debug,
+            query_hooks: vec![],
+            fixtures: None,
+            logger: Logger::for_target(module_path!()),
+        }
+    }
+
+    /// A database answered entirely from replayed `fixtures`, for tests without Postgres.
+    /// Only queries made through the ORM are replayed; anything else, transactions
+    /// included, fails to connect.
+    pub fn from_fixtures(fixtures: Fixtures) -> Self {
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(Duration::from_secs(1))
+            .connect_lazy_with(PgConnectOptions::new().socket("/dev/null/oxide-fixtures"));
+        Self::from_pool(pool).with_fixtures(fixtures)
+    }
+
+    /// Records or replays the results of queries made through the ORM, see [`Fixtures`].
+    pub fn with_fixtures(mut self, fixtures: Fixtures) -> Self {
+        self.fixtures = Some(Arc::new(fixtures));
+        self
+    }
+
+    pub fn fixtures(&self) -> Option<&Fixtures> {
+        self.fixtures.as_deref()
+    }
+
+    /// Logs queries taking at least `threshold` as warnings. Every query is logged at debug
+    /// level; both are tagged with the request id and route when run from a handler.
+    pub fn with_slow_query_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_query_threshold = Some(threshold);
+        self
+    }
+
+    /// The level every query is logged at with its latency and any error, `Debug` by
+    /// default. `LogLevel::Info` logs them without enabling debug output elsewhere.
+    pub fn with_query_log_level(mut self, level: LogLevel) -> Self {
+        self.query_log_level = level;
+        self
+    }
+
+    /// Calls `hook` after every statement with its SQL, latency and error, e.g. to feed a
+    /// histogram or flag slow endpoints. Hooks run inline, so they should be cheap. Statements
+    /// run through a transaction's [`PgTransactionRef`](super::PgTransactionRef) methods are
+    /// seen; ones run on its connection with `&mut *tx`, or on an acquired connection, aren't.
+    ///
+    /// ```rust,ignore
+    /// let db = PgDatabase::connect(&url).await?.on_query(|event| {
+    ///     if let Some(error) = event.error {
+    ///         eprintln!("{} failed after {:?}: {}", event.sql, event.duration, error);
+    ///     }
+    /// });
+    /// ```
+    pub fn on_query(mut self, hook: impl Fn(&QueryEvent<'_>) + Send + Sync + 'static) -> Self {
+        self.query_hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Connects using a connection string held as a secret, e.g. one loaded with
+    /// `SecretString::from_env_or_file("DATABASE_URL")`.
+    pub async fn connect_secret(database_url: &SecretString) -> Result<Self, Error> {
+        Self::connect(database_url.expose()).await
+    }
+
+    /// Connects with the password supplied separately from a password-less connection string,
+    /// so the credential never has to be embedded in config or logged URLs.
+    ///
+    /// # Arguments
+    /// * `database_url` - PostgreSQL connection string without a password
+    /// * `password` - Database password, e.g. read from `/run/secrets/db_password`
+    pub async fn connect_with_password(
+        database_url: &str,
+        password: &SecretString,
+    ) -> Result<Self, Error> {
+        Self::builder(database_url)
+            .password(password)
+            .connect()
             .await
-            .map_err(|_| Error::Database(sqlx::Error::Configuration("Connection timeout".into())))?
-            .map_err(Error::Database)?;
-        Ok(Self { pool })
+    }
+
+    /// Current pool size and how many of those connections are idle.
+    pub fn pool_status(&self) -> (u32, usize) {
+        (self.pool.size(), self.pool.num_idle())
     }
 
     /// Executes a query returning multiple rows.
@@ -83,10 +232,8 @@ impl PgDatabase {
     where
         T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
     {
-        sqlx::query_as::<_, T>(&query)
-            .fetch_all(&self.pool)
+        self.observe(&query, sqlx::query_as::<_, T>(&query).fetch_all(&self.pool))
             .await
-            .map_err(Error::Database)
     }
 
     /// Executes a query expecting exactly one row.
@@ -103,10 +250,8 @@ impl PgDatabase {
     where
         T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
     {
-        sqlx::query_as::<_, T>(&query)
-            .fetch_one(&self.pool)
+        self.observe(&query, sqlx::query_as::<_, T>(&query).fetch_one(&self.pool))
             .await
-            .map_err(Error::Database)
     }
 
     /// Executes a query returning zero or one row.
@@ -123,10 +268,11 @@ impl PgDatabase {
     where
         T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
     {
-        sqlx::query_as::<_, T>(&query)
-            .fetch_optional(&self.pool)
-            .await
-            .map_err(Error::Database)
+        self.observe(
+            &query,
+            sqlx::query_as::<_, T>(&query).fetch_optional(&self.pool),
+        )
+        .await
     }
 
     /// Executes a query that doesn't return rows (INSERT, UPDATE, DELETE).
@@ -137,20 +283,346 @@ impl PgDatabase {
     /// # Returns
     /// * `Result<PgQueryResult, Error>` - Query result containing affected rows or error
     pub async fn execute(&self, query: String) -> Result<PgQueryResult, Error> {
-        sqlx::query(&query)
-            .execute(&self.pool)
+        self.observe(&query, sqlx::query(&query).execute(&self.pool))
             .await
-            .map_err(Error::Database)
+    }
+
+    /// Like [`query`](Self::query), with `$1, $2, ...` placeholders bound from `args`.
+    pub async fn query_with<T>(&self, query: String, args: PgArguments) -> Result<Vec<T>, Error>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        self.observe(
+            &query,
+            sqlx::query_as_with::<_, T, _>(&query, args).fetch_all(&self.pool),
+        )
+        .await
+    }
+
+    /// Like [`query_one`](Self::query_one), with placeholders bound from `args`.
+    pub async fn query_one_with<T>(&self, query: String, args: PgArguments) -> Result<T, Error>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        self.observe(
+            &query,
+            sqlx::query_as_with::<_, T, _>(&query, args).fetch_one(&self.pool),
+        )
+        .await
+    }
+
+    /// Like [`query_optional`](Self::query_optional), with placeholders bound from `args`.
+    pub async fn query_optional_with<T>(
+        &self,
+        query: String,
+        args: PgArguments,
+    ) -> Result<Option<T>, Error>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        self.observe(
+            &query,
+            sqlx::query_as_with::<_, T, _>(&query, args).fetch_optional(&self.pool),
+        )
+        .await
+    }
+
+    /// Like [`execute`](Self::execute), with placeholders bound from `args`.
+    pub async fn execute_with(
+        &self,
+        query: String,
+        args: PgArguments,
+    ) -> Result<PgQueryResult, Error> {
+        self.observe(&query, sqlx::query_with(&query, args).execute(&self.pool))
+            .await
+    }
+
+    /// Streams the rows of a query with placeholders bound from `args`, decoding them as they
+    /// arrive instead of collecting them, for exports too large to hold in memory. Logging and
+    /// metrics time the query up to its first row, since the rest depends on how fast the
+    /// consumer reads.
+    pub fn query_stream_with<'a, T>(&'a self, query: String, args: PgArguments) -> RowStream<'a, T>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin + 'a,
+    {
+        self.stream_on(&self.pool, query, args)
+    }
+
+    /// Streams `query` on `executor`, timing it up to its first row.
+    pub(super) fn stream_on<'a, T, E>(
+        &'a self,
+        executor: E,
+        query: String,
+        args: PgArguments,
+    ) -> RowStream<'a, T>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin + 'a,
+        E: sqlx::Executor<'a, Database = Postgres> + 'a,
+    {
+        RowStream::produce(|sender| async move {
+            let mut rows = sqlx::query_as_with::<_, T, _>(&query, args).fetch(executor);
+            let first = match self.observe(&query, rows.try_next()).await {
+                Ok(Some(row)) => Ok(row),
+                Ok(None) => return,
+                Err(e) => Err(e),
+            };
+            let failed = first.is_err();
+            if sender.send(first).await.is_ok() && !failed {
+                stream::forward(rows, &sender).await;
+            }
+        })
     }
 
     /// Begins a new database transaction.
     ///
     /// # Returns
-    /// * `Result<Transaction<'_, Postgres>, Error>` - Transaction handle or error
+    /// * `Result<PgTransaction<'_>, Error>` - Transaction handle or error
     ///
     /// # Note
-    /// Remember to either commit or rollback the transaction when done by calling tx.commit().await or tx.rollback().await
-    pub async fn begin(&self) -> Result<Transaction<'_, Postgres>, Error> {
-        self.pool.begin().await.map_err(Error::Database)
+    /// Remember to either commit or rollback the transaction when done by calling tx.commit().await or tx.rollback().await.
+    /// Work that must only happen once the data is committed can be registered with `tx.after_commit(...)`.
+    /// [`transaction`](Self::transaction) does both for you and is harder to get wrong.
+    pub async fn begin(&self) -> Result<PgTransaction<'_>, Error> {
+        self.pool
+            .begin()
+            .await
+            .map(|inner| PgTransaction::new(inner, self))
+            .map_err(Error::Database)
+    }
+
+    /// Runs `work` in a transaction, committing it if `work` returns `Ok` and rolling it back
+    /// if it returns `Err`, which is then returned. The transaction is passed to `work` as the
+    /// executor for its queries.
+    ///
+    /// ```rust,ignore
+    /// let user = db
+    ///     .transaction(|tx| {
+    ///         Box::pin(async move {
+    ///             let user = User::insert().value(User::columns().name, name).execute(&mut *tx).await?;
+    ///             Account::insert().value(Account::columns().user_id, user.id).execute(tx).await?;
+    ///             Ok(user)
+    ///         })
+    ///     })
+    ///     .await?;
+    /// ```
+    pub async fn transaction<T, F>(&self, work: F) -> Result<T, Error>
+    where
+        F: for<'t> FnMut(&'t mut PgTransaction<'_>) -> TransactionFuture<'t, T>,
+    {
+        let policy = RetryPolicy::new().with_max_attempts(1);
+        self.transaction_with(IsolationLevel::ReadCommitted, &policy, work)
+            .await
+    }
+
+    /// [`transaction`](Self::transaction) at `isolation`, starting over in a new transaction
+    /// while `policy` retries the error it failed with, e.g. serialization failures under
+    /// [`IsolationLevel::Serializable`]. `work` may run several times, so keep side effects out
+    /// of it or in `tx.after_commit`.
+    ///
+    /// ```rust,ignore
+    /// let policy = RetryPolicy::new()
+    ///     .with_max_attempts(3)
+    ///     .retry_if(|e| e.code() == error_codes::SERIALIZATION_FAILURE);
+    /// db.transaction_with(IsolationLevel::RepeatableRead, &policy, |tx| {
+    ///     Box::pin(async move { restock(tx, sku).await })
+    /// })
+    /// .await?;
+    /// ```
+    pub async fn transaction_with<T, F>(
+        &self,
+        isolation: IsolationLevel,
+        policy: &RetryPolicy,
+        work: F,
+    ) -> Result<T, Error>
+    where
+        F: for<'t> FnMut(&'t mut PgTransaction<'_>) -> TransactionFuture<'t, T>,
+    {
+        // Each attempt's future only borrows its own transaction, so `work` is locked just
+        // long enough to start it.
+        let work = Mutex::new(work);
+        retry(policy, || async {
+            let mut tx = self.begin().await?;
+            if isolation != IsolationLevel::ReadCommitted {
+                let query = format!("SET TRANSACTION ISOLATION LEVEL {}", isolation);
+                self.observe(&query, sqlx::query(&query).execute(&mut *tx))
+                    .await?;
+            }
+            let attempt = {
+                let mut work = work.lock().unwrap_or_else(PoisonError::into_inner);
+                (*work)(&mut tx)
+            };
+            match attempt.await {
+                Ok(value) => {
+                    tx.commit().await?;
+                    Ok(value)
+                }
+                Err(e) => {
+                    // The error from `work` says more than a failed rollback would.
+                    let _ = tx.rollback().await;
+                    Err(e)
+                }
+            }
+        })
+        .await
+    }
+
+    /// Runs `work` in a `SERIALIZABLE` transaction and commits it, starting over in a new
+    /// transaction when Postgres aborts it with a serialization failure. Up to 5 attempts are
+    /// made, with jittered backoff between them; any other error rolls back and is returned.
+    /// `work` may run several times, so keep side effects out of it or in `tx.after_commit`.
+    ///
+    /// ```rust,ignore
+    /// let balance = db
+    ///     .serializable(|tx| Box::pin(async move { transfer(tx, from, to, amount).await }))
+    ///     .await?;
+    /// ```
+    pub async fn serializable<T, F>(&self, work: F) -> Result<T, Error>
+    where
+        F: for<'t> FnMut(&'t mut PgTransaction<'_>) -> TransactionFuture<'t, T>,
+    {
+        let policy = RetryPolicy::new()
+            .with_max_attempts(5)
+            .with_backoff(Duration::from_millis(10), Duration::from_secs(1))
+            .retry_if(|e| e.code() == error_codes::SERIALIZATION_FAILURE);
+        self.serializable_with(&policy, work).await
+    }
+
+    /// [`serializable`](Self::serializable) with a different retry policy, e.g. to also retry
+    /// deadlocks or make more attempts.
+    pub async fn serializable_with<T, F>(&self, policy: &RetryPolicy, work: F) -> Result<T, Error>
+    where
+        F: for<'t> FnMut(&'t mut PgTransaction<'_>) -> TransactionFuture<'t, T>,
+    {
+        self.transaction_with(IsolationLevel::Serializable, policy, work)
+            .await
+    }
+
+    /// Checks out one connection from the pool, for work that relies on session state such as
+    /// `SET` or advisory locks staying on the same connection. It returns to the pool on drop.
+    pub async fn acquire(&self) -> Result<PoolConnection<Postgres>, Error> {
+        self.pool.acquire().await.map_err(Error::Database)
+    }
+
+    /// Notifications sent on `channel` with `NOTIFY` or [`notify`](Self::notify), received
+    /// on a connection of its own that is kept out of the pool while the stream lives.
+    ///
+    /// A lost connection is reopened and `LISTEN` repeated, but anything sent in between is
+    /// missed, so consumers that must not miss an event, such as cache invalidation, should
+    /// treat a reconnect as a reason to resync. Notifications sent inside a transaction
+    /// arrive once it commits, and not at all if it rolls back.
+    ///
+    /// ```rust,ignore
+    /// let mut invalidations = db.listen("cache_invalidation").await?;
+    /// while let Some(notification) = invalidations.try_next().await? {
+    ///     cache.remove(&notification.payload);
+    /// }
+    /// ```
+    pub async fn listen(&self, channel: &str) -> Result<NotificationStream, Error> {
+        self.listen_all(&[channel]).await
+    }
+
+    /// Like [`listen`](Self::listen), receiving the notifications of several channels on
+    /// one connection.
+    pub async fn listen_all(&self, channels: &[&str]) -> Result<NotificationStream, Error> {
+        let mut listener = PgListener::connect_with(&self.pool)
+            .await
+            .map_err(Error::Database)?;
+        listener
+            .listen_all(channels.iter().copied())
+            .await
+            .map_err(Error::Database)?;
+        self.logger.log(
+            LogLevel::Debug,
+            &format!("Listening on {}", channels.join(", ")),
+        );
+        Ok(Box::pin(listener.into_stream().map(|notification| {
+            notification
+                .map(|n| Notification {
+                    channel: n.channel().to_string(),
+                    payload: n.payload().to_string(),
+                    process_id: n.process_id(),
+                })
+                .map_err(Error::Database)
+        })))
+    }
+
+    /// Sends `payload` to everything listening on `channel` with `pg_notify`. Payloads must
+    /// be under 8000 bytes; send a key to look up for anything bigger.
+    pub async fn notify(&self, channel: &str, payload: &str) -> Result<(), Error> {
+        let query = "SELECT pg_notify($1, $2)";
+        self.observe(
+            query,
+            sqlx::query(query)
+                .bind(channel)
+                .bind(payload)
+                .execute(&self.pool),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Times a query and logs it against the request currently being handled, if any.
+    pub(super) async fn observe<T>(
+        &self,
+        query: &str,
+        execution: impl Future<Output = Result<T, sqlx::Error>>,
+    ) -> Result<T, Error> {
+        let start = Instant::now();
+        let result = execution.await;
+        let elapsed = start.elapsed();
+        metering::charge_query(elapsed);
+        #[cfg(feature = "otel")]
+        crate::telemetry::record_query(
+            query,
+            elapsed,
+            result.as_ref().err().map(|e| e.to_string()),
+        );
+
+        let result = result.map_err(Error::Database);
+        let scope = RequestScope::current();
+        let slow = self
+            .slow_query_threshold
+            .is_some_and(|threshold| elapsed >= threshold);
+        let level = if slow {
+            LogLevel::Warning
+        } else {
+            self.query_log_level
+        };
+        if self.logger.enabled(level) {
+            let tag = scope
+                .as_ref()
+                .map(|scope| format!(" [{}]", scope))
+                .unwrap_or_default();
+            let failure = result
+                .as_ref()
+                .err()
+                .map(|e| format!(" failed: {}", e))
+                .unwrap_or_default();
+            self.logger.log(
+                level,
+                &format!(
+                    "{} ({:.1}ms){}: {}{}",
+                    if slow { "Slow query" } else { "Query" },
+                    elapsed.as_secs_f64() * 1000.0,
+                    tag,
+                    query,
+                    failure
+                ),
+            );
+        }
+
+        if !self.query_hooks.is_empty() {
+            let event = QueryEvent {
+                sql: query,
+                duration: elapsed,
+                error: result.as_ref().err(),
+                scope: scope.as_ref(),
+            };
+            for hook in &self.query_hooks {
+                hook(&event);
+            }
+        }
+
+        result
     }
 }
@@ -0,0 +1,217 @@
+use std::{
+    collections::HashMap,
+    fs,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::Error;
+
+/// Chooses between recording and replaying in [`Fixtures::from_env`]: `record` records,
+/// anything else replays.
+pub const FIXTURES_VAR: &str = "OXIDE_FIXTURES";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixtureMode {
+    /// Queries run against Postgres and their results are saved.
+    Record,
+    /// Queries are answered from the saved results without touching Postgres.
+    Replay,
+}
+
+/// Query results saved to a JSON file, so tests can run handler logic against realistic
+/// data without a database. Record once against a real database, commit the file, and
+/// replay it in the fast suite:
+///
+/// ```rust,ignore
+/// // OXIDE_FIXTURES=record cargo test  -- runs against Postgres and writes the file
+/// // cargo test                        -- replays it, no Postgres needed
+/// let fixtures = Fixtures::from_env("tests/fixtures/users.json")?;
+/// let db = match fixtures.mode() {
+///     FixtureMode::Record => PgDatabase::connect(&database_url).await?.with_fixtures(fixtures),
+///     FixtureMode::Replay => PgDatabase::from_fixtures(fixtures),
+/// };
+/// ```
+///
+/// Results are matched on the SQL and its bound parameters; a query run several times is
+/// answered in the order it was recorded, repeating the last answer once they run out.
+/// Errors are recorded too, so a missing row replays as `RowNotFound`. oxide-orm's builders
+/// only go through fixtures with its `fixtures` feature, typically enabled for tests in
+/// `[dev-dependencies]`.
+#[derive(Debug)]
+pub struct Fixtures {
+    path: PathBuf,
+    mode: FixtureMode,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    entries: Vec<Entry>,
+    replayed: HashMap<(String, Vec<String>), usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    sql: String,
+    params: Vec<String>,
+    #[serde(flatten)]
+    outcome: Outcome,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Outcome {
+    Result(serde_json::Value),
+    RowNotFound,
+    Error(String),
+}
+
+impl Fixtures {
+    /// Records into `path`, replacing what it held. The file is rewritten after every query,
+    /// so it is complete even if the test panics.
+    pub fn record(path: impl AsRef<Path>) -> Self {
+        Self::new(path.as_ref(), FixtureMode::Record, vec![])
+    }
+
+    /// Replays the results recorded in `path`.
+    pub fn replay(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file = fs::read_to_string(path).map_err(|e| {
+            Error::Config(format!("Cannot read fixtures {}: {}", path.display(), e))
+        })?;
+        let entries = serde_json::from_str(&file).map_err(|e| {
+            Error::Deserialization(format!("Invalid fixtures {}: {}", path.display(), e))
+        })?;
+        Ok(Self::new(path, FixtureMode::Replay, entries))
+    }
+
+    /// Records when `OXIDE_FIXTURES=record`, replays otherwise.
+    pub fn from_env(path: impl AsRef<Path>) -> Result<Self, Error> {
+        match std::env::var(FIXTURES_VAR).as_deref() {
+            Ok("record") => Ok(Self::record(path)),
+            _ => Self::replay(path),
+        }
+    }
+
+    fn new(path: &Path, mode: FixtureMode, entries: Vec<Entry>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            mode,
+            state: Mutex::new(State {
+                entries,
+                replayed: HashMap::new(),
+            }),
+        }
+    }
+
+    pub fn mode(&self) -> FixtureMode {
+        self.mode
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number of recorded results.
+    pub fn len(&self) -> usize {
+        self.state
+            .lock()
+            .map(|state| state.entries.len())
+            .unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Runs `query` and records its result when recording; answers from the recording
+    /// without running it when replaying. `params` are the bound values as SQL literals.
+    pub async fn run<T, F>(&self, sql: &str, params: Vec<String>, query: F) -> Result<T, Error>
+    where
+        T: Serialize + DeserializeOwned,
+        F: Future<Output = Result<T, Error>>,
+    {
+        match self.mode {
+            FixtureMode::Record => {
+                let result = query.await;
+                self.record_result(sql, params, result.as_ref())?;
+                result
+            }
+            FixtureMode::Replay => self.replay_result(sql, params),
+        }
+    }
+
+    /// Saves the result of a query that ran for real.
+    pub fn record_result<T: Serialize>(
+        &self,
+        sql: &str,
+        params: Vec<String>,
+        result: Result<&T, &Error>,
+    ) -> Result<(), Error> {
+        let outcome = match result {
+            Ok(value) => Outcome::Result(
+                serde_json::to_value(value).map_err(|e| Error::Serialization(e.to_string()))?,
+            ),
+            Err(Error::Database(sqlx::Error::RowNotFound)) => Outcome::RowNotFound,
+            Err(e) => Outcome::Error(e.to_string()),
+        };
+        let mut state = self.lock()?;
+        state.entries.push(Entry {
+            sql: sql.to_string(),
+            params,
+            outcome,
+        });
+        let file = serde_json::to_string_pretty(&state.entries)
+            .map_err(|e| Error::Serialization(e.to_string()))?;
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, file)?;
+        Ok(())
+    }
+
+    /// The next recorded result of `sql` with `params`.
+    pub fn replay_result<T: DeserializeOwned>(
+        &self,
+        sql: &str,
+        params: Vec<String>,
+    ) -> Result<T, Error> {
+        let mut state = self.lock()?;
+        let matches = state
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.sql == sql && entry.params == params)
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        let Some(&last) = matches.last() else {
+            return Err(Error::Config(format!(
+                "No fixture in {} for query: {} {:?}",
+                self.path.display(),
+                sql,
+                params
+            )));
+        };
+        let replayed = state.replayed.entry((sql.to_string(), params)).or_default();
+        let index = matches.get(*replayed).copied().unwrap_or(last);
+        *replayed += 1;
+
+        match &state.entries[index].outcome {
+            Outcome::Result(value) => T::deserialize(value).map_err(|e| {
+                Error::Deserialization(format!("Fixture for query {} does not decode: {}", sql, e))
+            }),
+            Outcome::RowNotFound => Err(Error::Database(sqlx::Error::RowNotFound)),
+            Outcome::Error(message) => Err(Error::Database(sqlx::Error::Protocol(message.clone()))),
+        }
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, State>, Error> {
+        self.state
+            .lock()
+            .map_err(|_| Error::InternalServer("fixtures lock poisoned".to_string()))
+    }
+}
@@ -1,3 +1,15 @@
 mod datasource;
+mod fixtures;
+mod pool;
+mod replicas;
+mod stream;
+mod transaction;
 
-pub use datasource::PgDatabase;
+pub use datasource::{
+    Notification, NotificationStream, PgDatabase, QueryEvent, QueryHook, TransactionFuture,
+};
+pub use fixtures::{FixtureMode, Fixtures, FIXTURES_VAR};
+pub use pool::PgDatabaseBuilder;
+pub use replicas::ReadReplicas;
+pub use stream::RowStream;
+pub use transaction::{IsolationLevel, PgTransaction, PgTransactionRef};
@@ -0,0 +1,157 @@
+use std::str::FromStr;
+
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use tokio::time::{timeout, Duration};
+
+use super::PgDatabase;
+use crate::{logger::LogLevel, secrets::SecretString, Error};
+
+/// Pool settings for [`PgDatabase`], created with [`PgDatabase::builder`]. Settings left
+/// unset keep sqlx's defaults, except the 5 second connect timeout.
+///
+/// ```rust,ignore
+/// let db = PgDatabase::builder("postgres://app@db/app")
+///     .max_connections(20)
+///     .min_connections(2)
+///     .acquire_timeout(Duration::from_secs(3))
+///     .idle_timeout(Duration::from_secs(300))
+///     .statement_timeout(Duration::from_secs(10))
+///     .connect()
+///     .await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct PgDatabaseBuilder {
+    database_url: String,
+    password: Option<SecretString>,
+    connect_timeout: Duration,
+    max_connections: Option<u32>,
+    min_connections: Option<u32>,
+    acquire_timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    max_lifetime: Option<Duration>,
+    statement_timeout: Option<Duration>,
+    slow_query_threshold: Option<Duration>,
+    query_log_level: Option<LogLevel>,
+}
+
+impl PgDatabaseBuilder {
+    pub(crate) fn new(database_url: &str) -> Self {
+        Self {
+            database_url: database_url.to_string(),
+            password: None,
+            connect_timeout: Duration::from_secs(5),
+            max_connections: None,
+            min_connections: None,
+            acquire_timeout: None,
+            idle_timeout: None,
+            max_lifetime: None,
+            statement_timeout: None,
+            slow_query_threshold: None,
+            query_log_level: None,
+        }
+    }
+
+    /// Password supplied separately from a password-less connection string.
+    pub fn password(mut self, password: &SecretString) -> Self {
+        self.password = Some(password.clone());
+        self
+    }
+
+    /// How long `connect` waits for the pool's first connection.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    pub fn max_connections(mut self, max: u32) -> Self {
+        self.max_connections = Some(max);
+        self
+    }
+
+    /// Connections kept open even when idle.
+    pub fn min_connections(mut self, min: u32) -> Self {
+        self.min_connections = Some(min);
+        self
+    }
+
+    /// How long a query waits for a free connection before failing.
+    pub fn acquire_timeout(mut self, timeout: Duration) -> Self {
+        self.acquire_timeout = Some(timeout);
+        self
+    }
+
+    /// How long a connection above `min_connections` stays open unused.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// How long a connection is used before being replaced.
+    pub fn max_lifetime(mut self, lifetime: Duration) -> Self {
+        self.max_lifetime = Some(lifetime);
+        self
+    }
+
+    /// Postgres `statement_timeout` for every connection in the pool; statements running
+    /// longer are cancelled by the server.
+    pub fn statement_timeout(mut self, timeout: Duration) -> Self {
+        self.statement_timeout = Some(timeout);
+        self
+    }
+
+    /// See [`PgDatabase::with_slow_query_threshold`].
+    pub fn slow_query_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_query_threshold = Some(threshold);
+        self
+    }
+
+    /// See [`PgDatabase::with_query_log_level`].
+    pub fn query_log_level(mut self, level: LogLevel) -> Self {
+        self.query_log_level = Some(level);
+        self
+    }
+
+    pub async fn connect(self) -> Result<PgDatabase, Error> {
+        let mut options =
+            PgConnectOptions::from_str(&self.database_url).map_err(Error::Database)?;
+        if let Some(password) = &self.password {
+            options = options.password(password.expose());
+        }
+        if let Some(statement_timeout) = self.statement_timeout {
+            options = options.options([(
+                "statement_timeout",
+                statement_timeout.as_millis().to_string(),
+            )]);
+        }
+
+        let mut pool = PgPoolOptions::new();
+        if let Some(max) = self.max_connections {
+            pool = pool.max_connections(max);
+        }
+        if let Some(min) = self.min_connections {
+            pool = pool.min_connections(min);
+        }
+        if let Some(acquire_timeout) = self.acquire_timeout {
+            pool = pool.acquire_timeout(acquire_timeout);
+        }
+        if let Some(idle_timeout) = self.idle_timeout {
+            pool = pool.idle_timeout(idle_timeout);
+        }
+        if let Some(max_lifetime) = self.max_lifetime {
+            pool = pool.max_lifetime(max_lifetime);
+        }
+
+        let pool = timeout(self.connect_timeout, pool.connect_with(options))
+            .await
+            .map_err(|_| Error::Database(sqlx::Error::Configuration("Connection timeout".into())))?
+            .map_err(Error::Database)?;
+        let mut db = PgDatabase::from_pool(pool);
+        if let Some(threshold) = self.slow_query_threshold {
+            db = db.with_slow_query_threshold(threshold);
+        }
+        if let Some(level) = self.query_log_level {
+            db = db.with_query_log_level(level);
+        }
+        Ok(db)
+    }
+}
@@ -0,0 +1,60 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::PgDatabase;
+
+/// Read replicas of the main datasource and of named ones, registered with
+/// `Server::with_read_replica` and `Server::with_named_read_replica`. Reads routed to a
+/// datasource with several replicas take turns among them.
+#[derive(Debug, Default)]
+pub struct ReadReplicas {
+    /// Keyed by datasource name; `None` for the main datasource.
+    replicas: HashMap<Option<String>, Vec<PgDatabase>>,
+    next: AtomicUsize,
+}
+
+impl Clone for ReadReplicas {
+    fn clone(&self) -> Self {
+        Self {
+            replicas: self.replicas.clone(),
+            next: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl ReadReplicas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a replica of the datasource named `name`, or of the main one for `None`.
+    pub fn add(&mut self, name: Option<&str>, replica: PgDatabase) {
+        self.replicas
+            .entry(name.map(str::to_string))
+            .or_default()
+            .push(replica);
+    }
+
+    /// The replica to read from next, if the datasource has any.
+    pub fn get(&self, name: Option<&str>) -> Option<&PgDatabase> {
+        let replicas = self.replicas.get(&name.map(str::to_string))?;
+        match replicas.len() {
+            0 => None,
+            1 => replicas.first(),
+            len => replicas.get(self.next.fetch_add(1, Ordering::Relaxed) % len),
+        }
+    }
+
+    /// The replicas of the datasource named `name`, or of the main one for `None`.
+    pub fn of(&self, name: Option<&str>) -> &[PgDatabase] {
+        self.replicas
+            .get(&name.map(str::to_string))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replicas.values().all(Vec::is_empty)
+    }
+}
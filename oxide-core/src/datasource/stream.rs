@@ -0,0 +1,125 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{stream::BoxStream, Stream, TryStreamExt};
+use sqlx::{
+    postgres::{PgArguments, PgRow},
+    FromRow, Postgres,
+};
+use tokio::sync::mpsc;
+
+use crate::Error;
+
+/// Rows read ahead of the consumer before the query waits for it to catch up.
+const READ_AHEAD: usize = 64;
+
+type Producer<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+/// Rows of a query, decoded one at a time as they arrive rather than collected into a `Vec`,
+/// so exports of millions of rows run in constant memory.
+///
+/// The query runs as the stream is polled and holds its connection until the stream ends or
+/// is dropped. At most a few dozen rows are read ahead of the consumer.
+///
+/// ```rust,ignore
+/// let mut rows = db.query_stream_with::<Order>(sql, args);
+/// while let Some(order) = rows.try_next().await? {
+///     writer.write_record(&order)?;
+/// }
+/// ```
+pub struct RowStream<'a, T> {
+    rows: mpsc::Receiver<Result<T, Error>>,
+    producer: Option<Producer<'a>>,
+}
+
+impl<'a, T> RowStream<'a, T>
+where
+    T: for<'r> FromRow<'r, PgRow> + Send + Unpin + 'a,
+{
+    /// Streams `query` on any sqlx executor, e.g. the connection of an open transaction
+    /// with `&mut *tx`.
+    pub fn new<E>(executor: E, query: String, args: PgArguments) -> Self
+    where
+        E: sqlx::Executor<'a, Database = Postgres> + 'a,
+    {
+        Self::produce(|sender| async move {
+            let rows = sqlx::query_as_with(&query, args).fetch(executor);
+            forward(rows, &sender).await;
+        })
+    }
+
+    /// Yields the rows `rows` resolves to, e.g. results replayed from fixtures, which have
+    /// no query to stream from.
+    pub fn from_rows<F>(rows: F) -> Self
+    where
+        F: Future<Output = Result<Vec<T>, Error>> + Send + 'a,
+    {
+        Self::produce(|sender| async move {
+            match rows.await {
+                Ok(rows) => {
+                    for row in rows {
+                        if sender.send(Ok(row)).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                Err(e) => {
+                    let _ = sender.send(Err(e)).await;
+                }
+            }
+        })
+    }
+
+    /// Runs `producer` as the stream is polled, yielding whatever it sends.
+    pub(crate) fn produce<F>(producer: impl FnOnce(mpsc::Sender<Result<T, Error>>) -> F) -> Self
+    where
+        F: Future<Output = ()> + Send + 'a,
+    {
+        let (sender, rows) = mpsc::channel(READ_AHEAD);
+        Self {
+            rows,
+            producer: Some(Box::pin(producer(sender))),
+        }
+    }
+}
+
+impl<T> Stream for RowStream<'_, T> {
+    type Item = Result<T, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Poll::Ready(row) = self.rows.poll_recv(cx) {
+            return Poll::Ready(row);
+        }
+        let finished = match self.producer.as_mut() {
+            Some(producer) => producer.as_mut().poll(cx).is_ready(),
+            None => false,
+        };
+        if finished {
+            // The producer dropped its sender, so this yields the last rows and then `None`.
+            self.producer = None;
+            return self.rows.poll_recv(cx);
+        }
+        Poll::Pending
+    }
+}
+
+/// Sends rows on until the query ends, fails or the stream is dropped.
+pub(crate) async fn forward<T>(
+    mut rows: BoxStream<'_, Result<T, sqlx::Error>>,
+    sender: &mpsc::Sender<Result<T, Error>>,
+) {
+    loop {
+        let row = match rows.try_next().await {
+            Ok(Some(row)) => Ok(row),
+            Ok(None) => return,
+            Err(e) => Err(Error::Database(e)),
+        };
+        let failed = row.is_err();
+        if sender.send(row).await.is_err() || failed {
+            return;
+        }
+    }
+}
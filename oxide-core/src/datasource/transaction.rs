@@ -0,0 +1,217 @@
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+use sqlx::{
+    postgres::{PgArguments, PgQueryResult, PgRow},
+    FromRow, PgConnection, Postgres, Transaction,
+};
+
+use super::{PgDatabase, RowStream};
+use crate::Error;
+
+type Hook = Box<dyn FnOnce() + Send>;
+
+/// The isolation level [`PgDatabase::transaction_with`](crate::PgDatabase::transaction_with)
+/// runs a transaction at. Postgres defaults to `READ COMMITTED`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IsolationLevel {
+    #[default]
+    ReadCommitted,
+    RepeatableRead,
+    /// Fails with a serialization failure rather than let concurrent transactions see each
+    /// other's writes out of order, so it is usually paired with retries.
+    Serializable,
+}
+
+impl IsolationLevel {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            IsolationLevel::ReadCommitted => "READ COMMITTED",
+            IsolationLevel::RepeatableRead => "REPEATABLE READ",
+            IsolationLevel::Serializable => "SERIALIZABLE",
+        }
+    }
+}
+
+impl fmt::Display for IsolationLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_sql())
+    }
+}
+
+/// A database transaction that can defer work until it has actually committed.
+///
+/// Dereferences to the underlying connection, so statements run with `&mut *tx` as before.
+/// Dropping it without calling [`commit`](Self::commit) rolls back and discards any hooks.
+pub struct PgTransaction<'a> {
+    inner: Transaction<'a, Postgres>,
+    after_commit: Vec<Hook>,
+    db: &'a PgDatabase,
+}
+
+impl<'a> PgTransaction<'a> {
+    pub(crate) fn new(inner: Transaction<'a, Postgres>, db: &'a PgDatabase) -> Self {
+        Self {
+            inner,
+            after_commit: vec![],
+            db,
+        }
+    }
+
+    /// Registers `hook` to run once the transaction commits successfully, e.g. to invalidate
+    /// caches or publish events. Hooks run in registration order; spawn a task from the hook
+    /// for async work.
+    pub fn after_commit(&mut self, hook: impl FnOnce() + Send + 'static) -> &mut Self {
+        self.after_commit.push(Box::new(hook));
+        self
+    }
+
+    /// Commits, then runs the `after_commit` hooks. If the commit fails none of them run.
+    pub async fn commit(self) -> Result<(), Error> {
+        self.inner.commit().await.map_err(Error::Database)?;
+        for hook in self.after_commit {
+            hook();
+        }
+        Ok(())
+    }
+
+    /// Rolls back, discarding the `after_commit` hooks.
+    pub async fn rollback(self) -> Result<(), Error> {
+        self.inner.rollback().await.map_err(Error::Database)
+    }
+
+    /// Borrows the connection and the `after_commit` hooks together, without the lifetime of
+    /// the pool the transaction came from.
+    pub fn reborrow(&mut self) -> PgTransactionRef<'_> {
+        PgTransactionRef {
+            conn: &mut self.inner,
+            after_commit: &mut self.after_commit,
+            db: self.db,
+        }
+    }
+}
+
+impl Deref for PgTransaction<'_> {
+    type Target = PgConnection;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl DerefMut for PgTransaction<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+/// A borrowed [`PgTransaction`], from [`PgTransaction::reborrow`]. Dereferences to the
+/// connection, and can still register `after_commit` hooks. Statements run through its
+/// `query_*` and `execute_with` methods are logged and passed to the database's
+/// [`on_query`](PgDatabase::on_query) hooks like those run on the pool.
+pub struct PgTransactionRef<'a> {
+    conn: &'a mut PgConnection,
+    after_commit: &'a mut Vec<Hook>,
+    db: &'a PgDatabase,
+}
+
+impl<'a> PgTransactionRef<'a> {
+    /// The connection, for as long as the transaction is borrowed.
+    pub fn into_connection(self) -> &'a mut PgConnection {
+        self.conn
+    }
+
+    /// Registers `hook` to run once the borrowed transaction commits successfully.
+    pub fn after_commit(&mut self, hook: impl FnOnce() + Send + 'static) -> &mut Self {
+        self.after_commit.push(Box::new(hook));
+        self
+    }
+
+    /// A shorter-lived borrow of the same transaction.
+    pub fn reborrow(&mut self) -> PgTransactionRef<'_> {
+        PgTransactionRef {
+            conn: self.conn,
+            after_commit: self.after_commit,
+            db: self.db,
+        }
+    }
+
+    /// Like [`PgDatabase::query_with`], in the transaction.
+    pub async fn query_with<T>(self, query: String, args: PgArguments) -> Result<Vec<T>, Error>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        self.db
+            .observe(
+                &query,
+                sqlx::query_as_with::<_, T, _>(&query, args).fetch_all(self.conn),
+            )
+            .await
+    }
+
+    /// Like [`PgDatabase::query_one_with`], in the transaction.
+    pub async fn query_one_with<T>(self, query: String, args: PgArguments) -> Result<T, Error>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        self.db
+            .observe(
+                &query,
+                sqlx::query_as_with::<_, T, _>(&query, args).fetch_one(self.conn),
+            )
+            .await
+    }
+
+    /// Like [`PgDatabase::query_optional_with`], in the transaction.
+    pub async fn query_optional_with<T>(
+        self,
+        query: String,
+        args: PgArguments,
+    ) -> Result<Option<T>, Error>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        self.db
+            .observe(
+                &query,
+                sqlx::query_as_with::<_, T, _>(&query, args).fetch_optional(self.conn),
+            )
+            .await
+    }
+
+    /// Like [`PgDatabase::execute_with`], in the transaction.
+    pub async fn execute_with(
+        self,
+        query: String,
+        args: PgArguments,
+    ) -> Result<PgQueryResult, Error> {
+        self.db
+            .observe(&query, sqlx::query_with(&query, args).execute(self.conn))
+            .await
+    }
+
+    /// Like [`PgDatabase::query_stream_with`], in the transaction. The stream holds the
+    /// borrow until it ends or is dropped.
+    pub fn query_stream_with<T>(self, query: String, args: PgArguments) -> RowStream<'a, T>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin + 'a,
+    {
+        self.db.stream_on(self.conn, query, args)
+    }
+}
+
+impl Deref for PgTransactionRef<'_> {
+    type Target = PgConnection;
+
+    fn deref(&self) -> &Self::Target {
+        self.conn
+    }
+}
+
+impl DerefMut for PgTransactionRef<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.conn
+    }
+}
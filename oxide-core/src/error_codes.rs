@@ -0,0 +1,235 @@
+//! Stable codes for every [`Error`](crate::Error) the framework reports.
+//!
+//! Each error carries a code such as `OXIDE-DB-0005`, sent as `oxide_code` in error response
+//! bodies and written at the start of error logs, so clients and dashboards can branch on it
+//! instead of parsing messages. A code keeps its meaning once published: codes are added,
+//! never renumbered or reused. [`REGISTRY`] lists them all and serializes to JSON for
+//! publishing alongside an API's documentation:
+//!
+//! ```rust,ignore
+//! #[handler]
+//! async fn error_codes(_ctx: &Context) -> OxideResponse {
+//!     OxideResponse::json(OxideRes::Success, oxide_core::error_codes::REGISTRY)
+//! }
+//!
+//! server.router.get("/errors", error_codes);
+//! ```
+
+use serde::Serialize;
+
+/// A stable error code with the status it's reported with and what it means.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ErrorCode {
+    pub code: &'static str,
+    pub status: u16,
+    pub title: &'static str,
+    pub description: &'static str,
+}
+
+impl ErrorCode {
+    const fn new(
+        code: &'static str,
+        status: u16,
+        title: &'static str,
+        description: &'static str,
+    ) -> Self {
+        Self {
+            code,
+            status,
+            title,
+            description,
+        }
+    }
+
+    /// The registered code `code`, e.g. `OXIDE-DB-0002`.
+    pub fn lookup(code: &str) -> Option<&'static ErrorCode> {
+        REGISTRY.iter().find(|entry| entry.code == code)
+    }
+}
+
+pub const BAD_REQUEST: ErrorCode = ErrorCode::new(
+    "OXIDE-HTTP-0001",
+    400,
+    "Bad request",
+    "The request was malformed or missing required data.",
+);
+pub const UNAUTHORIZED: ErrorCode = ErrorCode::new(
+    "OXIDE-HTTP-0002",
+    401,
+    "Unauthorized",
+    "The request lacks valid credentials.",
+);
+pub const FORBIDDEN: ErrorCode = ErrorCode::new(
+    "OXIDE-HTTP-0003",
+    403,
+    "Forbidden",
+    "The caller is not allowed to perform this operation.",
+);
+pub const NOT_FOUND: ErrorCode = ErrorCode::new(
+    "OXIDE-HTTP-0004",
+    404,
+    "Not found",
+    "The requested resource does not exist.",
+);
+pub const SERVICE_UNAVAILABLE: ErrorCode = ErrorCode::new(
+    "OXIDE-HTTP-0005",
+    503,
+    "Service unavailable",
+    "A dependency the request needs is unavailable; retry after the time given.",
+);
+pub const INTERNAL: ErrorCode = ErrorCode::new(
+    "OXIDE-SRV-0001",
+    500,
+    "Internal server error",
+    "The server failed to handle the request.",
+);
+pub const PANIC: ErrorCode = ErrorCode::new(
+    "OXIDE-SRV-0002",
+    500,
+    "Handler panicked",
+    "The route's handler panicked while handling the request.",
+);
+pub const CUSTOM: ErrorCode = ErrorCode::new(
+    "OXIDE-SRV-0003",
+    500,
+    "Application error",
+    "An error raised by application code without a more specific code.",
+);
+pub const DATABASE: ErrorCode = ErrorCode::new(
+    "OXIDE-DB-0001",
+    500,
+    "Database error",
+    "A database operation failed for a reason without a more specific code.",
+);
+pub const ROW_NOT_FOUND: ErrorCode = ErrorCode::new(
+    "OXIDE-DB-0002",
+    500,
+    "Row not found",
+    "A query expected to return a row returned none.",
+);
+pub const POOL_TIMED_OUT: ErrorCode = ErrorCode::new(
+    "OXIDE-DB-0003",
+    500,
+    "Connection pool timed out",
+    "No database connection became available in time.",
+);
+pub const POOL_CLOSED: ErrorCode = ErrorCode::new(
+    "OXIDE-DB-0004",
+    500,
+    "Connection pool closed",
+    "The database pool was closed, usually because the server is shutting down.",
+);
+pub const UNIQUE_VIOLATION: ErrorCode = ErrorCode::new(
+    "OXIDE-DB-0005",
+    500,
+    "Unique constraint violated",
+    "A write would have duplicated a value that must be unique.",
+);
+pub const FOREIGN_KEY_VIOLATION: ErrorCode = ErrorCode::new(
+    "OXIDE-DB-0006",
+    500,
+    "Foreign key constraint violated",
+    "A write referred to a row that does not exist, or removed one still referred to.",
+);
+pub const NOT_NULL_VIOLATION: ErrorCode = ErrorCode::new(
+    "OXIDE-DB-0007",
+    500,
+    "Not-null constraint violated",
+    "A write left a required column empty.",
+);
+pub const CHECK_VIOLATION: ErrorCode = ErrorCode::new(
+    "OXIDE-DB-0008",
+    500,
+    "Check constraint violated",
+    "A write failed one of the table's check constraints.",
+);
+pub const SERIALIZATION_FAILURE: ErrorCode = ErrorCode::new(
+    "OXIDE-DB-0009",
+    500,
+    "Transaction serialization failure",
+    "The transaction conflicted with a concurrent one and can be retried.",
+);
+pub const DEADLOCK: ErrorCode = ErrorCode::new(
+    "OXIDE-DB-0010",
+    500,
+    "Deadlock detected",
+    "The transaction was aborted to break a deadlock and can be retried.",
+);
+pub const QUERY_CANCELED: ErrorCode = ErrorCode::new(
+    "OXIDE-DB-0011",
+    500,
+    "Query canceled",
+    "The query was canceled, usually by a statement timeout.",
+);
+pub const DATABASE_CONNECTION: ErrorCode = ErrorCode::new(
+    "OXIDE-DB-0012",
+    500,
+    "Database connection failed",
+    "The database could not be reached or the connection was lost.",
+);
+pub const DECODE: ErrorCode = ErrorCode::new(
+    "OXIDE-DB-0013",
+    500,
+    "Row decoding failed",
+    "A column could not be decoded into the type the query expected.",
+);
+pub const VALIDATION: ErrorCode = ErrorCode::new(
+    "OXIDE-VAL-0001",
+    400,
+    "Validation failed",
+    "The request's data failed validation.",
+);
+pub const CONFIG: ErrorCode = ErrorCode::new(
+    "OXIDE-CFG-0001",
+    500,
+    "Configuration error",
+    "The server or one of its components is misconfigured.",
+);
+pub const SERIALIZATION: ErrorCode = ErrorCode::new(
+    "OXIDE-SER-0001",
+    500,
+    "Serialization failed",
+    "A value could not be serialized.",
+);
+pub const DESERIALIZATION: ErrorCode = ErrorCode::new(
+    "OXIDE-SER-0002",
+    400,
+    "Deserialization failed",
+    "The input could not be parsed into the expected shape.",
+);
+pub const IO: ErrorCode = ErrorCode::new(
+    "OXIDE-IO-0001",
+    500,
+    "I/O error",
+    "A file or network operation failed.",
+);
+
+/// Every code, in registration order.
+pub static REGISTRY: &[ErrorCode] = &[
+    BAD_REQUEST,
+    UNAUTHORIZED,
+    FORBIDDEN,
+    NOT_FOUND,
+    INTERNAL,
+    PANIC,
+    CUSTOM,
+    DATABASE,
+    ROW_NOT_FOUND,
+    POOL_TIMED_OUT,
+    POOL_CLOSED,
+    UNIQUE_VIOLATION,
+    FOREIGN_KEY_VIOLATION,
+    NOT_NULL_VIOLATION,
+    CHECK_VIOLATION,
+    SERIALIZATION_FAILURE,
+    DEADLOCK,
+    QUERY_CANCELED,
+    DATABASE_CONNECTION,
+    DECODE,
+    VALIDATION,
+    CONFIG,
+    SERIALIZATION,
+    DESERIALIZATION,
+    IO,
+    SERVICE_UNAVAILABLE,
+];
@@ -1,3 +1,5 @@
+use crate::config::Environment;
+use crate::error_codes::{self, ErrorCode};
 use sqlx::Error as SqlxError;
 use std::fmt;
 
@@ -9,6 +11,7 @@ pub enum Error {
     Forbidden(String),
     NotFound(String),
     InternalServer(String),
+    ServiceUnavailable(String),
 
     // Database errors
     Database(SqlxError),
@@ -38,6 +41,7 @@ impl Error {
             Error::Forbidden(_) => 403,
             Error::NotFound(_) => 404,
             Error::InternalServer(_) => 500,
+            Error::ServiceUnavailable(_) => 503,
             Error::Database(_) => 500,
             Error::Validation(_) => 400,
             Error::Config(_) => 500,
@@ -55,6 +59,7 @@ impl Error {
             Error::Forbidden(_) => "FORBIDDEN",
             Error::NotFound(_) => "NOT_FOUND",
             Error::InternalServer(_) => "INTERNAL_SERVER_ERROR",
+            Error::ServiceUnavailable(_) => "SERVICE_UNAVAILABLE",
             Error::Database(_) => "DATABASE_ERROR",
             Error::Validation(_) => "VALIDATION_ERROR",
             Error::Config(_) => "CONFIG_ERROR",
@@ -64,9 +69,77 @@ impl Error {
             Error::Custom(_) => "CUSTOM_ERROR",
         }
     }
+
+    /// The stable code identifying this error, see [`error_codes`].
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::BadRequest(_) => error_codes::BAD_REQUEST,
+            Error::Unauthorized(_) => error_codes::UNAUTHORIZED,
+            Error::Forbidden(_) => error_codes::FORBIDDEN,
+            Error::NotFound(_) => error_codes::NOT_FOUND,
+            Error::InternalServer(_) => error_codes::INTERNAL,
+            Error::ServiceUnavailable(_) => error_codes::SERVICE_UNAVAILABLE,
+            Error::Database(e) => database_code(e),
+            Error::Validation(_) => error_codes::VALIDATION,
+            Error::Config(_) => error_codes::CONFIG,
+            Error::Serialization(_) => error_codes::SERIALIZATION,
+            Error::Deserialization(_) => error_codes::DESERIALIZATION,
+            Error::Io(_) => error_codes::IO,
+            Error::Custom(_) => error_codes::CUSTOM,
+        }
+    }
+
+    /// Whether the same operation could succeed if tried again: lost or refused database
+    /// connections, an exhausted pool, serialization failures and deadlocks between
+    /// transactions, I/O errors and unavailable dependencies.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Database(e) => match e {
+                SqlxError::PoolTimedOut | SqlxError::Io(_) | SqlxError::Tls(_) => true,
+                SqlxError::Database(e) => e.code().is_some_and(|code| {
+                    // Connection exceptions, serialization failures, deadlocks, and the
+                    // server shutting down or still starting.
+                    code.starts_with("08")
+                        || matches!(&*code, "40001" | "40P01" | "57P01" | "57P02" | "57P03")
+                }),
+                _ => false,
+            },
+            Error::Io(_) | Error::ServiceUnavailable(_) => true,
+            _ => false,
+        }
+    }
+}
+
+fn database_code(error: &SqlxError) -> ErrorCode {
+    match error {
+        SqlxError::RowNotFound => error_codes::ROW_NOT_FOUND,
+        SqlxError::PoolTimedOut => error_codes::POOL_TIMED_OUT,
+        SqlxError::PoolClosed => error_codes::POOL_CLOSED,
+        SqlxError::Io(_) | SqlxError::Tls(_) => error_codes::DATABASE_CONNECTION,
+        SqlxError::ColumnDecode { .. } | SqlxError::Decode(_) => error_codes::DECODE,
+        SqlxError::Database(e) => match e.code().as_deref() {
+            Some("23505") => error_codes::UNIQUE_VIOLATION,
+            Some("23503") => error_codes::FOREIGN_KEY_VIOLATION,
+            Some("23502") => error_codes::NOT_NULL_VIOLATION,
+            Some("23514") => error_codes::CHECK_VIOLATION,
+            Some("40001") => error_codes::SERIALIZATION_FAILURE,
+            Some("40P01") => error_codes::DEADLOCK,
+            Some("57014") => error_codes::QUERY_CANCELED,
+            _ => error_codes::DATABASE,
+        },
+        _ => error_codes::DATABASE,
+    }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Database(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -76,6 +149,7 @@ impl fmt::Display for Error {
             Error::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
             Error::NotFound(msg) => write!(f, "Not Found: {}", msg),
             Error::InternalServer(msg) => write!(f, "Internal Server Error: {}", msg),
+            Error::ServiceUnavailable(msg) => write!(f, "Service Unavailable: {}", msg),
             Error::Database(e) => write!(f, "Database Error: {}", e),
             Error::Validation(msg) => write!(f, "Validation Error: {}", msg),
             Error::Config(msg) => write!(f, "Configuration Error: {}", msg),
@@ -120,18 +194,39 @@ pub trait IntoResponse {
 
 impl IntoResponse for Error {
     fn into_response(self) -> Vec<u8> {
-        let error_response = serde_json::json!({
-            "error": {
-                "type": self.error_type(),
-                "message": self.to_string(),
-                "status": self.status_code()
-            }
-        });
+        let status = self.status_code();
+        let error_response = if Environment::current().is_production() {
+            // Terse in production: never leak internal details of server errors.
+            let message = if status >= 500 {
+                "Internal Server Error".to_string()
+            } else {
+                self.to_string()
+            };
+            serde_json::json!({
+                "error": {
+                    "type": self.error_type(),
+                    "oxide_code": self.code().code,
+                    "message": message,
+                    "status": status
+                }
+            })
+        } else {
+            serde_json::json!({
+                "error": {
+                    "type": self.error_type(),
+                    "oxide_code": self.code().code,
+                    "message": self.to_string(),
+                    "status": status,
+                    "detail": format!("{:?}", self)
+                }
+            })
+        };
 
         serde_json::to_vec(&error_response).unwrap_or_else(|_| {
             serde_json::to_vec(&serde_json::json!({
                 "error": {
                     "type": "INTERNAL_SERVER_ERROR",
+                    "oxide_code": error_codes::SERIALIZATION.code,
                     "message": "Failed to serialize error response",
                     "status": 500
                 }
@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use super::{handler::Res, BufferBuilder};
+use crate::{logger::LogLevel, retry::roll, Logger};
+
+/// A fault [`Chaos`] applies while writing a response, carried on the [`Res`] to the
+/// connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Close the connection without writing the response.
+    Drop,
+    /// Write the response `chunk` bytes at a time, waiting `delay` between them.
+    SlowBody { chunk: usize, delay: Duration },
+}
+
+/// Makes the server misbehave on purpose, so clients' timeouts, retries and circuit
+/// breakers can be tested against it: added latency, random 500s, dropped connections and
+/// responses trickled out slowly. Each fault is rolled independently per request.
+///
+/// Chaos only applies outside production; a production server logs a warning and ignores
+/// it.
+///
+/// ```rust,ignore
+/// server.with_chaos(
+///     Chaos::new()
+///         .with_route("/api/payments")
+///         .with_latency(Duration::from_millis(50), Duration::from_millis(800))
+///         .with_error_rate(0.1)
+///         .with_drop_rate(0.02),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Chaos {
+    routes: Vec<String>,
+    latency: Option<(Duration, Duration)>,
+    error_rate: f64,
+    drop_rate: f64,
+    slow_body_rate: f64,
+    slow_body: Fault,
+}
+
+impl Default for Chaos {
+    fn default() -> Self {
+        Self {
+            routes: vec![],
+            latency: None,
+            error_rate: 0.0,
+            drop_rate: 0.0,
+            slow_body_rate: 0.0,
+            slow_body: Fault::Drop,
+        }
+    }
+}
+
+impl Chaos {
+    /// Injects nothing until faults are configured, on every route until some are given.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits faults to routes whose pattern starts with `prefix`; may be called repeatedly.
+    pub fn with_route(mut self, prefix: &str) -> Self {
+        self.routes.push(prefix.to_string());
+        self
+    }
+
+    /// Delays every affected request by a random duration between `min` and `max` before
+    /// it is handled.
+    pub fn with_latency(mut self, min: Duration, max: Duration) -> Self {
+        self.latency = Some((min.min(max), max.max(min)));
+        self
+    }
+
+    /// Answers this share of requests, between 0 and 1, with a 500 without running the
+    /// handler.
+    pub fn with_error_rate(mut self, rate: f64) -> Self {
+        self.error_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Closes the connection without a response for this share of requests, after the
+    /// handler has run.
+    pub fn with_drop_rate(mut self, rate: f64) -> Self {
+        self.drop_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Writes this share of responses `chunk` bytes at a time with `delay` between chunks.
+    pub fn with_slow_body(mut self, rate: f64, chunk: usize, delay: Duration) -> Self {
+        self.slow_body_rate = rate.clamp(0.0, 1.0);
+        self.slow_body = Fault::SlowBody {
+            chunk: chunk.max(1),
+            delay,
+        };
+        self
+    }
+
+    /// The faults configured, for the startup summary.
+    pub fn describe(&self) -> String {
+        let mut faults = vec![];
+        if let Some((min, max)) = self.latency {
+            faults.push(format!("{}-{}ms latency", min.as_millis(), max.as_millis()));
+        }
+        for (rate, fault) in [
+            (self.error_rate, "errors"),
+            (self.drop_rate, "drops"),
+            (self.slow_body_rate, "slow bodies"),
+        ] {
+            if rate > 0.0 {
+                faults.push(format!("{}% {}", rate * 100.0, fault));
+            }
+        }
+        if faults.is_empty() {
+            faults.push("no faults".to_string());
+        }
+        if self.routes.is_empty() {
+            format!("{} on all routes", faults.join(", "))
+        } else {
+            format!("{} on {}*", faults.join(", "), self.routes.join("*, "))
+        }
+    }
+
+    /// Applies latency to a request for `route` and rolls its faults. Returns the injected
+    /// 500 response, or the fault to apply when writing the real one.
+    pub async fn inject(&self, route: &str) -> Result<Option<Fault>, Res> {
+        if !self.routes.is_empty() && !self.routes.iter().any(|p| route.starts_with(p)) {
+            return Ok(None);
+        }
+        let logger = Logger::for_target(module_path!());
+
+        if let Some((min, max)) = self.latency {
+            let delay = min + (max - min).mul_f64(roll());
+            logger.log(
+                LogLevel::Debug,
+                &format!("Chaos: delaying {} by {}ms", route, delay.as_millis()),
+            );
+            tokio::time::sleep(delay).await;
+        }
+        if roll() < self.error_rate {
+            logger.log(LogLevel::Debug, &format!("Chaos: failing {}", route));
+            return Err(Res::new(
+                BufferBuilder::server_error()
+                    .header("X-Oxide-Chaos", "error")
+                    .text("Internal Server Error")
+                    .build(),
+                500,
+            ));
+        }
+        if roll() < self.drop_rate {
+            logger.log(LogLevel::Debug, &format!("Chaos: dropping {}", route));
+            return Ok(Some(Fault::Drop));
+        }
+        if roll() < self.slow_body_rate {
+            logger.log(LogLevel::Debug, &format!("Chaos: slowing {}", route));
+            return Ok(Some(self.slow_body));
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use serde::Serialize;
+use tokio::sync::OnceCell;
+
+use super::Context;
+
+/// Headers whose values are part of the coalescing key, since responses commonly differ by
+/// who is asking and what they accept.
+const VARY: [&str; 4] = ["authorization", "cookie", "accept", "accept-language"];
+
+/// Runs concurrent identical GETs to routes registered with `.coalesce()` once, sharing the
+/// first request's response with every request that arrives while it's in flight, so a burst
+/// on a hot endpoint costs one trip to the database instead of one per request.
+///
+/// Requests are identical when they have the same path and query string, tenant, principal,
+/// and `Authorization`, `Cookie`, `Accept` and `Accept-Language` headers, plus any headers the
+/// route names with `.coalesce_by(...)`. Nothing is kept once the response is ready; this only
+/// collapses requests that overlap. If the first request's connection drops, one of those
+/// waiting runs the handler instead, and a response that sets a cookie is never shared: each
+/// waiting request runs the handler for its own.
+#[derive(Debug, Default)]
+pub struct Coalescer {
+    in_flight: Mutex<HashMap<String, Arc<OnceCell<Shared>>>>,
+    handled: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+/// A response's status and raw bytes, as shared with coalesced requests.
+pub(crate) type Shared = (u16, Vec<u8>);
+
+/// Point-in-time view of a [`Coalescer`], suitable for metrics endpoints.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CoalescerStats {
+    /// Distinct requests currently being handled.
+    pub in_flight: usize,
+    /// Requests whose handler ran.
+    pub handled: u64,
+    /// Requests answered with another request's response.
+    pub coalesced: u64,
+}
+
+impl Coalescer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stats(&self) -> CoalescerStats {
+        CoalescerStats {
+            in_flight: self.in_flight.lock().map(|m| m.len()).unwrap_or(0),
+            handled: self.handled.load(Ordering::Relaxed),
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Identifies requests to `route` that can share a response, varying on `vary` headers
+    /// as well as the defaults.
+    pub(crate) fn key(route: &str, vary: &[String], ctx: &Context) -> String {
+        let mut key = format!(
+            "{}\n{}\n{}\n{}",
+            route,
+            ctx.request.path,
+            ctx.tenant().unwrap_or_default(),
+            ctx.principal().unwrap_or_default()
+        );
+        for header in VARY.into_iter().chain(vary.iter().map(String::as_str)) {
+            key.push('\n');
+            key.push_str(ctx.request.headers.get(header).map_or("", |v| v.as_str()));
+        }
+        key
+    }
+
+    /// The response for `key`: produced by `handle` if no identical request is in flight,
+    /// otherwise the in-flight one's. The flag is `true` when `handle` ran.
+    pub(crate) async fn run<F, Fut>(&self, key: String, handle: F) -> (Shared, bool)
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Shared>,
+    {
+        let cell = self
+            .in_flight
+            .lock()
+            .ok()
+            .map(|mut in_flight| Arc::clone(in_flight.entry(key.clone()).or_default()));
+        let Some(cell) = cell else {
+            return (handle().await, true);
+        };
+
+        let mut handle = Some(handle);
+        let mut handled = false;
+        let mut shared = cell
+            .get_or_init(|| {
+                handled = true;
+                handle.take().expect("handler runs once")()
+            })
+            .await
+            .clone();
+        if let Some(handle) = handle.filter(|_| sets_cookie(&shared.1)) {
+            shared = handle().await;
+            handled = true;
+        }
+
+        if let Ok(mut in_flight) = self.in_flight.lock() {
+            if in_flight.get(&key).is_some_and(|c| Arc::ptr_eq(c, &cell)) {
+                in_flight.remove(&key);
+            }
+        }
+        if handled {
+            self.handled.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.coalesced.fetch_add(1, Ordering::Relaxed);
+        }
+        (shared, handled)
+    }
+}
+
+/// Whether a built response has a `Set-Cookie` header, making it specific to the one request
+/// it answers.
+pub(crate) fn sets_cookie(buffer: &[u8]) -> bool {
+    let head = buffer
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map_or(buffer, |end| &buffer[..end]);
+    head.split(|&b| b == b'\n')
+        .any(|line| line.len() > 11 && line[..11].eq_ignore_ascii_case(b"set-cookie:"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicU64, Ordering},
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::http::{
+        handler::OxideRes, response_cache::ResponseCache, routes::Route, AsyncResponse, HttpMethod,
+        HttpRequest, OxideResponse,
+    };
+
+    fn ok(_ctx: &Context) -> AsyncResponse<'_> {
+        Box::pin(async { OxideResponse::text(OxideRes::Success, "ok") })
+    }
+
+    fn context(route: &Route, principal: Option<&str>, headers: &[(&str, &str)]) -> Context {
+        let headers = headers
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let request = HttpRequest::new(
+            HttpMethod::Get,
+            "/reports".to_string(),
+            headers,
+            vec![],
+            HashMap::new(),
+            HashMap::new(),
+            HashMap::new(),
+        );
+        let mut ctx = Context::new(request, HashMap::new());
+        ctx.with_route(route);
+        if let Some(principal) = principal {
+            ctx.with_principal(principal);
+        }
+        ctx
+    }
+
+    #[test]
+    fn keys_differ_by_principal() {
+        let route = Route::new("/reports", HttpMethod::Get, ok);
+        let alice = context(&route, Some("alice"), &[]);
+        let bob = context(&route, Some("bob"), &[]);
+        let anonymous = context(&route, None, &[]);
+        assert_ne!(alice.response_key(), bob.response_key());
+        assert_ne!(alice.response_key(), anonymous.response_key());
+        assert_eq!(
+            alice.response_key(),
+            context(&route, Some("alice"), &[]).response_key()
+        );
+    }
+
+    #[test]
+    fn keys_differ_by_route_sharing_a_pattern() {
+        let admin = Route::new("/reports", HttpMethod::Get, ok);
+        let public = Route::new("/reports", HttpMethod::Get, ok);
+        assert_ne!(
+            context(&admin, None, &[]).response_key(),
+            context(&public, None, &[]).response_key()
+        );
+    }
+
+    #[test]
+    fn keys_differ_by_vary_headers() {
+        let mut route = Route::new("/reports", HttpMethod::Get, ok);
+        route.coalesce_vary = vec!["x-api-key".to_string()];
+        assert_ne!(
+            context(&route, None, &[("x-api-key", "a")]).response_key(),
+            context(&route, None, &[("x-api-key", "b")]).response_key()
+        );
+    }
+
+    #[tokio::test]
+    async fn cached_responses_are_not_shared_between_principals() {
+        let route = Route::new("/reports", HttpMethod::Get, ok);
+        let cache = Arc::new(ResponseCache::new(16));
+        let runs = AtomicU64::new(0);
+        let run = |principal: &'static str| {
+            let mut ctx = context(&route, Some(principal), &[]);
+            ctx.with_response_cache(Arc::clone(&cache));
+            let runs = &runs;
+            async move {
+                ctx.cached(&["reports"], Duration::from_secs(60), || async {
+                    runs.fetch_add(1, Ordering::SeqCst);
+                    OxideResponse::text(OxideRes::Success, principal)
+                })
+                .await
+            }
+        };
+        run("alice").await;
+        run("alice").await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        run("bob").await;
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+}
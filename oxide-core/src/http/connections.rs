@@ -0,0 +1,63 @@
+use serde::Serialize;
+
+use super::{handler::Res, BufferBuilder, Guard, HttpMethod, HttpRequest};
+use crate::server::{ConnectionInfo, ConnectionTracker};
+
+/// Admin endpoint listing open connections as JSON, enabled with
+/// [`Server::with_connections_page`](crate::Server::with_connections_page). Only requests
+/// passing its guard see it; others fall through to the router.
+#[derive(Debug)]
+pub struct ConnectionsPage {
+    path: String,
+    guard: Guard,
+    connections: ConnectionTracker,
+}
+
+#[derive(Serialize)]
+struct Listing {
+    open: usize,
+    connections: Vec<ConnectionInfo>,
+}
+
+impl ConnectionsPage {
+    pub fn new(path: &str, guard: Guard, connections: ConnectionTracker) -> Self {
+        Self {
+            path: path.trim_end_matches('/').to_string(),
+            guard,
+            connections,
+        }
+    }
+
+    /// Whether the raw request targets the page, checked before parsing it.
+    pub fn targets(&self, buffer: &[u8]) -> bool {
+        let line = buffer.split(|&b| b == b'\n').next().unwrap_or_default();
+        let target = line.split(|&b| b == b' ').nth(1).unwrap_or_default();
+        target.starts_with(self.path.as_bytes())
+    }
+
+    /// Serves the listing if `request` targets it and passes the guard. The request asking
+    /// is itself listed, in the handling state.
+    pub async fn serve(&self, request: &HttpRequest) -> Option<Res> {
+        let path = request.path.split('?').next().unwrap_or("");
+        if request.method != HttpMethod::Get || path.trim_end_matches('/') != self.path {
+            return None;
+        }
+        if !self.guard.check(request) {
+            return None;
+        }
+
+        let connections = self.connections.snapshot();
+        let listing = Listing {
+            open: connections.len(),
+            connections,
+        };
+        let body = serde_json::to_string(&listing).unwrap_or_default();
+        Some(Res::new(
+            BufferBuilder::ok()
+                .header("Cache-Control", "no-store")
+                .json(body)
+                .build(),
+            200,
+        ))
+    }
+}
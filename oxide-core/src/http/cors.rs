@@ -0,0 +1,171 @@
+use super::HttpMethod;
+
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    allowed_origins: Vec<String>,
+    allowed_headers: Vec<String>,
+    allow_credentials: bool,
+    max_age: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec!["*".to_string()],
+            allowed_headers: vec![],
+            allow_credentials: false,
+            max_age: 86400,
+        }
+    }
+}
+
+impl CorsConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restricts the allowed origins. Calling this replaces the default wildcard.
+    pub fn allow_origin(mut self, origin: impl Into<String>) -> Self {
+        self.allowed_origins.retain(|o| o != "*");
+        self.allowed_origins.push(origin.into());
+        self
+    }
+
+    pub fn allow_header(mut self, header: impl Into<String>) -> Self {
+        self.allowed_headers.push(header.into());
+        self
+    }
+
+    /// Lets browsers send cookies and credentials cross-origin. Only origins added with
+    /// [`allow_origin`](Self::allow_origin) are then allowed; the default wildcard allows none.
+    pub fn allow_credentials(mut self, allow: bool) -> Self {
+        self.allow_credentials = allow;
+        self
+    }
+
+    /// How long (in seconds) browsers may cache a preflight response.
+    pub fn max_age(mut self, seconds: u64) -> Self {
+        self.max_age = seconds;
+        self
+    }
+
+    /// Returns the value to echo in `Access-Control-Allow-Origin`, if the origin is allowed.
+    /// A wildcard never allows credentialed requests, as browsers refuse `*` with them and
+    /// echoing any origin instead would let every site make them.
+    pub fn resolve_origin(&self, origin: &str) -> Option<String> {
+        if self.allowed_origins.iter().any(|o| o == origin) {
+            Some(origin.to_string())
+        } else if self.allowed_origins.iter().any(|o| o == "*") && !self.allow_credentials {
+            Some("*".to_string())
+        } else {
+            None
+        }
+    }
+
+    /// Builds the headers for a preflight response. `methods` are the methods actually
+    /// registered for the requested path, so no static method list needs to be configured.
+    pub fn preflight_headers(
+        &self,
+        origin: &str,
+        methods: &[HttpMethod],
+        requested_headers: Option<&str>,
+    ) -> Vec<(String, String)> {
+        let allow_origin = match self.resolve_origin(origin) {
+            Some(origin) => origin,
+            None => return vec![],
+        };
+
+        let allow_methods = methods
+            .iter()
+            .map(|m| m.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let allow_headers = if self.allowed_headers.is_empty() {
+            requested_headers.unwrap_or("").to_string()
+        } else {
+            self.allowed_headers.join(", ")
+        };
+
+        let mut headers = vec![
+            ("Access-Control-Allow-Origin".to_string(), allow_origin),
+            ("Access-Control-Allow-Methods".to_string(), allow_methods),
+            (
+                "Access-Control-Max-Age".to_string(),
+                self.max_age.to_string(),
+            ),
+            ("Vary".to_string(), "Origin".to_string()),
+        ];
+        if !allow_headers.is_empty() {
+            headers.push(("Access-Control-Allow-Headers".to_string(), allow_headers));
+        }
+        if self.allow_credentials {
+            headers.push((
+                "Access-Control-Allow-Credentials".to_string(),
+                "true".to_string(),
+            ));
+        }
+        headers
+    }
+
+    /// Headers attached to regular (non-preflight) responses for cross-origin requests.
+    pub fn response_headers(&self, origin: &str) -> Vec<(String, String)> {
+        let allow_origin = match self.resolve_origin(origin) {
+            Some(origin) => origin,
+            None => return vec![],
+        };
+
+        let mut headers = vec![
+            ("Access-Control-Allow-Origin".to_string(), allow_origin),
+            ("Vary".to_string(), "Origin".to_string()),
+        ];
+        if self.allow_credentials {
+            headers.push((
+                "Access-Control-Allow-Credentials".to_string(),
+                "true".to_string(),
+            ));
+        }
+        headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listed_origin_is_echoed() {
+        let cors = CorsConfig::new().allow_origin("https://app.example.com");
+        assert_eq!(
+            cors.resolve_origin("https://app.example.com").as_deref(),
+            Some("https://app.example.com")
+        );
+    }
+
+    #[test]
+    fn unlisted_origin_is_refused() {
+        let cors = CorsConfig::new()
+            .allow_origin("https://app.example.com")
+            .allow_credentials(true);
+        assert_eq!(cors.resolve_origin("https://evil.example.com"), None);
+        assert!(cors.response_headers("https://evil.example.com").is_empty());
+    }
+
+    #[test]
+    fn wildcard_allows_any_origin_without_credentials() {
+        let cors = CorsConfig::new();
+        assert_eq!(
+            cors.resolve_origin("https://any.example.com").as_deref(),
+            Some("*")
+        );
+    }
+
+    #[test]
+    fn wildcard_with_credentials_allows_none() {
+        let cors = CorsConfig::new().allow_credentials(true);
+        assert_eq!(cors.resolve_origin("https://any.example.com"), None);
+        assert!(cors
+            .preflight_headers("https://any.example.com", &[HttpMethod::Get], None)
+            .is_empty());
+    }
+}
@@ -0,0 +1,230 @@
+use std::sync::Arc;
+
+use super::{
+    error_page::escape, handler::Res, recorder::iso8601, BufferBuilder, Guard, HttpMethod,
+    HttpRequest,
+};
+use crate::messaging::{DeadLetter, DeadLetters, MessageBus};
+
+/// Most recent dead letters listed on the page.
+const PAGE_SIZE: i64 = 100;
+
+/// Admin page listing dead-lettered messages with buttons to retry or discard each, enabled
+/// with [`Server::with_dead_letter_page`](crate::Server::with_dead_letter_page). Only
+/// requests passing its guard see it; others fall through to the router. Retries and
+/// discards must come from the page's own origin, as browsers report in `Origin` or
+/// `Referer`, so another site can't submit them with an admin's cookies.
+#[derive(Debug)]
+pub struct DeadLetterPage {
+    path: String,
+    guard: Guard,
+    dead_letters: Arc<DeadLetters>,
+    message_bus: Option<Arc<dyn MessageBus>>,
+}
+
+impl DeadLetterPage {
+    pub fn new(
+        path: &str,
+        guard: Guard,
+        dead_letters: Arc<DeadLetters>,
+        message_bus: Option<Arc<dyn MessageBus>>,
+    ) -> Self {
+        Self {
+            path: path.trim_end_matches('/').to_string(),
+            guard,
+            dead_letters,
+            message_bus,
+        }
+    }
+
+    /// Whether the raw request targets the page, checked before parsing it.
+    pub fn targets(&self, buffer: &[u8]) -> bool {
+        let line = buffer.split(|&b| b == b'\n').next().unwrap_or_default();
+        let target = line.split(|&b| b == b' ').nth(1).unwrap_or_default();
+        target.starts_with(self.path.as_bytes())
+    }
+
+    /// Serves the page and its actions if `request` targets them and passes the guard.
+    pub async fn serve(&self, request: &HttpRequest) -> Option<Res> {
+        let path = request.path.split('?').next().unwrap_or("");
+        let rest = path.strip_prefix(self.path.as_str())?;
+        if !self.guard.check(request) {
+            return None;
+        }
+
+        let action = match (&request.method, rest.trim_end_matches('/')) {
+            (HttpMethod::Get, "") => return Some(self.list(request).await),
+            (HttpMethod::Post, rest) => rest.strip_prefix('/')?.split_once('/')?,
+            _ => return None,
+        };
+        if !same_origin(request) {
+            return Some(Res::new(
+                BufferBuilder::new()
+                    .status((403, "Forbidden"))
+                    .text("Cross-origin request refused")
+                    .build(),
+                403,
+            ));
+        }
+        let id = action.0.parse::<i64>().ok()?;
+        let result = match action.1 {
+            "retry" => match &self.message_bus {
+                Some(bus) => self.dead_letters.retry(id, bus.as_ref()).await,
+                None => return Some(error("No message bus configured to retry with")),
+            },
+            "discard" => self.dead_letters.discard(id).await,
+            _ => return None,
+        };
+        Some(match result {
+            Ok(_) => Res::new(
+                BufferBuilder::new()
+                    .status((303, "See Other"))
+                    .header("Location", &self.path)
+                    .build(),
+                303,
+            ),
+            Err(e) => error(&e.to_string()),
+        })
+    }
+
+    async fn list(&self, request: &HttpRequest) -> Res {
+        let topic = request
+            .query_params
+            .get("topic")
+            .map(|topic| percent_decode(topic));
+        let (counts, dead_letters) = match (
+            self.dead_letters.counts().await,
+            self.dead_letters.list(topic.as_deref(), PAGE_SIZE).await,
+        ) {
+            (Ok(counts), Ok(dead_letters)) => (counts, dead_letters),
+            (Err(e), _) | (_, Err(e)) => return error(&e.to_string()),
+        };
+        Res::new(
+            BufferBuilder::ok()
+                .html(self.html(&counts, &dead_letters))
+                .build(),
+            200,
+        )
+    }
+
+    fn html(&self, counts: &[(String, i64)], dead_letters: &[DeadLetter]) -> String {
+        let mut page = String::from(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Dead letters</title>\
+             <style>body{font-family:monospace;margin:2em}td{padding:0 1em 0 0}\
+             pre{background:#f4f4f4;padding:1em;overflow-x:auto}form{display:inline}</style>\
+             </head><body><h1>Dead letters</h1><table>",
+        );
+        for (topic, count) in counts {
+            page.push_str(&format!(
+                "<tr><td><a href=\"{}?topic={}\">{}</a></td><td>{}</td></tr>",
+                escape(&self.path),
+                percent_encode(topic),
+                escape(topic),
+                count
+            ));
+        }
+        page.push_str(&format!(
+            "</table><p><a href=\"{}\">All topics</a></p>",
+            escape(&self.path)
+        ));
+
+        for dead_letter in dead_letters {
+            let metadata = dead_letter
+                .metadata
+                .iter()
+                .map(|(key, value)| format!("{}: {}", key, value))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let action = |name: &str| {
+                format!(
+                    "<form method=\"post\" action=\"{}/{}/{}\"><button>{}</button></form> ",
+                    escape(&self.path),
+                    dead_letter.id,
+                    name,
+                    name
+                )
+            };
+            page.push_str(&format!(
+                "<details><summary>#{} {} &mdash; {} attempts, failed {} &mdash; {}</summary>\
+                 <p>{}{}</p><h3>Payload</h3><pre>{}</pre><h3>Metadata</h3><pre>{}</pre>\
+                 </details>",
+                dead_letter.id,
+                escape(&dead_letter.topic),
+                dead_letter.attempts,
+                iso8601(dead_letter.failed_at),
+                escape(&dead_letter.error),
+                action("retry"),
+                action("discard"),
+                escape(&String::from_utf8_lossy(&dead_letter.payload)),
+                escape(&metadata),
+            ));
+        }
+
+        page.push_str("</body></html>");
+        page
+    }
+}
+
+/// Whether `request` names its own host in `Origin`, or `Referer` when a browser leaves
+/// `Origin` out. Requests with neither are refused.
+fn same_origin(request: &HttpRequest) -> bool {
+    let Some(host) = request.headers.get("host") else {
+        return false;
+    };
+    let source = request
+        .headers
+        .get("origin")
+        .or_else(|| request.headers.get("referer"));
+    source
+        .and_then(|source| source.split_once("://"))
+        .map(|(_, rest)| rest.split('/').next().unwrap_or_default())
+        .is_some_and(|authority| authority.eq_ignore_ascii_case(host))
+}
+
+/// `value` with everything but unreserved characters percent-encoded, for query strings.
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Reverses [`percent_encode`]; malformed escapes are kept as they are.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn error(message: &str) -> Res {
+    Res::new(
+        BufferBuilder::new()
+            .status(BufferBuilder::INTERNAL_SERVER_ERROR)
+            .text(message)
+            .build(),
+        500,
+    )
+}
@@ -0,0 +1,40 @@
+use sha2::{Digest, Sha256};
+
+pub const CONTENT_DIGEST: &str = "Content-Digest";
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The `Content-Digest` value for `body` as sent (RFC 9530), e.g. `sha-256=:...:`.
+pub fn content_digest(body: &[u8]) -> String {
+    format!("sha-256=:{}:", base64(&Sha256::digest(body)))
+}
+
+/// Whether a `Content-Digest` header carries a SHA-256 digest matching `body`, the bytes as
+/// received before any content decoding. Other algorithms in the header are ignored, so a
+/// header without `sha-256` never verifies.
+pub fn verify_content_digest(header: &str, body: &[u8]) -> bool {
+    let expected = base64(&Sha256::digest(body));
+    header
+        .split(',')
+        .filter_map(|member| member.trim().split_once('='))
+        .filter(|(algorithm, _)| algorithm.trim().eq_ignore_ascii_case("sha-256"))
+        .any(|(_, value)| value.trim().trim_matches(':') == expected)
+}
+
+pub(crate) fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &byte)| n | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
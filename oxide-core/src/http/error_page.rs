@@ -0,0 +1,251 @@
+use std::{backtrace::Backtrace, cell::RefCell, collections::BTreeMap, panic, sync::Once};
+
+use crate::{config::Environment, error_codes, Error, Logger};
+
+use super::{replay::NONCE_HEADER, signing::SIGNATURE_HEADER, BufferBuilder, HttpRequest};
+
+/// Headers carrying credentials, shown as `[redacted]` since the page ends up in screenshots
+/// and tickets.
+const REDACTED_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "x-api-key",
+    SIGNATURE_HEADER,
+    NONCE_HEADER,
+];
+
+thread_local! {
+    static PANIC_BACKTRACE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+static PANIC_HOOK: Once = Once::new();
+
+/// Everything needed to render a development error page for a failed request.
+#[derive(Debug, Clone)]
+pub struct ErrorReport {
+    pub status: u16,
+    pub error_type: String,
+    /// The error's stable code, see [`error_codes`].
+    pub code: &'static str,
+    pub chain: Vec<String>,
+    pub backtrace: String,
+}
+
+impl ErrorReport {
+    pub fn from_error(error: &Error) -> Self {
+        let mut chain = vec![error.to_string()];
+        let mut source = std::error::Error::source(error);
+        while let Some(err) = source {
+            chain.push(err.to_string());
+            source = err.source();
+        }
+
+        Self {
+            status: error.status_code(),
+            error_type: error.error_type().to_string(),
+            code: error.code().code,
+            chain,
+            backtrace: if Environment::current().is_production() {
+                String::new()
+            } else {
+                Backtrace::force_capture().to_string()
+            },
+        }
+    }
+
+    /// Builds a report from a caught handler panic, using the backtrace recorded by the
+    /// panic hook installed with [`install_panic_hook`].
+    pub fn from_panic(payload: Box<dyn std::any::Any + Send>) -> Self {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "Handler panicked".to_string());
+
+        let backtrace = PANIC_BACKTRACE
+            .with(|bt| bt.borrow_mut().take())
+            .unwrap_or_default();
+
+        Self {
+            status: 500,
+            error_type: "PANIC".to_string(),
+            code: error_codes::PANIC.code,
+            chain: vec![format!("Handler panicked: {}", message)],
+            backtrace,
+        }
+    }
+
+    /// Renders the report for `request`. Outside production this is a detailed HTML page (or
+    /// its JSON equivalent when the client doesn't accept HTML); production always gets a
+    /// sanitized `application/problem+json` body.
+    pub fn render(&self, request: Option<&HttpRequest>) -> Vec<u8> {
+        if Environment::current().is_production() {
+            return self.problem_json();
+        }
+
+        let wants_html = request
+            .and_then(|r| r.headers.get("accept"))
+            .map(|accept| accept.contains("text/html"))
+            .unwrap_or(false);
+
+        if wants_html {
+            self.html(request)
+        } else {
+            self.detailed_json(request)
+        }
+    }
+
+    /// The error as a log line, prefixed with its code.
+    pub fn log_line(&self) -> String {
+        format!("[{}] {}", self.code, self.chain.join(": "))
+    }
+
+    pub fn problem_json(&self) -> Vec<u8> {
+        let detail = if self.status >= 500 {
+            "The server encountered an internal error".to_string()
+        } else {
+            self.chain.first().cloned().unwrap_or_default()
+        };
+
+        let body = serde_json::json!({
+            "type": "about:blank",
+            "title": BufferBuilder::reason_phrase(self.status),
+            "status": self.status,
+            "code": self.error_type,
+            "oxide_code": self.code,
+            "detail": detail,
+        });
+
+        BufferBuilder::new()
+            .status((self.status, BufferBuilder::reason_phrase(self.status)))
+            .content_type("application/problem+json")
+            .body(body.to_string())
+            .build()
+    }
+
+    fn detailed_json(&self, request: Option<&HttpRequest>) -> Vec<u8> {
+        let body = serde_json::json!({
+            "error": {
+                "type": self.error_type,
+                "oxide_code": self.code,
+                "status": self.status,
+                "chain": self.chain,
+                "backtrace": self.backtrace.lines().collect::<Vec<_>>(),
+                "request": request.map(|r| serde_json::json!({
+                    "method": r.method.to_string(),
+                    "path": r.path,
+                    "headers": redacted_headers(r),
+                    "query": r.query_params,
+                })),
+                "logs": Logger::recent_lines(),
+            }
+        });
+
+        BufferBuilder::new()
+            .status((self.status, BufferBuilder::reason_phrase(self.status)))
+            .json(body.to_string())
+            .build()
+    }
+
+    fn html(&self, request: Option<&HttpRequest>) -> Vec<u8> {
+        let mut page = String::new();
+        page.push_str("<!DOCTYPE html><html><head><meta charset=\"utf-8\">");
+        page.push_str(&format!(
+            "<title>{} {}</title>",
+            self.status,
+            escape(&self.error_type)
+        ));
+        page.push_str(
+            "<style>body{font-family:monospace;margin:2em;background:#fafafa;color:#222}\
+             h1{color:#b00020}section{margin-bottom:2em}\
+             pre{background:#fff;border:1px solid #ddd;padding:1em;overflow-x:auto}\
+             td{padding:0 1em 0 0;vertical-align:top}</style></head><body>",
+        );
+        page.push_str(&format!(
+            "<h1>{} {}</h1><p>{}</p>",
+            self.status,
+            escape(&self.error_type),
+            self.code
+        ));
+
+        page.push_str("<section><h2>Error chain</h2><ol>");
+        for cause in &self.chain {
+            page.push_str(&format!("<li>{}</li>", escape(cause)));
+        }
+        page.push_str("</ol></section>");
+
+        if let Some(request) = request {
+            page.push_str("<section><h2>Request</h2><table>");
+            page.push_str(&format!(
+                "<tr><td>Method</td><td>{}</td></tr><tr><td>Path</td><td>{}</td></tr>",
+                request.method,
+                escape(&request.path)
+            ));
+            for (key, value) in redacted_headers(request) {
+                page.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td></tr>",
+                    escape(key),
+                    escape(value)
+                ));
+            }
+            page.push_str("</table></section>");
+        }
+
+        page.push_str(&format!(
+            "<section><h2>Backtrace</h2><pre>{}</pre></section>",
+            escape(&self.backtrace)
+        ));
+        page.push_str(&format!(
+            "<section><h2>Recent logs</h2><pre>{}</pre></section>",
+            escape(&Logger::recent_lines().join("\n"))
+        ));
+        page.push_str("</body></html>");
+
+        BufferBuilder::new()
+            .status((self.status, BufferBuilder::reason_phrase(self.status)))
+            .html(page)
+            .build()
+    }
+}
+
+/// Records a backtrace for every panic so error pages can show where a handler panicked.
+/// Only installed outside production; the previous hook still runs.
+pub fn install_panic_hook() {
+    if Environment::current().is_production() {
+        return;
+    }
+
+    PANIC_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            PANIC_BACKTRACE.with(|bt| {
+                *bt.borrow_mut() = Some(Backtrace::force_capture().to_string());
+            });
+            previous(info);
+        }));
+    });
+}
+
+/// The request's headers sorted by name, with credentials replaced by `[redacted]`.
+fn redacted_headers(request: &HttpRequest) -> BTreeMap<&str, &str> {
+    request
+        .headers
+        .iter()
+        .map(|(key, value)| {
+            if REDACTED_HEADERS.contains(&key.to_lowercase().as_str()) {
+                (key.as_str(), "[redacted]")
+            } else {
+                (key.as_str(), value.as_str())
+            }
+        })
+        .collect()
+}
+
+pub(super) fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
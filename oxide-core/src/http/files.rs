@@ -6,6 +6,21 @@ use super::mime::{guess_mime_type, MimeType};
 #[folder = "src/static"]
 struct StaticAssets;
 
+/// Pre-compressed siblings of an asset in order of preference, as `Content-Encoding` and file
+/// suffix.
+const PRECOMPRESSED: [(&str, &str); 3] = [("br", ".br"), ("zstd", ".zst"), ("gzip", ".gz")];
+
+/// An asset chosen for a request, possibly one of its pre-compressed siblings.
+pub struct StaticFile {
+    pub data: Vec<u8>,
+    /// Type of the original asset, whichever encoding was picked.
+    pub mime: MimeType,
+    pub encoding: Option<&'static str>,
+    /// Whether the asset has pre-compressed siblings, so the response depends on
+    /// `Accept-Encoding`.
+    pub varies: bool,
+}
+
 pub struct StaticHandler;
 
 impl StaticHandler {
@@ -15,4 +30,52 @@ impl StaticHandler {
             (f.data.into(), mime)
         })
     }
+
+    /// Serves `path.br`, `path.zst` or `path.gz` as-is when one exists and `accept_encoding`
+    /// allows it, falling back to `path` itself. Fingerprinted assets can be compressed once
+    /// at build time this way instead of on every request.
+    pub fn serve_encoded(path: &str, accept_encoding: Option<&str>) -> Option<StaticFile> {
+        let mime = guess_mime_type(path);
+        let mut varies = false;
+        for (encoding, suffix) in PRECOMPRESSED {
+            let Some(file) = StaticAssets::get(&format!("{}{}", path, suffix)) else {
+                continue;
+            };
+            varies = true;
+            if accept_encoding.is_some_and(|accepted| accepts(accepted, encoding)) {
+                return Some(StaticFile {
+                    data: file.data.into(),
+                    mime,
+                    encoding: Some(encoding),
+                    varies,
+                });
+            }
+        }
+        StaticAssets::get(path).map(|file| StaticFile {
+            data: file.data.into(),
+            mime,
+            encoding: None,
+            varies,
+        })
+    }
+}
+
+/// Whether an `Accept-Encoding` header allows `encoding`, either by name or through `*`,
+/// with a non-zero quality.
+fn accepts(accept_encoding: &str, encoding: &str) -> bool {
+    let quality = |name: &str| {
+        accept_encoding.split(',').find_map(|entry| {
+            let mut parts = entry.split(';').map(str::trim);
+            if !parts.next()?.eq_ignore_ascii_case(name) {
+                return None;
+            }
+            Some(
+                parts
+                    .find_map(|param| param.strip_prefix("q="))
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0),
+            )
+        })
+    };
+    quality(encoding).or_else(|| quality("*")).unwrap_or(0.0) > 0.0
 }
@@ -0,0 +1,57 @@
+use super::HttpRequest;
+
+pub type GuardFn = fn(&HttpRequest) -> bool;
+
+/// Extra condition a request must meet for a route to match. Several routes can share a
+/// method and path with different guards; the first registered one whose guards all pass
+/// handles the request, and if none do the router falls through to later routes.
+#[derive(Debug, Clone)]
+pub enum Guard {
+    /// Header name (case-insensitive) and exact value.
+    Header(String, String),
+    /// Media type, ignoring parameters such as `charset`.
+    ContentType(String),
+    Custom(GuardFn),
+}
+
+impl Guard {
+    pub fn header(name: &str, value: &str) -> Self {
+        Guard::Header(name.to_lowercase(), value.to_string())
+    }
+
+    pub fn content_type(mime: &str) -> Self {
+        Guard::ContentType(mime.to_lowercase())
+    }
+
+    pub fn custom(check: GuardFn) -> Self {
+        Guard::Custom(check)
+    }
+
+    pub fn check(&self, request: &HttpRequest) -> bool {
+        match self {
+            Guard::Header(name, value) => request.headers.get(name) == Some(value),
+            Guard::ContentType(mime) => request.content_type().is_some_and(|content_type| {
+                content_type
+                    .split(';')
+                    .next()
+                    .unwrap_or("")
+                    .trim()
+                    .eq_ignore_ascii_case(mime)
+            }),
+            Guard::Custom(check) => check(request),
+        }
+    }
+}
+
+impl PartialEq for Guard {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Guard::Header(a, b), Guard::Header(c, d)) => a == c && b == d,
+            (Guard::ContentType(a), Guard::ContentType(b)) => a == b,
+            (Guard::Custom(a), Guard::Custom(b)) => std::ptr::fn_addr_eq(*a, *b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Guard {}
@@ -1,17 +1,37 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    future::Future,
+    panic::{catch_unwind, AssertUnwindSafe},
+    pin::Pin,
+    sync::Arc,
+    task::{Context as TaskContext, Poll},
+    time::{Duration, Instant, SystemTime},
+};
 
 use serde::Serialize;
 
-use crate::{logger::LogLevel, Logger, PgDatabase};
+use crate::{
+    circuit_breaker::{CircuitBreaker, CircuitBreakerStats},
+    datasource::ReadReplicas,
+    logger::LogLevel,
+    messaging::{Message, MessageBus, Propagation},
+    metering::{self, Metering, Usage},
+    server::SloTracker,
+    Error, Logger, PgDatabase,
+};
 
 use super::{
-    files::StaticHandler, BufferBuilder, HttpMethod, HttpRequest, MiddlewareHandler, RouteManager,
+    coalesce, error_page::ErrorReport, jsonp, recorder::RequestRecorder, routes::Route,
+    scope::RequestScope, state::AppState, AsyncResponse, BufferBuilder, Chaos, Coalescer,
+    ConnectionsPage, CorsConfig, DeadLetterPage, Fault, HttpMethod, HttpRequest, MiddlewareHandler,
+    MiddlewareResult, ReplayProtection, RequestVerifier, ResponseCache, RouteCache, RouteManager,
 };
 
 // pub type OxideResponse = OxideResult<Vec<u8>>;
 pub struct OxideResponse {
     buffer: Vec<u8>,
     status: u16,
+    report: Option<Box<ErrorReport>>,
 }
 
 pub enum OxideRes {
@@ -32,7 +52,25 @@ impl OxideResponse {
         let builder = Self::get_buffer_with_status(response_type);
         let buffer = builder.json(json_string).build();
 
-        Self { buffer, status }
+        Self {
+            buffer,
+            status,
+            report: None,
+        }
+    }
+
+    /// Responds with `body` as is, e.g. `OxideResponse::bytes(OxideRes::Success, "image/png", png)`.
+    pub fn bytes(response_type: OxideRes, content_type: &str, body: Vec<u8>) -> Self {
+        let status = Self::get_status(&response_type);
+        let buffer = Self::get_buffer_with_status(response_type)
+            .bytes(content_type, body)
+            .build();
+
+        Self {
+            buffer,
+            status,
+            report: None,
+        }
     }
 
     pub fn text(response_type: OxideRes, message: impl AsRef<str>) -> Self {
@@ -41,7 +79,46 @@ impl OxideResponse {
 
         let buffer = builder.text(message.as_ref()).build();
 
-        Self { buffer, status }
+        Self {
+            buffer,
+            status,
+            report: None,
+        }
+    }
+
+    /// Responds with the status of `error`. Server errors are rendered as a detailed error
+    /// page outside production and as a sanitized problem+json body in production.
+    pub fn error(error: impl Into<Error>) -> Self {
+        let report = ErrorReport::from_error(&error.into());
+        Self {
+            buffer: report.problem_json(),
+            status: report.status,
+            report: Some(Box::new(report)),
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn into_buffer(self) -> Vec<u8> {
+        self.buffer
+    }
+
+    /// Adds a `Content-Digest` header over the body, for exports clients should verify.
+    pub fn with_digest(mut self) -> Self {
+        if let Some(builder) = BufferBuilder::from_raw(&self.buffer) {
+            self.buffer = builder.digest().build();
+        }
+        self
+    }
+
+    /// Adds a header to the built response, e.g. `Link` or `X-Total-Count`.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        if let Some(builder) = BufferBuilder::from_raw(&self.buffer) {
+            self.buffer = builder.header(name, value).build();
+        }
+        self
     }
 
     fn get_buffer_with_status(response_type: OxideRes) -> BufferBuilder {
@@ -79,16 +156,75 @@ pub struct RequestResponse {
     pub ip: String,
     pub status: u16,
     pub duration: std::time::Duration,
+    /// Bytes written for the response, status line and headers included.
+    pub bytes_sent: usize,
+    pub body_bytes: usize,
+    pub trace: Option<Box<RequestTrace>>,
+}
+
+/// Routing details and per-phase timings, collected only when verbose logging is enabled.
+#[derive(Debug, Clone, Default)]
+pub struct RequestTrace {
+    pub route: String,
+    pub params: HashMap<String, String>,
+    pub global_middleware: usize,
+    pub route_middleware: usize,
+    pub parse: Duration,
+    pub middleware: Duration,
+    pub handler: Duration,
 }
 
 pub struct Res {
     pub buffer: Vec<u8>,
     pub status: u16,
+    /// Pattern of the route or static file that produced the response, for metrics.
+    pub route: Option<String>,
+    pub trace: Option<Box<RequestTrace>>,
+    /// Fault injected by [`Chaos`] for the connection to apply while writing.
+    pub fault: Option<Fault>,
 }
 
 impl Res {
     pub fn new(buffer: Vec<u8>, status: u16) -> Self {
-        Self { buffer, status }
+        Self {
+            buffer,
+            status,
+            route: None,
+            trace: None,
+            fault: None,
+        }
+    }
+
+    pub fn with_route(mut self, route: &str) -> Self {
+        self.route = Some(route.to_string());
+        self
+    }
+
+    /// Length of the body, i.e. everything after the blank line ending the headers.
+    pub fn body_len(&self) -> usize {
+        self.buffer
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .map_or(0, |pos| self.buffer.len() - pos - 4)
+    }
+
+    /// Inserts extra headers directly after the status line of an already built response.
+    pub fn with_headers(mut self, headers: Vec<(String, String)>) -> Self {
+        if headers.is_empty() {
+            return self;
+        }
+
+        if let Some(pos) = self.buffer.windows(2).position(|w| w == b"\r\n") {
+            let mut extra = Vec::new();
+            for (key, value) in headers {
+                extra.extend_from_slice(key.as_bytes());
+                extra.extend_from_slice(b": ");
+                extra.extend_from_slice(value.as_bytes());
+                extra.extend_from_slice(b"\r\n");
+            }
+            self.buffer.splice(pos + 2..pos + 2, extra);
+        }
+        self
     }
 }
 
@@ -96,8 +232,27 @@ impl Res {
 pub struct HttpHandler {
     routes: Arc<RouteManager>,
     middleware: Arc<MiddlewareHandler>,
+    #[cfg_attr(not(feature = "static-files"), allow(dead_code))]
     static_files: Arc<HashMap<String, &'static str>>,
     datasource: Option<Arc<PgDatabase>>,
+    named_datasources: Arc<HashMap<String, PgDatabase>>,
+    read_replicas: Arc<ReadReplicas>,
+    message_bus: Option<Arc<dyn MessageBus>>,
+    propagation: Propagation,
+    cors: Option<CorsConfig>,
+    verbose_logging: bool,
+    recorder: Option<Arc<RequestRecorder>>,
+    dead_letter_page: Option<Arc<DeadLetterPage>>,
+    connections_page: Option<Arc<ConnectionsPage>>,
+    metering: Option<Arc<Metering>>,
+    replay_protection: Option<Arc<ReplayProtection>>,
+    request_verifier: Option<Arc<RequestVerifier>>,
+    slo_tracker: Option<Arc<SloTracker>>,
+    chaos: Option<Arc<Chaos>>,
+    route_cache: Arc<RouteCache>,
+    coalescer: Arc<Coalescer>,
+    circuit_breakers: Arc<HashMap<String, CircuitBreaker>>,
+    response_cache: Arc<ResponseCache>,
 }
 
 impl HttpHandler {
@@ -112,6 +267,24 @@ impl HttpHandler {
             middleware,
             static_files,
             datasource,
+            named_datasources: Arc::new(HashMap::new()),
+            read_replicas: Arc::new(ReadReplicas::new()),
+            message_bus: None,
+            propagation: Propagation::default(),
+            cors: None,
+            verbose_logging: false,
+            recorder: None,
+            dead_letter_page: None,
+            connections_page: None,
+            metering: None,
+            replay_protection: None,
+            request_verifier: None,
+            slo_tracker: None,
+            chaos: None,
+            route_cache: Arc::new(RouteCache::new(0)),
+            coalescer: Arc::new(Coalescer::new()),
+            circuit_breakers: Arc::new(HashMap::new()),
+            response_cache: Arc::new(ResponseCache::new(0)),
         }
     }
 
@@ -120,37 +293,349 @@ impl HttpHandler {
         self
     }
 
+    pub fn with_named_datasources(
+        mut self,
+        named_datasources: Arc<HashMap<String, PgDatabase>>,
+    ) -> Self {
+        self.named_datasources = named_datasources;
+        self
+    }
+
+    pub fn with_read_replicas(mut self, read_replicas: Arc<ReadReplicas>) -> Self {
+        self.read_replicas = read_replicas;
+        self
+    }
+
+    pub fn with_message_bus(mut self, message_bus: Option<Arc<dyn MessageBus>>) -> Self {
+        self.message_bus = message_bus;
+        self
+    }
+
+    pub fn with_propagation(mut self, propagation: Propagation) -> Self {
+        self.propagation = propagation;
+        self
+    }
+
+    pub fn with_cors(mut self, cors: Option<CorsConfig>) -> Self {
+        self.cors = cors;
+        self
+    }
+
+    pub fn with_verbose_logging(mut self, verbose: bool) -> Self {
+        self.verbose_logging = verbose;
+        self
+    }
+
+    pub fn with_recorder(mut self, recorder: Option<Arc<RequestRecorder>>) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    pub fn with_dead_letter_page(mut self, page: Option<Arc<DeadLetterPage>>) -> Self {
+        self.dead_letter_page = page;
+        self
+    }
+
+    pub fn with_connections_page(mut self, page: Option<Arc<ConnectionsPage>>) -> Self {
+        self.connections_page = page;
+        self
+    }
+
+    /// Counts each handled request's usage against its tenant and principal.
+    pub fn with_metering(mut self, metering: Option<Arc<Metering>>) -> Self {
+        self.metering = metering;
+        self
+    }
+
+    /// Checks nonces and timestamps on routes registered as `replay_protected`.
+    pub fn with_replay_protection(mut self, replay: Option<Arc<ReplayProtection>>) -> Self {
+        self.replay_protection = replay;
+        self
+    }
+
+    /// Checks signatures on routes registered as `signed`.
+    pub fn with_request_verifier(mut self, verifier: Option<Arc<RequestVerifier>>) -> Self {
+        self.request_verifier = verifier;
+        self
+    }
+
+    /// Times requests to routes registered with an SLO, from parsing to the response.
+    pub fn with_slo_tracker(mut self, tracker: Arc<SloTracker>) -> Self {
+        self.slo_tracker = Some(tracker);
+        self
+    }
+
+    /// Injects faults into routes matched by `chaos`.
+    pub fn with_chaos(mut self, chaos: Option<Arc<Chaos>>) -> Self {
+        self.chaos = chaos;
+        self
+    }
+
+    pub fn with_route_cache(mut self, route_cache: Arc<RouteCache>) -> Self {
+        self.route_cache = route_cache;
+        self
+    }
+
+    /// Shares responses between identical requests to routes registered with `coalesce`.
+    pub fn with_coalescer(mut self, coalescer: Arc<Coalescer>) -> Self {
+        self.coalescer = coalescer;
+        self
+    }
+
+    /// Breakers available to handlers and checked for routes registered with `depends_on`.
+    pub fn with_circuit_breakers(
+        mut self,
+        circuit_breakers: Arc<HashMap<String, CircuitBreaker>>,
+    ) -> Self {
+        self.circuit_breakers = circuit_breakers;
+        self
+    }
+
+    /// Where handlers declared with `#[handler(cache(...))]` keep their responses.
+    pub fn with_response_cache(mut self, response_cache: Arc<ResponseCache>) -> Self {
+        self.response_cache = response_cache;
+        self
+    }
+
     pub async fn handle(&self, buffer: &[u8]) -> Res {
+        if let Some(page) = self.connections_page.as_ref().filter(|p| p.targets(buffer)) {
+            if let Some(request) = HttpRequest::parse(buffer) {
+                if let Some(res) = page.serve(&request).await {
+                    return res;
+                }
+            }
+        }
+        if let Some(page) = self.dead_letter_page.as_ref().filter(|p| p.targets(buffer)) {
+            if let Some(request) = HttpRequest::parse(buffer) {
+                if let Some(res) = page.serve(&request).await {
+                    return res;
+                }
+            }
+        }
+
+        let recorder = match &self.recorder {
+            Some(recorder) => recorder,
+            None => return self.dispatch(buffer).await,
+        };
+
+        if let Some(res) = HttpRequest::parse(buffer).and_then(|r| recorder.serve(&r)) {
+            return res;
+        }
+
+        let started = SystemTime::now();
+        let start_time = Instant::now();
+        let res = self.dispatch(buffer).await;
+        recorder.record(buffer, &res, started, start_time.elapsed());
+        res
+    }
+
+    /// The static file registered for the request's path, if there is one.
+    #[cfg(feature = "static-files")]
+    fn serve_static(&self, request: &HttpRequest) -> Option<Res> {
+        let path = request.path.split('?').next().unwrap_or("");
+        let file_path = self.static_files.get(path)?;
+        let accept_encoding = request.headers.get("accept-encoding");
+        let file =
+            super::StaticHandler::serve_encoded(file_path, accept_encoding.map(String::as_str))?;
+        let mut builder = BufferBuilder::ok().header("Content-Type", file.mime.as_str());
+        if let Some(encoding) = file.encoding {
+            builder = builder.header("Content-Encoding", encoding);
+        }
+        if file.varies {
+            builder = builder.header("Vary", "Accept-Encoding");
+        }
+        Some(Res::new(builder.body(file.data).build(), 200).with_route(path))
+    }
+
+    async fn dispatch(&self, buffer: &[u8]) -> Res {
+        let parse_start = Instant::now();
+        let request_bytes = buffer.len();
         match HttpRequest::parse(buffer) {
             Some(request) => {
-                if let Some(file_path) = self.static_files.get(&request.path) {
-                    if let Some((data, mime)) = StaticHandler::serve(file_path) {
-                        return Res::new(
-                            BufferBuilder::ok()
-                                .header("Content-Type", mime.as_str())
-                                .body(data)
-                                .build(),
-                            200,
-                        );
-                    }
+                let parse_time = parse_start.elapsed();
+
+                if request.method == HttpMethod::Options {
+                    return self.handle_options(&request);
                 }
 
-                if let Some(route) = self.routes.find_route(&request.path, request.method) {
-                    let params = self.extract_params(&route.pattern, &request.path);
+                let cors_headers = match (&self.cors, request.headers.get("origin")) {
+                    (Some(cors), Some(origin)) => cors.response_headers(origin),
+                    _ => vec![],
+                };
+
+                #[cfg(feature = "static-files")]
+                if let Some(res) = self.serve_static(&request) {
+                    return res;
+                }
+
+                if let Some((route, params)) =
+                    self.routes.resolve_cached(&request, &self.route_cache)
+                {
+                    let fault = match &self.chaos {
+                        Some(chaos) => match chaos.inject(&route.pattern).await {
+                            Ok(fault) => fault,
+                            Err(res) => return res.with_route(&route.pattern),
+                        },
+                        None => None,
+                    };
+                    if let Some(res) = self.check_dependencies(route) {
+                        return res.with_route(&route.pattern);
+                    }
+                    let mut trace = self.verbose_logging.then(|| {
+                        let (global_middleware, route_middleware) =
+                            self.middleware.chain_len(route);
+                        RequestTrace {
+                            route: route.pattern.clone(),
+                            params: params.clone(),
+                            global_middleware,
+                            route_middleware,
+                            parse: parse_time,
+                            ..Default::default()
+                        }
+                    });
+
+                    let scope = RequestScope::new(&request, &route.pattern);
+                    #[cfg(feature = "otel")]
+                    let span = crate::telemetry::ServerSpan::start(&request, &route.pattern);
                     let mut context = Context::new(request, params);
                     if let Some(db) = &self.datasource {
                         context.with_datasource(Arc::clone(db));
                     }
-                    match self.middleware.run(context, route) {
+                    context.with_named_datasources(Arc::clone(&self.named_datasources));
+                    context.with_read_replicas(Arc::clone(&self.read_replicas));
+                    context.with_circuit_breakers(Arc::clone(&self.circuit_breakers));
+                    context.with_response_cache(Arc::clone(&self.response_cache));
+                    if let Some(state) = &route.state {
+                        context.with_state(Arc::clone(state));
+                    }
+                    if let Some(bus) = &self.message_bus {
+                        context.with_message_bus(Arc::clone(bus));
+                    }
+                    context.with_propagation(self.propagation);
+                    context.with_route(route);
+
+                    let middleware_start = Instant::now();
+                    // Signatures are checked first, so unsigned requests never reach middleware.
+                    let signed = if route.signed {
+                        self.check_signature(context)
+                    } else {
+                        Ok(context)
+                    };
+                    let middleware_result = signed.and_then(|context| {
+                        scope
+                            .clone()
+                            .run_sync(|| self.middleware.run(context, route))
+                    });
+                    let middleware_result = match middleware_result {
+                        Ok(ctx) if route.replay_protected => {
+                            scope.clone().run(self.check_replay(ctx)).await
+                        }
+                        result => result,
+                    };
+                    if let Some(trace) = &mut trace {
+                        trace.middleware = middleware_start.elapsed();
+                    }
+
+                    let mut res = match middleware_result {
                         Ok(ctx) => {
-                            let logger = Logger::new();
+                            let mut db_usage = (Duration::ZERO, 0);
+                            let respond = async {
+                                let logger = Logger::for_target(module_path!());
+
+                                let handler_start = Instant::now();
+                                let handled = scope.run(CatchUnwind((route.handler)(&ctx)));
+                                #[cfg(feature = "otel")]
+                                let handled = span.context().run(handled);
+                                let (handled, db_time, db_queries) =
+                                    metering::measure(self.metering.is_some(), handled).await;
+                                db_usage = (db_time, db_queries);
+                                let res = match handled {
+                                    Ok(res) => res,
+                                    Err(payload) => {
+                                        let report = ErrorReport::from_panic(payload);
+                                        OxideResponse {
+                                            buffer: vec![],
+                                            status: report.status,
+                                            report: Some(Box::new(report)),
+                                        }
+                                    }
+                                };
+                                if let Some(trace) = &mut trace {
+                                    trace.handler = handler_start.elapsed();
+                                }
+                                logger.log(
+                                    LogLevel::Info,
+                                    format!("status: {}", res.status,).as_str(),
+                                );
+                                if let Some(report) = &res.report {
+                                    let level = if report.status >= 500 {
+                                        LogLevel::Error
+                                    } else {
+                                        LogLevel::Warning
+                                    };
+                                    logger.log(level, &report.log_line());
+                                }
 
-                            let res = (route.handler)(&ctx).await;
-                            logger.log(LogLevel::Info, format!("status: {}", res.status,).as_str());
-                            return Res::new(res.buffer, res.status);
+                                let mut status = res.status;
+                                let mut buffer = match &res.report {
+                                    Some(report) if report.status >= 500 => {
+                                        report.render(Some(&ctx.request))
+                                    }
+                                    _ => res.buffer,
+                                };
+                                if let Some(callback) = route
+                                    .jsonp
+                                    .as_ref()
+                                    .and_then(|param| ctx.request.query_params.get(param))
+                                {
+                                    buffer = if jsonp::is_valid_callback(callback) {
+                                        jsonp::wrap(buffer, callback)
+                                    } else {
+                                        status = 400;
+                                        BufferBuilder::bad_request_response(
+                                            "Invalid JSONP callback name",
+                                        )
+                                    };
+                                }
+                                (status, buffer)
+                            };
+                            let (status, buffer) = if route.coalesce
+                                && ctx.request.method == HttpMethod::Get
+                            {
+                                let key = Coalescer::key(&route.key(), &route.coalesce_vary, &ctx);
+                                self.coalescer.run(key, || respond).await.0
+                            } else {
+                                respond.await
+                            };
+                            let (db_time, db_queries) = db_usage;
+                            let res = Res::new(buffer, status).with_headers(cors_headers);
+                            if let Some(metering) = &self.metering {
+                                metering.record(
+                                    ctx.tenant(),
+                                    ctx.principal(),
+                                    Usage {
+                                        requests: 1,
+                                        errors: (status >= 500) as u64,
+                                        bytes_in: request_bytes as u64,
+                                        bytes_out: res.buffer.len() as u64,
+                                        db_time_us: db_time.as_micros() as u64,
+                                        db_queries,
+                                    },
+                                );
+                            }
+                            res
                         }
-                        Err(res) => res,
+                        Err(res) => res.with_headers(cors_headers),
+                    };
+                    #[cfg(feature = "otel")]
+                    span.end(res.status);
+                    if let (Some(slo), Some(tracker)) = (route.slo, &self.slo_tracker) {
+                        tracker.observe(&route.pattern, slo, parse_start.elapsed());
                     }
+                    res.route = Some(route.pattern.clone());
+                    res.trace = trace.map(Box::new);
+                    res.fault = fault;
+                    res
                 } else {
                     Res::new(BufferBuilder::not_found().text("Not Found").build(), 404)
                 }
@@ -162,17 +647,87 @@ impl HttpHandler {
         }
     }
 
-    fn extract_params(&self, pattern: &str, path: &str) -> HashMap<String, String> {
-        let mut params = HashMap::new();
-        let pattern_parts: Vec<_> = pattern.split('/').collect();
-        let path_parts: Vec<_> = path.split('/').collect();
+    /// 503 while any of the route's circuit breakers is open, with `Retry-After` set to when
+    /// the first of them will let calls through again.
+    fn check_dependencies(&self, route: &Route) -> Option<Res> {
+        let (name, retry_after) = route.dependencies.iter().find_map(|name| {
+            let retry_after = self.circuit_breakers.get(name)?.retry_after()?;
+            Some((name, retry_after))
+        })?;
+        let retry_after = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+        Some(Res::new(
+            BufferBuilder::new()
+                .status(BufferBuilder::SERVICE_UNAVAILABLE)
+                .header("Retry-After", &retry_after.to_string())
+                .text(format!("{} is unavailable", name))
+                .build(),
+            503,
+        ))
+    }
 
-        for (p, path_part) in pattern_parts.iter().zip(path_parts.iter()) {
-            if p.starts_with(':') {
-                params.insert(p[1..].to_string(), path_part.to_string());
+    /// Runs before the middleware. On success the signing key id becomes the principal,
+    /// which middleware may replace.
+    fn check_signature(&self, mut ctx: Context) -> MiddlewareResult {
+        let Some(verifier) = &self.request_verifier else {
+            return Ok(ctx);
+        };
+        let key_id = verifier.verify(&ctx.request)?.to_string();
+        ctx.with_principal(key_id);
+        Ok(ctx)
+    }
+
+    /// Runs after the middleware, so nonces are scoped to the principal they set.
+    async fn check_replay(&self, ctx: Context) -> MiddlewareResult {
+        let Some(replay) = &self.replay_protection else {
+            return Ok(ctx);
+        };
+        match replay.check(&ctx.request, ctx.principal()).await {
+            Ok(()) => Ok(ctx),
+            Err(res) => Err(res),
+        }
+    }
+
+    fn handle_options(&self, request: &HttpRequest) -> Res {
+        let mut methods = self.routes.allowed_methods(&request.path);
+        if methods.is_empty() {
+            return Res::new(BufferBuilder::not_found().text("Not Found").build(), 404);
+        }
+        methods.push(HttpMethod::Options);
+
+        let allow = methods
+            .iter()
+            .map(|m| m.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut builder = BufferBuilder::no_content().header("Allow", &allow);
+
+        if let (Some(cors), Some(origin)) = (&self.cors, request.headers.get("origin")) {
+            let requested_headers = request
+                .headers
+                .get("access-control-request-headers")
+                .map(|s| s.as_str());
+            for (key, value) in cors.preflight_headers(origin, &methods, requested_headers) {
+                builder = builder.header(&key, &value);
             }
         }
-        params
+
+        Res::new(builder.build(), 204)
+    }
+}
+
+/// Polls a handler future, turning a panic into an error instead of tearing down the
+/// connection task.
+struct CatchUnwind<'a>(AsyncResponse<'a>);
+
+impl Future for CatchUnwind<'_> {
+    type Output = Result<OxideResponse, Box<dyn std::any::Any + Send>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        match catch_unwind(AssertUnwindSafe(|| self.0.as_mut().poll(cx))) {
+            Ok(Poll::Ready(res)) => Poll::Ready(Ok(res)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
     }
 }
 
@@ -180,6 +735,17 @@ pub struct Context {
     pub request: HttpRequest,
     params: HashMap<String, String>,
     pub datasource: Option<Arc<PgDatabase>>,
+    named_datasources: Arc<HashMap<String, PgDatabase>>,
+    read_replicas: Arc<ReadReplicas>,
+    circuit_breakers: Arc<HashMap<String, CircuitBreaker>>,
+    response_cache: Arc<ResponseCache>,
+    state: Option<Arc<AppState>>,
+    message_bus: Option<Arc<dyn MessageBus>>,
+    propagation: Propagation,
+    tenant: Option<String>,
+    principal: Option<String>,
+    /// The matched route's key and coalescing headers, which cached responses vary on too.
+    route: (String, Vec<String>),
 }
 
 impl Context {
@@ -188,6 +754,16 @@ impl Context {
             request,
             params,
             datasource: None,
+            named_datasources: Arc::new(HashMap::new()),
+            read_replicas: Arc::new(ReadReplicas::new()),
+            circuit_breakers: Arc::new(HashMap::new()),
+            response_cache: Arc::new(ResponseCache::new(0)),
+            state: None,
+            message_bus: None,
+            propagation: Propagation::default(),
+            tenant: None,
+            principal: None,
+            route: (String::new(), vec![]),
         }
     }
 
@@ -196,11 +772,226 @@ impl Context {
         self
     }
 
+    pub fn with_named_datasources(
+        &mut self,
+        named_datasources: Arc<HashMap<String, PgDatabase>>,
+    ) -> &mut Self {
+        self.named_datasources = named_datasources;
+        self
+    }
+
+    pub fn with_read_replicas(&mut self, read_replicas: Arc<ReadReplicas>) -> &mut Self {
+        self.read_replicas = read_replicas;
+        self
+    }
+
+    pub fn with_circuit_breakers(
+        &mut self,
+        circuit_breakers: Arc<HashMap<String, CircuitBreaker>>,
+    ) -> &mut Self {
+        self.circuit_breakers = circuit_breakers;
+        self
+    }
+
+    pub fn with_response_cache(&mut self, response_cache: Arc<ResponseCache>) -> &mut Self {
+        self.response_cache = response_cache;
+        self
+    }
+
+    pub(crate) fn with_route(&mut self, route: &Route) -> &mut Self {
+        self.route = (route.key(), route.coalesce_vary.clone());
+        self
+    }
+
+    pub fn with_state(&mut self, state: Arc<AppState>) -> &mut Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// State of type `T` registered on the mounted app that owns this route.
+    pub fn state<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.state.as_ref()?.get()
+    }
+
+    pub fn with_message_bus(&mut self, message_bus: Arc<dyn MessageBus>) -> &mut Self {
+        self.message_bus = Some(message_bus);
+        self
+    }
+
+    pub fn with_propagation(&mut self, propagation: Propagation) -> &mut Self {
+        self.propagation = propagation;
+        self
+    }
+
+    /// The tenant the request belongs to, typically set by middleware resolving it from the
+    /// host or a header.
+    pub fn with_tenant(&mut self, tenant: impl Into<String>) -> &mut Self {
+        self.tenant = Some(tenant.into());
+        self
+    }
+
+    pub fn tenant(&self) -> Option<&str> {
+        self.tenant.as_deref()
+    }
+
+    /// The authenticated user or client, typically set by auth middleware.
+    pub fn with_principal(&mut self, principal: impl Into<String>) -> &mut Self {
+        self.principal = Some(principal.into());
+        self
+    }
+
+    pub fn principal(&self) -> Option<&str> {
+        self.principal.as_deref()
+    }
+
+    /// Publishes `payload` as JSON to `topic` on the server's message bus, with the request
+    /// id, tenant and principal as metadata unless the server's [`Propagation`] leaves them out.
+    pub async fn publish<T: Serialize>(&self, topic: &str, payload: &T) -> Result<(), Error> {
+        self.publish_message(Message::from_json(topic, payload)?)
+            .await
+    }
+
+    /// `publish` with the given propagation instead of the server's.
+    pub async fn publish_with<T: Serialize>(
+        &self,
+        topic: &str,
+        payload: &T,
+        propagation: Propagation,
+    ) -> Result<(), Error> {
+        self.send(Message::from_json(topic, payload)?, propagation)
+            .await
+    }
+
+    /// Publishes `payload` now for consumers to handle once `delay` has passed.
+    pub async fn publish_in<T: Serialize>(
+        &self,
+        delay: Duration,
+        topic: &str,
+        payload: &T,
+    ) -> Result<(), Error> {
+        self.publish_message(Message::from_json(topic, payload)?.deliver_in(delay))
+            .await
+    }
+
+    /// Publishes `payload` now for consumers to handle at `at`.
+    pub async fn publish_at<T: Serialize>(
+        &self,
+        at: SystemTime,
+        topic: &str,
+        payload: &T,
+    ) -> Result<(), Error> {
+        self.publish_message(Message::from_json(topic, payload)?.deliver_at(at))
+            .await
+    }
+
+    /// Publishes a message built by hand, e.g. with a [`Priority`](crate::messaging::Priority),
+    /// adding the context metadata `publish` would.
+    pub async fn publish_message(&self, message: Message) -> Result<(), Error> {
+        self.send(message, self.propagation).await
+    }
+
+    async fn send(&self, mut message: Message, propagation: Propagation) -> Result<(), Error> {
+        let bus = self
+            .message_bus
+            .as_ref()
+            .ok_or_else(|| Error::Config("No message bus configured".to_string()))?;
+        let scope = RequestScope::current();
+        message.metadata.extend(propagation.metadata(
+            scope.as_ref().map(|scope| scope.request_id.as_str()),
+            self.tenant(),
+            self.principal(),
+        ));
+        #[cfg(feature = "otel")]
+        if let Some(span) = crate::telemetry::SpanContext::current() {
+            message.metadata.insert(
+                crate::telemetry::TRACEPARENT.to_string(),
+                span.traceparent(),
+            );
+        }
+        bus.publish_message(message).await
+    }
+
     pub fn db(&self) -> Option<&PgDatabase> {
         self.datasource.as_ref().map(|db| db.as_ref())
     }
 
+    /// A pool registered with `Server::with_named_datasource`.
+    pub fn db_named(&self, name: &str) -> Option<&PgDatabase> {
+        self.named_datasources.get(name)
+    }
+
+    /// A read replica of the main datasource, or the datasource itself if it has none.
+    /// Replicas can lag behind, so read what a request just wrote from `db()`.
+    pub fn db_read(&self) -> Option<&PgDatabase> {
+        self.read_replicas.get(None).or_else(|| self.db())
+    }
+
+    /// A read replica of the datasource registered as `name`, or the datasource itself if it
+    /// has none.
+    pub fn db_named_read(&self, name: &str) -> Option<&PgDatabase> {
+        self.read_replicas
+            .get(Some(name))
+            .or_else(|| self.db_named(name))
+    }
+
+    /// A breaker registered with `Server::with_circuit_breaker`.
+    pub fn circuit_breaker(&self, name: &str) -> Option<&CircuitBreaker> {
+        self.circuit_breakers.get(name)
+    }
+
+    /// The state of every registered breaker, by name, for metrics endpoints.
+    pub fn circuit_breaker_stats(&self) -> Vec<CircuitBreakerStats> {
+        let mut stats: Vec<_> = self.circuit_breakers.values().map(|b| b.stats()).collect();
+        stats.sort_by(|a, b| a.name.cmp(&b.name));
+        stats
+    }
+
+    /// The response `handle` produces, or a copy of it cached by an earlier identical GET
+    /// to the same route within `ttl`, provided none of `tables` has been written through the
+    /// ORM since. Requests are identical as for [`Coalescer`], including the route's
+    /// `.coalesce_by(...)` headers. Only successful responses that don't set a cookie are
+    /// cached, and requests other than GETs always run `handle`.
+    /// This is what `#[handler(cache(models(...), ttl = "..."))]` wraps a handler in.
+    pub async fn cached<F, Fut>(&self, tables: &[&str], ttl: Duration, handle: F) -> OxideResponse
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = OxideResponse>,
+    {
+        if self.request.method != HttpMethod::Get || !self.response_cache.is_enabled() {
+            return handle().await;
+        }
+        let key = self.response_key();
+        if let Some((status, buffer)) = self.response_cache.get(&key) {
+            return OxideResponse {
+                buffer,
+                status,
+                report: None,
+            };
+        }
+        let versions = ResponseCache::versions(tables);
+        let res = handle().await;
+        if res.report.is_none()
+            && (200..300).contains(&res.status)
+            && !coalesce::sets_cookie(&res.buffer)
+        {
+            self.response_cache
+                .insert(key, versions, ttl, res.status, res.buffer.clone());
+        }
+        res
+    }
+
+    /// Identifies the requests [`cached`](Self::cached) answers with the same response.
+    pub(crate) fn response_key(&self) -> String {
+        let (route, vary) = &self.route;
+        Coalescer::key(route, vary, self)
+    }
+
     pub fn param(&self, key: &str) -> Option<&str> {
         self.params.get(key).map(|s| s.as_str())
     }
+
+    /// Parses a path parameter, e.g. `ctx.param_as::<i32>("id")` for a `/users/:id<i32>` route.
+    pub fn param_as<T: std::str::FromStr>(&self, key: &str) -> Option<T> {
+        self.param(key)?.parse().ok()
+    }
 }
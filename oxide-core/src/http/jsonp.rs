@@ -0,0 +1,50 @@
+use super::BufferBuilder;
+
+const MAX_CALLBACK_LENGTH: usize = 128;
+
+/// Wraps a built JSON response in `callback(...)`. Non-JSON responses are returned
+/// untouched; callers must check the name with [`is_valid_callback`] first.
+pub fn wrap(buffer: Vec<u8>, callback: &str) -> Vec<u8> {
+    let builder = match BufferBuilder::from_raw(&buffer) {
+        Some(builder) => builder,
+        None => return buffer,
+    };
+
+    let is_json = builder
+        .get_header("Content-Type")
+        .map(|ct| ct.starts_with(BufferBuilder::JSON))
+        .unwrap_or(false);
+    if !is_json || builder.get_header("Content-Encoding").is_some() {
+        return buffer;
+    }
+
+    // The leading comment guards against the Rosetta Flash family of attacks.
+    let mut body = format!("/**/ {}(", callback).into_bytes();
+    body.extend_from_slice(builder.body_bytes());
+    body.extend_from_slice(b");");
+
+    builder
+        .remove_header("Content-Type")
+        .remove_header("Content-Length")
+        .content_type("application/javascript; charset=utf-8")
+        .header("X-Content-Type-Options", "nosniff")
+        .body(body)
+        .build()
+}
+
+/// Accepts dotted JavaScript identifiers such as `cb`, `$jq_123` or `app.handlers.feed`.
+pub fn is_valid_callback(callback: &str) -> bool {
+    if callback.is_empty() || callback.len() > MAX_CALLBACK_LENGTH {
+        return false;
+    }
+
+    callback.split('.').all(|part| {
+        let mut chars = part.chars();
+        match chars.next() {
+            Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '$' => {
+                chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+            }
+            _ => false,
+        }
+    })
+}
@@ -2,7 +2,11 @@ use std::collections::HashMap;
 
 use crate::Logger;
 
-use super::{handler::Res, routes::Route, Context};
+use super::{
+    handler::Res,
+    routes::{join_path, Route},
+    Context,
+};
 
 pub type MiddlewareResult = Result<Context, Res>;
 pub type MiddlewareFn = fn(Context) -> MiddlewareResult;
@@ -32,7 +36,7 @@ impl MiddlewareHandler {
             .collect::<Vec<_>>()
             .join("/");
 
-        let logger = Logger::new();
+        let logger = Logger::for_target(module_path!());
         logger.log(
             crate::logger::LogLevel::Info,
             &format!("Registering middleware for route: {}", path),
@@ -44,6 +48,40 @@ impl MiddlewareHandler {
             .push(middleware);
     }
 
+    /// Merges a mounted app's middleware: its global middleware run for each of its
+    /// (already prefixed) `raw_paths`, ahead of its own route-specific middleware.
+    pub(crate) fn mount(&mut self, prefix: &str, app: MiddlewareHandler, raw_paths: &[String]) {
+        if !app.global.is_empty() {
+            for raw_path in raw_paths {
+                self.route_specific
+                    .entry(raw_path.clone())
+                    .or_default()
+                    .extend(&app.global);
+            }
+        }
+        for (path, middleware) in app.route_specific {
+            self.route_specific
+                .entry(join_path(prefix, &path))
+                .or_default()
+                .extend(middleware);
+        }
+    }
+
+    /// Number of global middleware and of routes with their own middleware attached.
+    pub fn summary(&self) -> (usize, usize) {
+        (self.global.len(), self.route_specific.len())
+    }
+
+    /// Number of global and route-specific middleware that run for `route`.
+    pub fn chain_len(&self, route: &Route) -> (usize, usize) {
+        let route_specific = self
+            .route_specific
+            .get(&route.raw_path)
+            .map(|m| m.len())
+            .unwrap_or(0);
+        (self.global.len(), route_specific)
+    }
+
     pub fn run(&self, mut context: Context, route: &Route) -> MiddlewareResult {
         for middleware in &self.global {
             context = middleware(context)?;
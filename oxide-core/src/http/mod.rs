@@ -1,14 +1,58 @@
+mod chaos;
+mod coalesce;
+mod connections;
+mod cors;
+mod dead_letters;
+mod digest;
+mod error_page;
+#[cfg(feature = "static-files")]
 mod files;
+mod guard;
 mod handler;
+mod jsonp;
 mod middleware;
+#[cfg(feature = "static-files")]
 mod mime;
+mod params;
+mod recorder;
+mod replay;
 mod request;
 mod response;
+mod response_cache;
+mod route_cache;
 mod routes;
+mod scope;
+mod signing;
+mod state;
 
-pub use files::StaticHandler;
-pub use handler::{Context, HttpHandler, OxideRes, OxideResponse, RequestResponse};
+pub use chaos::{Chaos, Fault};
+pub use coalesce::{Coalescer, CoalescerStats};
+pub use connections::ConnectionsPage;
+pub use cors::CorsConfig;
+pub use dead_letters::DeadLetterPage;
+pub use digest::{content_digest, verify_content_digest, CONTENT_DIGEST};
+pub use error_page::{install_panic_hook, ErrorReport};
+#[cfg(feature = "static-files")]
+pub use files::{StaticFile, StaticHandler};
+pub use guard::{Guard, GuardFn};
+pub use handler::{
+    Context, HttpHandler, OxideRes, OxideResponse, RequestResponse, RequestTrace, Res,
+};
 pub use middleware::{MiddlewareFn, MiddlewareHandler, MiddlewareResult};
+pub use params::ParamKind;
+pub use recorder::{RequestRecorder, RECORDER_PATH};
+pub use replay::{
+    MemoryNonceStore, NonceFuture, NonceStore, PgNonceStore, ReplayProtection, NONCE_HEADER,
+    NONCE_TABLE, TIMESTAMP_HEADER,
+};
 pub use request::{HttpMethod, HttpRequest};
 pub use response::BufferBuilder;
-pub use routes::{AsyncResponse, RouteManager};
+pub use response_cache::{invalidate_responses, ResponseCache, ResponseCacheStats};
+pub use route_cache::{RouteCache, RouteCacheStats};
+pub(crate) use routes::join_path;
+pub use routes::{AsyncHandler, AsyncResponse, RouteManager};
+pub use scope::RequestScope;
+pub use signing::{
+    canonical_request, hmac_sha256, RequestSigner, RequestVerifier, KEY_ID_HEADER, SIGNATURE_HEADER,
+};
+pub use state::AppState;
@@ -0,0 +1,146 @@
+use std::{collections::HashMap, str::FromStr};
+
+use crate::Logger;
+
+/// Type constraint on a path placeholder, written `:id<i32>` or `*path<PathBuf>`.
+/// Segments that don't satisfy it make the route not match, so handlers never see them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamKind {
+    Str,
+    I32,
+    I64,
+    U32,
+    U64,
+    F64,
+    Bool,
+    Uuid,
+    /// Relative file path without `..` or empty segments; only valid on wildcards.
+    Path,
+}
+
+impl ParamKind {
+    pub fn accepts(&self, value: &str) -> bool {
+        match self {
+            ParamKind::Str => !value.is_empty(),
+            ParamKind::I32 => value.parse::<i32>().is_ok(),
+            ParamKind::I64 => value.parse::<i64>().is_ok(),
+            ParamKind::U32 => value.parse::<u32>().is_ok(),
+            ParamKind::U64 => value.parse::<u64>().is_ok(),
+            ParamKind::F64 => value.parse::<f64>().is_ok_and(|v| v.is_finite()),
+            ParamKind::Bool => value.parse::<bool>().is_ok(),
+            ParamKind::Uuid => is_uuid(value),
+            ParamKind::Path => value
+                .split('/')
+                .all(|part| !part.is_empty() && part != ".." && part != "."),
+        }
+    }
+}
+
+impl FromStr for ParamKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "str" | "String" => Ok(ParamKind::Str),
+            "i32" => Ok(ParamKind::I32),
+            "i64" => Ok(ParamKind::I64),
+            "u32" => Ok(ParamKind::U32),
+            "u64" | "usize" => Ok(ParamKind::U64),
+            "f64" => Ok(ParamKind::F64),
+            "bool" => Ok(ParamKind::Bool),
+            "uuid" | "Uuid" => Ok(ParamKind::Uuid),
+            "path" | "PathBuf" => Ok(ParamKind::Path),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum Segment {
+    Static(String),
+    Param(String, ParamKind),
+    /// Matches the rest of the path, one or more segments.
+    Wildcard(String, ParamKind),
+}
+
+impl Segment {
+    /// Parses a route pattern, panicking on unknown placeholder types so typos fail at
+    /// startup rather than silently matching everything.
+    pub(super) fn parse_pattern(pattern: &str) -> Vec<Segment> {
+        pattern.split('/').map(Self::parse).collect()
+    }
+
+    fn parse(segment: &str) -> Segment {
+        let placeholder = |name: &str| -> (String, Option<ParamKind>) {
+            match name.split_once('<') {
+                Some((name, kind)) => {
+                    let kind = kind.strip_suffix('>').and_then(|k| k.parse().ok());
+                    if kind.is_none() {
+                        Logger::for_target(module_path!()).panic(&format!(
+                            "Unknown route parameter type in '{}', expected one of \
+                             str, i32, i64, u32, u64, f64, bool, uuid, PathBuf",
+                            segment
+                        ));
+                    }
+                    (name.to_string(), kind)
+                }
+                None => (name.to_string(), None),
+            }
+        };
+
+        if let Some(name) = segment.strip_prefix(':') {
+            let (name, kind) = placeholder(name);
+            Segment::Param(name, kind.unwrap_or(ParamKind::Str))
+        } else if let Some(name) = segment.strip_prefix('*') {
+            let (name, kind) = placeholder(name);
+            Segment::Wildcard(name, kind.unwrap_or(ParamKind::Str))
+        } else {
+            Segment::Static(segment.to_string())
+        }
+    }
+
+    /// Matches `path` (without its query string) against `segments`, returning the
+    /// extracted parameters.
+    pub(super) fn match_path(segments: &[Segment], path: &str) -> Option<HashMap<String, String>> {
+        let parts: Vec<&str> = path.split('/').collect();
+        let mut params = HashMap::new();
+
+        for (i, segment) in segments.iter().enumerate() {
+            match segment {
+                Segment::Static(expected) => {
+                    if parts.get(i) != Some(&expected.as_str()) {
+                        return None;
+                    }
+                }
+                Segment::Param(name, kind) => {
+                    let value = parts.get(i)?;
+                    if !kind.accepts(value) {
+                        return None;
+                    }
+                    params.insert(name.clone(), value.to_string());
+                }
+                Segment::Wildcard(name, kind) => {
+                    let rest = parts.get(i..).filter(|rest| !rest.is_empty())?.join("/");
+                    if !kind.accepts(&rest) {
+                        return None;
+                    }
+                    params.insert(name.clone(), rest);
+                    return Some(params);
+                }
+            }
+        }
+
+        (parts.len() == segments.len()).then_some(params)
+    }
+}
+
+/// Hyphenated (`8-4-4-4-12`) or simple 32-digit hex form.
+fn is_uuid(value: &str) -> bool {
+    let hex = |s: &str, len: usize| s.len() == len && s.chars().all(|c| c.is_ascii_hexdigit());
+    let groups: Vec<&str> = value.split('-').collect();
+    match groups.as_slice() {
+        [simple] => hex(simple, 32),
+        [a, b, c, d, e] => hex(a, 8) && hex(b, 4) && hex(c, 4) && hex(d, 4) && hex(e, 12),
+        _ => false,
+    }
+}
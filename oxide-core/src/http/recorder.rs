@@ -0,0 +1,277 @@
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::{digest::base64, error_page::escape, handler::Res, BufferBuilder, HttpRequest};
+
+pub const RECORDER_PATH: &str = "/_oxide/requests";
+const HAR_PATH: &str = "/_oxide/requests.har";
+
+/// Development-only ring buffer of full request/response exchanges, browsable at
+/// `/_oxide/requests` and downloadable as a HAR file from `/_oxide/requests.har`.
+#[derive(Debug)]
+pub struct RequestRecorder {
+    capacity: usize,
+    entries: Mutex<VecDeque<Exchange>>,
+}
+
+#[derive(Debug, Clone)]
+struct Exchange {
+    started: SystemTime,
+    duration: Duration,
+    request: Vec<u8>,
+    response: Vec<u8>,
+}
+
+impl RequestRecorder {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn record(&self, request: &[u8], response: &Res, started: SystemTime, duration: Duration) {
+        if let Ok(mut entries) = self.entries.lock() {
+            if entries.len() == self.capacity {
+                entries.pop_front();
+            }
+            entries.push_back(Exchange {
+                started,
+                duration,
+                request: request.to_vec(),
+                response: response.buffer.clone(),
+            });
+        }
+    }
+
+    /// Serves the recorder pages if `request` targets them.
+    pub fn serve(&self, request: &HttpRequest) -> Option<Res> {
+        let path = request.path.split('?').next().unwrap_or("");
+        if path == HAR_PATH {
+            let har = self.har().to_string();
+            return Some(Res::new(
+                BufferBuilder::ok()
+                    .header(
+                        "Content-Disposition",
+                        "attachment; filename=\"oxide-requests.har\"",
+                    )
+                    .json(har)
+                    .build(),
+                200,
+            ));
+        }
+        if path == RECORDER_PATH {
+            return Some(Res::new(BufferBuilder::ok().html(self.html()).build(), 200));
+        }
+        None
+    }
+
+    fn snapshot(&self) -> Vec<Exchange> {
+        self.entries
+            .lock()
+            .map(|entries| entries.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn har(&self) -> serde_json::Value {
+        let entries = self
+            .snapshot()
+            .iter()
+            .map(|exchange| exchange.har_entry())
+            .collect::<Vec<_>>();
+
+        serde_json::json!({
+            "log": {
+                "version": "1.2",
+                "creator": { "name": "oxide", "version": env!("CARGO_PKG_VERSION") },
+                "entries": entries,
+            }
+        })
+    }
+
+    fn html(&self) -> String {
+        let mut page = String::from(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Recorded requests</title>\
+             <style>body{font-family:monospace;margin:2em}td{padding:0 1em 0 0}\
+             pre{background:#f4f4f4;padding:1em;overflow-x:auto}</style></head><body>",
+        );
+        page.push_str(&format!(
+            "<h1>Recorded requests</h1><p><a href=\"{}\">Download HAR</a></p>",
+            HAR_PATH
+        ));
+
+        for exchange in self.snapshot().iter().rev() {
+            let (status, _, _, _) = parse_response(&exchange.response);
+            let (method, path) = request_line(&exchange.request);
+            page.push_str(&format!(
+                "<details><summary>{} {} {} &mdash; {:.2}ms</summary>\
+                 <h3>Request</h3><pre>{}</pre><h3>Response</h3><pre>{}</pre></details>",
+                escape(&method),
+                escape(&path),
+                status,
+                exchange.duration.as_secs_f64() * 1000.0,
+                escape(&String::from_utf8_lossy(&exchange.request)),
+                escape(&String::from_utf8_lossy(&exchange.response)),
+            ));
+        }
+
+        page.push_str("</body></html>");
+        page
+    }
+}
+
+impl Exchange {
+    fn har_entry(&self) -> serde_json::Value {
+        let request = HttpRequest::parse(&self.request);
+        let (status, status_text, response_headers, response_body) = parse_response(&self.response);
+        let time = self.duration.as_secs_f64() * 1000.0;
+
+        let request_json = match &request {
+            Some(request) => {
+                let host = request
+                    .headers
+                    .get("host")
+                    .map(|h| h.as_str())
+                    .unwrap_or("localhost");
+                serde_json::json!({
+                    "method": request.method.to_string(),
+                    "url": format!("http://{}{}", host, request.path),
+                    "httpVersion": "HTTP/1.1",
+                    "headers": name_values(request.headers.iter()),
+                    "queryString": name_values(request.query_params.iter()),
+                    "cookies": name_values(request.cookies.iter()),
+                    "headersSize": -1,
+                    "bodySize": request.body.len(),
+                    "postData": har_content(request.content_type().unwrap_or(""), &request.body),
+                })
+            }
+            None => serde_json::json!({
+                "method": "UNKNOWN",
+                "url": "",
+                "httpVersion": "HTTP/1.1",
+                "headers": [],
+                "queryString": [],
+                "cookies": [],
+                "headersSize": -1,
+                "bodySize": self.request.len(),
+            }),
+        };
+
+        let mime_type = response_headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("content-type"))
+            .map(|(_, v)| v.clone())
+            .unwrap_or_default();
+
+        serde_json::json!({
+            "startedDateTime": iso8601(self.started),
+            "time": time,
+            "request": request_json,
+            "response": {
+                "status": status,
+                "statusText": status_text,
+                "httpVersion": "HTTP/1.1",
+                "headers": name_values(response_headers.iter().map(|(k, v)| (k, v))),
+                "cookies": [],
+                "content": har_content(&mime_type, &response_body),
+                "redirectURL": "",
+                "headersSize": -1,
+                "bodySize": response_body.len(),
+            },
+            "cache": {},
+            "timings": { "send": 0, "wait": time, "receive": 0 },
+        })
+    }
+}
+
+/// A HAR body: text when it's UTF-8, base64 otherwise so binary bodies survive the export.
+fn har_content(mime_type: &str, body: &[u8]) -> serde_json::Value {
+    match std::str::from_utf8(body) {
+        Ok(text) => serde_json::json!({
+            "size": body.len(),
+            "mimeType": mime_type,
+            "text": text,
+        }),
+        Err(_) => serde_json::json!({
+            "size": body.len(),
+            "mimeType": mime_type,
+            "text": base64(body),
+            "encoding": "base64",
+        }),
+    }
+}
+
+fn name_values<'a>(
+    pairs: impl Iterator<Item = (&'a String, &'a String)>,
+) -> Vec<serde_json::Value> {
+    pairs
+        .map(|(name, value)| serde_json::json!({ "name": name, "value": value }))
+        .collect()
+}
+
+fn request_line(request: &[u8]) -> (String, String) {
+    let line = request
+        .split(|&b| b == b'\r' || b == b'\n')
+        .next()
+        .unwrap_or_default();
+    let line = String::from_utf8_lossy(line);
+    let mut parts = line.split_whitespace();
+    (
+        parts.next().unwrap_or("").to_string(),
+        parts.next().unwrap_or("").to_string(),
+    )
+}
+
+/// Splits a raw response into status, reason, headers and body.
+fn parse_response(response: &[u8]) -> (u16, String, Vec<(String, String)>, Vec<u8>) {
+    let split = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .unwrap_or(response.len());
+    let head = String::from_utf8_lossy(&response[..split]);
+    let body = response.get(split + 4..).unwrap_or_default().to_vec();
+
+    let mut lines = head.split("\r\n");
+    let mut status_line = lines.next().unwrap_or("").splitn(3, ' ').skip(1);
+    let status = status_line.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+    let reason = status_line.next().unwrap_or("").to_string();
+
+    let headers = lines
+        .filter_map(|line| line.split_once(": "))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+    (status, reason, headers, body)
+}
+
+/// Formats a timestamp as ISO 8601 in UTC, as required by HAR's `startedDateTime`.
+pub(super) fn iso8601(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs() as i64;
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // Civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60,
+        since_epoch.subsec_millis()
+    )
+}
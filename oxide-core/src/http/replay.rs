@@ -0,0 +1,429 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use sqlx::{error::BoxDynError, postgres::PgArguments, Arguments};
+
+use super::{handler::Res, BufferBuilder, HttpRequest};
+use crate::{logger::LogLevel, Error, Logger, PgDatabase};
+
+pub const NONCE_HEADER: &str = "x-nonce";
+pub const TIMESTAMP_HEADER: &str = "x-timestamp";
+pub const NONCE_TABLE: &str = "oxide_nonces";
+
+const MAX_NONCE_LEN: usize = 128;
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+pub type NonceFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, Error>> + Send + 'a>>;
+
+/// Where seen nonces are remembered. `MemoryNonceStore` suits a single instance;
+/// `PgNonceStore` shares them between instances behind a load balancer.
+pub trait NonceStore: fmt::Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Called once before the server starts accepting requests, e.g. to create tables.
+    fn prepare(&self) -> NonceFuture<'_, ()> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Remembers `nonce` until `expires_at`, returning `false` if it is already remembered.
+    fn insert<'a>(&'a self, nonce: &'a str, expires_at: SystemTime) -> NonceFuture<'a, bool>;
+}
+
+/// Keeps nonces in process memory, forgetting them once they expire.
+#[derive(Debug)]
+pub struct MemoryNonceStore {
+    seen: Mutex<Seen>,
+}
+
+#[derive(Debug)]
+struct Seen {
+    nonces: HashMap<String, SystemTime>,
+    purged_at: Instant,
+}
+
+impl MemoryNonceStore {
+    pub fn new() -> Self {
+        Self {
+            seen: Mutex::new(Seen {
+                nonces: HashMap::new(),
+                purged_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Number of nonces currently remembered.
+    pub fn len(&self) -> usize {
+        self.seen
+            .lock()
+            .map(|seen| seen.nonces.len())
+            .unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for MemoryNonceStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NonceStore for MemoryNonceStore {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn insert<'a>(&'a self, nonce: &'a str, expires_at: SystemTime) -> NonceFuture<'a, bool> {
+        let now = SystemTime::now();
+        let inserted = match self.seen.lock() {
+            Ok(mut seen) => {
+                if seen.purged_at.elapsed() >= PURGE_INTERVAL {
+                    seen.nonces.retain(|_, expires_at| *expires_at > now);
+                    seen.purged_at = Instant::now();
+                }
+                match seen.nonces.get(nonce) {
+                    Some(expires) if *expires > now => Ok(false),
+                    _ => {
+                        seen.nonces.insert(nonce.to_string(), expires_at);
+                        Ok(true)
+                    }
+                }
+            }
+            Err(_) => Err(Error::InternalServer(
+                "nonce store lock poisoned".to_string(),
+            )),
+        };
+        Box::pin(async move { inserted })
+    }
+}
+
+/// Keeps nonces in `oxide_nonces`, so every instance sharing the database rejects a
+/// request replayed against any of them. Expired rows are deleted at most once a minute.
+#[derive(Debug)]
+pub struct PgNonceStore {
+    db: PgDatabase,
+    purged_at: AtomicU64,
+}
+
+impl PgNonceStore {
+    pub fn new(db: PgDatabase) -> Self {
+        Self {
+            db,
+            purged_at: AtomicU64::new(0),
+        }
+    }
+
+    pub async fn create_table(&self) -> Result<(), Error> {
+        self.db
+            .execute(format!(
+                "CREATE TABLE IF NOT EXISTS {} (
+                    nonce TEXT PRIMARY KEY,
+                    expires_at TIMESTAMPTZ NOT NULL
+                )",
+                NONCE_TABLE
+            ))
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes expired nonces, returning how many were removed.
+    pub async fn purge_expired(&self) -> Result<u64, Error> {
+        let result = self
+            .db
+            .execute(format!(
+                "DELETE FROM {} WHERE expires_at <= now()",
+                NONCE_TABLE
+            ))
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+impl NonceStore for PgNonceStore {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    fn prepare(&self) -> NonceFuture<'_, ()> {
+        Box::pin(self.create_table())
+    }
+
+    fn insert<'a>(&'a self, nonce: &'a str, expires_at: SystemTime) -> NonceFuture<'a, bool> {
+        Box::pin(async move {
+            let now = unix_seconds(SystemTime::now());
+            let purged_at = self.purged_at.load(Ordering::Relaxed);
+            if now >= purged_at + PURGE_INTERVAL.as_secs()
+                && self
+                    .purged_at
+                    .compare_exchange(purged_at, now, Ordering::Relaxed, Ordering::Relaxed)
+                    .is_ok()
+            {
+                // A failed purge is retried next interval; the insert below still decides.
+                let _ = self.purge_expired().await;
+            }
+
+            let expires_at = expires_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64();
+            let args = args(|args| {
+                args.add(nonce)?;
+                args.add(expires_at)
+            })?;
+            // A row left behind by an expired nonce is taken over rather than rejected.
+            let inserted: Option<(i32,)> = self
+                .db
+                .query_optional_with(
+                    format!(
+                        "INSERT INTO {table} (nonce, expires_at) VALUES ($1, to_timestamp($2)) \
+                         ON CONFLICT (nonce) DO UPDATE SET expires_at = EXCLUDED.expires_at \
+                         WHERE {table}.expires_at <= now() \
+                         RETURNING 1",
+                        table = NONCE_TABLE
+                    ),
+                    args,
+                )
+                .await?;
+            Ok(inserted.is_some())
+        })
+    }
+}
+
+/// Rejects signed requests that are stale or have been seen before. Each request to a
+/// protected route must carry a unique nonce and the Unix time in seconds it was sent at;
+/// sign both along with the body so neither can be swapped. Nonces are remembered for as
+/// long as their timestamp stays inside the window, so an old request can be neither
+/// replayed as-is nor revived with a fresh timestamp.
+///
+/// Nonces are scoped to the request's principal when a middleware has set one, so
+/// different API keys cannot collide.
+///
+/// ```rust,ignore
+/// server.with_replay_protection(
+///     ReplayProtection::new(PgNonceStore::new(db.clone())).with_window(Duration::from_secs(300)),
+/// );
+/// server.router.post("/webhooks/stripe", stripe_handler).replay_protected();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReplayProtection {
+    store: Arc<dyn NonceStore>,
+    window: Duration,
+    nonce_header: String,
+    timestamp_header: String,
+}
+
+impl ReplayProtection {
+    /// Uses a five minute window and the `X-Nonce` and `X-Timestamp` headers.
+    pub fn new(store: impl NonceStore + 'static) -> Self {
+        Self {
+            store: Arc::new(store),
+            window: Duration::from_secs(300),
+            nonce_header: NONCE_HEADER.to_string(),
+            timestamp_header: TIMESTAMP_HEADER.to_string(),
+        }
+    }
+
+    /// How far a request's timestamp may be from the server's clock, either way.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn with_nonce_header(mut self, header: &str) -> Self {
+        self.nonce_header = header.to_lowercase();
+        self
+    }
+
+    pub fn with_timestamp_header(mut self, header: &str) -> Self {
+        self.timestamp_header = header.to_lowercase();
+        self
+    }
+
+    pub fn store(&self) -> &dyn NonceStore {
+        self.store.as_ref()
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Records the request's nonce, or returns the response rejecting it: 400 when the
+    /// headers are missing or the timestamp is outside the window, 409 when the nonce was
+    /// already used and 503 when the store cannot be reached.
+    pub async fn check(&self, request: &HttpRequest, principal: Option<&str>) -> Result<(), Res> {
+        let nonce = match request.headers.get(&self.nonce_header) {
+            Some(nonce) if !nonce.is_empty() && nonce.len() <= MAX_NONCE_LEN => nonce,
+            Some(_) => return Err(reject(BufferBuilder::BAD_REQUEST, "Invalid nonce")),
+            None => {
+                return Err(reject(
+                    BufferBuilder::BAD_REQUEST,
+                    &format!("Missing {} header", self.nonce_header),
+                ))
+            }
+        };
+        let timestamp = match request.headers.get(&self.timestamp_header) {
+            Some(timestamp) => match timestamp.trim().parse::<u64>() {
+                Ok(seconds) => UNIX_EPOCH + Duration::from_secs(seconds),
+                Err(_) => return Err(reject(BufferBuilder::BAD_REQUEST, "Invalid timestamp")),
+            },
+            None => {
+                return Err(reject(
+                    BufferBuilder::BAD_REQUEST,
+                    &format!("Missing {} header", self.timestamp_header),
+                ))
+            }
+        };
+
+        let now = SystemTime::now();
+        let skew = now
+            .duration_since(timestamp)
+            .or_else(|_| timestamp.duration_since(now))
+            .unwrap_or_default();
+        if skew > self.window {
+            return Err(reject(
+                BufferBuilder::BAD_REQUEST,
+                "Request timestamp is outside the replay window",
+            ));
+        }
+
+        // Length-prefixed so no principal and nonce pair can spell another's key.
+        let key = match principal {
+            Some(principal) => format!("{}:{}:{}", principal.len(), principal, nonce),
+            None => format!(":{}", nonce),
+        };
+        let logger = Logger::for_target(module_path!());
+        match self.store.insert(&key, timestamp + self.window).await {
+            Ok(true) => Ok(()),
+            Ok(false) => {
+                logger.log(
+                    LogLevel::Warning,
+                    &format!("Rejected replayed request {} (nonce {})", request.path, key),
+                );
+                Err(reject(
+                    BufferBuilder::CONFLICT,
+                    "Nonce has already been used",
+                ))
+            }
+            Err(e) => {
+                logger.log(
+                    LogLevel::Error,
+                    &format!(
+                        "Failed to record nonce in {} store: {}",
+                        self.store.name(),
+                        e
+                    ),
+                );
+                Err(reject(
+                    BufferBuilder::SERVICE_UNAVAILABLE,
+                    "Unable to verify request nonce",
+                ))
+            }
+        }
+    }
+}
+
+fn reject(status: (u16, &str), message: &str) -> Res {
+    Res::new(
+        BufferBuilder::new().status(status).text(message).build(),
+        status.0,
+    )
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+fn args(
+    add: impl FnOnce(&mut PgArguments) -> Result<(), BoxDynError>,
+) -> Result<PgArguments, Error> {
+    let mut args = PgArguments::default();
+    add(&mut args).map_err(|e| Error::Database(sqlx::Error::Encode(e)))?;
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::HttpMethod;
+
+    fn request(nonce: &str, sent_at: SystemTime) -> HttpRequest {
+        let headers = [
+            (NONCE_HEADER.to_string(), nonce.to_string()),
+            (
+                TIMESTAMP_HEADER.to_string(),
+                unix_seconds(sent_at).to_string(),
+            ),
+        ];
+        HttpRequest::new(
+            HttpMethod::Post,
+            "/webhooks".to_string(),
+            headers.into_iter().collect(),
+            vec![],
+            HashMap::new(),
+            HashMap::new(),
+            HashMap::new(),
+        )
+    }
+
+    async fn status(protection: &ReplayProtection, nonce: &str, principal: Option<&str>) -> u16 {
+        match protection
+            .check(&request(nonce, SystemTime::now()), principal)
+            .await
+        {
+            Ok(()) => 200,
+            Err(res) => res.status,
+        }
+    }
+
+    #[tokio::test]
+    async fn nonces_are_scoped_to_the_principal() {
+        let protection = ReplayProtection::new(MemoryNonceStore::new());
+        assert_eq!(status(&protection, "n1", Some("alice")).await, 200);
+        assert_eq!(status(&protection, "n1", Some("alice")).await, 409);
+        assert_eq!(status(&protection, "n1", Some("bob")).await, 200);
+        assert_eq!(status(&protection, "n1", None).await, 200);
+        assert_eq!(status(&protection, "n1", None).await, 409);
+    }
+
+    #[tokio::test]
+    async fn principal_and_nonce_cannot_spell_another_key() {
+        let protection = ReplayProtection::new(MemoryNonceStore::new());
+        assert_eq!(status(&protection, "b/c", Some("a")).await, 200);
+        assert_eq!(status(&protection, "c", Some("a/b")).await, 200);
+        assert_eq!(status(&protection, "1:a:b", None).await, 200);
+        assert_eq!(status(&protection, "b", Some("a")).await, 200);
+    }
+
+    #[tokio::test]
+    async fn stale_timestamps_are_rejected() {
+        let protection =
+            ReplayProtection::new(MemoryNonceStore::new()).with_window(Duration::from_secs(60));
+        let sent_at = SystemTime::now() - Duration::from_secs(120);
+        let res = protection.check(&request("n1", sent_at), None).await;
+        assert_eq!(res.err().map(|res| res.status), Some(400));
+    }
+
+    #[tokio::test]
+    async fn expired_nonces_can_be_reused() {
+        let store = MemoryNonceStore::new();
+        let expired = SystemTime::now() - Duration::from_secs(1);
+        let later = SystemTime::now() + Duration::from_secs(60);
+        assert!(store.insert("n1", expired).await.unwrap());
+        assert!(store.insert("n1", later).await.unwrap());
+        assert!(!store.insert("n1", later).await.unwrap());
+        assert_eq!(store.len(), 1);
+    }
+}
@@ -1,12 +1,15 @@
 use std::{collections::HashMap, str::FromStr};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use memchr::{memchr, memchr_iter, memmem};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum HttpMethod {
     Get,
     Post,
     Put,
     Patch,
     Delete,
+    Options,
     Unknown,
 }
 
@@ -20,6 +23,7 @@ impl FromStr for HttpMethod {
             "PUT" => Ok(HttpMethod::Put),
             "PATCH" => Ok(HttpMethod::Patch),
             "DELETE" => Ok(HttpMethod::Delete),
+            "OPTIONS" => Ok(HttpMethod::Options),
             _ => Ok(HttpMethod::Unknown),
         }
     }
@@ -74,6 +78,16 @@ impl HttpRequest {
         self.headers.get("content-length")?.parse().ok()
     }
 
+    /// The body exactly as received, which needn't be UTF-8.
+    pub fn body_raw(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// The body as text, or `None` if it isn't valid UTF-8.
+    pub fn body_text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.body).ok()
+    }
+
     pub fn json_body<T: serde::de::DeserializeOwned>(&self) -> Option<T> {
         if self.content_type()? != "application/json" {
             return None;
@@ -82,14 +96,16 @@ impl HttpRequest {
     }
 
     /** Static interface */
+    /// Parses a buffered request. Only the head has to be UTF-8; the body is taken as is,
+    /// everything after the blank line.
     pub fn parse(buffer: &[u8]) -> Option<HttpRequest> {
-        let request = std::str::from_utf8(buffer).ok()?;
-        let mut parts = request.split("\r\n\r\n");
-        let headers_part = parts.next()?;
-        let body_part = parts.next().unwrap_or("");
+        let (head, body) = match memmem::find(buffer, b"\r\n\r\n") {
+            Some(end) => (&buffer[..end], &buffer[end + 4..]),
+            None => (buffer, &[][..]),
+        };
 
-        let mut lines = headers_part.lines();
-        let request_line = lines.next()?;
+        let mut lines = lines(head);
+        let request_line = std::str::from_utf8(lines.next()?).ok()?;
         let mut parts = request_line.split_whitespace();
 
         let method = HttpMethod::from_str(parts.next()?).ok()?;
@@ -97,8 +113,14 @@ impl HttpRequest {
 
         let mut headers = HashMap::new();
         for line in lines {
-            if let Some((key, value)) = line.split_once(": ") {
-                headers.insert(key.to_lowercase(), value.to_string());
+            let Some(colon) = memchr(b':', line) else {
+                continue;
+            };
+            if let (Ok(key), Ok(value)) = (
+                std::str::from_utf8(&line[..colon]),
+                std::str::from_utf8(&line[colon + 1..]),
+            ) {
+                headers.insert(key.to_lowercase(), value.trim().to_string());
             }
         }
 
@@ -107,7 +129,7 @@ impl HttpRequest {
             None => HashMap::new(),
         };
 
-        let body = body_part.as_bytes().to_vec();
+        let body = body.to_vec();
 
         let query_params = HttpRequest::parse_query_params(&path.as_str());
         let path_params = HttpRequest::parse_path_params(&path.as_str(), &path);
@@ -123,6 +145,31 @@ impl HttpRequest {
         })
     }
 
+    /// The first line of a buffered request, e.g. `GET /users HTTP/1.1`, or `""` if it
+    /// isn't UTF-8.
+    pub(crate) fn request_line(buffer: &[u8]) -> &str {
+        lines(buffer)
+            .next()
+            .and_then(|line| std::str::from_utf8(line).ok())
+            .unwrap_or("")
+    }
+
+    /// Length of the whole request once its head has arrived: the head plus its
+    /// `Content-Length` body. `None` while the head is incomplete.
+    pub(crate) fn expected_len(buffer: &[u8]) -> Option<usize> {
+        let end = memmem::find(buffer, b"\r\n\r\n")?;
+        let content_length = lines(&buffer[..end])
+            .filter_map(|line| {
+                let colon = memchr(b':', line)?;
+                line[..colon]
+                    .eq_ignore_ascii_case(b"content-length")
+                    .then(|| std::str::from_utf8(&line[colon + 1..]).ok())?
+            })
+            .find_map(|value| value.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+        Some(end + 4 + content_length)
+    }
+
     /** Private interface */
     fn parse_path_params(path: &str, pattern: &str) -> HashMap<String, String> {
         let mut params = HashMap::new();
@@ -159,3 +206,16 @@ impl HttpRequest {
         params
     }
 }
+
+/// Lines of a request head without their `\r\n` (or bare `\n`) endings. memchr finds the
+/// line breaks a word or vector at a time, which matters since every request is split this way.
+fn lines(head: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut start = 0;
+    memchr_iter(b'\n', head)
+        .chain(std::iter::once(head.len()))
+        .map(move |end| {
+            let line = &head[start.min(end)..end];
+            start = end + 1;
+            line.strip_suffix(b"\r").unwrap_or(line)
+        })
+}
@@ -1,11 +1,14 @@
 use flate2::{write::GzEncoder, Compression};
 use std::io::Write;
 
+use super::digest::{content_digest, CONTENT_DIGEST};
+
 #[derive(Default)]
 pub struct BufferBuilder {
     status_line: String,
     headers: Vec<(String, String)>,
     body: Vec<u8>,
+    digest: bool,
 }
 
 impl BufferBuilder {
@@ -17,12 +20,17 @@ impl BufferBuilder {
     pub const DELETED: (u16, &'static str) = (200, "Success");
     pub const NOT_FOUND: (u16, &'static str) = (404, "Not Found");
     pub const BAD_REQUEST: (u16, &'static str) = (400, "Bad Request");
+    pub const UNAUTHORIZED: (u16, &'static str) = (401, "Unauthorized");
+    pub const CONFLICT: (u16, &'static str) = (409, "Conflict");
+    pub const PAYLOAD_TOO_LARGE: (u16, &'static str) = (413, "Payload Too Large");
     pub const INTERNAL_SERVER_ERROR: (u16, &'static str) = (500, "Internal Server Error");
+    pub const SERVICE_UNAVAILABLE: (u16, &'static str) = (503, "Service Unavailable");
 
     // Content type constants
     pub const PLAIN: &'static str = "text/plain";
     pub const HTML: &'static str = "text/html";
     pub const JSON: &'static str = "application/json";
+    pub const OCTET_STREAM: &'static str = "application/octet-stream";
 
     const COMPRESSIBLE_TYPES: [&'static str; 4] =
         ["text/plain", "text/html", "text/css", "application/json"];
@@ -32,6 +40,31 @@ impl BufferBuilder {
         Self::default()
     }
 
+    pub fn reason_phrase(status: u16) -> &'static str {
+        match status {
+            200 => "OK",
+            201 => "Created",
+            204 => "No Content",
+            301 => "Moved Permanently",
+            302 => "Found",
+            304 => "Not Modified",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            409 => "Conflict",
+            413 => "Payload Too Large",
+            422 => "Unprocessable Entity",
+            429 => "Too Many Requests",
+            500 => "Internal Server Error",
+            502 => "Bad Gateway",
+            503 => "Service Unavailable",
+            504 => "Gateway Timeout",
+            _ => "Unknown",
+        }
+    }
+
     pub fn status(mut self, status: (u16, &str)) -> Self {
         self.status_line = format!("HTTP/1.1 {} {}", status.0, status.1);
         self
@@ -42,6 +75,47 @@ impl BufferBuilder {
         self
     }
 
+    pub fn get_header(&self, key: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn remove_header(mut self, key: &str) -> Self {
+        self.headers.retain(|(k, _)| !k.eq_ignore_ascii_case(key));
+        self
+    }
+
+    pub fn status_code(&self) -> u16 {
+        self.status_line
+            .split(' ')
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .unwrap_or(0)
+    }
+
+    pub fn body_bytes(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Parses an already built response back into a builder so it can be modified.
+    pub fn from_raw(buffer: &[u8]) -> Option<Self> {
+        let split = buffer.windows(4).position(|w| w == b"\r\n\r\n")?;
+        let head = std::str::from_utf8(&buffer[..split]).ok()?;
+        let mut lines = head.split("\r\n");
+
+        Some(Self {
+            status_line: lines.next()?.to_string(),
+            headers: lines
+                .filter_map(|line| line.split_once(": "))
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            body: buffer[split + 4..].to_vec(),
+            digest: false,
+        })
+    }
+
     pub fn content_type(self, content_type: &str) -> Self {
         self.header("Content-Type", content_type)
     }
@@ -67,6 +141,25 @@ impl BufferBuilder {
             .body(body.as_ref().as_bytes().to_vec())
     }
 
+    /// A binary body sent exactly as given, e.g. an image, PDF or protobuf message.
+    pub fn bytes(self, content_type: &str, body: Vec<u8>) -> Self {
+        self.content_type(content_type).body(body)
+    }
+
+    /// Bytes of no particular type, as `application/octet-stream`.
+    pub fn octet_stream(self, body: Vec<u8>) -> Self {
+        self.bytes(Self::OCTET_STREAM, body)
+    }
+
+    /// Adds a `Content-Digest` header with the SHA-256 of the body as sent, after any
+    /// compression, so clients can check large downloads with [`verify_content_digest`].
+    ///
+    /// [`verify_content_digest`]: crate::http::verify_content_digest
+    pub fn digest(mut self) -> Self {
+        self.digest = true;
+        self
+    }
+
     fn get_accepted_encoding(&self) -> Option<&str> {
         self.headers
             .iter()
@@ -104,6 +197,12 @@ impl BufferBuilder {
         if self.should_compress() {
             self.compress_body();
         }
+        if self.digest {
+            self.headers
+                .retain(|(k, _)| !k.eq_ignore_ascii_case(CONTENT_DIGEST));
+            self.headers
+                .push((CONTENT_DIGEST.to_string(), content_digest(&self.body)));
+        }
 
         let mut response = Vec::new();
 
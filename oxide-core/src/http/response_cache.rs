@@ -0,0 +1,196 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use hashlink::LruCache;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+/// How many times each table has been written through the ORM in this process. The write
+/// lock is only taken the first time a table is written; after that writes bump its counter
+/// under the read lock.
+static VERSIONS: Lazy<RwLock<HashMap<String, AtomicU64>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Marks every cached response derived from `table` as stale, in every response cache in the
+/// process. The ORM's builders call this once each write to a model is visible, after the
+/// transaction commits for writes inside one; call it after changing a table with raw SQL.
+pub fn invalidate_responses(table: &str) {
+    if let Ok(versions) = VERSIONS.read() {
+        if let Some(version) = versions.get(table) {
+            version.fetch_add(1, Ordering::Release);
+            return;
+        }
+    }
+    if let Ok(mut versions) = VERSIONS.write() {
+        versions
+            .entry(table.to_string())
+            .or_default()
+            .fetch_add(1, Ordering::Release);
+    }
+}
+
+fn versions(tables: &[&str]) -> Vec<(String, u64)> {
+    let versions = VERSIONS.read().ok();
+    tables
+        .iter()
+        .map(|table| {
+            let version = versions
+                .as_ref()
+                .and_then(|v| v.get(*table))
+                .map_or(0, |version| version.load(Ordering::Acquire));
+            (table.to_string(), version)
+        })
+        .collect()
+}
+
+/// Successful GET responses of handlers declared with `#[handler(cache(models(...)))]`, kept
+/// until their time to live runs out or one of the models they were derived from is written
+/// through the ORM. Keyed like the [`Coalescer`](super::Coalescer): by path and query string,
+/// tenant, principal, and the headers responses commonly vary by. Responses that set a
+/// cookie are never cached. Disabled with a capacity of 0.
+///
+/// Invalidation is local to the process; other instances serve their copies until the time
+/// to live runs out. Keep the time to live short where that matters.
+#[derive(Debug)]
+pub struct ResponseCache {
+    capacity: usize,
+    entries: Mutex<LruCache<String, CachedResponse>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    stale: AtomicU64,
+}
+
+#[derive(Debug, Clone)]
+struct CachedResponse {
+    status: u16,
+    buffer: Vec<u8>,
+    /// Tables the response was derived from, with their versions when the handler started.
+    tables: Vec<(String, u64)>,
+    expires: Instant,
+}
+
+impl CachedResponse {
+    /// Whether none of the response's tables has been written since it was produced.
+    fn is_current(&self) -> bool {
+        let tables: Vec<_> = self.tables.iter().map(|(t, _)| t.as_str()).collect();
+        versions(&tables) == self.tables
+    }
+}
+
+/// Point-in-time view of a [`ResponseCache`], suitable for metrics endpoints.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ResponseCacheStats {
+    pub capacity: usize,
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Responses dropped because a model they were derived from was written.
+    pub stale: u64,
+    /// Share of lookups answered from the cache, between 0 and 1.
+    pub hit_rate: f64,
+}
+
+/// The versions of a response's tables, read before its handler runs, so a write that lands
+/// while it runs leaves the response stale rather than cached as current.
+#[derive(Debug)]
+pub(crate) struct Versions(Vec<(String, u64)>);
+
+impl ResponseCache {
+    pub const DEFAULT_CAPACITY: usize = 1000;
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(LruCache::new(capacity)),
+            hits: AtomicU64::default(),
+            misses: AtomicU64::default(),
+            stale: AtomicU64::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// The cached status and bytes for `key`, unless expired or stale, counting the lookup as
+    /// a hit or miss.
+    pub(crate) fn get(&self, key: &str) -> Option<(u16, Vec<u8>)> {
+        let mut entries = self.entries.lock().ok()?;
+        match entries.get(key) {
+            Some(cached) if cached.is_current() && cached.expires > Instant::now() => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some((cached.status, cached.buffer.clone()))
+            }
+            Some(cached) => {
+                if !cached.is_current() {
+                    self.stale.fetch_add(1, Ordering::Relaxed);
+                }
+                entries.remove(key);
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub(crate) fn versions(tables: &[&str]) -> Versions {
+        Versions(versions(tables))
+    }
+
+    /// Caches a response for `key` for `ttl`, evicting the least recently used entry when
+    /// full.
+    pub(crate) fn insert(
+        &self,
+        key: String,
+        versions: Versions,
+        ttl: Duration,
+        status: u16,
+        buffer: Vec<u8>,
+    ) {
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        entries.insert(
+            key,
+            CachedResponse {
+                status,
+                buffer,
+                tables: versions.0,
+                expires: Instant::now() + ttl,
+            },
+        );
+    }
+
+    /// Drops every cached response.
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.clear();
+        }
+    }
+
+    pub fn stats(&self) -> ResponseCacheStats {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        ResponseCacheStats {
+            capacity: self.capacity,
+            entries: self.entries.lock().map(|e| e.len()).unwrap_or(0),
+            hits,
+            misses,
+            stale: self.stale.load(Ordering::Relaxed),
+            hit_rate: if hits + misses == 0 {
+                0.0
+            } else {
+                hits as f64 / (hits + misses) as f64
+            },
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+
+use hashlink::LruCache;
+use serde::Serialize;
+
+use super::HttpMethod;
+
+/// Remembers which route a method and path resolved to, so repeated requests for the same
+/// hot paths skip the matcher. Only routes without parameters are cached: a parameterized
+/// route would fill the cache with one entry per id. Routes with guards are never cached
+/// either, since whether they match depends on more than the path. Disabled with a capacity
+/// of 0.
+#[derive(Debug)]
+pub struct RouteCache {
+    capacity: usize,
+    entries: Mutex<LruCache<(HttpMethod, String), usize>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// Point-in-time view of a [`RouteCache`], suitable for metrics endpoints.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct RouteCacheStats {
+    pub capacity: usize,
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Share of lookups answered from the cache, between 0 and 1.
+    pub hit_rate: f64,
+}
+
+impl RouteCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(LruCache::new(capacity)),
+            hits: AtomicU64::default(),
+            misses: AtomicU64::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Index of the cached route for `method` and `path`, counting the lookup as a hit or
+    /// miss.
+    pub(crate) fn get(&self, method: HttpMethod, path: &str) -> Option<usize> {
+        let mut entries = self.entries.lock().ok()?;
+        match entries.get(&(method, path.to_string())) {
+            Some(&index) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(index)
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Caches route `index` for `method` and `path`, evicting the least recently used entry
+    /// when full.
+    pub(crate) fn insert(&self, method: HttpMethod, path: &str, index: usize) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert((method, path.to_string()), index);
+        }
+    }
+
+    pub fn stats(&self) -> RouteCacheStats {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        RouteCacheStats {
+            capacity: self.capacity,
+            entries: self.entries.lock().map(|e| e.len()).unwrap_or(0),
+            hits,
+            misses,
+            hit_rate: if hits + misses == 0 {
+                0.0
+            } else {
+                hits as f64 / (hits + misses) as f64
+            },
+        }
+    }
+}
@@ -1,12 +1,25 @@
-use std::{future::Future, pin::Pin};
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
-use crate::Logger;
+use crate::{server::Slo, Logger};
 
-use super::{handler::Context, HttpMethod, OxideResponse};
+use super::{
+    guard::Guard, handler::Context, params::Segment, route_cache::RouteCache, state::AppState,
+    HttpMethod, HttpRequest, OxideResponse,
+};
 
 pub type AsyncHandler = fn(&Context) -> AsyncResponse;
 pub type AsyncResponse<'a> = Pin<Box<dyn Future<Output = OxideResponse> + Send + 'a>>;
 
+static NEXT_ROUTE_ID: AtomicU64 = AtomicU64::new(0);
+
 #[derive(Debug, Clone, Default)]
 pub struct RouteManager {
     routes: Vec<Route>,
@@ -17,7 +30,7 @@ impl RouteManager {
     pub fn new() -> Self {
         Self {
             routes: vec![],
-            logger: Logger::new(),
+            logger: Logger::for_target(module_path!()),
         }
     }
 
@@ -74,6 +87,153 @@ impl RouteManager {
         self
     }
 
+    /// Registers every route of `router` under `prefix`, sharing `state` with their handlers.
+    /// Returns the mounted routes' raw paths, for attaching the app's middleware.
+    pub(crate) fn mount(
+        &mut self,
+        prefix: &str,
+        router: RouteManager,
+        state: Arc<AppState>,
+    ) -> Vec<String> {
+        let mut raw_paths = vec![];
+        for route in router.routes {
+            let mut mounted = Route::new(
+                &join_path(prefix, &route.pattern),
+                route.method,
+                route.handler,
+            );
+            mounted.jsonp = route.jsonp;
+            mounted.guards = route.guards;
+            mounted.replay_protected = route.replay_protected;
+            mounted.signed = route.signed;
+            mounted.slo = route.slo;
+            mounted.coalesce = route.coalesce;
+            mounted.coalesce_vary = route.coalesce_vary;
+            mounted.dependencies = route.dependencies;
+            mounted.state = Some(Arc::clone(&state));
+            if !raw_paths.contains(&mounted.raw_path) {
+                raw_paths.push(mounted.raw_path.clone());
+            }
+            self.add_route(mounted);
+        }
+        raw_paths
+    }
+
+    /// Enables JSONP on the GET route registered at `path`: when the request carries the
+    /// `callback_param` query parameter, JSON responses are wrapped in that callback.
+    pub fn jsonp(&mut self, path: &str, callback_param: &str) -> &mut Self {
+        match self
+            .routes
+            .iter_mut()
+            .find(|r| r.method == HttpMethod::Get && r.pattern == path)
+        {
+            Some(route) => route.jsonp = Some(callback_param.to_string()),
+            None => self.logger.log(
+                crate::logger::LogLevel::Warning,
+                &format!("Cannot enable JSONP, no GET route registered for: {}", path),
+            ),
+        }
+        self
+    }
+
+    /// Adds a guard to the most recently registered route, e.g.
+    /// `router.post("/upload", json_handler).guard(Guard::content_type("application/json"))`.
+    pub fn guard(&mut self, guard: Guard) -> &mut Self {
+        match self.routes.last_mut() {
+            Some(route) => route.guards.push(guard),
+            None => self.logger.log(
+                crate::logger::LogLevel::Warning,
+                "Cannot add guard, no route has been registered yet",
+            ),
+        }
+        self
+    }
+
+    /// Requires the most recently registered route to carry a fresh nonce and timestamp, as
+    /// checked by the server's `with_replay_protection`. Duplicates are rejected with 409.
+    pub fn replay_protected(&mut self) -> &mut Self {
+        match self.routes.last_mut() {
+            Some(route) => route.replay_protected = true,
+            None => self.logger.log(
+                crate::logger::LogLevel::Warning,
+                "Cannot enable replay protection, no route has been registered yet",
+            ),
+        }
+        self
+    }
+
+    /// Requires the most recently registered route to be signed with a key known to the
+    /// server's `with_request_verifier`. Unsigned or badly signed requests get 401.
+    pub fn signed(&mut self) -> &mut Self {
+        match self.routes.last_mut() {
+            Some(route) => route.signed = true,
+            None => self.logger.log(
+                crate::logger::LogLevel::Warning,
+                "Cannot require signing, no route has been registered yet",
+            ),
+        }
+        self
+    }
+
+    /// Sets a latency objective on the most recently registered route, tracked by the
+    /// server's `slo_tracker`, e.g. `.slo(Slo::p99(Duration::from_millis(200)))`.
+    pub fn slo(&mut self, slo: Slo) -> &mut Self {
+        match self.routes.last_mut() {
+            Some(route) => route.slo = Some(slo),
+            None => self.logger.log(
+                crate::logger::LogLevel::Warning,
+                "Cannot set an SLO, no route has been registered yet",
+            ),
+        }
+        self
+    }
+
+    /// Lets concurrent identical requests to the most recently registered route, which must
+    /// be a GET, share the response of the first while it's in flight, e.g. for hot
+    /// endpoints whose handlers all run the same expensive query.
+    pub fn coalesce(&mut self) -> &mut Self {
+        match self.routes.last_mut() {
+            Some(route) if route.method == HttpMethod::Get => route.coalesce = true,
+            Some(route) => self.logger.log(
+                crate::logger::LogLevel::Warning,
+                &format!(
+                    "Cannot coalesce {} {}, only GET requests are coalesced",
+                    route.method, route.pattern
+                ),
+            ),
+            None => self.logger.log(
+                crate::logger::LogLevel::Warning,
+                "Cannot enable coalescing, no route has been registered yet",
+            ),
+        }
+        self
+    }
+
+    /// Like [`coalesce`](Self::coalesce), but requests only share a response when `headers`
+    /// match too, e.g. `&["x-api-key"]` for a route that answers per API key.
+    pub fn coalesce_by(&mut self, headers: &[&str]) -> &mut Self {
+        self.coalesce();
+        if let Some(route) = self.routes.last_mut().filter(|route| route.coalesce) {
+            route
+                .coalesce_vary
+                .extend(headers.iter().map(|h| h.to_ascii_lowercase()));
+        }
+        self
+    }
+
+    /// Answers requests to the most recently registered route with 503 while the server's
+    /// circuit breaker named `breaker` is open, without running middleware or the handler.
+    pub fn depends_on(&mut self, breaker: &str) -> &mut Self {
+        match self.routes.last_mut() {
+            Some(route) => route.dependencies.push(breaker.to_string()),
+            None => self.logger.log(
+                crate::logger::LogLevel::Warning,
+                "Cannot add a dependency, no route has been registered yet",
+            ),
+        }
+        self
+    }
+
     fn add_route(&mut self, route: Route) -> &mut Self {
         self.logger.log(
             crate::logger::LogLevel::Info,
@@ -86,11 +246,70 @@ impl RouteManager {
         self
     }
 
+    /// First route matching `path` and `method`, without checking guards.
     pub fn find_route(&self, path: &str, method: HttpMethod) -> Option<&Route> {
         self.routes
             .iter()
             .find(|r| r.method == method && r.matches(path))
     }
+
+    /// First route matching the request whose guards all pass.
+    pub fn resolve(&self, request: &HttpRequest) -> Option<&Route> {
+        self.routes.iter().find(|r| {
+            r.method == request.method
+                && r.matches(&request.path)
+                && r.guards.iter().all(|guard| guard.check(request))
+        })
+    }
+
+    /// Like [`resolve`](Self::resolve), also returning the route's parameters. Lookups go
+    /// through `cache` when it's enabled, and results are cached when no guard was involved.
+    pub fn resolve_cached(
+        &self,
+        request: &HttpRequest,
+        cache: &RouteCache,
+    ) -> Option<(&Route, HashMap<String, String>)> {
+        if !cache.is_enabled() {
+            let route = self.resolve(request)?;
+            return Some((route, route.params(&request.path).unwrap_or_default()));
+        }
+
+        let path = request.path.split('?').next().unwrap_or("");
+        if let Some(index) = cache.get(request.method, path) {
+            return Some((&self.routes[index], HashMap::new()));
+        }
+
+        // Only the first route matching the path can be cached: if an earlier one was
+        // skipped for its guards, another request to the same path might pass them. Routes
+        // with parameters aren't cached, so a cached route always has none.
+        let mut first = None;
+        for (index, route) in self.routes.iter().enumerate() {
+            if route.method != request.method {
+                continue;
+            }
+            let Some(params) = route.params(path) else {
+                continue;
+            };
+            let first = *first.get_or_insert(index);
+            if route.guards.iter().all(|guard| guard.check(request)) {
+                if index == first && route.guards.is_empty() && route.path_params.is_empty() {
+                    cache.insert(request.method, path, index);
+                }
+                return Some((route, params));
+            }
+        }
+        None
+    }
+
+    pub fn allowed_methods(&self, path: &str) -> Vec<HttpMethod> {
+        let mut methods = Vec::new();
+        for route in self.routes.iter().filter(|r| r.matches(path)) {
+            if !methods.contains(&route.method) {
+                methods.push(route.method);
+            }
+        }
+        methods
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -100,19 +319,43 @@ pub struct Route {
     pub path_params: Vec<String>,
     pub method: HttpMethod,
     pub handler: AsyncHandler,
+    pub jsonp: Option<String>,
+    pub guards: Vec<Guard>,
+    /// Whether requests must pass the server's [`ReplayProtection`](super::ReplayProtection).
+    pub replay_protected: bool,
+    /// Whether requests must be signed for the server's [`RequestVerifier`](super::RequestVerifier).
+    pub signed: bool,
+    /// Latency objective tracked by the server's [`SloTracker`](crate::server::SloTracker).
+    pub slo: Option<Slo>,
+    /// Whether concurrent identical requests share one response, see [`Coalescer`](super::Coalescer).
+    pub coalesce: bool,
+    /// Headers that must also match for coalesced requests to share a response, lowercased.
+    pub coalesce_vary: Vec<String>,
+    /// Circuit breakers that must not be open for the route to run, see
+    /// [`CircuitBreaker`](crate::circuit_breaker::CircuitBreaker).
+    pub dependencies: Vec<String>,
+    pub(super) state: Option<Arc<AppState>>,
+    segments: Vec<Segment>,
+    /// Tells apart routes sharing a pattern, e.g. with different guards.
+    id: u64,
 }
 
 impl Route {
+    /// `pattern` may contain `:name` placeholders and a trailing `*name` wildcard, each
+    /// optionally typed, e.g. `/users/:id<i32>` or `/files/*path<PathBuf>`.
     pub fn new(pattern: &str, method: HttpMethod, handler: AsyncHandler) -> Self {
-        let path_params = pattern
-            .split('/')
-            .filter(|s| s.starts_with(':'))
-            .map(|s| s[1..].to_string())
+        let segments = Segment::parse_pattern(pattern);
+        let path_params = segments
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Param(name, _) | Segment::Wildcard(name, _) => Some(name.clone()),
+                Segment::Static(_) => None,
+            })
             .collect();
 
         let raw_path = pattern
             .split('/')
-            .take_while(|s| !s.starts_with(':'))
+            .take_while(|s| !s.starts_with(':') && !s.starts_with('*'))
             .collect::<Vec<_>>()
             .join("/");
 
@@ -122,21 +365,34 @@ impl Route {
             path_params,
             method,
             handler,
+            jsonp: None,
+            guards: vec![],
+            replay_protected: false,
+            signed: false,
+            slo: None,
+            coalesce: false,
+            coalesce_vary: vec![],
+            dependencies: vec![],
+            state: None,
+            segments,
+            id: NEXT_ROUTE_ID.fetch_add(1, Ordering::Relaxed),
         }
     }
 
-    fn matches(&self, path: &str) -> bool {
-        let pattern_parts: Vec<&str> = self.pattern.split('/').collect();
-        let path_parts: Vec<&str> = path.split('/').collect();
+    /// Identifies this route in the keys of responses shared or cached per route.
+    pub(crate) fn key(&self) -> String {
+        format!("{} {}#{}", self.method, self.pattern, self.id)
+    }
 
-        if pattern_parts.len() != path_parts.len() {
-            return false;
-        }
+    fn matches(&self, path: &str) -> bool {
+        self.params(path).is_some()
+    }
 
-        pattern_parts
-            .iter()
-            .zip(path_parts.iter())
-            .all(|(p, u)| p.starts_with(':') || p == u)
+    /// Parameters extracted from `path`, or `None` if it doesn't match this route
+    /// (including when a typed parameter fails to parse).
+    pub fn params(&self, path: &str) -> Option<HashMap<String, String>> {
+        let path = path.split('?').next().unwrap_or(path);
+        Segment::match_path(&self.segments, path)
     }
 }
 
@@ -181,7 +437,86 @@ impl RouteGroup {
         self
     }
 
+    /// Adds a guard to the most recently registered route in this group.
+    pub fn guard(&mut self, guard: Guard) -> &mut Self {
+        if let Some(route) = self.routes.last_mut() {
+            route.guards.push(guard);
+        }
+        self
+    }
+
+    /// Enables replay protection on the most recently registered route in this group.
+    pub fn replay_protected(&mut self) -> &mut Self {
+        if let Some(route) = self.routes.last_mut() {
+            route.replay_protected = true;
+        }
+        self
+    }
+
+    /// Requires signing on the most recently registered route in this group.
+    pub fn signed(&mut self) -> &mut Self {
+        if let Some(route) = self.routes.last_mut() {
+            route.signed = true;
+        }
+        self
+    }
+
+    /// Sets a latency objective on the most recently registered route in this group.
+    pub fn slo(&mut self, slo: Slo) -> &mut Self {
+        if let Some(route) = self.routes.last_mut() {
+            route.slo = Some(slo);
+        }
+        self
+    }
+
+    /// Makes the most recently registered route in this group depend on circuit breaker
+    /// `breaker`.
+    pub fn depends_on(&mut self, breaker: &str) -> &mut Self {
+        if let Some(route) = self.routes.last_mut() {
+            route.dependencies.push(breaker.to_string());
+        }
+        self
+    }
+
+    /// Enables coalescing on the most recently registered route in this group, if it's a GET.
+    pub fn coalesce(&mut self) -> &mut Self {
+        if let Some(route) = self
+            .routes
+            .last_mut()
+            .filter(|route| route.method == HttpMethod::Get)
+        {
+            route.coalesce = true;
+        }
+        self
+    }
+
+    /// Enables coalescing on the most recently registered route in this group, if it's a GET,
+    /// keyed on `headers` as well as the defaults.
+    pub fn coalesce_by(&mut self, headers: &[&str]) -> &mut Self {
+        if let Some(route) = self
+            .routes
+            .last_mut()
+            .filter(|route| route.method == HttpMethod::Get)
+        {
+            route.coalesce = true;
+            route
+                .coalesce_vary
+                .extend(headers.iter().map(|h| h.to_ascii_lowercase()));
+        }
+        self
+    }
+
     pub fn group(&mut self, prefix: &str) -> RouteGroup {
         RouteGroup::new(&format!("{}{}", self.prefix, prefix))
     }
 }
+
+/// Joins a mount prefix and a path registered relative to it, so `/` maps to the prefix itself.
+pub(crate) fn join_path(prefix: &str, path: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+    match path.trim_start_matches('/') {
+        "" if prefix.is_empty() => "/".to_string(),
+        "" => prefix.to_string(),
+        path => format!("{}/{}", prefix, path),
+    }
+}
@@ -0,0 +1,62 @@
+use std::{
+    fmt,
+    future::Future,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use super::HttpRequest;
+
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+tokio::task_local! {
+    static REQUEST_SCOPE: RequestScope;
+}
+
+/// Identifies the request being handled on the current task, so code without access to the
+/// `Context` (database queries, for one) can tag its logs with the endpoint responsible.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestScope {
+    pub request_id: String,
+    pub route: String,
+}
+
+impl RequestScope {
+    /// Uses the client's `X-Request-Id` when present, otherwise generates one.
+    pub fn new(request: &HttpRequest, route: &str) -> Self {
+        let request_id = request
+            .headers
+            .get("x-request-id")
+            .filter(|id| !id.is_empty() && id.len() <= 128)
+            .cloned()
+            .unwrap_or_else(|| {
+                format!(
+                    "{:x}-{:x}",
+                    std::process::id(),
+                    NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed)
+                )
+            });
+        Self {
+            request_id,
+            route: route.to_string(),
+        }
+    }
+
+    /// The scope of the request being handled on this task, if any.
+    pub fn current() -> Option<RequestScope> {
+        REQUEST_SCOPE.try_with(|scope| scope.clone()).ok()
+    }
+
+    pub async fn run<F: Future>(self, future: F) -> F::Output {
+        REQUEST_SCOPE.scope(self, future).await
+    }
+
+    pub fn run_sync<R>(self, f: impl FnOnce() -> R) -> R {
+        REQUEST_SCOPE.sync_scope(self, f)
+    }
+}
+
+impl fmt::Display for RequestScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "request_id={} route={}", self.request_id, self.route)
+    }
+}
@@ -1,21 +1,50 @@
+//! # Features
+//!
+//! | Feature        | Default | Enables                                                             |
+//! |----------------|---------|---------------------------------------------------------------------|
+//! | `static-files` | yes     | `Server::static_file`, serving assets embedded from `src/static`    |
+//! | `metrics`      | yes     | `Server::response_sizes`, a histogram of response sizes by route    |
+//! | `nats`         | no      | `messaging::NatsBus`                                                |
+//! | `kafka`        | no      | `messaging::KafkaBus`                                               |
+//! | `otel`         | no      | `telemetry`, OTLP export of traces and metrics                      |
+//!
+//! A JSON API without assets of its own can leave out the defaults it doesn't need with
+//! `default-features = false`. `tests/features.rs` keeps this table in step with
+//! `Cargo.toml`.
+pub mod circuit_breaker;
 pub mod config;
 pub mod connection;
 pub mod datasource;
+pub mod error_codes;
 pub mod errors;
 pub mod http;
 pub mod logger;
+pub mod messaging;
+pub mod metering;
+pub mod retry;
+pub mod secrets;
 pub mod server;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+pub mod testing;
 pub mod macros {
     pub use oxide_macros::handler;
 }
 
-pub use config::Config;
+pub use circuit_breaker::CircuitBreaker;
+pub use config::{Config, Environment};
 pub use connection::Connection;
-pub use datasource::PgDatabase;
+pub use datasource::{
+    Fixtures, IsolationLevel, Notification, NotificationStream, PgDatabase, PgDatabaseBuilder,
+    PgTransaction, PgTransactionRef, QueryEvent, RowStream,
+};
+pub use error_codes::ErrorCode;
 pub use errors::Error;
 pub use http::{HttpHandler, HttpMethod, RequestResponse};
 pub use logger::Logger;
-pub use server::Server;
+pub use retry::{retry, RetryPolicy};
+pub use secrets::SecretString;
+pub use server::{App, OxidePlugin, Server};
 
 pub mod prelude {
     pub use crate::datasource;
@@ -23,6 +52,8 @@ pub mod prelude {
     pub use crate::http::{BufferBuilder, HttpHandler, HttpMethod, OxideResponse};
     pub use crate::macros::handler;
     pub use crate::Config;
+    pub use crate::Environment;
     pub use crate::Logger;
+    pub use crate::SecretString;
     pub use crate::Server;
 }
@@ -0,0 +1,90 @@
+use std::cmp::Reverse;
+use std::str::FromStr;
+
+use super::LogLevel;
+
+/// Per-target level filter parsed from `RUST_LOG`-style directives, e.g.
+/// `oxide_core=debug,oxide_orm=warn,my_app=info` or just `warn`.
+///
+/// A level of `None` means logging is switched off for that target.
+#[derive(Debug, Clone)]
+pub struct LogFilter {
+    default: Option<LogLevel>,
+    directives: Vec<(String, Option<LogLevel>)>,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self {
+            default: Some(LogLevel::Debug),
+            directives: vec![],
+        }
+    }
+}
+
+impl LogFilter {
+    pub fn parse(directives: &str) -> Self {
+        let mut filter = Self::default();
+
+        for directive in directives
+            .split(',')
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+        {
+            match directive.split_once('=') {
+                Some((target, level)) => match parse_level(level) {
+                    Ok(level) => filter.directives.push((target.trim().to_string(), level)),
+                    Err(_) => eprintln!("Ignoring invalid log directive: {}", directive),
+                },
+                None => match parse_level(directive) {
+                    Ok(level) => filter.default = level,
+                    // A bare target enables everything for it, like RUST_LOG does.
+                    Err(_) => filter
+                        .directives
+                        .push((directive.to_string(), Some(LogLevel::Debug))),
+                },
+            }
+        }
+
+        // Longest (most specific) targets are matched first.
+        filter
+            .directives
+            .sort_by_key(|(target, _)| Reverse(target.len()));
+        filter
+    }
+
+    pub fn set_level(&mut self, target: &str, level: Option<LogLevel>) {
+        self.directives.retain(|(t, _)| t != target);
+        self.directives.push((target.to_string(), level));
+        self.directives
+            .sort_by_key(|(target, _)| Reverse(target.len()));
+    }
+
+    pub fn enabled(&self, target: &str, level: LogLevel) -> bool {
+        let threshold = self
+            .directives
+            .iter()
+            .find(|(t, _)| Self::target_matches(t, target))
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default);
+
+        match threshold {
+            Some(threshold) => level.severity() >= threshold.severity(),
+            None => false,
+        }
+    }
+
+    pub(super) fn target_matches(directive: &str, target: &str) -> bool {
+        match target.strip_prefix(directive) {
+            Some(rest) => rest.is_empty() || rest.starts_with("::"),
+            None => false,
+        }
+    }
+}
+
+fn parse_level(level: &str) -> Result<Option<LogLevel>, ()> {
+    if level.trim().eq_ignore_ascii_case("off") {
+        return Ok(None);
+    }
+    LogLevel::from_str(level).map(Some)
+}
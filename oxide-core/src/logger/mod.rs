@@ -0,0 +1,391 @@
+mod filter;
+mod sampling;
+
+use once_cell::sync::Lazy;
+use std::collections::VecDeque;
+use std::env;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::{Mutex, RwLock};
+
+use crate::config::Environment;
+use crate::http::{HttpMethod, RequestResponse, RequestTrace};
+
+pub use filter::LogFilter;
+pub use sampling::LogSampler;
+
+/// Pretty, colored output everywhere except production, where only terse warnings and
+/// errors are written.
+fn dev_mode() -> bool {
+    !Environment::current().is_production()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LogLevel {
+    Info,
+    Debug,
+    Warning,
+    Error,
+    Application,
+}
+
+static LOGGER_INIT: Lazy<()> = Lazy::new(|| {
+    if !dev_mode() {
+        println!("Note: Development logger is disabled in production mode");
+    } else {
+        println!("Development logger enabled, to disable set OXIDE_ENV=production");
+    }
+});
+
+/// Active filter, seeded from `OXIDE_LOG` (falling back to `RUST_LOG`) and replaceable at runtime.
+static LOG_FILTER: Lazy<RwLock<LogFilter>> = Lazy::new(|| {
+    let directives = env::var("OXIDE_LOG")
+        .or_else(|_| env::var("RUST_LOG"))
+        .unwrap_or_default();
+    RwLock::new(LogFilter::parse(&directives))
+});
+
+const RECENT_LINES: usize = 50;
+
+/// The last few log lines, uncolored, shown on development error pages.
+static RECENT: Lazy<Mutex<VecDeque<String>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(RECENT_LINES)));
+
+/// Per-target burst protection, seeded from `OXIDE_LOG_RATE_LIMIT` (e.g. `20,oxide_core=5`).
+static LOG_SAMPLER: Lazy<Mutex<LogSampler>> = Lazy::new(|| {
+    let directives = env::var("OXIDE_LOG_RATE_LIMIT").unwrap_or_default();
+    Mutex::new(LogSampler::parse(&directives))
+});
+
+#[derive(Default, Debug, Clone)]
+pub struct Logger {
+    target: &'static str,
+}
+
+impl Logger {
+    pub fn new() -> Self {
+        Lazy::force(&LOGGER_INIT);
+        Self { target: "" }
+    }
+
+    /// Creates a logger whose messages are tagged with, and filtered by, `target`.
+    /// Typically called with `module_path!()`.
+    pub fn for_target(target: &'static str) -> Self {
+        Lazy::force(&LOGGER_INIT);
+        Self { target }
+    }
+
+    pub fn target(&self) -> &'static str {
+        self.target
+    }
+
+    /// Replaces the active filter with new directives, e.g. on config reload.
+    pub fn set_filter(directives: &str) {
+        if let Ok(mut filter) = LOG_FILTER.write() {
+            *filter = LogFilter::parse(directives);
+        }
+    }
+
+    /// Changes the level of a single target at runtime. `None` switches it off.
+    pub fn set_level(target: &str, level: Option<LogLevel>) {
+        if let Ok(mut filter) = LOG_FILTER.write() {
+            filter.set_level(target, level);
+        }
+    }
+
+    /// Limits `target` to `per_second` identical messages per second. `None` removes the limit.
+    pub fn set_rate_limit(target: &str, per_second: Option<u32>) {
+        if let Ok(mut sampler) = LOG_SAMPLER.lock() {
+            sampler.set_limit(target, per_second);
+        }
+    }
+
+    /// The most recent log lines, oldest first.
+    pub fn recent_lines() -> Vec<String> {
+        RECENT
+            .lock()
+            .map(|recent| recent.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn remember(line: String) {
+        if let Ok(mut recent) = RECENT.lock() {
+            if recent.len() == RECENT_LINES {
+                recent.pop_front();
+            }
+            recent.push_back(line);
+        }
+    }
+
+    pub fn enabled(&self, level: LogLevel) -> bool {
+        LOG_FILTER
+            .read()
+            .map(|filter| filter.enabled(self.target, level))
+            .unwrap_or(true)
+    }
+
+    fn format_status(status: u16) -> Option<String> {
+        if !dev_mode() {
+            return None;
+        }
+
+        let color = match status {
+            s if s >= 500 => ColorCode::BG_RED,
+            s if s >= 400 => ColorCode::BG_YELLOW,
+            s if s >= 300 => ColorCode::BG_CYAN,
+            s if s >= 200 => ColorCode::BG_GREEN,
+            _ => ColorCode::BG_BLACK,
+        };
+
+        Some(format!("{} {} {}", color.0, status, ColorCode::RESET.0))
+    }
+
+    fn format_method(method: HttpMethod) -> Option<String> {
+        if !dev_mode() {
+            return None;
+        }
+
+        let (color, padding) = match method {
+            HttpMethod::Get => (ColorCode::BG_BLUE, "      "),
+            HttpMethod::Post => (ColorCode::BG_CYAN, "     "),
+            HttpMethod::Put => (ColorCode::BG_YELLOW, "      "),
+            HttpMethod::Patch => (ColorCode::BG_MAGENTA, "    "),
+            HttpMethod::Delete => (ColorCode::BG_RED, "   "),
+            HttpMethod::Options => (ColorCode::BG_BLACK, "  "),
+            HttpMethod::Unknown => (ColorCode::BG_BLACK, ""),
+        };
+
+        Some(format!(
+            "{} {}{} => {}",
+            color.0,
+            method,
+            padding,
+            ColorCode::RESET.0
+        ))
+    }
+
+    pub fn log_request(&self, method: HttpMethod, path: &str, status: u16) {
+        if let (Some(method_str), Some(status_str)) =
+            (Self::format_method(method), Self::format_status(status))
+        {
+            println!("{} {} {}", method_str, path, status_str);
+        }
+    }
+
+    pub fn log_http(request: &RequestResponse) {
+        if !dev_mode() {
+            return;
+        }
+
+        let method_str =
+            Self::format_method(request.method).unwrap_or_else(|| request.method.to_string());
+        let status_str =
+            Self::format_status(request.status).unwrap_or_else(|| request.status.to_string());
+
+        println!(
+            "{} {} | {} | {} | {}ms | {}B",
+            method_str,
+            request.path,
+            request.ip,
+            status_str,
+            request.duration.as_millis(),
+            request.bytes_sent
+        );
+
+        if let Some(trace) = &request.trace {
+            Self::log_trace(trace);
+        }
+    }
+
+    fn log_trace(trace: &RequestTrace) {
+        let dim = ColorCode::FG_DIM.0;
+        let reset = ColorCode::RESET.0;
+
+        let mut params = trace
+            .params
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>();
+        params.sort();
+
+        println!("{}    route      {}{}", dim, trace.route, reset);
+        if !params.is_empty() {
+            println!("{}    params     {}{}", dim, params.join(", "), reset);
+        }
+        println!(
+            "{}    middleware {} global, {} route{}",
+            dim, trace.global_middleware, trace.route_middleware, reset
+        );
+        println!(
+            "{}    timeline   parse {:.2}ms -> middleware {:.2}ms -> handler {:.2}ms{}",
+            dim,
+            trace.parse.as_secs_f64() * 1000.0,
+            trace.middleware.as_secs_f64() * 1000.0,
+            trace.handler.as_secs_f64() * 1000.0,
+            reset
+        );
+    }
+
+    pub fn panic(&self, message: &str) -> ! {
+        let (bg_color, fg_color, label) = (ColorCode::BG_RED, ColorCode::FG_RED, "ERROR");
+        println!(
+            "{} {} {} {} {} {}",
+            bg_color.0,
+            label,
+            ColorCode::RESET.0,
+            fg_color.0,
+            message,
+            ColorCode::RESET.0
+        );
+        panic!();
+    }
+
+    pub fn log(&self, level: LogLevel, message: &str) {
+        let (bg_color, fg_color, label) = match level {
+            LogLevel::Info => (
+                ColorCode::BG_GREEN,
+                ColorCode::FG_GREEN,
+                format!("{} ", &level.as_str()),
+            ),
+            LogLevel::Debug => (
+                ColorCode::BG_BLUE,
+                ColorCode::FG_BLUE,
+                format!("{}", &level.as_str()),
+            ),
+            LogLevel::Warning => (
+                ColorCode::BG_YELLOW,
+                ColorCode::FG_YELLOW,
+                format!("{} ", &level.as_str()),
+            ),
+            LogLevel::Error => (
+                ColorCode::BG_RED,
+                ColorCode::FG_RED,
+                format!("{}", &level.as_str()),
+            ),
+            LogLevel::Application => return println!("{}", message),
+        };
+
+        if !self.enabled(level) {
+            return;
+        }
+        if !dev_mode() && level.severity() < LogLevel::Warning.severity() {
+            return;
+        }
+
+        let suppressed = match LOG_SAMPLER.lock() {
+            Ok(mut sampler) => match sampler.sample(self.target, level, message) {
+                Some(suppressed) => suppressed,
+                None => return,
+            },
+            Err(_) => 0,
+        };
+        let message = if suppressed > 0 {
+            format!("{} (suppressed {} similar)", message, suppressed)
+        } else {
+            message.to_string()
+        };
+
+        if self.target.is_empty() {
+            Self::remember(format!("{} {}", level.as_str(), message));
+        } else {
+            Self::remember(format!("{} {}: {}", level.as_str(), self.target, message));
+        }
+
+        if !dev_mode() {
+            if self.target.is_empty() {
+                println!("{} {}", level.as_str(), message);
+            } else {
+                println!("{} {}: {}", level.as_str(), self.target, message);
+            }
+        } else if self.target.is_empty() {
+            println!(
+                "{} {} {} {} {} {}",
+                bg_color.0,
+                label,
+                ColorCode::RESET.0,
+                fg_color.0,
+                message,
+                ColorCode::RESET.0
+            );
+        } else {
+            println!(
+                "{} {} {} {}: {} {} {}",
+                bg_color.0,
+                label,
+                ColorCode::RESET.0,
+                self.target,
+                fg_color.0,
+                message,
+                ColorCode::RESET.0
+            );
+        }
+    }
+}
+
+impl LogLevel {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Info => "INFO",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Warning => "WARN",
+            LogLevel::Error => "ERROR",
+            LogLevel::Application => "APPLICATION",
+        }
+    }
+
+    fn severity(&self) -> u8 {
+        match self {
+            LogLevel::Debug => 0,
+            LogLevel::Info => 1,
+            LogLevel::Warning => 2,
+            LogLevel::Error => 3,
+            LogLevel::Application => 4,
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "trace" | "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warn" | "warning" => Ok(LogLevel::Warning),
+            "error" => Ok(LogLevel::Error),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for HttpMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HttpMethod::Get => write!(f, "GET"),
+            HttpMethod::Post => write!(f, "POST"),
+            HttpMethod::Put => write!(f, "PUT"),
+            HttpMethod::Patch => write!(f, "PATCH"),
+            HttpMethod::Delete => write!(f, "DELETE"),
+            HttpMethod::Options => write!(f, "OPTIONS"),
+            HttpMethod::Unknown => write!(f, "UNKNOWN"),
+        }
+    }
+}
+
+struct ColorCode(&'static str);
+
+impl ColorCode {
+    const BG_BLACK: ColorCode = ColorCode("\x1b[40m");
+    const BG_RED: ColorCode = ColorCode("\x1b[41m");
+    const BG_GREEN: ColorCode = ColorCode("\x1b[42m");
+    const BG_YELLOW: ColorCode = ColorCode("\x1b[43m");
+    const BG_BLUE: ColorCode = ColorCode("\x1b[44m");
+    const BG_MAGENTA: ColorCode = ColorCode("\x1b[45m");
+    const BG_CYAN: ColorCode = ColorCode("\x1b[46m");
+    const FG_RED: ColorCode = ColorCode("\x1b[31m");
+    const FG_GREEN: ColorCode = ColorCode("\x1b[32m");
+    const FG_YELLOW: ColorCode = ColorCode("\x1b[33m");
+    const FG_BLUE: ColorCode = ColorCode("\x1b[34m");
+    const FG_DIM: ColorCode = ColorCode("\x1b[2m");
+    const RESET: ColorCode = ColorCode("\x1b[0m");
+}
@@ -0,0 +1,125 @@
+use std::time::{Duration, Instant};
+
+use hashlink::{linked_hash_map::Entry, LruCache};
+
+use super::{LogFilter, LogLevel};
+
+const WINDOW: Duration = Duration::from_secs(1);
+const MAX_TRACKED_MESSAGES: usize = 1024;
+
+/// Rate limits identical log lines per target so error storms can't dominate CPU and disk.
+///
+/// Limits are parsed from directives in the same shape as the log filter, where the value is
+/// the number of identical messages allowed per second, e.g. `20,oxide_core::connection=5`.
+/// Targets without a limit are never sampled. Only the most recently logged 1024 messages
+/// are tracked; a message pushed out by a storm of distinct ones starts a fresh window.
+#[derive(Debug)]
+pub struct LogSampler {
+    default_limit: Option<u32>,
+    limits: Vec<(String, Option<u32>)>,
+    windows: LruCache<(&'static str, LogLevel, String), Window>,
+}
+
+#[derive(Debug)]
+struct Window {
+    started: Instant,
+    count: u32,
+    suppressed: u32,
+}
+
+impl Default for LogSampler {
+    fn default() -> Self {
+        Self {
+            default_limit: None,
+            limits: vec![],
+            windows: LruCache::new(MAX_TRACKED_MESSAGES),
+        }
+    }
+}
+
+impl LogSampler {
+    pub fn parse(directives: &str) -> Self {
+        let mut sampler = Self::default();
+
+        for directive in directives
+            .split(',')
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+        {
+            match directive.split_once('=') {
+                Some((target, limit)) => match parse_limit(limit) {
+                    Some(limit) => sampler.set_limit(target.trim(), limit),
+                    None => eprintln!("Ignoring invalid log rate limit: {}", directive),
+                },
+                None => match parse_limit(directive) {
+                    Some(limit) => sampler.default_limit = limit,
+                    None => eprintln!("Ignoring invalid log rate limit: {}", directive),
+                },
+            }
+        }
+        sampler
+    }
+
+    /// Sets how many identical messages per second `target` may emit. `None` removes the limit.
+    pub fn set_limit(&mut self, target: &str, limit: Option<u32>) {
+        self.limits.retain(|(t, _)| t != target);
+        self.limits.push((target.to_string(), limit));
+        self.limits.sort_by_key(|(t, _)| std::cmp::Reverse(t.len()));
+    }
+
+    /// Returns `None` if the message should be dropped, otherwise the number of identical
+    /// messages suppressed since it was last let through.
+    pub fn sample(&mut self, target: &'static str, level: LogLevel, message: &str) -> Option<u32> {
+        let limit = match self.limit_for(target) {
+            Some(limit) => limit,
+            None => return Some(0),
+        };
+
+        let now = Instant::now();
+        let window = match self.windows.entry((target, level, message.to_string())) {
+            Entry::Occupied(mut window) => {
+                window.to_back();
+                window.into_mut()
+            }
+            Entry::Vacant(window) => window.insert(Window {
+                started: now,
+                count: 0,
+                suppressed: 0,
+            }),
+        };
+
+        if now.duration_since(window.started) >= WINDOW {
+            let suppressed = window.suppressed;
+            *window = Window {
+                started: now,
+                count: 1,
+                suppressed: 0,
+            };
+            return Some(suppressed);
+        }
+
+        if window.count < limit {
+            window.count += 1;
+            Some(0)
+        } else {
+            window.suppressed += 1;
+            None
+        }
+    }
+
+    fn limit_for(&self, target: &str) -> Option<u32> {
+        self.limits
+            .iter()
+            .find(|(t, _)| LogFilter::target_matches(t, target))
+            .map(|(_, limit)| *limit)
+            .unwrap_or(self.default_limit)
+    }
+}
+
+fn parse_limit(limit: &str) -> Option<Option<u32>> {
+    let limit = limit.trim();
+    if limit.eq_ignore_ascii_case("off") {
+        return Some(None);
+    }
+    limit.parse().ok().map(Some)
+}
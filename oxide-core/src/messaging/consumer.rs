@@ -0,0 +1,297 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use tokio::sync::{mpsc, Semaphore};
+
+use super::{DeadLetters, Message, MessageBus, Priority};
+use crate::{logger::LogLevel, retry::RetryPolicy, server::Shutdown, Error, Logger};
+
+pub type ConsumerFuture = Pin<Box<dyn Future<Output = Result<(), Error>> + Send>>;
+pub type ConsumerFn = fn(Message) -> ConsumerFuture;
+
+/// Messages a consumer takes off its subscription ahead of handling them, so that it can
+/// pick by priority; past this it leaves them with the bus.
+const MAX_WAITING: usize = 1024;
+
+/// The longest a message waits between attempts under the default retry policy.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(3600);
+
+/// A due message waiting for a permit, ordered by priority and then arrival.
+struct Waiting {
+    priority: Priority,
+    sequence: Reverse<u64>,
+    message: Message,
+}
+
+impl Waiting {
+    fn key(&self) -> (Priority, Reverse<u64>) {
+        (self.priority, self.sequence)
+    }
+}
+
+impl PartialEq for Waiting {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Waiting {}
+
+impl PartialOrd for Waiting {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiting {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Handles messages published to one topic.
+///
+/// A message whose handler keeps failing is retried `max_retries` times with exponential
+/// backoff and jitter, or as its [`RetryPolicy`] says, then published to the dead-letter topic (`<topic>.dlq` unless configured) and
+/// recorded in the server's [`DeadLetters`], if it has them.
+/// Handlers run under the publishing request's id, when the message carries one.
+///
+/// When all `concurrency` slots are busy, waiting messages are handled highest
+/// [`Priority`] first, so a consumer's urgent messages don't queue behind its heavy ones;
+/// give heavy and light work separate topics to bound each independently. Delayed
+/// messages are held until due; any still held when the subscription closes are dropped.
+///
+/// On server shutdown a consumer stops taking messages, and messages already being handled
+/// get until the drain timeout to finish. Messages it had received but not started, and
+/// delayed ones, are logged as abandoned.
+#[derive(Debug, Clone)]
+pub struct Consumer {
+    topic: String,
+    handler: ConsumerFn,
+    concurrency: usize,
+    retry: RetryPolicy,
+    dead_letter_topic: Option<String>,
+}
+
+impl Consumer {
+    pub fn new(topic: &str, handler: ConsumerFn) -> Self {
+        Self {
+            topic: topic.to_string(),
+            handler,
+            concurrency: 1,
+            retry: RetryPolicy::new()
+                .with_max_attempts(4)
+                .with_backoff(Duration::from_millis(500), MAX_RETRY_BACKOFF)
+                .retry_all(),
+            dead_letter_topic: Some(format!("{}.dlq", topic)),
+        }
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// Maximum number of messages handled at the same time.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.retry = self.retry.with_max_attempts(max_retries.saturating_add(1));
+        self
+    }
+
+    /// Delay before the first retry, doubled for each one after up to an hour.
+    pub fn with_retry_backoff(mut self, retry_backoff: Duration) -> Self {
+        self.retry = self.retry.with_backoff(retry_backoff, MAX_RETRY_BACKOFF);
+        self
+    }
+
+    /// Replaces the retry settings, e.g. to dead-letter errors that retrying can't fix
+    /// straight away. Messages are retried on every error unless the policy says otherwise.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Where failed messages go; `None` drops them after logging.
+    pub fn with_dead_letter_topic(mut self, topic: Option<&str>) -> Self {
+        self.dead_letter_topic = topic.map(str::to_string);
+        self
+    }
+
+    /// Subscribes and handles messages until the subscription closes.
+    pub(crate) async fn run(
+        self,
+        bus: Arc<dyn MessageBus>,
+        dead_letters: Option<Arc<DeadLetters>>,
+        shutdown: Shutdown,
+    ) -> Result<(), Error> {
+        let mut subscription = bus.subscribe(&self.topic).await?;
+        let permits = Arc::new(Semaphore::new(self.concurrency));
+        let consumer = Arc::new(self);
+        let (due_tx, mut due) = mpsc::unbounded_channel();
+        let mut waiting = BinaryHeap::new();
+        let mut sequence = 0u64;
+        let mut delayed = 0usize;
+        let mut open = true;
+        let mut abandoned = 0usize;
+
+        let mut enqueue = |waiting: &mut BinaryHeap<Waiting>, message: Message| {
+            sequence += 1;
+            waiting.push(Waiting {
+                priority: message.priority(),
+                sequence: Reverse(sequence),
+                message,
+            });
+        };
+
+        while open || !waiting.is_empty() {
+            // Takes in everything already received before handing out a permit, so the
+            // highest priority message gets it.
+            tokio::select! {
+                biased;
+                _ = shutdown.triggered(), if open => {
+                    open = false;
+                    abandoned += waiting.len();
+                    waiting.clear();
+                }
+                Some(message) = due.recv() => {
+                    delayed -= 1;
+                    enqueue(&mut waiting, message);
+                }
+                message = subscription.recv(), if open && waiting.len() < MAX_WAITING => {
+                    let Some(message) = message else {
+                        open = false;
+                        continue;
+                    };
+                    let delay = message
+                        .due_at()
+                        .and_then(|at| at.duration_since(SystemTime::now()).ok());
+                    match delay {
+                        Some(delay) => {
+                            delayed += 1;
+                            let due_tx = due_tx.clone();
+                            tokio::spawn(async move {
+                                tokio::time::sleep(delay).await;
+                                let _ = due_tx.send(message);
+                            });
+                        }
+                        None => enqueue(&mut waiting, message),
+                    }
+                }
+                permit = Arc::clone(&permits).acquire_owned(), if !waiting.is_empty() => {
+                    let (Ok(permit), Some(next)) = (permit, waiting.pop()) else {
+                        break;
+                    };
+                    let consumer = Arc::clone(&consumer);
+                    let bus = Arc::clone(&bus);
+                    let dead_letters = dead_letters.clone();
+                    let job = shutdown.job(match next.message.request_id() {
+                        Some(request_id) => {
+                            format!("message from '{}' ({})", consumer.topic, request_id)
+                        }
+                        None => format!("message from '{}'", consumer.topic),
+                    });
+                    tokio::spawn(async move {
+                        consumer
+                            .handle(next.message, bus.as_ref(), dead_letters.as_deref())
+                            .await;
+                        drop(permit);
+                        drop(job);
+                    });
+                }
+            }
+        }
+
+        if abandoned + delayed > 0 {
+            Logger::for_target(module_path!()).log(
+                LogLevel::Warning,
+                &format!(
+                    "Consumer for '{}' stopped with {} received and {} delayed message(s) \
+                     unhandled",
+                    consumer.topic, abandoned, delayed
+                ),
+            );
+        }
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        mut message: Message,
+        bus: &dyn MessageBus,
+        dead_letters: Option<&DeadLetters>,
+    ) {
+        let logger = Logger::for_target(module_path!());
+        loop {
+            let handled = (self.handler)(message.clone());
+            #[cfg(feature = "otel")]
+            let span = crate::telemetry::ConsumerSpan::start(&message);
+            #[cfg(feature = "otel")]
+            let handled = span.context().run(handled);
+            let result = match message.scope() {
+                Some(scope) => scope.run(handled).await,
+                None => handled.await,
+            };
+            #[cfg(feature = "otel")]
+            span.end(result.as_ref().err());
+            let error = match result {
+                Ok(()) => return,
+                Err(e) => e,
+            };
+
+            if message.attempt >= self.retry.max_attempts() || !self.retry.should_retry(&error) {
+                logger.log(
+                    LogLevel::Error,
+                    &format!(
+                        "[{}] Giving up on message from '{}' after {} attempts: {}",
+                        error.code().code,
+                        self.topic,
+                        message.attempt,
+                        error
+                    ),
+                );
+                if let Some(dead_letters) = dead_letters {
+                    if let Err(e) = dead_letters.record(&message, &error.to_string()).await {
+                        logger.log(
+                            LogLevel::Error,
+                            &format!("Failed to record dead letter from '{}': {}", self.topic, e),
+                        );
+                    }
+                }
+                if let Some(dead_letter) = &self.dead_letter_topic {
+                    let dead = Message::new(dead_letter.as_str(), message.payload)
+                        .with_metadata(message.metadata);
+                    if let Err(e) = bus.publish_message(dead).await {
+                        logger.log(
+                            LogLevel::Error,
+                            &format!("Failed to dead-letter to '{}': {}", dead_letter, e),
+                        );
+                    }
+                }
+                return;
+            }
+
+            logger.log(
+                LogLevel::Warning,
+                &format!(
+                    "[{}] Consumer for '{}' failed (attempt {}), retrying: {}",
+                    error.code().code,
+                    self.topic,
+                    message.attempt,
+                    error
+                ),
+            );
+            tokio::time::sleep(self.retry.delay(message.attempt)).await;
+            message.attempt += 1;
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use sqlx::{error::BoxDynError, postgres::PgArguments, Arguments, FromRow};
+
+use super::{Message, MessageBus};
+use crate::{Error, PgDatabase};
+
+pub const DEAD_LETTER_TABLE: &str = "oxide_dead_letters";
+
+/// A message a consumer gave up on, kept with the error from its last attempt.
+#[derive(Debug, Clone)]
+pub struct DeadLetter {
+    pub id: i64,
+    /// The topic the message was consumed from, and is republished to on retry.
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub metadata: HashMap<String, String>,
+    pub error: String,
+    pub attempts: u32,
+    pub failed_at: SystemTime,
+}
+
+#[derive(FromRow)]
+struct DeadLetterRow {
+    id: i64,
+    topic: String,
+    payload: Vec<u8>,
+    metadata: String,
+    error: String,
+    attempts: i32,
+    failed_at_ms: i64,
+}
+
+impl From<DeadLetterRow> for DeadLetter {
+    fn from(row: DeadLetterRow) -> Self {
+        Self {
+            id: row.id,
+            topic: row.topic,
+            payload: row.payload,
+            metadata: serde_json::from_str(&row.metadata).unwrap_or_default(),
+            error: row.error,
+            attempts: row.attempts.max(0) as u32,
+            failed_at: UNIX_EPOCH + Duration::from_millis(row.failed_at_ms.max(0) as u64),
+        }
+    }
+}
+
+impl DeadLetter {
+    /// The message as originally published, ready to be handled again from the first attempt.
+    pub fn message(&self) -> Message {
+        Message::new(self.topic.as_str(), self.payload.clone()).with_metadata(self.metadata.clone())
+    }
+}
+
+const COLUMNS: &str = "id, topic, payload, metadata::TEXT AS metadata, error, attempts, \
+                       (EXTRACT(EPOCH FROM failed_at) * 1000)::BIGINT AS failed_at_ms";
+
+/// Failed messages recorded in `oxide_dead_letters`, for operators to inspect and then retry
+/// or discard. Consumers record into it when configured with
+/// [`Server::with_dead_letters`](crate::Server::with_dead_letters).
+#[derive(Debug, Clone)]
+pub struct DeadLetters {
+    db: PgDatabase,
+}
+
+impl DeadLetters {
+    pub fn new(db: PgDatabase) -> Self {
+        Self { db }
+    }
+
+    pub async fn create_table(&self) -> Result<(), Error> {
+        self.db
+            .execute(format!(
+                "CREATE TABLE IF NOT EXISTS {} (
+                    id BIGSERIAL PRIMARY KEY,
+                    topic TEXT NOT NULL,
+                    payload BYTEA NOT NULL,
+                    metadata JSONB NOT NULL DEFAULT '{{}}',
+                    error TEXT NOT NULL,
+                    attempts INT NOT NULL,
+                    failed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+                )",
+                DEAD_LETTER_TABLE
+            ))
+            .await?;
+        Ok(())
+    }
+
+    /// Records `message` as failed after its current attempt with `error`.
+    pub async fn record(&self, message: &Message, error: &str) -> Result<i64, Error> {
+        let metadata = serde_json::to_string(&message.metadata)
+            .map_err(|e| Error::Serialization(e.to_string()))?;
+        let args = args(|args| {
+            args.add(&message.topic)?;
+            args.add(&message.payload)?;
+            args.add(metadata)?;
+            args.add(error)?;
+            args.add(message.attempt as i32)
+        })?;
+        let (id,): (i64,) = self
+            .db
+            .query_one_with(
+                format!(
+                    "INSERT INTO {} (topic, payload, metadata, error, attempts) \
+                     VALUES ($1, $2, $3::jsonb, $4, $5) RETURNING id",
+                    DEAD_LETTER_TABLE
+                ),
+                args,
+            )
+            .await?;
+        Ok(id)
+    }
+
+    /// The most recent failures first, optionally only those from `topic`.
+    pub async fn list(&self, topic: Option<&str>, limit: i64) -> Result<Vec<DeadLetter>, Error> {
+        let rows: Vec<DeadLetterRow> = self
+            .db
+            .query_with(
+                format!(
+                    "SELECT {} FROM {} WHERE $1::TEXT IS NULL OR topic = $1 \
+                     ORDER BY id DESC LIMIT $2",
+                    COLUMNS, DEAD_LETTER_TABLE
+                ),
+                args(|args| {
+                    args.add(topic)?;
+                    args.add(limit)
+                })?,
+            )
+            .await?;
+        Ok(rows.into_iter().map(DeadLetter::from).collect())
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<DeadLetter>, Error> {
+        let row: Option<DeadLetterRow> = self
+            .db
+            .query_optional_with(
+                format!(
+                    "SELECT {} FROM {} WHERE id = $1",
+                    COLUMNS, DEAD_LETTER_TABLE
+                ),
+                args(|args| args.add(id))?,
+            )
+            .await?;
+        Ok(row.map(DeadLetter::from))
+    }
+
+    /// Failures per topic, largest first.
+    pub async fn counts(&self) -> Result<Vec<(String, i64)>, Error> {
+        self.db
+            .query(format!(
+                "SELECT topic, COUNT(*) FROM {} GROUP BY topic ORDER BY COUNT(*) DESC, topic",
+                DEAD_LETTER_TABLE
+            ))
+            .await
+    }
+
+    /// Republishes the message to its original topic and forgets it. Returns `false` if there
+    /// is no dead letter `id`; if it fails again, the consumer records it anew.
+    pub async fn retry(&self, id: i64, bus: &dyn MessageBus) -> Result<bool, Error> {
+        let Some(dead_letter) = self.get(id).await? else {
+            return Ok(false);
+        };
+        bus.publish_message(dead_letter.message()).await?;
+        self.discard(id).await
+    }
+
+    /// Deletes dead letter `id` without handling it, returning whether it existed.
+    pub async fn discard(&self, id: i64) -> Result<bool, Error> {
+        let result = self
+            .db
+            .execute_with(
+                format!("DELETE FROM {} WHERE id = $1", DEAD_LETTER_TABLE),
+                args(|args| args.add(id))?,
+            )
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+fn args(
+    add: impl FnOnce(&mut PgArguments) -> Result<(), BoxDynError>,
+) -> Result<PgArguments, Error> {
+    let mut args = PgArguments::default();
+    add(&mut args).map_err(|e| Error::Database(sqlx::Error::Encode(e)))?;
+    Ok(args)
+}
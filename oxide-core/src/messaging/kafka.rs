@@ -0,0 +1,127 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures::StreamExt;
+use rskafka::{
+    chrono::DateTime,
+    client::{
+        consumer::{StartOffset, StreamConsumerBuilder},
+        partition::{Compression, PartitionClient, UnknownTopicHandling},
+        Client, ClientBuilder,
+    },
+    record::Record,
+};
+use tokio::sync::{mpsc, Mutex};
+
+use super::{BusFuture, Message, MessageBus, Subscription};
+use crate::Error;
+
+/// Kafka producer/consumer on partition 0 of each topic, consuming from the latest offset.
+/// There is no consumer group coordination or offset commit, so every subscribed instance
+/// sees every message published while it is running.
+pub struct KafkaBus {
+    client: Client,
+    partitions: Mutex<HashMap<String, Arc<PartitionClient>>>,
+}
+
+impl std::fmt::Debug for KafkaBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KafkaBus").finish_non_exhaustive()
+    }
+}
+
+impl KafkaBus {
+    pub async fn connect(brokers: Vec<String>) -> Result<Self, Error> {
+        let client = ClientBuilder::new(brokers)
+            .build()
+            .await
+            .map_err(|e| Error::Custom(format!("Kafka connection failed: {}", e)))?;
+        Ok(Self {
+            client,
+            partitions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    async fn partition(&self, topic: &str) -> Result<Arc<PartitionClient>, Error> {
+        let mut partitions = self.partitions.lock().await;
+        if let Some(partition) = partitions.get(topic) {
+            return Ok(Arc::clone(partition));
+        }
+        let partition = self
+            .client
+            .partition_client(topic, 0, UnknownTopicHandling::Retry)
+            .await
+            .map(Arc::new)
+            .map_err(|e| Error::Custom(format!("Kafka topic '{}' unavailable: {}", topic, e)))?;
+        partitions.insert(topic.to_string(), Arc::clone(&partition));
+        Ok(partition)
+    }
+}
+
+impl MessageBus for KafkaBus {
+    fn name(&self) -> &'static str {
+        "kafka"
+    }
+
+    fn publish<'a>(&'a self, topic: &'a str, payload: Vec<u8>) -> BusFuture<'a, ()> {
+        self.publish_message(Message::new(topic, payload))
+    }
+
+    fn publish_message(&self, message: Message) -> BusFuture<'_, ()> {
+        Box::pin(async move {
+            let topic = message.topic.as_str();
+            let record = Record {
+                key: None,
+                value: Some(message.payload),
+                headers: message
+                    .metadata
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone().into_bytes()))
+                    .collect(),
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .ok()
+                    .and_then(|now| DateTime::from_timestamp_millis(now.as_millis() as i64))
+                    .unwrap_or_default(),
+            };
+            self.partition(topic)
+                .await?
+                .produce(vec![record], Compression::NoCompression)
+                .await
+                .map(|_| ())
+                .map_err(|e| Error::Custom(format!("Kafka publish to '{}' failed: {}", topic, e)))
+        })
+    }
+
+    fn subscribe<'a>(&'a self, topic: &'a str) -> BusFuture<'a, Subscription> {
+        Box::pin(async move {
+            let partition = self.partition(topic).await?;
+            let mut stream = StreamConsumerBuilder::new(partition, StartOffset::Latest).build();
+            let topic = topic.to_string();
+
+            let (sender, receiver) = mpsc::channel(256);
+            tokio::spawn(async move {
+                while let Some(Ok((record, _high_watermark))) = stream.next().await {
+                    let metadata = record
+                        .record
+                        .headers
+                        .into_iter()
+                        .filter_map(|(key, value)| Some((key, String::from_utf8(value).ok()?)))
+                        .collect();
+                    let payload = record.record.value.unwrap_or_default();
+                    if sender
+                        .send(Message::new(topic.clone(), payload).with_metadata(metadata))
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+            Ok(receiver)
+        })
+    }
+}
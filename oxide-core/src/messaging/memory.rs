@@ -0,0 +1,59 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use tokio::sync::mpsc;
+
+use super::{BusFuture, Message, MessageBus, Subscription};
+
+/// In-process bus delivering each message to every current subscriber of its topic. Useful
+/// for tests and single-instance deployments; messages are lost on restart.
+#[derive(Debug, Default)]
+pub struct InMemoryBus {
+    subscribers: Mutex<HashMap<String, Vec<mpsc::Sender<Message>>>>,
+}
+
+impl InMemoryBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl MessageBus for InMemoryBus {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn publish<'a>(&'a self, topic: &'a str, payload: Vec<u8>) -> BusFuture<'a, ()> {
+        self.publish_message(Message::new(topic, payload))
+    }
+
+    fn publish_message(&self, message: Message) -> BusFuture<'_, ()> {
+        let senders = self
+            .subscribers
+            .lock()
+            .map(|mut subscribers| {
+                let senders = subscribers.entry(message.topic.clone()).or_default();
+                senders.retain(|sender| !sender.is_closed());
+                senders.clone()
+            })
+            .unwrap_or_default();
+
+        Box::pin(async move {
+            for sender in senders {
+                // A subscriber that went away in the meantime just misses the message.
+                let _ = sender.send(message.clone()).await;
+            }
+            Ok(())
+        })
+    }
+
+    fn subscribe<'a>(&'a self, topic: &'a str) -> BusFuture<'a, Subscription> {
+        let (sender, receiver) = mpsc::channel(256);
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers
+                .entry(topic.to_string())
+                .or_default()
+                .push(sender);
+        }
+        Box::pin(async move { Ok(receiver) })
+    }
+}
@@ -0,0 +1,263 @@
+//! Publish/subscribe messaging for event-driven services.
+//!
+//! Handlers publish with `ctx.publish(topic, &payload)`; consumers registered on the server
+//! with [`Server::consumer`](crate::Server::consumer) receive messages with bounded
+//! concurrency, retries and dead-lettering. [`InMemoryBus`] is always available; NATS and
+//! Kafka clients are behind the `nats` and `kafka` features.
+//!
+//! Messages published from a handler carry the request's id, tenant and principal as
+//! metadata (see [`Propagation`]), and consumers handle them under the same request id, so
+//! their logs and queries can be traced back to the request that caused them.
+//!
+//! Messages can also be delayed with `ctx.publish_in(delay, topic, &payload)` or
+//! `ctx.publish_at(time, ..)`, and given a [`Priority`] so that a consumer at its concurrency
+//! limit handles urgent messages before a backlog of heavy ones. Delays are honoured by the
+//! consuming process, which holds delayed messages in memory until they are due.
+mod consumer;
+mod dead_letter;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod memory;
+#[cfg(feature = "nats")]
+mod nats;
+
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    pin::Pin,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::mpsc;
+
+use crate::{http::RequestScope, Error};
+
+pub use consumer::{Consumer, ConsumerFn, ConsumerFuture};
+pub use dead_letter::{DeadLetter, DeadLetters, DEAD_LETTER_TABLE};
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaBus;
+pub use memory::InMemoryBus;
+#[cfg(feature = "nats")]
+pub use nats::NatsBus;
+
+pub type BusFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, Error>> + Send + 'a>>;
+
+/// Messages delivered for one subscribed topic.
+pub type Subscription = mpsc::Receiver<Message>;
+
+/// Metadata keys set from the publishing request's context.
+pub const REQUEST_ID: &str = "request_id";
+pub const TENANT: &str = "tenant";
+pub const PRINCIPAL: &str = "principal";
+
+/// Metadata keys set by [`Message::with_priority`] and [`Message::deliver_at`].
+pub const PRIORITY: &str = "priority";
+pub const DELIVER_AT: &str = "deliver_at";
+
+/// The order a consumer handles waiting messages in: higher first, then oldest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl Priority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "low" => Some(Priority::Low),
+            "normal" => Some(Priority::Normal),
+            "high" => Some(Priority::High),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    /// Delivery attempt, starting at 1; higher on retries.
+    pub attempt: u32,
+    /// Context from the publisher, sent as broker headers, e.g. [`REQUEST_ID`].
+    pub metadata: HashMap<String, String>,
+}
+
+impl Message {
+    pub fn new(topic: impl Into<String>, payload: Vec<u8>) -> Self {
+        Self {
+            topic: topic.into(),
+            payload,
+            attempt: 1,
+            metadata: HashMap::new(),
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: HashMap<String, String>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// A message with `payload` serialized as JSON.
+    pub fn from_json<T: Serialize>(topic: impl Into<String>, payload: &T) -> Result<Self, Error> {
+        let payload =
+            serde_json::to_vec(payload).map_err(|e| Error::Serialization(e.to_string()))?;
+        Ok(Self::new(topic, payload))
+    }
+
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.metadata
+            .insert(PRIORITY.to_string(), priority.as_str().to_string());
+        self
+    }
+
+    /// Holds the message back from consumers until `at`.
+    pub fn deliver_at(mut self, at: SystemTime) -> Self {
+        let millis = at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        self.metadata
+            .insert(DELIVER_AT.to_string(), millis.to_string());
+        self
+    }
+
+    /// Holds the message back from consumers until `delay` from now.
+    pub fn deliver_in(self, delay: Duration) -> Self {
+        self.deliver_at(SystemTime::now() + delay)
+    }
+
+    /// The message's priority; [`Priority::Normal`] when unset or unrecognised.
+    pub fn priority(&self) -> Priority {
+        self.metadata
+            .get(PRIORITY)
+            .and_then(|value| Priority::parse(value))
+            .unwrap_or_default()
+    }
+
+    /// When the message becomes due, if it was delayed.
+    pub fn due_at(&self) -> Option<SystemTime> {
+        let millis = self.metadata.get(DELIVER_AT)?.parse::<u64>().ok()?;
+        Some(UNIX_EPOCH + Duration::from_millis(millis))
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        serde_json::from_slice(&self.payload).map_err(|e| Error::Deserialization(e.to_string()))
+    }
+
+    /// Id of the request that published the message.
+    pub fn request_id(&self) -> Option<&str> {
+        self.metadata.get(REQUEST_ID).map(String::as_str)
+    }
+
+    pub fn tenant(&self) -> Option<&str> {
+        self.metadata.get(TENANT).map(String::as_str)
+    }
+
+    /// The authenticated user or client the publishing request acted for.
+    pub fn principal(&self) -> Option<&str> {
+        self.metadata.get(PRINCIPAL).map(String::as_str)
+    }
+
+    /// The scope a consumer handles the message in: the publishing request's id, with the
+    /// topic as the route.
+    pub(crate) fn scope(&self) -> Option<RequestScope> {
+        Some(RequestScope {
+            request_id: self.request_id()?.to_string(),
+            route: format!("consumer {}", self.topic),
+        })
+    }
+}
+
+/// Which parts of a handler's context `ctx.publish` copies into message metadata. Everything
+/// is propagated by default; set per server with
+/// [`Server::with_propagation`](crate::Server::with_propagation) or per message with
+/// `ctx.publish_with`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Propagation {
+    request_id: bool,
+    tenant: bool,
+    principal: bool,
+}
+
+impl Default for Propagation {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl Propagation {
+    pub fn all() -> Self {
+        Self {
+            request_id: true,
+            tenant: true,
+            principal: true,
+        }
+    }
+
+    pub fn none() -> Self {
+        Self {
+            request_id: false,
+            tenant: false,
+            principal: false,
+        }
+    }
+
+    pub fn with_request_id(mut self, enabled: bool) -> Self {
+        self.request_id = enabled;
+        self
+    }
+
+    pub fn with_tenant(mut self, enabled: bool) -> Self {
+        self.tenant = enabled;
+        self
+    }
+
+    pub fn with_principal(mut self, enabled: bool) -> Self {
+        self.principal = enabled;
+        self
+    }
+
+    /// The metadata to attach, from whichever values are both enabled and present.
+    pub fn metadata(
+        &self,
+        request_id: Option<&str>,
+        tenant: Option<&str>,
+        principal: Option<&str>,
+    ) -> HashMap<String, String> {
+        [
+            (REQUEST_ID, self.request_id, request_id),
+            (TENANT, self.tenant, tenant),
+            (PRINCIPAL, self.principal, principal),
+        ]
+        .into_iter()
+        .filter_map(|(key, enabled, value)| {
+            Some((key.to_string(), value.filter(|_| enabled)?.to_string()))
+        })
+        .collect()
+    }
+}
+
+/// A message broker client. Implement this to plug in brokers other than the built-in ones.
+pub trait MessageBus: fmt::Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+    fn publish<'a>(&'a self, topic: &'a str, payload: Vec<u8>) -> BusFuture<'a, ()>;
+    fn subscribe<'a>(&'a self, topic: &'a str) -> BusFuture<'a, Subscription>;
+
+    /// Publishes `message` with its metadata. Buses without headers fall back to `publish`,
+    /// dropping the metadata.
+    fn publish_message(&self, message: Message) -> BusFuture<'_, ()> {
+        Box::pin(async move { self.publish(&message.topic, message.payload).await })
+    }
+}
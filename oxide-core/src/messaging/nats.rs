@@ -0,0 +1,96 @@
+use futures::StreamExt;
+use tokio::sync::mpsc;
+
+use super::{BusFuture, Message, MessageBus, Subscription};
+use crate::Error;
+
+/// NATS core pub/sub. With a queue group, each message goes to one of the server instances
+/// subscribed under that group instead of all of them.
+#[derive(Debug, Clone)]
+pub struct NatsBus {
+    client: async_nats::Client,
+    queue_group: Option<String>,
+}
+
+impl NatsBus {
+    pub async fn connect(url: &str) -> Result<Self, Error> {
+        let client = async_nats::connect(url)
+            .await
+            .map_err(|e| Error::Custom(format!("NATS connection failed: {}", e)))?;
+        Ok(Self {
+            client,
+            queue_group: None,
+        })
+    }
+
+    pub fn with_queue_group(mut self, queue_group: &str) -> Self {
+        self.queue_group = Some(queue_group.to_string());
+        self
+    }
+}
+
+impl MessageBus for NatsBus {
+    fn name(&self) -> &'static str {
+        "nats"
+    }
+
+    fn publish<'a>(&'a self, topic: &'a str, payload: Vec<u8>) -> BusFuture<'a, ()> {
+        Box::pin(async move {
+            self.client
+                .publish(topic.to_string(), payload.into())
+                .await
+                .map_err(|e| Error::Custom(format!("NATS publish to '{}' failed: {}", topic, e)))
+        })
+    }
+
+    fn publish_message(&self, message: Message) -> BusFuture<'_, ()> {
+        Box::pin(async move {
+            let mut headers = async_nats::HeaderMap::new();
+            for (key, value) in &message.metadata {
+                headers.insert(key.as_str(), value.as_str());
+            }
+            self.client
+                .publish_with_headers(message.topic.clone(), headers, message.payload.into())
+                .await
+                .map_err(|e| {
+                    Error::Custom(format!("NATS publish to '{}' failed: {}", message.topic, e))
+                })
+        })
+    }
+
+    fn subscribe<'a>(&'a self, topic: &'a str) -> BusFuture<'a, Subscription> {
+        Box::pin(async move {
+            let subscribed = match &self.queue_group {
+                Some(group) => {
+                    self.client
+                        .queue_subscribe(topic.to_string(), group.clone())
+                        .await
+                }
+                None => self.client.subscribe(topic.to_string()).await,
+            };
+            let mut subscriber = subscribed.map_err(|e| {
+                Error::Custom(format!("NATS subscribe to '{}' failed: {}", topic, e))
+            })?;
+
+            let (sender, receiver) = mpsc::channel(256);
+            tokio::spawn(async move {
+                while let Some(message) = subscriber.next().await {
+                    let metadata = message
+                        .headers
+                        .iter()
+                        .flat_map(|headers| headers.iter())
+                        .filter_map(|(key, values)| {
+                            Some((key.to_string(), values.first()?.to_string()))
+                        })
+                        .collect();
+                    let message = Message::new(message.subject.to_string(), message.payload.into())
+                        .with_metadata(metadata);
+                    if sender.send(message).await.is_err() {
+                        break;
+                    }
+                }
+            });
+            Ok(receiver)
+        })
+    }
+}
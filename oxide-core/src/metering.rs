@@ -0,0 +1,455 @@
+//! Usage metering for billed APIs.
+//!
+//! With [`Server::with_metering`](crate::Server::with_metering), every request that reaches
+//! its handler with a tenant or principal set (by middleware calling `ctx.with_tenant` or
+//! `ctx.with_principal`, the latter typically to an API key's id) is counted against them:
+//! requests, 5xx errors, bytes received and sent, and time spent in database queries made
+//! while handling it. Counts are kept in memory and added to per-period rollups in
+//! `oxide_usage` every flush interval and on shutdown, so several processes can share the
+//! table. Requests with neither a tenant nor a principal aren't metered.
+//!
+//! ```rust,ignore
+//! let metering = Metering::new(db.clone()).with_period(Duration::from_secs(3600));
+//! server.with_metering(metering);
+//!
+//! // Later, e.g. from a billing job or an admin handler:
+//! let usage = server.metering().unwrap().total(&UsageFilter::new().tenant("acme")).await?;
+//! ```
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use sqlx::{error::BoxDynError, postgres::PgArguments, Arguments, FromRow};
+
+use crate::{logger::LogLevel, server::Shutdown, Error, Logger, PgDatabase};
+
+pub const USAGE_TABLE: &str = "oxide_usage";
+
+/// Rows per upsert, well under Postgres' limit of 65535 bound parameters.
+const FLUSH_BATCH: usize = 1000;
+
+tokio::task_local! {
+    static QUERY_TIME: Arc<QueryTime>;
+}
+
+/// Database time spent by the request being handled on this task.
+#[derive(Debug, Default)]
+struct QueryTime {
+    micros: AtomicU64,
+    queries: AtomicU64,
+}
+
+/// Counts for one tenant and principal over some period.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Usage {
+    pub requests: u64,
+    /// Requests answered with a 5xx status.
+    pub errors: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    /// Time spent in queries made while handling the requests, in microseconds.
+    pub db_time_us: u64,
+    pub db_queries: u64,
+}
+
+impl Usage {
+    pub fn add(&mut self, other: &Usage) {
+        self.requests += other.requests;
+        self.errors += other.errors;
+        self.bytes_in += other.bytes_in;
+        self.bytes_out += other.bytes_out;
+        self.db_time_us += other.db_time_us;
+        self.db_queries += other.db_queries;
+    }
+}
+
+/// One persisted rollup: a tenant and principal's usage over the period starting at
+/// `period_start`.
+#[derive(Debug, Clone)]
+pub struct UsageRollup {
+    pub tenant: Option<String>,
+    pub principal: Option<String>,
+    pub period_start: SystemTime,
+    pub usage: Usage,
+}
+
+/// Which rollups [`Metering`] queries cover. Everything by default.
+#[derive(Debug, Clone, Default)]
+pub struct UsageFilter {
+    tenant: Option<String>,
+    principal: Option<String>,
+    since: Option<SystemTime>,
+    until: Option<SystemTime>,
+}
+
+impl UsageFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.tenant = Some(tenant.into());
+        self
+    }
+
+    pub fn principal(mut self, principal: impl Into<String>) -> Self {
+        self.principal = Some(principal.into());
+        self
+    }
+
+    /// Only periods starting at or after `since`.
+    pub fn since(mut self, since: SystemTime) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    /// Only periods starting before `until`.
+    pub fn until(mut self, until: SystemTime) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    fn args(&self) -> Result<PgArguments, Error> {
+        args(|args| {
+            args.add(self.tenant.as_deref())?;
+            args.add(self.principal.as_deref())?;
+            args.add(self.since.map(unix_seconds))?;
+            args.add(self.until.map(unix_seconds))
+        })
+    }
+}
+
+const FILTER: &str = "($1::TEXT IS NULL OR tenant = $1) \
+                      AND ($2::TEXT IS NULL OR principal = $2) \
+                      AND ($3::BIGINT IS NULL OR period_start >= to_timestamp($3)) \
+                      AND ($4::BIGINT IS NULL OR period_start < to_timestamp($4))";
+
+const SUMS: &str = "SUM(requests)::BIGINT AS requests, SUM(errors)::BIGINT AS errors, \
+                    SUM(bytes_in)::BIGINT AS bytes_in, SUM(bytes_out)::BIGINT AS bytes_out, \
+                    SUM(db_time_us)::BIGINT AS db_time_us, \
+                    SUM(db_queries)::BIGINT AS db_queries";
+
+#[derive(FromRow)]
+struct RollupRow {
+    tenant: String,
+    principal: String,
+    period_start_s: i64,
+    #[sqlx(flatten)]
+    usage: UsageRow,
+}
+
+#[derive(FromRow)]
+struct GroupRow {
+    key: String,
+    #[sqlx(flatten)]
+    usage: UsageRow,
+}
+
+#[derive(FromRow)]
+struct UsageRow {
+    requests: Option<i64>,
+    errors: Option<i64>,
+    bytes_in: Option<i64>,
+    bytes_out: Option<i64>,
+    db_time_us: Option<i64>,
+    db_queries: Option<i64>,
+}
+
+impl From<UsageRow> for Usage {
+    fn from(row: UsageRow) -> Self {
+        let count = |n: Option<i64>| n.unwrap_or(0).max(0) as u64;
+        Self {
+            requests: count(row.requests),
+            errors: count(row.errors),
+            bytes_in: count(row.bytes_in),
+            bytes_out: count(row.bytes_out),
+            db_time_us: count(row.db_time_us),
+            db_queries: count(row.db_queries),
+        }
+    }
+}
+
+/// Counts usage per tenant and principal and persists it to `oxide_usage`.
+#[derive(Debug)]
+pub struct Metering {
+    db: PgDatabase,
+    period: Duration,
+    flush_interval: Duration,
+    pending: Mutex<HashMap<(String, String, i64), Usage>>,
+    logger: Logger,
+}
+
+impl Metering {
+    /// Hourly rollups, flushed every minute.
+    pub fn new(db: PgDatabase) -> Self {
+        Self {
+            db,
+            period: Duration::from_secs(3600),
+            flush_interval: Duration::from_secs(60),
+            pending: Mutex::new(HashMap::new()),
+            logger: Logger::for_target(module_path!()),
+        }
+    }
+
+    /// Length of each rollup period, e.g. a day for daily invoices. Whole seconds, at least
+    /// one.
+    pub fn with_period(mut self, period: Duration) -> Self {
+        self.period = period.max(Duration::from_secs(1));
+        self
+    }
+
+    /// How often counts are written out. Queries lag the live numbers by up to this much.
+    pub fn with_flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    pub async fn create_table(&self) -> Result<(), Error> {
+        self.db
+            .execute(format!(
+                "CREATE TABLE IF NOT EXISTS {} (
+                    tenant TEXT NOT NULL DEFAULT '',
+                    principal TEXT NOT NULL DEFAULT '',
+                    period_start TIMESTAMPTZ NOT NULL,
+                    requests BIGINT NOT NULL DEFAULT 0,
+                    errors BIGINT NOT NULL DEFAULT 0,
+                    bytes_in BIGINT NOT NULL DEFAULT 0,
+                    bytes_out BIGINT NOT NULL DEFAULT 0,
+                    db_time_us BIGINT NOT NULL DEFAULT 0,
+                    db_queries BIGINT NOT NULL DEFAULT 0,
+                    PRIMARY KEY (tenant, principal, period_start)
+                )",
+                USAGE_TABLE
+            ))
+            .await?;
+        Ok(())
+    }
+
+    /// Adds `usage` to the current period's counts for `tenant` and `principal`. Does nothing
+    /// if both are `None`.
+    pub fn record(&self, tenant: Option<&str>, principal: Option<&str>, usage: Usage) {
+        if tenant.is_none() && principal.is_none() {
+            return;
+        }
+        let period = self.period.as_secs() as i64;
+        let period_start = unix_seconds(SystemTime::now()) / period * period;
+        let key = (
+            tenant.unwrap_or_default().to_string(),
+            principal.unwrap_or_default().to_string(),
+            period_start,
+        );
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.entry(key).or_default().add(&usage);
+        }
+    }
+
+    /// Writes out the counts recorded since the last flush, returning how many rollups were
+    /// updated. Counts that fail to write are kept for the next flush.
+    pub async fn flush(&self) -> Result<usize, Error> {
+        let pending: Vec<_> = match self.pending.lock() {
+            Ok(mut pending) => pending.drain().collect(),
+            Err(_) => return Ok(0),
+        };
+        let mut flushed = 0;
+        for (i, batch) in pending.chunks(FLUSH_BATCH).enumerate() {
+            if let Err(e) = self.upsert(batch).await {
+                if let Ok(mut unflushed) = self.pending.lock() {
+                    for (key, usage) in &pending[i * FLUSH_BATCH..] {
+                        unflushed.entry(key.clone()).or_default().add(usage);
+                    }
+                }
+                return Err(e);
+            }
+            flushed += batch.len();
+        }
+        Ok(flushed)
+    }
+
+    async fn upsert(&self, batch: &[((String, String, i64), Usage)]) -> Result<(), Error> {
+        let values = (0..batch.len())
+            .map(|i| {
+                let n = i * 9;
+                format!(
+                    "(${}, ${}, to_timestamp(${}), ${}, ${}, ${}, ${}, ${}, ${})",
+                    n + 1,
+                    n + 2,
+                    n + 3,
+                    n + 4,
+                    n + 5,
+                    n + 6,
+                    n + 7,
+                    n + 8,
+                    n + 9
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let args = args(|args| {
+            for ((tenant, principal, period_start), usage) in batch {
+                args.add(tenant)?;
+                args.add(principal)?;
+                args.add(*period_start)?;
+                args.add(usage.requests as i64)?;
+                args.add(usage.errors as i64)?;
+                args.add(usage.bytes_in as i64)?;
+                args.add(usage.bytes_out as i64)?;
+                args.add(usage.db_time_us as i64)?;
+                args.add(usage.db_queries as i64)?;
+            }
+            Ok(())
+        })?;
+        self.db
+            .execute_with(
+                format!(
+                    "INSERT INTO {table} AS rollup (tenant, principal, period_start, requests, \
+                     errors, bytes_in, bytes_out, db_time_us, db_queries) VALUES {values} \
+                     ON CONFLICT (tenant, principal, period_start) DO UPDATE SET \
+                     requests = rollup.requests + EXCLUDED.requests, \
+                     errors = rollup.errors + EXCLUDED.errors, \
+                     bytes_in = rollup.bytes_in + EXCLUDED.bytes_in, \
+                     bytes_out = rollup.bytes_out + EXCLUDED.bytes_out, \
+                     db_time_us = rollup.db_time_us + EXCLUDED.db_time_us, \
+                     db_queries = rollup.db_queries + EXCLUDED.db_queries",
+                    table = USAGE_TABLE,
+                    values = values
+                ),
+                args,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Flushes every interval until shutdown. The server flushes once more itself after
+    /// draining connections, so nothing recorded by in-flight requests is lost.
+    pub(crate) async fn run(&self, shutdown: Shutdown) {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(self.flush_interval) => {}
+                _ = shutdown.triggered() => return,
+            }
+            if let Err(e) = self.flush().await {
+                self.logger
+                    .log(LogLevel::Warning, &format!("Failed to flush usage: {}", e));
+            }
+        }
+    }
+
+    /// Persisted rollups matching `filter`, oldest period first.
+    pub async fn rollups(&self, filter: &UsageFilter) -> Result<Vec<UsageRollup>, Error> {
+        let rows: Vec<RollupRow> = self
+            .db
+            .query_with(
+                format!(
+                    "SELECT tenant, principal, \
+                     EXTRACT(EPOCH FROM period_start)::BIGINT AS period_start_s, \
+                     requests, errors, bytes_in, bytes_out, db_time_us, db_queries \
+                     FROM {} WHERE {} ORDER BY period_start, tenant, principal",
+                    USAGE_TABLE, FILTER
+                ),
+                filter.args()?,
+            )
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| UsageRollup {
+                tenant: Some(row.tenant).filter(|t| !t.is_empty()),
+                principal: Some(row.principal).filter(|p| !p.is_empty()),
+                period_start: UNIX_EPOCH + Duration::from_secs(row.period_start_s.max(0) as u64),
+                usage: row.usage.into(),
+            })
+            .collect())
+    }
+
+    /// Total usage matching `filter`.
+    pub async fn total(&self, filter: &UsageFilter) -> Result<Usage, Error> {
+        let row: UsageRow = self
+            .db
+            .query_one_with(
+                format!("SELECT {} FROM {} WHERE {}", SUMS, USAGE_TABLE, FILTER),
+                filter.args()?,
+            )
+            .await?;
+        Ok(row.into())
+    }
+
+    /// Usage matching `filter` per tenant, heaviest first. Requests metered by principal
+    /// alone are left out.
+    pub async fn by_tenant(&self, filter: &UsageFilter) -> Result<Vec<(String, Usage)>, Error> {
+        self.grouped("tenant", filter).await
+    }
+
+    /// Usage matching `filter` per principal, heaviest first. Requests metered by tenant
+    /// alone are left out.
+    pub async fn by_principal(&self, filter: &UsageFilter) -> Result<Vec<(String, Usage)>, Error> {
+        self.grouped("principal", filter).await
+    }
+
+    async fn grouped(
+        &self,
+        column: &str,
+        filter: &UsageFilter,
+    ) -> Result<Vec<(String, Usage)>, Error> {
+        let rows: Vec<GroupRow> = self
+            .db
+            .query_with(
+                format!(
+                    "SELECT {column} AS key, {sums} FROM {table} WHERE {filter} AND {column} <> '' \
+                     GROUP BY {column} ORDER BY requests DESC, {column}",
+                    column = column,
+                    sums = SUMS,
+                    table = USAGE_TABLE,
+                    filter = FILTER
+                ),
+                filter.args()?,
+            )
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.key, row.usage.into()))
+            .collect())
+    }
+}
+
+/// Runs `future`, also returning the database time and query count it spent when `enabled`.
+pub(crate) async fn measure<F: Future>(enabled: bool, future: F) -> (F::Output, Duration, u64) {
+    if !enabled {
+        return (future.await, Duration::ZERO, 0);
+    }
+    let time = Arc::new(QueryTime::default());
+    let output = QUERY_TIME.scope(Arc::clone(&time), future).await;
+    (
+        output,
+        Duration::from_micros(time.micros.load(Ordering::Relaxed)),
+        time.queries.load(Ordering::Relaxed),
+    )
+}
+
+/// Charges a query to the request being measured on this task, if any.
+pub(crate) fn charge_query(elapsed: Duration) {
+    let _ = QUERY_TIME.try_with(|time| {
+        time.micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        time.queries.fetch_add(1, Ordering::Relaxed);
+    });
+}
+
+fn unix_seconds(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+fn args(
+    add: impl FnOnce(&mut PgArguments) -> Result<(), BoxDynError>,
+) -> Result<PgArguments, Error> {
+    let mut args = PgArguments::default();
+    add(&mut args).map_err(|e| Error::Database(sqlx::Error::Encode(e)))?;
+    Ok(args)
+}
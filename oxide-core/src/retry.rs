@@ -0,0 +1,172 @@
+//! Retrying operations that fail for reasons likely to pass, such as a dropped connection or
+//! a serialization failure between concurrent transactions.
+//!
+//! ```rust,ignore
+//! let policy = RetryPolicy::new().with_max_attempts(5);
+//! let user = retry(&policy, || async {
+//!     let mut tx = db.begin().await?;
+//!     let user = User::update(id).set(User::columns().credits, credits).fetch_one(&mut tx).await?;
+//!     tx.commit().await?;
+//!     Ok(user)
+//! })
+//! .await?;
+//! ```
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::{logger::LogLevel, Error, Logger};
+
+pub type RetryPredicate = Arc<dyn Fn(&Error) -> bool + Send + Sync>;
+
+/// How often and how far apart to retry, and which errors are worth retrying.
+///
+/// The delay before the first retry is the initial backoff, multiplied for each retry after
+/// and capped at the maximum backoff. Jitter shortens each delay by a random share of up to
+/// the given fraction, so clients that failed together don't retry in lockstep. The defaults
+/// make 3 attempts, waiting 100ms then 200ms less up to half, and retry only errors that
+/// [`Error::is_transient`] accepts.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f64,
+    jitter: f64,
+    retry_if: RetryPredicate,
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("multiplier", &self.multiplier)
+            .field("jitter", &self.jitter)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.5,
+            retry_if: Arc::new(Error::is_transient),
+        }
+    }
+
+    /// Attempts in total, including the first.
+    pub fn with_max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Delay before the first retry, and the most any retry waits.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Factor the delay grows by with each retry; 1 keeps it constant.
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// Largest share, between 0 and 1, randomly taken off each delay.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Retries only errors `predicate` accepts, instead of transient ones.
+    pub fn retry_if(mut self, predicate: impl Fn(&Error) -> bool + Send + Sync + 'static) -> Self {
+        self.retry_if = Arc::new(predicate);
+        self
+    }
+
+    /// Retries every error.
+    pub fn retry_all(self) -> Self {
+        self.retry_if(|_| true)
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Whether an attempt that failed with `error` should be followed by another, provided
+    /// attempts remain.
+    pub fn should_retry(&self, error: &Error) -> bool {
+        (self.retry_if)(error)
+    }
+
+    /// How long to wait after failed attempt number `attempt`, counting from 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let backoff = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+        let backoff = backoff.min(self.max_backoff.as_secs_f64());
+        Duration::from_secs_f64(backoff * (1.0 - self.jitter * roll()))
+    }
+}
+
+/// Runs `operation` until it succeeds, fails with an error `policy` doesn't retry, or runs
+/// out of attempts, waiting between attempts as `policy` says. Returns the last error.
+pub async fn retry<T, F, Fut>(policy: &RetryPolicy, mut operation: F) -> Result<T, Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    let mut attempt = 1;
+    loop {
+        let error = match operation().await {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+        if attempt >= policy.max_attempts || !policy.should_retry(&error) {
+            return Err(error);
+        }
+        let delay = policy.delay(attempt);
+        Logger::for_target(module_path!()).log(
+            LogLevel::Warning,
+            &format!(
+                "[{}] Attempt {} of {} failed, retrying in {}ms: {}",
+                error.code().code,
+                attempt,
+                policy.max_attempts,
+                delay.as_millis(),
+                error
+            ),
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+static ROLLS: AtomicU64 = AtomicU64::new(0);
+
+/// A uniform value in `[0, 1)` from the process's randomly keyed hasher.
+pub(crate) fn roll() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(ROLLS.fetch_add(1, Ordering::Relaxed));
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,208 @@
+use std::{
+    env, fmt, fs,
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::atomic::{compiler_fence, Ordering},
+};
+
+use serde::{Deserialize, Deserializer};
+
+use crate::Error;
+
+/// A string holding sensitive data (database passwords, cookie keys, JWT secrets).
+///
+/// The contents are overwritten with zeros when dropped and never appear in `Debug` or
+/// `Display` output, so secrets can't leak into logs by accident. Use [`SecretString::expose`]
+/// at the point where the raw value is actually needed.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Reads a secret from an environment variable.
+    pub fn from_env(key: &str) -> Option<Self> {
+        env::var(key).ok().map(Self)
+    }
+
+    /// Reads a secret from a file, as mounted by Docker (`/run/secrets/*`) or Kubernetes.
+    /// A single trailing newline is stripped.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let mut contents = fs::read_to_string(path)?;
+        if contents.ends_with('\n') {
+            contents.pop();
+            if contents.ends_with('\r') {
+                contents.pop();
+            }
+        }
+        Ok(Self(contents))
+    }
+
+    /// Reads `KEY` from the environment, or the file named by `KEY_FILE` if `KEY` is unset.
+    pub fn from_env_or_file(key: &str) -> Result<Option<Self>, Error> {
+        if let Some(secret) = Self::from_env(key) {
+            return Ok(Some(secret));
+        }
+        match env::var(format!("{}_FILE", key)) {
+            Ok(path) => Self::from_file(path).map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        // Safety: zeroes are valid UTF-8, and volatile writes keep the compiler from
+        // eliding the wipe of memory that is about to be freed.
+        unsafe {
+            for byte in self.0.as_mut_vec().iter_mut() {
+                std::ptr::write_volatile(byte, 0);
+            }
+        }
+        compiler_fence(Ordering::SeqCst);
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretString([REDACTED])")
+    }
+}
+
+impl fmt::Display for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[REDACTED]")
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self)
+    }
+}
+
+pub type SecretFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Option<SecretString>, Error>> + Send + 'a>>;
+
+/// A source of secrets. Implement this for Vault-style backends; `EnvSecretProvider` and
+/// `FileSecretProvider` cover environment variables and mounted secret files.
+pub trait SecretProvider: Send + Sync {
+    fn name(&self) -> &str;
+    fn get<'a>(&'a self, key: &'a str) -> SecretFuture<'a>;
+}
+
+/// Looks secrets up as environment variables, with an optional prefix (`APP_` + key).
+#[derive(Debug, Default)]
+pub struct EnvSecretProvider {
+    prefix: String,
+}
+
+impl EnvSecretProvider {
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+        }
+    }
+}
+
+impl SecretProvider for EnvSecretProvider {
+    fn name(&self) -> &str {
+        "env"
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> SecretFuture<'a> {
+        Box::pin(async move { Ok(SecretString::from_env(&format!("{}{}", self.prefix, key))) })
+    }
+}
+
+/// Reads each secret from a file named after its key inside a directory, e.g. `/run/secrets`.
+#[derive(Debug)]
+pub struct FileSecretProvider {
+    dir: PathBuf,
+}
+
+impl FileSecretProvider {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl SecretProvider for FileSecretProvider {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> SecretFuture<'a> {
+        Box::pin(async move {
+            let path = self.dir.join(key);
+            if !path.is_file() {
+                return Ok(None);
+            }
+            SecretString::from_file(path).map(Some)
+        })
+    }
+}
+
+/// Resolves secrets from a chain of providers, first match wins.
+#[derive(Default)]
+pub struct SecretStore {
+    providers: Vec<Box<dyn SecretProvider>>,
+}
+
+impl SecretStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn provider(mut self, provider: impl SecretProvider + 'static) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<SecretString>, Error> {
+        for provider in &self.providers {
+            if let Some(secret) = provider.get(key).await? {
+                return Ok(Some(secret));
+            }
+        }
+        Ok(None)
+    }
+
+    pub async fn require(&self, key: &str) -> Result<SecretString, Error> {
+        self.get(key).await?.ok_or_else(|| {
+            let providers = self
+                .providers
+                .iter()
+                .map(|p| p.name())
+                .collect::<Vec<_>>()
+                .join(", ");
+            Error::Config(format!(
+                "Secret '{}' not found (searched: {})",
+                key, providers
+            ))
+        })
+    }
+}
@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+
+use crate::http::{AppState, MiddlewareHandler, RouteManager};
+
+/// A self-contained bundle of routes, middleware, state and static files that can be built
+/// independently (e.g. by a library crate) and mounted under a prefix with
+/// [`Server::mount`](super::Server::mount). Paths are registered relative to the prefix.
+#[derive(Debug, Default)]
+pub struct App {
+    pub router: RouteManager,
+    pub middleware: MiddlewareHandler,
+
+    state: AppState,
+    static_files: HashMap<String, &'static str>,
+}
+
+impl App {
+    pub fn new() -> Self {
+        Self {
+            router: RouteManager::new(),
+            ..Self::default()
+        }
+    }
+
+    /// Makes `value` available to this app's handlers through `ctx.state::<T>()`.
+    pub fn with_state<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.state.insert(value);
+        self
+    }
+
+    #[cfg(feature = "static-files")]
+    pub fn static_file(&mut self, route: &str, file_path: &'static str) {
+        self.static_files.insert(route.to_string(), file_path);
+    }
+
+    pub(super) fn into_parts(
+        self,
+    ) -> (
+        RouteManager,
+        MiddlewareHandler,
+        AppState,
+        HashMap<String, &'static str>,
+    ) {
+        (self.router, self.middleware, self.state, self.static_files)
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use tokio::sync::Notify;
+
+/// What a connection is doing, for spotting requests stuck in one phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionState {
+    /// Accepted, waiting for the first bytes of a request.
+    Idle,
+    Reading,
+    Handling,
+    Writing,
+}
+
+impl ConnectionState {
+    fn from_u8(state: u8) -> Self {
+        match state {
+            1 => Self::Reading,
+            2 => Self::Handling,
+            3 => Self::Writing,
+            _ => Self::Idle,
+        }
+    }
+}
+
+/// Counts in-flight connections so the server can wait for them to finish before exiting,
+/// and keeps what each is doing for diagnosing stuck requests and leaked connections. The
+/// registry is only locked when a connection opens or closes; progress is recorded on the
+/// connection's own entry.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionTracker {
+    inner: Arc<TrackerInner>,
+}
+
+#[derive(Debug, Default)]
+struct TrackerInner {
+    next_id: AtomicU64,
+    active: AtomicUsize,
+    idle: Notify,
+    open: Mutex<HashMap<u64, Arc<Entry>>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    peer: String,
+    opened: Instant,
+    state: AtomicU8,
+    /// When the current state was entered, in milliseconds since `opened`.
+    since: AtomicU64,
+    request: Mutex<Option<String>>,
+    route: Mutex<Option<String>>,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+}
+
+/// Point-in-time view of one open connection, suitable for metrics endpoints.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionInfo {
+    pub id: u64,
+    pub peer: String,
+    pub age_ms: u64,
+    pub state: ConnectionState,
+    /// How long the connection has been in its current state.
+    pub state_ms: u64,
+    /// Method and path of the request being served, once its head has been read.
+    pub request: Option<String>,
+    /// Pattern of the route that handled it, once handled.
+    pub route: Option<String>,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+}
+
+/// Marks a connection as finished when dropped.
+#[derive(Debug)]
+pub struct ConnectionGuard {
+    id: u64,
+    entry: Arc<Entry>,
+    inner: Arc<TrackerInner>,
+}
+
+impl ConnectionTracker {
+    pub fn track(&self, peer: impl Into<String>) -> ConnectionGuard {
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let entry = Arc::new(Entry {
+            peer: peer.into(),
+            opened: Instant::now(),
+            state: AtomicU8::new(ConnectionState::Idle as u8),
+            since: AtomicU64::new(0),
+            request: Mutex::new(None),
+            route: Mutex::new(None),
+            bytes_read: AtomicU64::new(0),
+            bytes_written: AtomicU64::new(0),
+        });
+        if let Ok(mut open) = self.inner.open.lock() {
+            open.insert(id, Arc::clone(&entry));
+        }
+        self.inner.active.fetch_add(1, Ordering::SeqCst);
+        ConnectionGuard {
+            id,
+            entry,
+            inner: Arc::clone(&self.inner),
+        }
+    }
+
+    pub fn active(&self) -> usize {
+        self.inner.active.load(Ordering::SeqCst)
+    }
+
+    /// Every open connection, oldest first.
+    pub fn snapshot(&self) -> Vec<ConnectionInfo> {
+        let open: Vec<(u64, Arc<Entry>)> = match self.inner.open.lock() {
+            Ok(open) => open
+                .iter()
+                .map(|(id, entry)| (*id, Arc::clone(entry)))
+                .collect(),
+            Err(_) => return vec![],
+        };
+        let mut connections: Vec<_> = open
+            .into_iter()
+            .map(|(id, entry)| {
+                let age_ms = entry.opened.elapsed().as_millis() as u64;
+                ConnectionInfo {
+                    id,
+                    peer: entry.peer.clone(),
+                    age_ms,
+                    state: ConnectionState::from_u8(entry.state.load(Ordering::Relaxed)),
+                    state_ms: age_ms.saturating_sub(entry.since.load(Ordering::Relaxed)),
+                    request: entry.request.lock().ok().and_then(|r| r.clone()),
+                    route: entry.route.lock().ok().and_then(|r| r.clone()),
+                    bytes_read: entry.bytes_read.load(Ordering::Relaxed),
+                    bytes_written: entry.bytes_written.load(Ordering::Relaxed),
+                }
+            })
+            .collect();
+        connections.sort_by_key(|connection| connection.id);
+        connections
+    }
+
+    /// Waits until no connections are active. Returns `false` if `timeout` elapsed first.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        tokio::time::timeout(timeout, async {
+            loop {
+                let idle = self.inner.idle.notified();
+                if self.active() == 0 {
+                    return;
+                }
+                idle.await;
+            }
+        })
+        .await
+        .is_ok()
+    }
+}
+
+impl ConnectionGuard {
+    pub fn set_state(&self, state: ConnectionState) {
+        if self.entry.state.swap(state as u8, Ordering::Relaxed) != state as u8 {
+            let since = self.entry.opened.elapsed().as_millis() as u64;
+            self.entry.since.store(since, Ordering::Relaxed);
+        }
+    }
+
+    pub fn set_request(&self, method: &str, path: &str) {
+        if let Ok(mut request) = self.entry.request.lock() {
+            *request = Some(format!("{} {}", method, path));
+        }
+    }
+
+    pub fn set_route(&self, route: Option<&str>) {
+        if let Ok(mut current) = self.entry.route.lock() {
+            *current = route.map(str::to_string);
+        }
+    }
+
+    pub fn add_read(&self, bytes: usize) {
+        self.entry
+            .bytes_read
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_written(&self, bytes: usize) {
+        self.entry
+            .bytes_written
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if let Ok(mut open) = self.inner.open.lock() {
+            open.remove(&self.id);
+        }
+        if self.inner.active.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.inner.idle.notify_waiters();
+        }
+    }
+}
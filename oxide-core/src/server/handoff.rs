@@ -0,0 +1,88 @@
+use std::{
+    env, io,
+    net::TcpListener,
+    os::fd::{AsRawFd, FromRawFd, RawFd},
+    process::{Child, Command},
+};
+
+/// Set on a successor process to the descriptor of the listening socket it inherits.
+pub const LISTEN_FD_VAR: &str = "OXIDE_LISTEN_FD";
+
+/// First descriptor passed by systemd socket activation (`SD_LISTEN_FDS_START`).
+const SYSTEMD_FIRST_FD: RawFd = 3;
+
+/// Picks up a listening socket handed over by a previous process (`OXIDE_LISTEN_FD`) or by
+/// systemd socket activation (`LISTEN_FDS`/`LISTEN_PID`). Returns `None` if there is none,
+/// in which case the server binds its own.
+pub fn inherited_listener() -> io::Result<Option<TcpListener>> {
+    let fd = if let Ok(fd) = env::var(LISTEN_FD_VAR) {
+        env::remove_var(LISTEN_FD_VAR);
+        fd.parse::<RawFd>().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a file descriptor: {}", LISTEN_FD_VAR, fd),
+            )
+        })?
+    } else if is_systemd_activated() {
+        env::remove_var("LISTEN_PID");
+        env::remove_var("LISTEN_FDS");
+        env::remove_var("LISTEN_FDNAMES");
+        SYSTEMD_FIRST_FD
+    } else {
+        return Ok(None);
+    };
+
+    // Safety: the descriptor was handed to this process for exactly this purpose and nothing
+    // else in the process owns it.
+    let listener = unsafe { TcpListener::from_raw_fd(fd) };
+    set_cloexec(fd)?;
+    listener.set_nonblocking(true)?;
+    Ok(Some(listener))
+}
+
+fn is_systemd_activated() -> bool {
+    let for_us = env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        .is_some_and(|pid| pid == std::process::id());
+    let count = env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|n| n.parse::<u32>().ok())
+        .unwrap_or(0);
+    for_us && count >= 1
+}
+
+/// Re-executes the current binary with the same arguments, passing it a duplicate of
+/// `listener` so it can start accepting before this process stops.
+pub fn spawn_successor(listener: &impl AsRawFd) -> io::Result<Child> {
+    // Safety: `dup` has no memory-safety preconditions; the result is checked below.
+    let fd = unsafe { libc::dup(listener.as_raw_fd()) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // Descriptors returned by `dup` don't carry FD_CLOEXEC, so this one survives the exec.
+    let child = Command::new(env::current_exe()?)
+        .args(env::args_os().skip(1))
+        .env(LISTEN_FD_VAR, fd.to_string())
+        .spawn();
+
+    // Safety: `fd` was created above and is only owned here.
+    unsafe { libc::close(fd) };
+    child
+}
+
+/// Keeps an inherited socket from leaking into processes this one spawns later.
+fn set_cloexec(fd: RawFd) -> io::Result<()> {
+    // Safety: fcntl on a descriptor we own, with flag arguments only.
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFD);
+        if flags < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
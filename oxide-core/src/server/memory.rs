@@ -0,0 +1,100 @@
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc,
+};
+
+use serde::Serialize;
+
+/// Tracks bytes buffered for requests and responses across all connections, with an
+/// optional global ceiling above which new requests are shed with a 503.
+#[derive(Debug, Default)]
+pub struct MemoryBudget {
+    limit: Option<usize>,
+    in_use: AtomicUsize,
+    peak: AtomicUsize,
+    shed: AtomicU64,
+}
+
+/// Point-in-time view of a [`MemoryBudget`], suitable for metrics endpoints.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct MemoryStats {
+    pub limit: Option<usize>,
+    pub in_use: usize,
+    pub peak: usize,
+    pub shed_requests: u64,
+}
+
+/// Bytes charged to the budget on behalf of one request, released when dropped.
+#[derive(Debug)]
+pub struct MemoryReservation {
+    budget: Arc<MemoryBudget>,
+    bytes: usize,
+}
+
+impl MemoryBudget {
+    pub fn new(limit: Option<usize>) -> Self {
+        Self {
+            limit,
+            ..Self::default()
+        }
+    }
+
+    /// Charges `bytes` to the budget, or returns `None` (and counts a shed request) if that
+    /// would exceed the limit.
+    pub fn try_reserve(self: &Arc<Self>, bytes: usize) -> Option<MemoryReservation> {
+        let reserved = self
+            .in_use
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |in_use| {
+                let total = in_use.checked_add(bytes)?;
+                match self.limit {
+                    Some(limit) if total > limit => None,
+                    _ => Some(total),
+                }
+            });
+
+        match reserved {
+            Ok(previous) => {
+                self.peak.fetch_max(previous + bytes, Ordering::SeqCst);
+                Some(MemoryReservation {
+                    budget: Arc::clone(self),
+                    bytes,
+                })
+            }
+            Err(_) => {
+                self.shed.fetch_add(1, Ordering::SeqCst);
+                None
+            }
+        }
+    }
+
+    pub fn stats(&self) -> MemoryStats {
+        MemoryStats {
+            limit: self.limit,
+            in_use: self.in_use.load(Ordering::SeqCst),
+            peak: self.peak.load(Ordering::SeqCst),
+            shed_requests: self.shed.load(Ordering::SeqCst),
+        }
+    }
+}
+
+impl MemoryReservation {
+    /// Charges additional bytes, such as the response buffer, without checking the limit:
+    /// the memory is already allocated by the time it is accounted for.
+    pub fn add(&mut self, bytes: usize) {
+        let previous = self.budget.in_use.fetch_add(bytes, Ordering::SeqCst);
+        self.budget
+            .peak
+            .fetch_max(previous + bytes, Ordering::SeqCst);
+        self.bytes += bytes;
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.budget.in_use.fetch_sub(self.bytes, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,130 @@
+use std::{collections::HashMap, fmt::Write, sync::Mutex};
+
+use serde::Serialize;
+
+/// Upper bounds of the response size buckets, in bytes.
+pub const RESPONSE_SIZE_BUCKETS: [u64; 9] = [
+    256,
+    1024,
+    4 * 1024,
+    16 * 1024,
+    64 * 1024,
+    256 * 1024,
+    1024 * 1024,
+    4 * 1024 * 1024,
+    16 * 1024 * 1024,
+];
+
+/// Label for responses no route or static file produced, such as 404s.
+const UNMATCHED: &str = "unmatched";
+
+/// Histogram of bytes written per response, by route pattern, for finding the endpoints
+/// that use the most bandwidth.
+#[derive(Debug, Default)]
+pub struct ResponseSizes {
+    routes: Mutex<HashMap<String, Histogram>>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    counts: [u64; RESPONSE_SIZE_BUCKETS.len()],
+    responses: u64,
+    bytes: u64,
+}
+
+/// Point-in-time view of one route's response sizes, suitable for metrics endpoints.
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteSizes {
+    pub route: String,
+    pub responses: u64,
+    pub bytes: u64,
+    /// Cumulative counts per upper bound in [`RESPONSE_SIZE_BUCKETS`]; responses larger than
+    /// the last are only counted in `responses`.
+    pub buckets: Vec<(u64, u64)>,
+}
+
+impl ResponseSizes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a response of `bytes` from `route`, or from no route if `None`.
+    pub fn observe(&self, route: Option<&str>, bytes: usize) {
+        let bytes = bytes as u64;
+        let Ok(mut routes) = self.routes.lock() else {
+            return;
+        };
+        let histogram = routes
+            .entry(route.unwrap_or(UNMATCHED).to_string())
+            .or_default();
+        if let Some(bucket) = RESPONSE_SIZE_BUCKETS.iter().position(|&le| bytes <= le) {
+            histogram.counts[bucket] += 1;
+        }
+        histogram.responses += 1;
+        histogram.bytes += bytes;
+    }
+
+    /// Every route seen so far, most bytes first.
+    pub fn stats(&self) -> Vec<RouteSizes> {
+        let routes = match self.routes.lock() {
+            Ok(routes) => routes.clone(),
+            Err(_) => return vec![],
+        };
+        let mut stats = routes
+            .into_iter()
+            .map(|(route, histogram)| {
+                let mut cumulative = 0;
+                let buckets = RESPONSE_SIZE_BUCKETS
+                    .iter()
+                    .zip(histogram.counts)
+                    .map(|(&le, count)| {
+                        cumulative += count;
+                        (le, cumulative)
+                    })
+                    .collect();
+                RouteSizes {
+                    route,
+                    responses: histogram.responses,
+                    bytes: histogram.bytes,
+                    buckets,
+                }
+            })
+            .collect::<Vec<_>>();
+        stats.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.route.cmp(&b.route)));
+        stats
+    }
+
+    /// The histogram in the Prometheus text format, as `oxide_response_size_bytes`.
+    pub fn prometheus(&self) -> String {
+        let mut out = String::from(
+            "# HELP oxide_response_size_bytes Bytes written per response.\n\
+             # TYPE oxide_response_size_bytes histogram\n",
+        );
+        for stats in self.stats() {
+            let route = stats.route.replace('\\', "\\\\").replace('"', "\\\"");
+            for (le, count) in &stats.buckets {
+                let _ = writeln!(
+                    out,
+                    "oxide_response_size_bytes_bucket{{route=\"{}\",le=\"{}\"}} {}",
+                    route, le, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "oxide_response_size_bytes_bucket{{route=\"{}\",le=\"+Inf\"}} {}",
+                route, stats.responses
+            );
+            let _ = writeln!(
+                out,
+                "oxide_response_size_bytes_sum{{route=\"{}\"}} {}",
+                route, stats.bytes
+            );
+            let _ = writeln!(
+                out,
+                "oxide_response_size_bytes_count{{route=\"{}\"}} {}",
+                route, stats.responses
+            );
+        }
+        out
+    }
+}
@@ -0,0 +1,1000 @@
+mod app;
+mod connections;
+mod handoff;
+mod memory;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod plugin;
+mod shutdown;
+mod slo;
+
+use crate::{
+    circuit_breaker::CircuitBreaker,
+    config::Config,
+    connection::Connection,
+    datasource::ReadReplicas,
+    http::{
+        install_panic_hook, join_path, Chaos, Coalescer, ConnectionsPage, DeadLetterPage, Guard,
+        HttpHandler, MiddlewareHandler, ReplayProtection, RequestRecorder, RequestVerifier,
+        ResponseCache, RouteCache, RouteManager,
+    },
+    logger::LogLevel,
+    messaging::{Consumer, DeadLetters, MessageBus, Propagation},
+    metering::Metering,
+    Error, Logger, PgDatabase,
+};
+use std::{
+    collections::HashMap,
+    io,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    net::TcpListener,
+    signal::unix::{signal, SignalKind},
+};
+
+pub use app::App;
+pub use connections::{ConnectionGuard, ConnectionInfo, ConnectionState, ConnectionTracker};
+pub use handoff::LISTEN_FD_VAR;
+pub use memory::{MemoryBudget, MemoryReservation, MemoryStats};
+#[cfg(feature = "metrics")]
+pub use metrics::{ResponseSizes, RouteSizes, RESPONSE_SIZE_BUCKETS};
+pub use plugin::{OxidePlugin, PluginCommand, PluginFuture};
+pub use shutdown::{JobGuard, Shutdown, ShutdownPhase, ShutdownReport};
+pub use slo::{Slo, SloAlert, SloAlertFn, SloStats, SloTracker};
+
+/// How long a freshly spawned successor must stay up before this process hands off to it.
+const HANDOFF_GRACE: Duration = Duration::from_secs(2);
+
+pub struct Server {
+    pub router: RouteManager,
+    pub middleware: MiddlewareHandler,
+
+    config: Config,
+    logger: Logger,
+    http_handler: Option<Arc<HttpHandler>>,
+    static_files: HashMap<String, &'static str>,
+    datasource: Option<PgDatabase>,
+    named_datasources: HashMap<String, PgDatabase>,
+    read_replicas: ReadReplicas,
+    memory: Arc<MemoryBudget>,
+    #[cfg(feature = "metrics")]
+    response_sizes: Arc<ResponseSizes>,
+    slo_tracker: Arc<SloTracker>,
+    route_cache: Arc<RouteCache>,
+    coalescer: Arc<Coalescer>,
+    circuit_breakers: HashMap<String, CircuitBreaker>,
+    response_cache: Arc<ResponseCache>,
+    connections: ConnectionTracker,
+    connections_page: Option<(String, Guard)>,
+    plugins: Vec<Box<dyn OxidePlugin>>,
+    message_bus: Option<Arc<dyn MessageBus>>,
+    propagation: Propagation,
+    consumers: Vec<Consumer>,
+    dead_letters: Option<Arc<DeadLetters>>,
+    dead_letter_page: Option<(String, Guard)>,
+    metering: Option<Arc<Metering>>,
+    replay_protection: Option<Arc<ReplayProtection>>,
+    request_verifier: Option<Arc<RequestVerifier>>,
+    chaos: Option<Arc<Chaos>>,
+    shutdown: Shutdown,
+    shutdown_report: Option<ShutdownReport>,
+}
+
+impl Server {
+    pub fn new(config: Config) -> Self {
+        config.environment.activate();
+        let memory = Arc::new(MemoryBudget::new(config.max_buffered_bytes));
+        Self {
+            config,
+            logger: Logger::for_target(module_path!()),
+            router: RouteManager::new(),
+            http_handler: None,
+            middleware: MiddlewareHandler::new(),
+            static_files: HashMap::new(),
+            datasource: None,
+            named_datasources: HashMap::new(),
+            read_replicas: ReadReplicas::new(),
+            memory,
+            #[cfg(feature = "metrics")]
+            response_sizes: Arc::new(ResponseSizes::new()),
+            slo_tracker: Arc::new(SloTracker::new()),
+            route_cache: Arc::new(RouteCache::new(0)),
+            coalescer: Arc::new(Coalescer::new()),
+            circuit_breakers: HashMap::new(),
+            response_cache: Arc::new(ResponseCache::new(ResponseCache::DEFAULT_CAPACITY)),
+            connections: ConnectionTracker::default(),
+            connections_page: None,
+            plugins: vec![],
+            message_bus: None,
+            propagation: Propagation::default(),
+            consumers: vec![],
+            dead_letters: None,
+            dead_letter_page: None,
+            metering: None,
+            replay_protection: None,
+            request_verifier: None,
+            chaos: None,
+            shutdown: Shutdown::new(),
+            shutdown_report: None,
+        }
+    }
+
+    /// Bytes currently buffered for in-flight requests, for exposing as metrics.
+    pub fn memory(&self) -> Arc<MemoryBudget> {
+        Arc::clone(&self.memory)
+    }
+
+    /// Bytes written per response by route, for exposing as metrics.
+    #[cfg(feature = "metrics")]
+    pub fn response_sizes(&self) -> Arc<ResponseSizes> {
+        Arc::clone(&self.response_sizes)
+    }
+
+    /// Compliance of routes registered with `.slo(...)`, for exposing as metrics.
+    pub fn slo_tracker(&self) -> Arc<SloTracker> {
+        Arc::clone(&self.slo_tracker)
+    }
+
+    /// Replaces the default SLO tracker, e.g. to change its window or register alert hooks.
+    pub fn with_slo_tracker(&mut self, tracker: SloTracker) -> &mut Self {
+        self.slo_tracker = Arc::new(tracker);
+        self
+    }
+
+    /// Hits and misses of the route cache, for judging whether it's worth its size.
+    pub fn route_cache(&self) -> Arc<RouteCache> {
+        Arc::clone(&self.route_cache)
+    }
+
+    /// How many requests to routes registered with `.coalesce()` shared another's response.
+    pub fn coalescer(&self) -> Arc<Coalescer> {
+        Arc::clone(&self.coalescer)
+    }
+
+    /// Registers a breaker for a downstream dependency under its name, for handlers to call
+    /// through and for routes declared with `.depends_on(name)`.
+    pub fn with_circuit_breaker(&mut self, breaker: CircuitBreaker) -> &mut Self {
+        self.circuit_breakers
+            .insert(breaker.name().to_string(), breaker);
+        self
+    }
+
+    /// A registered breaker, sharing state with the one requests see, e.g. for metrics.
+    pub fn circuit_breaker(&self, name: &str) -> Option<CircuitBreaker> {
+        self.circuit_breakers.get(name).cloned()
+    }
+
+    /// Hits, misses and stale drops of responses cached by `#[handler(cache(...))]`.
+    pub fn response_cache(&self) -> Arc<ResponseCache> {
+        Arc::clone(&self.response_cache)
+    }
+
+    /// Keeps up to `capacity` responses of handlers declared with `#[handler(cache(...))]`,
+    /// 1000 by default. 0 turns caching off, so those handlers run for every request.
+    pub fn with_response_cache(&mut self, capacity: usize) -> &mut Self {
+        self.response_cache = Arc::new(ResponseCache::new(capacity));
+        self
+    }
+
+    /// Open connections with what each is doing, for finding stuck requests and leaks.
+    pub fn connections(&self) -> ConnectionTracker {
+        self.connections.clone()
+    }
+
+    /// Serves the open connections as JSON at `path` to requests passing `guard`, e.g.
+    /// `Guard::header("authorization", &admin_token)`.
+    pub fn with_connections_page(&mut self, path: &str, guard: Guard) -> &mut Self {
+        self.connections_page = Some((path.to_string(), guard));
+        self
+    }
+
+    /// Caches up to `capacity` resolved method and path pairs, so hot endpoints skip route
+    /// matching. Only routes without parameters are cached. Pays off with many routes and a
+    /// few very frequent paths; `route_cache()` reports the hit rate.
+    pub fn with_route_cache(&mut self, capacity: usize) -> &mut Self {
+        self.route_cache = Arc::new(RouteCache::new(capacity));
+        self
+    }
+
+    /// The shutdown signal, for background workers started outside the server (such as an
+    /// outbox worker) to stop with it. Triggering it stops the server too.
+    pub fn shutdown(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    /// What the last graceful shutdown drained and abandoned, once `run` has returned.
+    pub fn shutdown_report(&self) -> Option<&ShutdownReport> {
+        self.shutdown_report.as_ref()
+    }
+
+    pub fn with_datasource(&mut self, datasource: PgDatabase) -> &mut Self {
+        self.datasource = Some(datasource);
+        self
+    }
+
+    /// Registers an additional pool, available to handlers as `ctx.db_named(name)` and used by
+    /// models declared with `#[model(database = "name")]`.
+    pub fn with_named_datasource(&mut self, name: &str, datasource: PgDatabase) -> &mut Self {
+        self.named_datasources.insert(name.to_string(), datasource);
+        self
+    }
+
+    /// Registers a read replica of the main datasource. Query builders given a handler's
+    /// `Context` send their selects to it, taking turns when there are several, while writes,
+    /// raw queries and anything in a transaction stay on the primary.
+    pub fn with_read_replica(&mut self, replica: PgDatabase) -> &mut Self {
+        self.read_replicas.add(None, replica);
+        self
+    }
+
+    /// Registers a read replica of the datasource registered as `name`.
+    pub fn with_named_read_replica(&mut self, name: &str, replica: PgDatabase) -> &mut Self {
+        self.read_replicas.add(Some(name), replica);
+        self
+    }
+
+    /// Makes `ctx.publish(...)` available to handlers and is what consumers subscribe to.
+    pub fn with_message_bus(&mut self, message_bus: impl MessageBus + 'static) -> &mut Self {
+        self.message_bus = Some(Arc::new(message_bus));
+        self
+    }
+
+    /// Which of the request id, tenant and principal `ctx.publish` attaches to messages.
+    pub fn with_propagation(&mut self, propagation: Propagation) -> &mut Self {
+        self.propagation = propagation;
+        self
+    }
+
+    /// Registers a consumer, started alongside the server. Requires a message bus.
+    pub fn consumer(&mut self, consumer: Consumer) -> &mut Self {
+        self.consumers.push(consumer);
+        self
+    }
+
+    /// Records messages consumers give up on in `dead_letters`, whose table is created on
+    /// startup.
+    pub fn with_dead_letters(&mut self, dead_letters: DeadLetters) -> &mut Self {
+        self.dead_letters = Some(Arc::new(dead_letters));
+        self
+    }
+
+    /// Serves a page at `path` listing dead letters with buttons to retry or discard each, to
+    /// requests passing `guard`, e.g. `Guard::header("authorization", &admin_token)`.
+    /// Requires `with_dead_letters`.
+    pub fn with_dead_letter_page(&mut self, path: &str, guard: Guard) -> &mut Self {
+        self.dead_letter_page = Some((path.to_string(), guard));
+        self
+    }
+
+    /// Meters requests per tenant and principal into `metering`, whose table is created on
+    /// startup. Tenants and principals are whatever middleware sets on the context.
+    pub fn with_metering(&mut self, metering: Metering) -> &mut Self {
+        self.metering = Some(Arc::new(metering));
+        self
+    }
+
+    /// The usage meter, for querying rollups, if one is configured.
+    pub fn metering(&self) -> Option<Arc<Metering>> {
+        self.metering.clone()
+    }
+
+    /// Rejects stale or repeated requests to routes registered with `.replay_protected()`.
+    /// The nonce store is prepared, e.g. its table created, on startup.
+    pub fn with_replay_protection(&mut self, replay: ReplayProtection) -> &mut Self {
+        self.replay_protection = Some(Arc::new(replay));
+        self
+    }
+
+    /// Verifies HMAC signatures on routes registered with `.signed()`; the signing key id
+    /// becomes the request's principal.
+    pub fn with_request_verifier(&mut self, verifier: RequestVerifier) -> &mut Self {
+        self.request_verifier = Some(Arc::new(verifier));
+        self
+    }
+
+    /// Injects latency, errors, dropped connections and slow responses, for testing how
+    /// clients cope. Ignored in production.
+    pub fn with_chaos(&mut self, chaos: Chaos) -> &mut Self {
+        self.chaos = Some(Arc::new(chaos));
+        self
+    }
+
+    /// Serves the embedded file at `file_path` for GET requests to `route`.
+    #[cfg(feature = "static-files")]
+    pub fn static_file(&mut self, route: &str, file_path: &'static str) {
+        self.static_files.insert(route.to_string(), file_path);
+    }
+
+    /// Mounts a sub-application under `prefix`: its routes, middleware and static files are
+    /// registered relative to it, and its state is visible only to its own handlers.
+    pub fn mount(&mut self, prefix: &str, app: App) -> &mut Self {
+        let (router, middleware, state, static_files) = app.into_parts();
+        let route_count = router.routes().len();
+
+        let raw_paths = self.router.mount(prefix, router, Arc::new(state));
+        self.middleware.mount(prefix, middleware, &raw_paths);
+        for (route, file_path) in static_files {
+            self.static_files
+                .insert(join_path(prefix, &route), file_path);
+        }
+
+        self.logger.log(
+            LogLevel::Info,
+            &format!("Mounted app at {} ({} routes)", prefix, route_count),
+        );
+        self
+    }
+
+    /// Installs a plugin, letting it register its routes, middleware and state right away.
+    pub fn plugin(&mut self, plugin: impl OxidePlugin + 'static) -> &mut Self {
+        plugin.register(self);
+        self.logger.log(
+            LogLevel::Info,
+            &format!("Plugin registered: {}", plugin.name()),
+        );
+        self.plugins.push(Box::new(plugin));
+        self
+    }
+
+    /// Runs the plugin subcommand named by `args[0]`, if any plugin provides one.
+    pub fn run_plugin_command(&self, args: &[String]) -> Option<Result<(), Error>> {
+        let name = args.first()?;
+        let command = self
+            .plugins
+            .iter()
+            .flat_map(|plugin| plugin.commands())
+            .find(|command| command.name == name)?;
+        Some((command.run)(&args[1..]))
+    }
+
+    /// What this server is about to run with, gathered before routes and middleware are
+    /// moved into the shared handler.
+    fn startup_summary(&self) -> Vec<(&'static str, String)> {
+        let mut methods: Vec<(String, usize)> = vec![];
+        for route in self.router.routes() {
+            let method = route.method.to_string();
+            match methods.iter_mut().find(|(m, _)| *m == method) {
+                Some((_, count)) => *count += 1,
+                None => methods.push((method, 1)),
+            }
+        }
+        let methods = methods
+            .iter()
+            .map(|(method, count)| format!("{} {}", count, method))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let (global, route_specific) = self.middleware.summary();
+        let pool = |db: &PgDatabase| {
+            let (size, idle) = db.pool_status();
+            format!("connected ({} connections, {} idle)", size, idle)
+        };
+        let replicas = |name: Option<&str>| match self.read_replicas.of(name).len() {
+            0 => String::new(),
+            1 => ", 1 read replica".to_string(),
+            n => format!(", {} read replicas", n),
+        };
+        let mut database = match &self.datasource {
+            Some(db) => format!("{}{}", pool(db), replicas(None)),
+            None => "not configured".to_string(),
+        };
+        let mut named = self.named_datasources.iter().collect::<Vec<_>>();
+        named.sort_by_key(|(name, _)| name.as_str());
+        for (name, db) in named {
+            database.push_str(&format!("; {}: {}{}", name, pool(db), replicas(Some(name))));
+        }
+        let sources = if self.config.sources().is_empty() {
+            "defaults and environment variables".to_string()
+        } else {
+            self.config
+                .sources()
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let enabled = |on: bool| if on { "enabled" } else { "disabled" }.to_string();
+
+        vec![
+            ("environment", self.config.environment.to_string()),
+            ("tls", "disabled".to_string()),
+            (
+                "routes",
+                if methods.is_empty() {
+                    "0".to_string()
+                } else {
+                    format!("{} ({})", self.router.routes().len(), methods)
+                },
+            ),
+            (
+                "middleware",
+                format!("{} global, {} route-specific", global, route_specific),
+            ),
+            (
+                "plugins",
+                if self.plugins.is_empty() {
+                    "none".to_string()
+                } else {
+                    self.plugins
+                        .iter()
+                        .map(|plugin| plugin.name())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                },
+            ),
+            (
+                "route cache",
+                match self.route_cache.stats().capacity {
+                    0 => "disabled".to_string(),
+                    capacity => format!("{} entries", capacity),
+                },
+            ),
+            (
+                "response cache",
+                match self.response_cache.stats().capacity {
+                    0 => "disabled".to_string(),
+                    capacity => format!("{} entries", capacity),
+                },
+            ),
+            (
+                "coalescing",
+                match self.router.routes().iter().filter(|r| r.coalesce).count() {
+                    0 => "none".to_string(),
+                    routes => format!("{} routes", routes),
+                },
+            ),
+            (
+                "breakers",
+                if self.circuit_breakers.is_empty() {
+                    "none".to_string()
+                } else {
+                    let mut names: Vec<_> = self.circuit_breakers.keys().cloned().collect();
+                    names.sort();
+                    names.join(", ")
+                },
+            ),
+            ("static files", self.static_files.len().to_string()),
+            ("database", database),
+            (
+                "messaging",
+                match &self.message_bus {
+                    Some(bus) => format!("{} ({} consumers)", bus.name(), self.consumers.len()),
+                    None => "not configured".to_string(),
+                },
+            ),
+            ("metering", enabled(self.metering.is_some())),
+            (
+                "signing",
+                match &self.request_verifier {
+                    Some(verifier) => format!(
+                        "{} keys, {}s window",
+                        verifier.key_count(),
+                        verifier.window().as_secs()
+                    ),
+                    None => "disabled".to_string(),
+                },
+            ),
+            (
+                "replay",
+                match &self.replay_protection {
+                    Some(replay) => format!(
+                        "{} store, {}s window",
+                        replay.store().name(),
+                        replay.window().as_secs()
+                    ),
+                    None => "disabled".to_string(),
+                },
+            ),
+            (
+                "slo",
+                match self
+                    .router
+                    .routes()
+                    .iter()
+                    .filter(|r| r.slo.is_some())
+                    .count()
+                {
+                    0 => "none".to_string(),
+                    routes => format!(
+                        "{} routes, alert at {}x burn over {}s",
+                        routes,
+                        self.slo_tracker.burn_rate_threshold(),
+                        self.slo_tracker.window().as_secs()
+                    ),
+                },
+            ),
+            (
+                "chaos",
+                match &self.chaos {
+                    Some(_) if self.config.is_production() => "ignored in production".to_string(),
+                    Some(chaos) => chaos.describe(),
+                    None => "disabled".to_string(),
+                },
+            ),
+            ("cors", enabled(self.config.cors.is_some())),
+            ("verbose logs", enabled(self.config.verbose_logging)),
+            (
+                "recording",
+                enabled(self.config.record_requests && !self.config.is_production()),
+            ),
+            (
+                "max request",
+                format!("{} bytes", self.config.max_request_size),
+            ),
+            (
+                "memory limit",
+                match self.config.max_buffered_bytes {
+                    Some(limit) => format!("{} bytes", limit),
+                    None => "unlimited".to_string(),
+                },
+            ),
+            ("config", sources),
+        ]
+    }
+
+    /// Printed on every boot, production included, so a misconfigured deployment can be
+    /// diagnosed from its first lines of output.
+    fn log_startup_summary(&self, address: &str, summary: Vec<(&'static str, String)>) {
+        self.logger.log(
+            LogLevel::Application,
+            &format!(
+                "oxide v{} listening on http://{} ({})",
+                env!("CARGO_PKG_VERSION"),
+                address,
+                self.config.environment
+            ),
+        );
+        for (key, value) in summary {
+            self.logger
+                .log(LogLevel::Application, &format!("  {:<14}{}", key, value));
+        }
+    }
+
+    pub async fn run(&mut self) -> io::Result<()> {
+        let args = std::env::args().skip(1).collect::<Vec<_>>();
+        if let Some(result) = self.run_plugin_command(&args) {
+            return result.map_err(|e| io::Error::other(e.to_string()));
+        }
+
+        let listener = match handoff::inherited_listener()? {
+            Some(listener) => {
+                self.logger.log(
+                    LogLevel::Info,
+                    "Inherited listening socket from the previous process",
+                );
+                TcpListener::from_std(listener)?
+            }
+            None => {
+                let addr = format!("{}:{}", self.config.host, self.config.port);
+                TcpListener::bind(&addr).await?
+            }
+        };
+        self.serve(listener).await
+    }
+
+    /// Serves on an already bound listener, e.g. one bound to port 0 in tests.
+    pub async fn serve(&mut self, listener: TcpListener) -> io::Result<()> {
+        if let Err(e) = self.config.validate() {
+            self.logger.log(LogLevel::Error, &e.to_string());
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, e.to_string()));
+        }
+
+        if self.router.routes().len() == 0 {
+            self.logger.log(
+                LogLevel::Application,
+                "No routes have been registered, you can register routes by calling the `get`, `post`, `put` and `delete` methods on the route manager.",
+            )
+        }
+
+        for plugin in &self.plugins {
+            if let Err(e) = plugin.on_startup(self.datasource.as_ref()).await {
+                let message = format!("Plugin '{}' failed to start: {}", plugin.name(), e);
+                self.logger.log(LogLevel::Error, &message);
+                return Err(io::Error::other(message));
+            }
+        }
+
+        if !self.consumers.is_empty() && self.message_bus.is_none() {
+            let message = "Consumers are registered but no message bus is configured";
+            self.logger.log(LogLevel::Error, message);
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+
+        if self.dead_letter_page.is_some() && self.dead_letters.is_none() {
+            let message = "A dead letter page is configured but no dead letter store is";
+            self.logger.log(LogLevel::Error, message);
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+
+        if let Some(dead_letters) = &self.dead_letters {
+            if let Err(e) = dead_letters.create_table().await {
+                let message = format!("Failed to create the dead letter table: {}", e);
+                self.logger.log(LogLevel::Error, &message);
+                return Err(io::Error::other(message));
+            }
+        }
+
+        if self.replay_protection.is_none()
+            && self.router.routes().iter().any(|r| r.replay_protected)
+        {
+            let message = "Routes are replay protected but no replay protection is configured";
+            self.logger.log(LogLevel::Error, message);
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+
+        if self.request_verifier.is_none() && self.router.routes().iter().any(|r| r.signed) {
+            let message = "Routes require signing but no request verifier is configured";
+            self.logger.log(LogLevel::Error, message);
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+
+        if let Some(replay) = &self.replay_protection {
+            if let Err(e) = replay.store().prepare().await {
+                let message = format!(
+                    "Failed to prepare the {} nonce store: {}",
+                    replay.store().name(),
+                    e
+                );
+                self.logger.log(LogLevel::Error, &message);
+                return Err(io::Error::other(message));
+            }
+        }
+
+        if let Some(metering) = &self.metering {
+            if let Err(e) = metering.create_table().await {
+                let message = format!("Failed to create the usage table: {}", e);
+                self.logger.log(LogLevel::Error, &message);
+                return Err(io::Error::other(message));
+            }
+            let metering = Arc::clone(metering);
+            let shutdown = self.shutdown.clone();
+            tokio::spawn(async move { metering.run(shutdown).await });
+        }
+
+        install_panic_hook();
+        #[cfg(feature = "otel")]
+        let telemetry = self.install_telemetry()?;
+
+        #[allow(unused_mut)]
+        let mut summary = self.startup_summary();
+        #[cfg(feature = "otel")]
+        summary.insert(summary.len() - 1, ("telemetry", telemetry));
+
+        if let Some(bus) = &self.message_bus {
+            for consumer in std::mem::take(&mut self.consumers) {
+                let bus = Arc::clone(bus);
+                let dead_letters = self.dead_letters.clone();
+                let shutdown = self.shutdown.clone();
+                let logger = self.logger.clone();
+                tokio::spawn(async move {
+                    let topic = consumer.topic().to_string();
+                    if let Err(e) = consumer.run(bus, dead_letters, shutdown).await {
+                        logger.log(
+                            LogLevel::Error,
+                            &format!("Consumer for '{}' stopped: {}", topic, e),
+                        );
+                    }
+                });
+            }
+        }
+
+        let shared_router = Arc::new(std::mem::take(&mut self.router));
+        let shared_middleware = Arc::new(std::mem::take(&mut self.middleware));
+        let static_files = Arc::new(std::mem::take(&mut self.static_files));
+
+        let datasource = match &self.datasource {
+            Some(db) => Some(Arc::new(db.clone())),
+            None => None,
+        };
+
+        let recorder = (self.config.record_requests && !self.config.is_production())
+            .then(|| Arc::new(RequestRecorder::new(100)));
+
+        if self.chaos.is_some() && self.config.is_production() {
+            self.logger.log(
+                LogLevel::Warning,
+                "Chaos is configured but ignored in production",
+            );
+        }
+        let chaos = self.chaos.clone().filter(|_| !self.config.is_production());
+
+        let dead_letter_page = self
+            .dead_letter_page
+            .clone()
+            .zip(self.dead_letters.clone())
+            .map(|((path, guard), dead_letters)| {
+                Arc::new(DeadLetterPage::new(
+                    &path,
+                    guard,
+                    dead_letters,
+                    self.message_bus.clone(),
+                ))
+            });
+
+        let connections_page = self.connections_page.clone().map(|(path, guard)| {
+            Arc::new(ConnectionsPage::new(&path, guard, self.connections.clone()))
+        });
+
+        self.http_handler = Some(Arc::new(
+            HttpHandler::new(shared_router, shared_middleware, static_files, datasource)
+                .with_named_datasources(Arc::new(self.named_datasources.clone()))
+                .with_read_replicas(Arc::new(self.read_replicas.clone()))
+                .with_message_bus(self.message_bus.clone())
+                .with_propagation(self.propagation)
+                .with_cors(self.config.cors.clone())
+                .with_verbose_logging(self.config.verbose_logging)
+                .with_recorder(recorder)
+                .with_dead_letter_page(dead_letter_page)
+                .with_connections_page(connections_page)
+                .with_metering(self.metering.clone())
+                .with_replay_protection(self.replay_protection.clone())
+                .with_request_verifier(self.request_verifier.clone())
+                .with_slo_tracker(Arc::clone(&self.slo_tracker))
+                .with_chaos(chaos)
+                .with_route_cache(Arc::clone(&self.route_cache))
+                .with_coalescer(Arc::clone(&self.coalescer))
+                .with_circuit_breakers(Arc::new(self.circuit_breakers.clone()))
+                .with_response_cache(Arc::clone(&self.response_cache)),
+        ));
+
+        self.logger.log(
+            LogLevel::Info,
+            &format!(
+                "Server is listening on Port: {}",
+                listener.local_addr()?.port()
+            ),
+        );
+        self.log_startup_summary(&listener.local_addr()?.to_string(), summary);
+
+        let tracker = self.connections.clone();
+        let mut upgrade = signal(SignalKind::user_defined2())?;
+        let mut terminate = signal(SignalKind::terminate())?;
+        let mut interrupt = signal(SignalKind::interrupt())?;
+
+        let reason = loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (socket, addr) = accepted?;
+                    let handler = Arc::clone(self.http_handler.as_ref().unwrap());
+                    let memory = Arc::clone(&self.memory);
+                    #[cfg(feature = "metrics")]
+                    let response_sizes = Arc::clone(&self.response_sizes);
+                    let max_request_size = self.config.max_request_size;
+                    let guard = tracker.track(addr.to_string());
+                    tokio::spawn(async move {
+                        let connection = Connection::new(socket, handler)
+                            .unwrap()
+                            .with_memory_budget(memory)
+                            .with_max_request_size(max_request_size)
+                            .with_connection_guard(guard);
+                        #[cfg(feature = "metrics")]
+                        let connection = connection.with_response_sizes(response_sizes);
+                        if let Err(e) = connection.process().await {
+                            eprintln!("Connection error: {}", e);
+                        }
+                    });
+                }
+                _ = upgrade.recv() => {
+                    if self.hand_off(&listener).await {
+                        break "hand-off";
+                    }
+                }
+                _ = terminate.recv() => {
+                    self.logger.log(LogLevel::Info, "SIGTERM received, shutting down");
+                    break "SIGTERM";
+                }
+                _ = interrupt.recv() => {
+                    self.logger.log(LogLevel::Info, "SIGINT received, shutting down");
+                    break "SIGINT";
+                }
+                _ = self.shutdown.triggered() => break "shutdown triggered",
+            }
+        };
+
+        let started = Instant::now();
+        drop(listener);
+        self.shutdown.trigger();
+        let stopped = ShutdownPhase::new("stop accepting", started.elapsed(), false);
+        let connections = tracker.active();
+        let jobs = self.shutdown.jobs().len();
+        self.logger.log(
+            LogLevel::Info,
+            &format!(
+                "Draining {} in-flight connection(s) and {} background job(s)",
+                connections, jobs
+            ),
+        );
+        let draining = Instant::now();
+        let ((drained, connection_phase), (abandoned, job_phase)) = tokio::join!(
+            async {
+                let drained = tracker.drain(self.config.drain_timeout).await;
+                let phase = ShutdownPhase::new("drain connections", draining.elapsed(), !drained);
+                (drained, phase)
+            },
+            async {
+                let abandoned = self.shutdown.drain(self.config.drain_timeout).await;
+                let phase =
+                    ShutdownPhase::new("drain jobs", draining.elapsed(), !abandoned.is_empty());
+                (abandoned, phase)
+            }
+        );
+        let aborted_requests: Vec<String> = tracker
+            .snapshot()
+            .into_iter()
+            .map(|connection| match connection.request {
+                Some(request) => request,
+                None => format!("{} ({:?})", connection.peer, connection.state).to_lowercase(),
+            })
+            .collect();
+        if !drained {
+            self.logger.log(
+                LogLevel::Warning,
+                &format!(
+                    "Drain timeout reached, exiting with {} connection(s) still open",
+                    tracker.active()
+                ),
+            );
+        }
+        if !abandoned.is_empty() {
+            self.logger.log(
+                LogLevel::Warning,
+                &format!(
+                    "Drain timeout reached, abandoning {} background job(s): {}",
+                    abandoned.len(),
+                    abandoned.join("; ")
+                ),
+            );
+        }
+
+        let mut phases = vec![stopped, connection_phase, job_phase];
+        if let Some(metering) = &self.metering {
+            let flushing = Instant::now();
+            if let Err(e) = metering.flush().await {
+                self.logger
+                    .log(LogLevel::Error, &format!("Failed to flush usage: {}", e));
+            }
+            phases.push(ShutdownPhase::new("flush usage", flushing.elapsed(), false));
+        }
+        #[cfg(feature = "otel")]
+        if let Some(telemetry) = crate::telemetry::Telemetry::get() {
+            let flushing = Instant::now();
+            telemetry.flush().await;
+            phases.push(ShutdownPhase::new(
+                "flush telemetry",
+                flushing.elapsed(),
+                false,
+            ));
+        }
+
+        let report = ShutdownReport {
+            reason: reason.to_string(),
+            connections,
+            connections_drained: connections.saturating_sub(tracker.active()),
+            aborted_requests,
+            jobs,
+            jobs_completed: jobs.saturating_sub(abandoned.len()),
+            jobs_abandoned: abandoned,
+            phases,
+            total_ms: started.elapsed().as_millis() as u64,
+        };
+        self.log_shutdown_report(&report);
+        self.shutdown_report = Some(report);
+        Ok(())
+    }
+
+    /// Starts exporting traces and metrics as the `OTEL_*` variables describe, returning how
+    /// for the startup summary.
+    #[cfg(feature = "otel")]
+    fn install_telemetry(&self) -> io::Result<String> {
+        use crate::telemetry::{Telemetry, TelemetryConfig};
+
+        let config = match TelemetryConfig::from_env() {
+            Ok(Some(config)) => config,
+            Ok(None) => return Ok("disabled".to_string()),
+            Err(e) => {
+                self.logger.log(LogLevel::Error, &e.to_string());
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, e.to_string()));
+            }
+        };
+        let summary = format!(
+            "{} (traces: {}, metrics: {})",
+            config.service_name(),
+            config
+                .traces_endpoint
+                .as_ref()
+                .map_or("off".to_string(), ToString::to_string),
+            config
+                .metrics_endpoint
+                .as_ref()
+                .map_or("off".to_string(), ToString::to_string),
+        );
+        Telemetry::install(config);
+        Ok(summary)
+    }
+
+    /// Printed on every graceful shutdown, production included, alongside the startup summary.
+    fn log_shutdown_report(&self, report: &ShutdownReport) {
+        self.logger.log(
+            LogLevel::Application,
+            &format!("oxide stopped ({}) in {}ms", report.reason, report.total_ms),
+        );
+        let mut lines = vec![
+            (
+                "connections",
+                format!(
+                    "{} of {} drained",
+                    report.connections_drained, report.connections
+                ),
+            ),
+            (
+                "jobs",
+                format!("{} of {} completed", report.jobs_completed, report.jobs),
+            ),
+        ];
+        if !report.aborted_requests.is_empty() {
+            lines.push(("aborted", report.aborted_requests.join("; ")));
+        }
+        if !report.jobs_abandoned.is_empty() {
+            lines.push(("abandoned", report.jobs_abandoned.join("; ")));
+        }
+        for phase in &report.phases {
+            lines.push((
+                phase.name,
+                format!(
+                    "{}ms{}",
+                    phase.duration_ms,
+                    if phase.timed_out { " (timed out)" } else { "" }
+                ),
+            ));
+        }
+        for (key, value) in lines {
+            self.logger
+                .log(LogLevel::Application, &format!("  {:<18}{}", key, value));
+        }
+    }
+
+    /// Starts a new copy of this binary on the same listening socket (triggered by `SIGUSR2`).
+    /// Returns `true` once the successor is up and this process should stop accepting.
+    async fn hand_off(&self, listener: &TcpListener) -> bool {
+        self.logger
+            .log(LogLevel::Info, "SIGUSR2 received, starting a new process");
+        let mut child = match handoff::spawn_successor(listener) {
+            Ok(child) => child,
+            Err(e) => {
+                self.logger.log(
+                    LogLevel::Error,
+                    &format!("Failed to start new process: {}", e),
+                );
+                return false;
+            }
+        };
+
+        // Both processes accept from the shared socket meanwhile, so nothing is dropped.
+        tokio::time::sleep(HANDOFF_GRACE).await;
+        match child.try_wait() {
+            Ok(None) => {
+                self.logger.log(
+                    LogLevel::Info,
+                    &format!("New process {} is serving, handing off", child.id()),
+                );
+                true
+            }
+            Ok(Some(status)) => {
+                self.logger.log(
+                    LogLevel::Error,
+                    &format!("New process exited early ({}), still serving", status),
+                );
+                false
+            }
+            Err(e) => {
+                self.logger.log(
+                    LogLevel::Error,
+                    &format!("Could not check new process: {}, still serving", e),
+                );
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use std::{future::Future, pin::Pin};
+
+use crate::{Error, PgDatabase};
+
+use super::Server;
+
+pub type PluginFuture<'a> = Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+
+/// A CLI subcommand contributed by a plugin, run instead of the server when the binary's
+/// first argument is `name` (e.g. `./app create-admin alice`). `run` receives the
+/// remaining arguments.
+#[derive(Debug, Clone, Copy)]
+pub struct PluginCommand {
+    pub name: &'static str,
+    pub about: &'static str,
+    pub run: fn(&[String]) -> Result<(), Error>,
+}
+
+/// Standard extension point for third-party integrations such as auth providers or admin
+/// panels, installed with [`Server::plugin`]. Every hook has a no-op default.
+pub trait OxidePlugin: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Registers routes, middleware, state and static files, typically by mounting an
+    /// [`App`](super::App).
+    fn register(&self, _server: &mut Server) {}
+
+    /// Runs once the server is configured and before it accepts connections. An error
+    /// aborts startup.
+    fn on_startup<'a>(&'a self, _datasource: Option<&'a PgDatabase>) -> PluginFuture<'a> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn commands(&self) -> Vec<PluginCommand> {
+        vec![]
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use serde::Serialize;
+use tokio::sync::Notify;
+
+/// The server's shutdown signal, shared with background workers so they stop with it.
+///
+/// Workers wait on [`triggered`](Self::triggered) to stop pulling new work, and hold a
+/// [`JobGuard`] from [`job`](Self::job) while handling an item. On shutdown the server waits
+/// up to its drain timeout for every guard to drop, then logs the jobs it abandoned.
+#[derive(Debug, Clone, Default)]
+pub struct Shutdown {
+    inner: Arc<ShutdownInner>,
+}
+
+#[derive(Debug, Default)]
+struct ShutdownInner {
+    triggered: AtomicBool,
+    trigger: Notify,
+    next_job: AtomicU64,
+    jobs: Mutex<BTreeMap<u64, String>>,
+    idle: Notify,
+}
+
+/// What a graceful shutdown drained and what it gave up on, logged when the server stops and
+/// kept in [`Server::shutdown_report`](crate::Server::shutdown_report), for tuning the drain
+/// timeout against real traffic.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShutdownReport {
+    /// What started the shutdown, e.g. `SIGTERM` or `hand-off`.
+    pub reason: String,
+    /// Connections open when the server stopped accepting.
+    pub connections: usize,
+    pub connections_drained: usize,
+    /// Requests still being served at the drain timeout, as method and path.
+    pub aborted_requests: Vec<String>,
+    /// Background jobs in flight when the server stopped accepting.
+    pub jobs: usize,
+    pub jobs_completed: usize,
+    pub jobs_abandoned: Vec<String>,
+    pub phases: Vec<ShutdownPhase>,
+    pub total_ms: u64,
+}
+
+/// One step of a shutdown. Connections and jobs drain concurrently, so their phases overlap.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShutdownPhase {
+    pub name: &'static str,
+    pub duration_ms: u64,
+    /// Whether the phase was cut short by the drain timeout.
+    pub timed_out: bool,
+}
+
+impl ShutdownPhase {
+    pub fn new(name: &'static str, duration: Duration, timed_out: bool) -> Self {
+        Self {
+            name,
+            duration_ms: duration.as_millis() as u64,
+            timed_out,
+        }
+    }
+}
+
+/// An in-flight job, finished when dropped.
+#[derive(Debug)]
+pub struct JobGuard {
+    id: u64,
+    inner: Arc<ShutdownInner>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts shutting down; called by the server on `SIGTERM`, `SIGINT` or a hand-off.
+    pub fn trigger(&self) {
+        self.inner.triggered.store(true, Ordering::SeqCst);
+        self.inner.trigger.notify_waiters();
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.inner.triggered.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once shutdown has started.
+    pub async fn triggered(&self) {
+        loop {
+            let trigger = self.inner.trigger.notified();
+            if self.is_triggered() {
+                return;
+            }
+            trigger.await;
+        }
+    }
+
+    /// Registers an in-flight job, described in the log if shutdown has to abandon it.
+    pub fn job(&self, description: impl Into<String>) -> JobGuard {
+        let id = self.inner.next_job.fetch_add(1, Ordering::SeqCst);
+        if let Ok(mut jobs) = self.inner.jobs.lock() {
+            jobs.insert(id, description.into());
+        }
+        JobGuard {
+            id,
+            inner: Arc::clone(&self.inner),
+        }
+    }
+
+    /// Descriptions of the jobs still in flight, oldest first.
+    pub fn jobs(&self) -> Vec<String> {
+        self.inner
+            .jobs
+            .lock()
+            .map(|jobs| jobs.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Waits until no jobs are in flight. Returns the ones still running if `timeout` elapsed
+    /// first.
+    pub async fn drain(&self, timeout: Duration) -> Vec<String> {
+        let drained = tokio::time::timeout(timeout, async {
+            loop {
+                let idle = self.inner.idle.notified();
+                if self.jobs().is_empty() {
+                    return;
+                }
+                idle.await;
+            }
+        })
+        .await;
+        match drained {
+            Ok(()) => vec![],
+            Err(_) => self.jobs(),
+        }
+    }
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        if let Ok(mut jobs) = self.inner.jobs.lock() {
+            jobs.remove(&self.id);
+            if jobs.is_empty() {
+                self.inner.idle.notify_waiters();
+            }
+        }
+    }
+}
@@ -0,0 +1,358 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Write},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::{logger::LogLevel, Logger};
+
+/// Slots the rolling window is divided into; each covers `window / SLOTS`.
+const SLOTS: usize = 60;
+
+const PPM: f64 = 1_000_000.0;
+
+/// A latency objective for a route: the share of requests that must complete within a
+/// threshold. `Slo::p99(Duration::from_millis(200))` reads "p99 < 200ms".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Slo {
+    threshold: Duration,
+    /// Parts per million, so routes can stay `Eq`.
+    objective: u32,
+}
+
+impl Slo {
+    /// `objective` of requests, e.g. `0.999`, must complete within `threshold`. Objectives
+    /// are clamped below 1, since a 100% target leaves no error budget to burn.
+    pub fn latency(threshold: Duration, objective: f64) -> Self {
+        Self {
+            threshold,
+            objective: (objective * PPM).round().clamp(0.0, PPM - 1.0) as u32,
+        }
+    }
+
+    pub fn p99(threshold: Duration) -> Self {
+        Self::latency(threshold, 0.99)
+    }
+
+    pub fn p95(threshold: Duration) -> Self {
+        Self::latency(threshold, 0.95)
+    }
+
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+
+    pub fn objective(&self) -> f64 {
+        self.objective as f64 / PPM
+    }
+
+    /// The share of requests allowed to be slow.
+    pub fn error_budget(&self) -> f64 {
+        1.0 - self.objective()
+    }
+}
+
+/// Sent to alert hooks when a route starts burning its error budget too fast, and again
+/// once it recovers.
+#[derive(Debug, Clone, Serialize)]
+pub struct SloAlert {
+    pub route: String,
+    pub threshold_ms: u64,
+    pub objective: f64,
+    /// Requests in the window.
+    pub requests: u64,
+    /// Share of those requests within the threshold.
+    pub compliance: f64,
+    /// How many times faster than sustainable the error budget is being spent.
+    pub burn_rate: f64,
+    /// `true` when the alert starts, `false` when it resolves.
+    pub firing: bool,
+}
+
+pub type SloAlertFn = Arc<dyn Fn(&SloAlert) + Send + Sync>;
+
+/// Point-in-time view of one route's objective over the window, suitable for metrics
+/// endpoints.
+#[derive(Debug, Clone, Serialize)]
+pub struct SloStats {
+    pub route: String,
+    pub threshold_ms: u64,
+    pub objective: f64,
+    pub requests: u64,
+    pub good: u64,
+    pub compliance: f64,
+    pub burn_rate: f64,
+    pub alerting: bool,
+}
+
+/// Tracks routes registered with `.slo(...)` over a rolling window and alerts when one
+/// spends its error budget faster than `burn_rate_threshold` times the sustainable rate.
+/// The defaults, a one hour window and a threshold of 14.4, alert when an hour uses 2% of
+/// a 30 day budget.
+///
+/// Alerts are logged as warnings and passed to every hook registered with `on_alert`,
+/// once when they start and once when they resolve. Compliance is evaluated as requests
+/// arrive, so a route that stops receiving traffic keeps its last state.
+///
+/// ```rust,ignore
+/// server.with_slo_tracker(SloTracker::new().on_alert(|alert| {
+///     if alert.firing {
+///         pager.notify(&format!("{} is burning its latency budget at {:.1}x", alert.route, alert.burn_rate));
+///     }
+/// }));
+/// server.router.get("/search", search_handler).slo(Slo::p99(Duration::from_millis(200)));
+/// ```
+pub struct SloTracker {
+    window: Duration,
+    burn_rate_threshold: f64,
+    min_requests: u64,
+    hooks: Vec<SloAlertFn>,
+    routes: Mutex<HashMap<String, Budget>>,
+    started: Instant,
+}
+
+#[derive(Debug)]
+struct Budget {
+    slo: Slo,
+    slots: [Slot; SLOTS],
+    firing: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Slot {
+    tick: u64,
+    requests: u64,
+    good: u64,
+}
+
+struct Totals {
+    requests: u64,
+    good: u64,
+    compliance: f64,
+    burn_rate: f64,
+}
+
+impl Default for SloTracker {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(3600),
+            burn_rate_threshold: 14.4,
+            min_requests: 100,
+            hooks: vec![],
+            routes: Mutex::new(HashMap::new()),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl fmt::Debug for SloTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SloTracker")
+            .field("window", &self.window)
+            .field("burn_rate_threshold", &self.burn_rate_threshold)
+            .field("min_requests", &self.min_requests)
+            .field("hooks", &self.hooks.len())
+            .finish()
+    }
+}
+
+impl SloTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How far back compliance is measured.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window.max(Duration::from_millis(SLOTS as u64));
+        self
+    }
+
+    /// The burn rate at which an alert fires: 1 spends the budget exactly over the SLO
+    /// period, higher values spend it faster.
+    pub fn with_burn_rate_threshold(mut self, threshold: f64) -> Self {
+        self.burn_rate_threshold = threshold;
+        self
+    }
+
+    /// Requests a route needs in the window before it can alert, so a handful of slow
+    /// requests on a quiet route don't page anyone.
+    pub fn with_min_requests(mut self, min_requests: u64) -> Self {
+        self.min_requests = min_requests;
+        self
+    }
+
+    /// Calls `hook` with every alert, e.g. to post it to a webhook. Hooks run on the
+    /// request's task, so anything slow should be spawned.
+    pub fn on_alert(mut self, hook: impl Fn(&SloAlert) + Send + Sync + 'static) -> Self {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    pub fn burn_rate_threshold(&self) -> f64 {
+        self.burn_rate_threshold
+    }
+
+    /// Records a request to `route` that took `latency`, firing or resolving its alert if
+    /// the burn rate crosses the threshold.
+    pub fn observe(&self, route: &str, slo: Slo, latency: Duration) {
+        let alert = {
+            let Ok(mut routes) = self.routes.lock() else {
+                return;
+            };
+            // Read under the lock, so no slot is ever newer than the tick totals are taken at.
+            let tick = self.tick();
+            let budget = routes.entry(route.to_string()).or_insert_with(|| Budget {
+                slo,
+                slots: [Slot::default(); SLOTS],
+                firing: false,
+            });
+            budget.slo = slo;
+            let slot = &mut budget.slots[tick as usize % SLOTS];
+            if slot.tick != tick {
+                *slot = Slot {
+                    tick,
+                    ..Slot::default()
+                };
+            }
+            slot.requests += 1;
+            slot.good += (latency <= slo.threshold) as u64;
+
+            let totals = budget.totals(tick);
+            let burning = totals.requests >= self.min_requests
+                && totals.burn_rate >= self.burn_rate_threshold;
+            if burning == budget.firing {
+                None
+            } else {
+                budget.firing = burning;
+                Some(SloAlert {
+                    route: route.to_string(),
+                    threshold_ms: slo.threshold.as_millis() as u64,
+                    objective: slo.objective(),
+                    requests: totals.requests,
+                    compliance: totals.compliance,
+                    burn_rate: totals.burn_rate,
+                    firing: burning,
+                })
+            }
+        };
+        if let Some(alert) = alert {
+            self.alert(&alert);
+        }
+    }
+
+    /// Every route observed so far, highest burn rate first.
+    pub fn stats(&self) -> Vec<SloStats> {
+        let Ok(routes) = self.routes.lock() else {
+            return vec![];
+        };
+        let tick = self.tick();
+        let mut stats = routes
+            .iter()
+            .map(|(route, budget)| {
+                let totals = budget.totals(tick);
+                SloStats {
+                    route: route.clone(),
+                    threshold_ms: budget.slo.threshold.as_millis() as u64,
+                    objective: budget.slo.objective(),
+                    requests: totals.requests,
+                    good: totals.good,
+                    compliance: totals.compliance,
+                    burn_rate: totals.burn_rate,
+                    alerting: budget.firing,
+                }
+            })
+            .collect::<Vec<_>>();
+        stats.sort_by(|a, b| {
+            b.burn_rate
+                .total_cmp(&a.burn_rate)
+                .then_with(|| a.route.cmp(&b.route))
+        });
+        stats
+    }
+
+    /// Compliance and burn rate in the Prometheus text format, as `oxide_slo_compliance`
+    /// and `oxide_slo_burn_rate`.
+    pub fn prometheus(&self) -> String {
+        let stats = self.stats();
+        let mut compliance = String::from(
+            "# HELP oxide_slo_compliance Share of requests in the window within the latency threshold.\n\
+             # TYPE oxide_slo_compliance gauge\n",
+        );
+        let mut burn_rate = String::from(
+            "# HELP oxide_slo_burn_rate Rate the latency error budget is spent, 1 being sustainable.\n\
+             # TYPE oxide_slo_burn_rate gauge\n",
+        );
+        for stats in stats {
+            let route = stats.route.replace('\\', "\\\\").replace('"', "\\\"");
+            let _ = writeln!(
+                compliance,
+                "oxide_slo_compliance{{route=\"{}\"}} {}",
+                route, stats.compliance
+            );
+            let _ = writeln!(
+                burn_rate,
+                "oxide_slo_burn_rate{{route=\"{}\"}} {}",
+                route, stats.burn_rate
+            );
+        }
+        compliance + &burn_rate
+    }
+
+    fn tick(&self) -> u64 {
+        let slot = (self.window.as_millis() / SLOTS as u128).max(1);
+        (self.started.elapsed().as_millis() / slot) as u64
+    }
+
+    fn alert(&self, alert: &SloAlert) {
+        let message = if alert.firing {
+            format!(
+                "SLO alert for {}: {:.2}% of {} requests within {}ms (objective {}%), burning error budget at {:.1}x",
+                alert.route,
+                alert.compliance * 100.0,
+                alert.requests,
+                alert.threshold_ms,
+                alert.objective * 100.0,
+                alert.burn_rate
+            )
+        } else {
+            format!(
+                "SLO alert for {} resolved: burn rate down to {:.1}x",
+                alert.route, alert.burn_rate
+            )
+        };
+        Logger::for_target(module_path!()).log(LogLevel::Warning, &message);
+        for hook in &self.hooks {
+            hook(alert);
+        }
+    }
+}
+
+impl Budget {
+    fn totals(&self, tick: u64) -> Totals {
+        let (requests, good) = self
+            .slots
+            .iter()
+            .filter(|slot| tick.saturating_sub(slot.tick) < SLOTS as u64)
+            .fold((0, 0), |(requests, good), slot| {
+                (requests + slot.requests, good + slot.good)
+            });
+        let compliance = if requests == 0 {
+            1.0
+        } else {
+            good as f64 / requests as f64
+        };
+        Totals {
+            requests,
+            good,
+            compliance,
+            burn_rate: (1.0 - compliance) / self.slo.error_budget(),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use super::AttributeValue;
+
+/// Bucket boundaries in seconds for duration histograms, as recommended by the OpenTelemetry
+/// semantic conventions for HTTP.
+pub const DURATION_BUCKETS: [f64; 14] = [
+    0.005, 0.01, 0.025, 0.05, 0.075, 0.1, 0.25, 0.5, 0.75, 1.0, 2.5, 5.0, 7.5, 10.0,
+];
+
+pub const HTTP_SERVER_DURATION: &str = "http.server.request.duration";
+pub const DB_CLIENT_DURATION: &str = "db.client.operation.duration";
+pub const MESSAGING_PROCESS_DURATION: &str = "messaging.process.duration";
+
+pub(crate) fn description(name: &str) -> &'static str {
+    match name {
+        HTTP_SERVER_DURATION => "Duration of HTTP server requests.",
+        DB_CLIENT_DURATION => "Duration of database client operations.",
+        MESSAGING_PROCESS_DURATION => "Duration of processing operations.",
+        _ => "",
+    }
+}
+
+/// Cumulative duration histograms keyed by instrument and attributes.
+#[derive(Debug, Default)]
+pub(crate) struct Metrics {
+    series: Mutex<HashMap<(&'static str, String), Series>>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Series {
+    pub name: &'static str,
+    pub attributes: Vec<(&'static str, AttributeValue)>,
+    pub counts: [u64; DURATION_BUCKETS.len() + 1],
+    pub count: u64,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Metrics {
+    pub fn record(
+        &self,
+        name: &'static str,
+        attributes: Vec<(&'static str, AttributeValue)>,
+        seconds: f64,
+    ) {
+        let key = (name, format!("{:?}", attributes));
+        let Ok(mut series) = self.series.lock() else {
+            return;
+        };
+        let series = series.entry(key).or_insert_with(|| Series {
+            name,
+            attributes,
+            counts: [0; DURATION_BUCKETS.len() + 1],
+            count: 0,
+            sum: 0.0,
+            min: f64::MAX,
+            max: f64::MIN,
+        });
+        let bucket = DURATION_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(DURATION_BUCKETS.len());
+        series.counts[bucket] += 1;
+        series.count += 1;
+        series.sum += seconds;
+        series.min = series.min.min(seconds);
+        series.max = series.max.max(seconds);
+    }
+
+    /// Every series recorded so far, grouped by instrument.
+    pub fn collect(&self) -> Vec<Series> {
+        let mut series: Vec<Series> = self
+            .series
+            .lock()
+            .map(|series| series.values().cloned().collect())
+            .unwrap_or_default();
+        series.sort_by_key(|series| series.name);
+        series
+    }
+}
@@ -0,0 +1,489 @@
+//! OpenTelemetry export of traces and metrics over OTLP/HTTP with JSON encoding, behind the
+//! `otel` feature.
+//!
+//! The server installs it on startup from the standard `OTEL_*` environment variables (see
+//! [`TelemetryConfig::from_env`]) and then records:
+//!
+//! - a server span and an `http.server.request.duration` measurement per routed request,
+//!   continuing the caller's trace when it sends a `traceparent` header;
+//! - a client span and a `db.client.operation.duration` measurement per query, the span only
+//!   inside a traced request or consumer so background queries don't start traces of their
+//!   own;
+//! - a consumer span and a `messaging.process.duration` measurement per message handling
+//!   attempt. Messages published from a traced handler carry its `traceparent`, so the
+//!   consumer's span joins the request's trace.
+//!
+//! Spans are batched and sent every `OTEL_BSP_SCHEDULE_DELAY`, metrics every
+//! `OTEL_METRIC_EXPORT_INTERVAL`, and both are flushed on graceful shutdown. Application
+//! code can add its own spans with [`Span`].
+mod metrics;
+mod otlp;
+mod trace;
+
+use std::{
+    env,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+use once_cell::sync::OnceCell;
+
+use crate::{http::HttpRequest, logger::LogLevel, messaging::Message, Error, Logger};
+
+pub use metrics::{
+    DB_CLIENT_DURATION, DURATION_BUCKETS, HTTP_SERVER_DURATION, MESSAGING_PROCESS_DURATION,
+};
+pub use otlp::Endpoint;
+pub use trace::{AttributeValue, Sampler, Span, SpanContext, SpanKind, TRACEPARENT};
+
+use metrics::Metrics;
+use trace::{unix_nanos, SpanData};
+
+static TELEMETRY: OnceCell<Telemetry> = OnceCell::new();
+
+/// Exporter settings, read from the standard OpenTelemetry environment variables.
+#[derive(Debug, Clone)]
+pub struct TelemetryConfig {
+    /// `service.name` and the rest of the resource attributes attached to everything sent.
+    pub resource: Vec<(String, String)>,
+    /// `None` when `OTEL_TRACES_EXPORTER=none`.
+    pub traces_endpoint: Option<Endpoint>,
+    /// `None` when `OTEL_METRICS_EXPORTER=none`.
+    pub metrics_endpoint: Option<Endpoint>,
+    pub headers: Vec<(String, String)>,
+    pub timeout: Duration,
+    pub sampler: Sampler,
+    pub schedule_delay: Duration,
+    pub max_queue_size: usize,
+    pub max_export_batch_size: usize,
+    pub metric_export_interval: Duration,
+}
+
+impl TelemetryConfig {
+    /// Reads the `OTEL_*` variables, returning `None` when `OTEL_SDK_DISABLED=true`, or with a
+    /// warning when `OTEL_EXPORTER_OTLP_PROTOCOL` names a protocol other than `http/json`.
+    ///
+    /// | Variable | Default |
+    /// |---|---|
+    /// | `OTEL_SERVICE_NAME`, `OTEL_RESOURCE_ATTRIBUTES` | `unknown_service:<binary>` |
+    /// | `OTEL_EXPORTER_OTLP_ENDPOINT` | `http://localhost:4318` |
+    /// | `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`, `OTEL_EXPORTER_OTLP_METRICS_ENDPOINT` | base + `/v1/traces`, `/v1/metrics` |
+    /// | `OTEL_EXPORTER_OTLP_HEADERS` | none |
+    /// | `OTEL_EXPORTER_OTLP_TIMEOUT` | 10000 ms |
+    /// | `OTEL_EXPORTER_OTLP_PROTOCOL` | `http/json`, the only one supported; others disable export |
+    /// | `OTEL_TRACES_EXPORTER`, `OTEL_METRICS_EXPORTER` | `otlp`; `none` turns one off |
+    /// | `OTEL_TRACES_SAMPLER`, `OTEL_TRACES_SAMPLER_ARG` | `parentbased_always_on` |
+    /// | `OTEL_BSP_SCHEDULE_DELAY` | 5000 ms |
+    /// | `OTEL_BSP_MAX_QUEUE_SIZE`, `OTEL_BSP_MAX_EXPORT_BATCH_SIZE` | 2048, 512 |
+    /// | `OTEL_METRIC_EXPORT_INTERVAL` | 60000 ms |
+    pub fn from_env() -> Result<Option<Self>, Error> {
+        if var("OTEL_SDK_DISABLED").is_some_and(|disabled| disabled.eq_ignore_ascii_case("true")) {
+            return Ok(None);
+        }
+        if let Some(protocol) = var("OTEL_EXPORTER_OTLP_PROTOCOL").filter(|p| p != "http/json") {
+            Logger::for_target(module_path!()).log(
+                LogLevel::Warning,
+                &format!(
+                    "OTEL_EXPORTER_OTLP_PROTOCOL={} is not supported, telemetry export is \
+                     disabled; use http/json",
+                    protocol
+                ),
+            );
+            return Ok(None);
+        }
+
+        let base = var("OTEL_EXPORTER_OTLP_ENDPOINT")
+            .unwrap_or_else(|| "http://localhost:4318".to_string());
+        let endpoint = |exporter: &str, specific: &str, path: &str| {
+            if var(exporter).is_some_and(|exporter| exporter == "none") {
+                return Ok(None);
+            }
+            let url =
+                var(specific).unwrap_or_else(|| format!("{}{}", base.trim_end_matches('/'), path));
+            Endpoint::parse(&url).map(Some).map_err(Error::Config)
+        };
+
+        let mut resource = pairs(&var("OTEL_RESOURCE_ATTRIBUTES").unwrap_or_default());
+        let service_name = var("OTEL_SERVICE_NAME")
+            .or_else(|| {
+                resource
+                    .iter()
+                    .find(|(key, _)| key == "service.name")
+                    .map(|(_, value)| value.clone())
+            })
+            .unwrap_or_else(|| {
+                let binary = env::current_exe()
+                    .ok()
+                    .and_then(|exe| exe.file_name().map(|n| n.to_string_lossy().into_owned()))
+                    .unwrap_or_default();
+                format!("unknown_service:{}", binary)
+            });
+        resource.retain(|(key, _)| key != "service.name");
+        resource.insert(0, ("service.name".to_string(), service_name));
+        resource.push(("telemetry.sdk.name".to_string(), "oxide".to_string()));
+        resource.push(("telemetry.sdk.language".to_string(), "rust".to_string()));
+        resource.push((
+            "telemetry.sdk.version".to_string(),
+            env!("CARGO_PKG_VERSION").to_string(),
+        ));
+
+        Ok(Some(Self {
+            resource,
+            traces_endpoint: endpoint(
+                "OTEL_TRACES_EXPORTER",
+                "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+                "/v1/traces",
+            )?,
+            metrics_endpoint: endpoint(
+                "OTEL_METRICS_EXPORTER",
+                "OTEL_EXPORTER_OTLP_METRICS_ENDPOINT",
+                "/v1/metrics",
+            )?,
+            headers: pairs(&var("OTEL_EXPORTER_OTLP_HEADERS").unwrap_or_default()),
+            timeout: millis("OTEL_EXPORTER_OTLP_TIMEOUT", 10_000),
+            sampler: Sampler::parse(
+                &var("OTEL_TRACES_SAMPLER").unwrap_or_default(),
+                var("OTEL_TRACES_SAMPLER_ARG").as_deref(),
+            ),
+            schedule_delay: millis("OTEL_BSP_SCHEDULE_DELAY", 5_000),
+            max_queue_size: number("OTEL_BSP_MAX_QUEUE_SIZE", 2048),
+            max_export_batch_size: number("OTEL_BSP_MAX_EXPORT_BATCH_SIZE", 512).max(1),
+            metric_export_interval: millis("OTEL_METRIC_EXPORT_INTERVAL", 60_000),
+        }))
+    }
+
+    /// The service name, for the startup summary.
+    pub fn service_name(&self) -> &str {
+        self.resource
+            .iter()
+            .find(|(key, _)| key == "service.name")
+            .map(|(_, value)| value.as_str())
+            .unwrap_or_default()
+    }
+}
+
+/// The process-wide exporter, installed once with [`Telemetry::install`].
+#[derive(Debug)]
+pub struct Telemetry {
+    config: TelemetryConfig,
+    started: SystemTime,
+    spans: Mutex<Vec<SpanData>>,
+    dropped_spans: AtomicU64,
+    metrics: Metrics,
+    logger: Logger,
+}
+
+impl Telemetry {
+    /// Installs the exporter and starts sending in the background. Returns `false` if one was
+    /// already installed, which keeps the first.
+    pub fn install(config: TelemetryConfig) -> bool {
+        let telemetry = Telemetry {
+            config,
+            started: SystemTime::now(),
+            spans: Mutex::new(vec![]),
+            dropped_spans: AtomicU64::new(0),
+            metrics: Metrics::default(),
+            logger: Logger::for_target(module_path!()),
+        };
+        if TELEMETRY.set(telemetry).is_err() {
+            return false;
+        }
+        let Some(telemetry) = Self::get() else {
+            return false;
+        };
+        if telemetry.config.traces_endpoint.is_some() {
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(telemetry.config.schedule_delay).await;
+                    telemetry.export_spans().await;
+                }
+            });
+        }
+        if telemetry.config.metrics_endpoint.is_some() {
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(telemetry.config.metric_export_interval).await;
+                    telemetry.export_metrics().await;
+                }
+            });
+        }
+        true
+    }
+
+    pub fn get() -> Option<&'static Telemetry> {
+        TELEMETRY.get()
+    }
+
+    pub fn config(&self) -> &TelemetryConfig {
+        &self.config
+    }
+
+    /// Spans dropped because the queue was full, which suggests exporting more often.
+    pub fn dropped_spans(&self) -> u64 {
+        self.dropped_spans.load(Ordering::Relaxed)
+    }
+
+    /// Sends everything recorded so far, e.g. before exiting.
+    pub async fn flush(&self) {
+        self.export_spans().await;
+        self.export_metrics().await;
+    }
+
+    pub(crate) fn sampler(&self) -> &Sampler {
+        &self.config.sampler
+    }
+
+    pub(crate) fn record_span(&self, span: SpanData) {
+        if self.config.traces_endpoint.is_none() {
+            return;
+        }
+        let Ok(mut spans) = self.spans.lock() else {
+            return;
+        };
+        if spans.len() >= self.config.max_queue_size {
+            self.dropped_spans.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        spans.push(span);
+    }
+
+    pub(crate) fn record_duration(
+        &self,
+        name: &'static str,
+        attributes: Vec<(&'static str, AttributeValue)>,
+        duration: Duration,
+    ) {
+        if self.config.metrics_endpoint.is_some() {
+            self.metrics
+                .record(name, attributes, duration.as_secs_f64());
+        }
+    }
+
+    async fn export_spans(&self) {
+        let Some(endpoint) = &self.config.traces_endpoint else {
+            return;
+        };
+        loop {
+            let batch: Vec<SpanData> = match self.spans.lock() {
+                Ok(mut spans) => {
+                    let take = spans.len().min(self.config.max_export_batch_size);
+                    spans.drain(..take).collect()
+                }
+                Err(_) => return,
+            };
+            if batch.is_empty() {
+                return;
+            }
+            let body = otlp::traces(&self.config.resource, &batch);
+            if let Err(e) =
+                otlp::export(endpoint, &self.config.headers, body, self.config.timeout).await
+            {
+                self.logger.log(
+                    LogLevel::Warning,
+                    &format!(
+                        "Failed to export {} span(s) to {}: {}",
+                        batch.len(),
+                        endpoint,
+                        e
+                    ),
+                );
+                return;
+            }
+        }
+    }
+
+    async fn export_metrics(&self) {
+        let Some(endpoint) = &self.config.metrics_endpoint else {
+            return;
+        };
+        let series = self.metrics.collect();
+        if series.is_empty() {
+            return;
+        }
+        let body = otlp::metrics(
+            &self.config.resource,
+            &series,
+            unix_nanos(self.started),
+            unix_nanos(SystemTime::now()),
+        );
+        if let Err(e) =
+            otlp::export(endpoint, &self.config.headers, body, self.config.timeout).await
+        {
+            self.logger.log(
+                LogLevel::Warning,
+                &format!("Failed to export metrics to {}: {}", endpoint, e),
+            );
+        }
+    }
+}
+
+/// The span and timer for one routed request.
+pub(crate) struct ServerSpan {
+    span: Span,
+    method: String,
+    route: String,
+    start: Instant,
+}
+
+impl ServerSpan {
+    /// Starts the span for `request`, continuing the caller's trace if it sent a
+    /// `traceparent`.
+    pub fn start(request: &HttpRequest, route: &str) -> Self {
+        let parent = request
+            .headers
+            .get(TRACEPARENT)
+            .and_then(|header| SpanContext::from_traceparent(header));
+        let method = request.method.to_string();
+        let mut span = Span::with_parent(format!("{} {}", method, route), SpanKind::Server, parent);
+        span.set_attribute("http.request.method", method.as_str());
+        span.set_attribute("http.route", route);
+        span.set_attribute("url.path", request.path.split('?').next().unwrap_or(""));
+        Self {
+            span,
+            method,
+            route: route.to_string(),
+            start: Instant::now(),
+        }
+    }
+
+    pub fn context(&self) -> SpanContext {
+        self.span.context()
+    }
+
+    /// Ends the span, marking 5xx responses as errors, and records the request's duration.
+    pub fn end(mut self, status: u16) {
+        self.span.set_attribute("http.response.status_code", status);
+        if status >= 500 {
+            self.span.set_error(status.to_string());
+        }
+        self.span.end();
+        if let Some(telemetry) = Telemetry::get() {
+            telemetry.record_duration(
+                HTTP_SERVER_DURATION,
+                vec![
+                    ("http.request.method", self.method.into()),
+                    ("http.route", self.route.into()),
+                    ("http.response.status_code", status.into()),
+                ],
+                self.start.elapsed(),
+            );
+        }
+    }
+}
+
+/// The span and timer for one attempt at handling a message.
+pub(crate) struct ConsumerSpan {
+    span: Span,
+    topic: String,
+    start: Instant,
+}
+
+impl ConsumerSpan {
+    /// Starts the span for `message`, joining the publisher's trace if it carried one.
+    pub fn start(message: &Message) -> Self {
+        let parent = message
+            .metadata
+            .get(TRACEPARENT)
+            .and_then(|header| SpanContext::from_traceparent(header));
+        let mut span = Span::with_parent(
+            format!("process {}", message.topic),
+            SpanKind::Consumer,
+            parent,
+        );
+        span.set_attribute("messaging.operation.type", "process");
+        span.set_attribute("messaging.destination.name", message.topic.as_str());
+        span.set_attribute("messaging.message.delivery_attempt", message.attempt as i64);
+        Self {
+            span,
+            topic: message.topic.clone(),
+            start: Instant::now(),
+        }
+    }
+
+    pub fn context(&self) -> SpanContext {
+        self.span.context()
+    }
+
+    pub fn end(mut self, error: Option<&Error>) {
+        let mut attributes = vec![
+            ("messaging.operation.type", AttributeValue::from("process")),
+            ("messaging.destination.name", self.topic.into()),
+        ];
+        if let Some(error) = error {
+            attributes.push(("error.type", error.error_type().into()));
+            attributes.push(("oxide.error.code", error.code().code.into()));
+            self.span.set_error(error.to_string());
+        }
+        self.span.end();
+        if let Some(telemetry) = Telemetry::get() {
+            telemetry.record_duration(MESSAGING_PROCESS_DURATION, attributes, self.start.elapsed());
+        }
+    }
+}
+
+/// Records a query that took `elapsed`: a span if it ran inside a traced request or consumer,
+/// and a duration measurement either way.
+pub(crate) fn record_query(query: &str, elapsed: Duration, error: Option<String>) {
+    let Some(telemetry) = Telemetry::get() else {
+        return;
+    };
+    let operation = query
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_uppercase();
+    let mut attributes = vec![
+        ("db.system.name", AttributeValue::from("postgresql")),
+        (
+            "db.operation.name",
+            AttributeValue::from(operation.as_str()),
+        ),
+    ];
+    if error.is_some() {
+        attributes.push(("error.type", AttributeValue::from("database")));
+    }
+    telemetry.record_duration(DB_CLIENT_DURATION, attributes.clone(), elapsed);
+
+    if let Some(parent) = SpanContext::current() {
+        let mut span = Span::with_parent(operation, SpanKind::Client, Some(parent))
+            .started_at(SystemTime::now() - elapsed);
+        for (key, value) in attributes {
+            span.set_attribute(key, value);
+        }
+        span.set_attribute("db.query.text", query);
+        if let Some(error) = error {
+            span.set_error(error);
+        }
+        span.end();
+    }
+}
+
+fn var(name: &str) -> Option<String> {
+    env::var(name)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn millis(name: &str, default: u64) -> Duration {
+    Duration::from_millis(number(name, default as usize) as u64)
+}
+
+fn number(name: &str, default: usize) -> usize {
+    var(name)
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Parses `key=value,key=value` lists as `OTEL_RESOURCE_ATTRIBUTES` and
+/// `OTEL_EXPORTER_OTLP_HEADERS` use them.
+fn pairs(list: &str) -> Vec<(String, String)> {
+    list.split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .filter(|(key, _)| !key.is_empty())
+        .collect()
+}
@@ -0,0 +1,210 @@
+use std::{io, time::Duration};
+
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use super::{
+    metrics::{description, Series, DURATION_BUCKETS},
+    trace::{encode_hex, SpanData},
+    AttributeValue,
+};
+
+const SCOPE: &str = "oxide";
+
+/// Where a signal is sent, parsed from an `http://` URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+impl Endpoint {
+    /// Only plain HTTP is supported; put a collector on the host or sidecar to reach TLS
+    /// backends.
+    pub fn parse(url: &str) -> Result<Self, String> {
+        let rest = url.trim().strip_prefix("http://").ok_or_else(|| {
+            format!(
+                "unsupported OTLP endpoint '{}', only http:// is supported",
+                url
+            )
+        })?;
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !authority.ends_with(']') => (
+                host,
+                port.parse::<u16>()
+                    .map_err(|_| format!("invalid port in OTLP endpoint '{}'", url))?,
+            ),
+            _ => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("missing host in OTLP endpoint '{}'", url));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "http://{}:{}{}", self.host, self.port, self.path)
+    }
+}
+
+/// POSTs an OTLP/HTTP JSON body, returning an error unless the collector answers 2xx.
+pub(crate) async fn export(
+    endpoint: &Endpoint,
+    headers: &[(String, String)],
+    body: Vec<u8>,
+    timeout: Duration,
+) -> io::Result<()> {
+    let status = tokio::time::timeout(timeout, post(endpoint, headers, body))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "export timed out"))??;
+    if (200..300).contains(&status) {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("collector answered {}", status)))
+    }
+}
+
+async fn post(endpoint: &Endpoint, headers: &[(String, String)], body: Vec<u8>) -> io::Result<u16> {
+    let host = endpoint.host.trim_start_matches('[').trim_end_matches(']');
+    let mut stream = TcpStream::connect((host, endpoint.port)).await?;
+    let mut request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        endpoint.path,
+        endpoint.host,
+        endpoint.port,
+        body.len()
+    );
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+    stream.write_all(&body).await?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    let line = response.split(|&b| b == b'\n').next().unwrap_or_default();
+    String::from_utf8_lossy(line)
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed response"))
+}
+
+pub(crate) fn traces(resource: &[(String, String)], spans: &[SpanData]) -> Vec<u8> {
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|span| {
+            let mut encoded = json!({
+                "traceId": encode_hex(&span.context.trace_id),
+                "spanId": encode_hex(&span.context.span_id),
+                "name": span.name,
+                "kind": span.kind as u8,
+                "startTimeUnixNano": span.start.to_string(),
+                "endTimeUnixNano": span.end.to_string(),
+                "attributes": attributes(&span.attributes),
+                "status": match &span.error {
+                    Some(message) => json!({ "code": 2, "message": message }),
+                    None => json!({}),
+                },
+            });
+            if let Some(parent) = &span.parent {
+                encoded["parentSpanId"] = json!(encode_hex(parent));
+            }
+            encoded
+        })
+        .collect();
+    json!({
+        "resourceSpans": [{
+            "resource": { "attributes": resource_attributes(resource) },
+            "scopeSpans": [{
+                "scope": { "name": SCOPE, "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans,
+            }],
+        }],
+    })
+    .to_string()
+    .into_bytes()
+}
+
+pub(crate) fn metrics(
+    resource: &[(String, String)],
+    series: &[Series],
+    start: u64,
+    now: u64,
+) -> Vec<u8> {
+    let mut metrics: Vec<Value> = vec![];
+    for series in series {
+        let point = json!({
+            "attributes": attributes(&series.attributes),
+            "startTimeUnixNano": start.to_string(),
+            "timeUnixNano": now.to_string(),
+            "count": series.count.to_string(),
+            "sum": series.sum,
+            "min": series.min,
+            "max": series.max,
+            "bucketCounts": series.counts.iter().map(u64::to_string).collect::<Vec<_>>(),
+            "explicitBounds": DURATION_BUCKETS,
+        });
+        match metrics.last_mut() {
+            Some(metric) if metric["name"] == series.name => {
+                if let Some(points) = metric["histogram"]["dataPoints"].as_array_mut() {
+                    points.push(point);
+                }
+            }
+            _ => metrics.push(json!({
+                "name": series.name,
+                "description": description(series.name),
+                "unit": "s",
+                "histogram": { "aggregationTemporality": 2, "dataPoints": [point] },
+            })),
+        }
+    }
+    json!({
+        "resourceMetrics": [{
+            "resource": { "attributes": resource_attributes(resource) },
+            "scopeMetrics": [{
+                "scope": { "name": SCOPE, "version": env!("CARGO_PKG_VERSION") },
+                "metrics": metrics,
+            }],
+        }],
+    })
+    .to_string()
+    .into_bytes()
+}
+
+fn resource_attributes(resource: &[(String, String)]) -> Vec<Value> {
+    resource
+        .iter()
+        .map(|(key, value)| json!({ "key": key, "value": { "stringValue": value } }))
+        .collect()
+}
+
+fn attributes(attributes: &[(&'static str, AttributeValue)]) -> Vec<Value> {
+    attributes
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                AttributeValue::String(s) => json!({ "stringValue": s }),
+                AttributeValue::Int(i) => json!({ "intValue": i.to_string() }),
+                AttributeValue::Double(d) => json!({ "doubleValue": d }),
+                AttributeValue::Bool(b) => json!({ "boolValue": b }),
+            };
+            json!({ "key": key, "value": value })
+        })
+        .collect()
+}
@@ -0,0 +1,322 @@
+use std::{
+    collections::hash_map::RandomState,
+    fmt::Write,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::Telemetry;
+
+/// Metadata key and header carrying the W3C trace context.
+pub const TRACEPARENT: &str = "traceparent";
+
+tokio::task_local! {
+    static CURRENT_SPAN: SpanContext;
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Identifies a span within a trace, as carried by a `traceparent` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpanContext {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+    pub sampled: bool,
+}
+
+impl SpanContext {
+    /// The span the current task is running in, if any.
+    pub fn current() -> Option<SpanContext> {
+        CURRENT_SPAN.try_with(|context| *context).ok()
+    }
+
+    /// Parses a W3C `traceparent` header, e.g.
+    /// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`.
+    pub fn from_traceparent(header: &str) -> Option<Self> {
+        let mut parts = header.trim().split('-');
+        let version = parts.next().filter(|v| v.len() == 2 && *v != "ff")?;
+        let trace_id = decode_hex::<16>(parts.next()?)?;
+        let span_id = decode_hex::<8>(parts.next()?)?;
+        let flags = decode_hex::<1>(parts.next()?)?[0];
+        if version == "00" && parts.next().is_some() {
+            return None;
+        }
+        if trace_id == [0; 16] || span_id == [0; 8] {
+            return None;
+        }
+        Some(Self {
+            trace_id,
+            span_id,
+            sampled: flags & 1 == 1,
+        })
+    }
+
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{}-{}-{:02x}",
+            encode_hex(&self.trace_id),
+            encode_hex(&self.span_id),
+            self.sampled as u8
+        )
+    }
+
+    /// Runs `future` with this as the current span, so spans started inside it are its
+    /// children.
+    pub async fn run<F: Future>(self, future: F) -> F::Output {
+        CURRENT_SPAN.scope(self, future).await
+    }
+}
+
+/// What a span represents, as OTLP's `SpanKind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanKind {
+    Internal = 1,
+    Server = 2,
+    Client = 3,
+    Producer = 4,
+    Consumer = 5,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    String(String),
+    Int(i64),
+    Double(f64),
+    Bool(bool),
+}
+
+impl From<&str> for AttributeValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for AttributeValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<i64> for AttributeValue {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<u16> for AttributeValue {
+    fn from(value: u16) -> Self {
+        Self::Int(value as i64)
+    }
+}
+
+impl From<f64> for AttributeValue {
+    fn from(value: f64) -> Self {
+        Self::Double(value)
+    }
+}
+
+impl From<bool> for AttributeValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+/// Which traces are recorded, from `OTEL_TRACES_SAMPLER` and `OTEL_TRACES_SAMPLER_ARG`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sampler {
+    AlwaysOn,
+    AlwaysOff,
+    /// Records this share of traces, decided from the trace id so every service agrees.
+    TraceIdRatio(f64),
+    /// Follows the caller's decision when there is one, otherwise the inner sampler's.
+    ParentBased(Box<Sampler>),
+}
+
+impl Sampler {
+    /// Parses a sampler name as the `OTEL_TRACES_SAMPLER` variable spells it, defaulting to
+    /// `parentbased_always_on` for unknown names.
+    pub fn parse(name: &str, arg: Option<&str>) -> Self {
+        let ratio = arg
+            .and_then(|arg| arg.trim().parse::<f64>().ok())
+            .map(|ratio| ratio.clamp(0.0, 1.0))
+            .unwrap_or(1.0);
+        match name.trim() {
+            "always_on" => Self::AlwaysOn,
+            "always_off" => Self::AlwaysOff,
+            "traceidratio" => Self::TraceIdRatio(ratio),
+            "parentbased_always_off" => Self::ParentBased(Box::new(Self::AlwaysOff)),
+            "parentbased_traceidratio" => Self::ParentBased(Box::new(Self::TraceIdRatio(ratio))),
+            _ => Self::ParentBased(Box::new(Self::AlwaysOn)),
+        }
+    }
+
+    pub fn should_sample(&self, parent: Option<&SpanContext>, trace_id: &[u8; 16]) -> bool {
+        match self {
+            Self::AlwaysOn => true,
+            Self::AlwaysOff => false,
+            Self::TraceIdRatio(ratio) => {
+                let mut low = [0; 8];
+                low.copy_from_slice(&trace_id[8..]);
+                (u64::from_be_bytes(low) as f64) < ratio * u64::MAX as f64
+            }
+            Self::ParentBased(root) => match parent {
+                Some(parent) => parent.sampled,
+                None => root.should_sample(None, trace_id),
+            },
+        }
+    }
+}
+
+/// A timed operation, exported when [`end`](Self::end) is called if its trace is sampled.
+///
+/// ```rust,ignore
+/// let mut span = Span::start("charge card", SpanKind::Client);
+/// span.set_attribute("payment.provider", "stripe");
+/// let result = span.context().run(gateway.charge(amount)).await;
+/// if let Err(e) = &result {
+///     span.set_error(e.to_string());
+/// }
+/// span.end();
+/// ```
+#[derive(Debug)]
+pub struct Span {
+    context: SpanContext,
+    parent: Option<[u8; 8]>,
+    name: String,
+    kind: SpanKind,
+    start: SystemTime,
+    attributes: Vec<(&'static str, AttributeValue)>,
+    error: Option<String>,
+}
+
+/// A finished span, waiting to be exported.
+#[derive(Debug, Clone)]
+pub(crate) struct SpanData {
+    pub context: SpanContext,
+    pub parent: Option<[u8; 8]>,
+    pub name: String,
+    pub kind: SpanKind,
+    pub start: u64,
+    pub end: u64,
+    pub attributes: Vec<(&'static str, AttributeValue)>,
+    pub error: Option<String>,
+}
+
+impl Span {
+    /// Starts a span as a child of the current one, or a new trace if there is none.
+    pub fn start(name: impl Into<String>, kind: SpanKind) -> Self {
+        Self::with_parent(name, kind, SpanContext::current())
+    }
+
+    /// Starts a span under `parent`, e.g. one taken from an incoming `traceparent`.
+    pub fn with_parent(
+        name: impl Into<String>,
+        kind: SpanKind,
+        parent: Option<SpanContext>,
+    ) -> Self {
+        let trace_id = match &parent {
+            Some(parent) => parent.trace_id,
+            None => {
+                let mut id = [0; 16];
+                id[..8].copy_from_slice(&random_id());
+                id[8..].copy_from_slice(&random_id());
+                id
+            }
+        };
+        let sampled = Telemetry::get().is_some_and(|telemetry| {
+            telemetry
+                .sampler()
+                .should_sample(parent.as_ref(), &trace_id)
+        });
+        Self {
+            context: SpanContext {
+                trace_id,
+                span_id: random_id(),
+                sampled,
+            },
+            parent: parent.map(|parent| parent.span_id),
+            name: name.into(),
+            kind,
+            start: SystemTime::now(),
+            attributes: vec![],
+            error: None,
+        }
+    }
+
+    pub fn context(&self) -> SpanContext {
+        self.context
+    }
+
+    /// Backdates the span, for operations timed before deciding to record them.
+    pub fn started_at(mut self, start: SystemTime) -> Self {
+        self.start = start;
+        self
+    }
+
+    pub fn set_attribute(&mut self, key: &'static str, value: impl Into<AttributeValue>) {
+        self.attributes.push((key, value.into()));
+    }
+
+    /// Marks the span as failed.
+    pub fn set_error(&mut self, message: impl Into<String>) {
+        self.error = Some(message.into());
+    }
+
+    pub fn end(self) {
+        if !self.context.sampled {
+            return;
+        }
+        if let Some(telemetry) = Telemetry::get() {
+            telemetry.record_span(SpanData {
+                context: self.context,
+                parent: self.parent,
+                name: self.name,
+                kind: self.kind,
+                start: unix_nanos(self.start),
+                end: unix_nanos(SystemTime::now()),
+                attributes: self.attributes,
+                error: self.error,
+            });
+        }
+    }
+}
+
+pub(crate) fn unix_nanos(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+/// A non-zero id from the process's randomly keyed hasher, which is plenty for trace ids
+/// without pulling in a random number generator.
+fn random_id() -> [u8; 8] {
+    loop {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+        hasher.write_u64(unix_nanos(SystemTime::now()));
+        let id = hasher.finish();
+        if id != 0 {
+            return id.to_be_bytes();
+        }
+    }
+}
+
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+fn decode_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != N * 2 {
+        return None;
+    }
+    let mut bytes = [0; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(bytes)
+}
@@ -0,0 +1,300 @@
+//! Helpers for unit testing handlers and middleware without starting a server.
+//!
+//! ```rust,ignore
+//! let ctx = TestContext::builder()
+//!     .param("id", "1")
+//!     .json_body(&serde_json::json!({ "name": "Alice" }))
+//!     .build();
+//! let response = TestResponse::from(get_user(&ctx).await);
+//! assert_eq!(response.status(), 200);
+//! ```
+use std::{collections::HashMap, sync::Arc};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    http::{
+        verify_content_digest, AsyncHandler, BufferBuilder, Context, HttpMethod, HttpRequest,
+        MiddlewareFn, OxideResponse, RequestSigner, Res, CONTENT_DIGEST,
+    },
+    PgDatabase,
+};
+
+/// Entry point for building fake handler contexts.
+pub struct TestContext;
+
+impl TestContext {
+    pub fn builder() -> TestContextBuilder {
+        TestContextBuilder::default()
+    }
+}
+
+/// Builds an `HttpRequest`, its raw bytes, or a `Context` wrapping it. Defaults to `GET /`.
+#[derive(Default)]
+pub struct TestContextBuilder {
+    method: Option<HttpMethod>,
+    path: Option<String>,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    cookies: Vec<(String, String)>,
+    params: HashMap<String, String>,
+    body: Vec<u8>,
+    datasource: Option<Arc<PgDatabase>>,
+}
+
+impl TestContextBuilder {
+    pub fn method(mut self, method: HttpMethod) -> Self {
+        self.method = Some(method);
+        self
+    }
+
+    /// Request path, optionally with a query string.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn query(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn cookie(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.cookies.push((key.into(), value.into()));
+        self
+    }
+
+    /// Path parameter, as a route like `/users/:id` would extract it.
+    pub fn param(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.params.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Serializes `body` as JSON and sets `Content-Type: application/json`.
+    pub fn json_body<T: Serialize>(self, body: &T) -> Self {
+        let body = serde_json::to_vec(body).expect("test body should serialize to JSON");
+        self.header("Content-Type", BufferBuilder::JSON).body(body)
+    }
+
+    /// Adds the headers `signer` produces for the request so far, so call it after setting
+    /// the method, path, query and body.
+    pub fn signed(self, signer: &RequestSigner) -> Self {
+        let method = self.method.unwrap_or(HttpMethod::Get).to_string();
+        signer
+            .sign(&method, &self.target(), &self.body)
+            .into_iter()
+            .fold(self, |builder, (name, value)| builder.header(name, value))
+    }
+
+    pub fn datasource(mut self, datasource: Arc<PgDatabase>) -> Self {
+        self.datasource = Some(datasource);
+        self
+    }
+
+    fn target(&self) -> String {
+        let mut target = self.path.clone().unwrap_or_else(|| "/".to_string());
+        if !self.query.is_empty() {
+            let query = self
+                .query
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<_>>()
+                .join("&");
+            target.push(if target.contains('?') { '&' } else { '?' });
+            target.push_str(&query);
+        }
+        target
+    }
+
+    fn all_headers(&self) -> Vec<(String, String)> {
+        let mut headers = self.headers.clone();
+        if !self.cookies.is_empty() {
+            let cookie = self
+                .cookies
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<_>>()
+                .join("; ");
+            headers.push(("Cookie".to_string(), cookie));
+        }
+        if !self.body.is_empty() {
+            headers.push(("Content-Length".to_string(), self.body.len().to_string()));
+        }
+        headers
+    }
+
+    fn head(&self) -> String {
+        let method = self.method.unwrap_or(HttpMethod::Get);
+        let mut head = format!(
+            "{} {} HTTP/1.1\r\nHost: localhost\r\n",
+            method,
+            self.target()
+        );
+        for (key, value) in self.all_headers() {
+            head.push_str(&format!("{}: {}\r\n", key, value));
+        }
+        head.push_str("\r\n");
+        head
+    }
+
+    /// The request as it would arrive on the wire, for feeding to `HttpRequest::parse` or
+    /// `HttpHandler::handle`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut raw = self.head().into_bytes();
+        raw.extend_from_slice(&self.body);
+        raw
+    }
+
+    pub fn build_request(&self) -> HttpRequest {
+        // The body is attached after parsing so binary payloads don't need to be UTF-8.
+        let mut request =
+            HttpRequest::parse(self.head().as_bytes()).expect("test request should be valid HTTP");
+        request.body = self.body.clone();
+        request.path_params = self.params.clone();
+        request
+    }
+
+    pub fn build(self) -> Context {
+        let mut context = Context::new(self.build_request(), self.params.clone());
+        if let Some(datasource) = self.datasource {
+            context.with_datasource(datasource);
+        }
+        context
+    }
+
+    /// Builds the context and runs `handler` against it.
+    pub async fn call(self, handler: AsyncHandler) -> TestResponse {
+        let context = self.build();
+        TestResponse::from(handler(&context).await)
+    }
+}
+
+/// A built response split into status, headers and body for assertions.
+#[derive(Debug, Clone)]
+pub struct TestResponse {
+    status: u16,
+    raw: Vec<u8>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl TestResponse {
+    pub fn from_raw(raw: Vec<u8>) -> Self {
+        let builder = BufferBuilder::from_raw(&raw);
+        Self {
+            status: builder.as_ref().map(|b| b.status_code()).unwrap_or(0),
+            body: builder
+                .as_ref()
+                .map(|b| b.body_bytes().to_vec())
+                .unwrap_or_default(),
+            headers: raw_headers(&raw),
+            raw,
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Header value by case-insensitive name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        serde_json::from_slice(&self.body)
+    }
+
+    /// Whether the response's `Content-Digest` matches its body; `false` without one.
+    pub fn verify_digest(&self) -> bool {
+        self.header(CONTENT_DIGEST)
+            .is_some_and(|header| verify_content_digest(header, &self.body))
+    }
+
+    /// The full response bytes, status line and headers included.
+    pub fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+}
+
+impl From<OxideResponse> for TestResponse {
+    fn from(response: OxideResponse) -> Self {
+        let status = response.status();
+        let mut test = Self::from_raw(response.into_buffer());
+        // Panics and error reports carry their status without a rendered buffer.
+        test.status = status;
+        test
+    }
+}
+
+impl From<Res> for TestResponse {
+    fn from(response: Res) -> Self {
+        let mut test = Self::from_raw(response.buffer);
+        test.status = response.status;
+        test
+    }
+}
+
+fn raw_headers(raw: &[u8]) -> Vec<(String, String)> {
+    let split = raw
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .unwrap_or(raw.len());
+    String::from_utf8_lossy(&raw[..split])
+        .split("\r\n")
+        .skip(1)
+        .filter_map(|line| line.split_once(": "))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+/// Runs middleware in order against a context, like the server does before a handler.
+#[derive(Default)]
+pub struct MiddlewareHarness {
+    middleware: Vec<MiddlewareFn>,
+}
+
+impl MiddlewareHarness {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, middleware: MiddlewareFn) -> Self {
+        self.middleware.push(middleware);
+        self
+    }
+
+    /// Returns the context that would reach the handler, or the response of the first
+    /// middleware that short-circuited.
+    pub fn run(&self, mut context: Context) -> Result<Context, TestResponse> {
+        for middleware in &self.middleware {
+            context = middleware(context).map_err(TestResponse::from)?;
+        }
+        Ok(context)
+    }
+}
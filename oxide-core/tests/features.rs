@@ -0,0 +1,67 @@
+//! Keeps the feature table in `src/lib.rs` in step with `Cargo.toml`, and checks that what
+//! each enabled feature promises is there. Passes under any combination of features, e.g.
+//! `cargo test -p oxide-core --test features --no-default-features --features metrics`.
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// Features declared in the manifest, with whether `default` enables them.
+fn manifest_features() -> BTreeMap<String, bool> {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+    let manifest: toml::Table = fs::read_to_string(manifest).unwrap().parse().unwrap();
+    let features = manifest["features"].as_table().unwrap();
+    let defaults: Vec<&str> = features
+        .get("default")
+        .and_then(|default| default.as_array())
+        .map(|default| default.iter().filter_map(|f| f.as_str()).collect())
+        .unwrap_or_default();
+    features
+        .keys()
+        .filter(|name| *name != "default")
+        .map(|name| (name.clone(), defaults.contains(&name.as_str())))
+        .collect()
+}
+
+/// Features listed in the table in the crate docs, with whether it marks them as default.
+fn documented_features() -> BTreeMap<String, bool> {
+    let lib = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/lib.rs");
+    fs::read_to_string(lib)
+        .unwrap()
+        .lines()
+        .filter_map(|line| line.strip_prefix("//! |"))
+        .filter_map(|row| {
+            let mut cells = row.split('|').map(str::trim);
+            let name = cells.next()?.strip_prefix('`')?.strip_suffix('`')?;
+            let default = match cells.next()? {
+                "yes" => true,
+                "no" => false,
+                other => panic!("`{}` is neither yes nor no for {}", other, name),
+            };
+            Some((name.to_string(), default))
+        })
+        .collect()
+}
+
+#[test]
+fn feature_table_matches_manifest() {
+    assert_eq!(documented_features(), manifest_features());
+}
+
+#[cfg(feature = "static-files")]
+#[test]
+fn static_files_serves_embedded_assets() {
+    let file = oxide_core::http::StaticHandler::serve_encoded("index.html", None).unwrap();
+    assert_eq!(file.mime.as_str(), "text/html");
+    assert!(!file.data.is_empty());
+
+    let _register: fn(&mut oxide_core::Server, &str, &'static str) =
+        oxide_core::Server::static_file;
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn metrics_records_response_sizes() {
+    let sizes = oxide_core::server::ResponseSizes::new();
+    sizes.observe(Some("/users"), 2048);
+    let stats = sizes.stats();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].bytes, 2048);
+}
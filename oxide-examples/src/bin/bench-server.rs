@@ -0,0 +1,61 @@
+//! Minimal server for load testing with wrk or oha, no database required.
+//!
+//! ```sh
+//! cargo run --release --bin bench-server
+//! wrk -t4 -c64 -d30s http://127.0.0.1:8080/plaintext
+//! oha -z 30s http://127.0.0.1:8080/json
+//! ```
+use oxide_core::{
+    config::ConfigBuilder,
+    http::{AsyncResponse, Context, OxideRes, OxideResponse},
+    prelude::*,
+};
+
+#[derive(serde::Serialize)]
+struct Message {
+    message: &'static str,
+}
+
+#[handler]
+async fn plaintext(_ctx: &Context) -> OxideResponse {
+    OxideResponse::text(OxideRes::Success, "Hello, World!")
+}
+
+#[handler]
+async fn json(_ctx: &Context) -> OxideResponse {
+    OxideResponse::json(
+        OxideRes::Success,
+        Message {
+            message: "Hello, World!",
+        },
+    )
+}
+
+#[handler]
+async fn param(ctx: &Context) -> OxideResponse {
+    let id = ctx.param("id").unwrap_or("0");
+    OxideResponse::text(OxideRes::Success, format!("item {}", id))
+}
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let port = std::env::var("PORT")
+        .ok()
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(8080);
+
+    // Production keeps per-request logging out of the measurements.
+    let config = ConfigBuilder::new()
+        .port(port)
+        .environment(Environment::Production)
+        .build();
+    let mut server = Server::new(config);
+
+    server
+        .router
+        .get("/plaintext", plaintext_handler)
+        .get("/json", json_handler)
+        .get("/items/:id", param_handler);
+
+    server.run().await
+}
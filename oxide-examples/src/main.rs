@@ -2,9 +2,13 @@ use oxide_core::{
     http::{AsyncResponse, Context, MiddlewareResult, OxideRes, OxideResponse},
     logger::LogLevel,
     prelude::*,
-    PgDatabase,
+    PgDatabase, PgTransaction,
+};
+use oxide_orm::{
+    model,
+    prelude::*,
+    seed::{SeedFuture, Seeder},
 };
-use oxide_orm::{model, prelude::*};
 
 #[derive(Debug, serde::Deserialize)]
 pub struct JsonData {
@@ -93,7 +97,7 @@ async fn user(ctx: &Context) -> OxideResponse {
     };
 }
 
-#[handler]
+#[handler(cache(models(User), ttl = "30s"))]
 async fn users(ctx: &Context) -> OxideResponse {
     let db = match ctx.db() {
         Some(db) => db,
@@ -183,6 +187,35 @@ fn specific_middleware(ctx: Context) -> MiddlewareResult {
     Ok(ctx)
 }
 
+// Example data, inserted once per development database
+struct Users;
+
+impl Seeder for Users {
+    fn name(&self) -> &str {
+        "users"
+    }
+
+    fn run<'a>(&'a self, tx: &'a mut PgTransaction<'_>) -> SeedFuture<'a> {
+        Box::pin(async move {
+            let users = [
+                ("Ada", "ada@example.com", 36),
+                ("Alan", "alan@example.com", 41),
+            ];
+            User::insert_many()
+                .rows(users.into_iter().map(|(name, email, age)| {
+                    User::insert()
+                        .value(User::columns().name, name.to_string())
+                        .value(User::columns().email, email.to_string())
+                        .value(User::columns().age, age)
+                        .value(User::columns().active, true)
+                }))
+                .execute(tx)
+                .await?;
+            Ok(())
+        })
+    }
+}
+
 // Route setup functions
 fn user_routes(server: &mut Server) {
     server.router.get("/users/:id", get_user_handler);
@@ -227,6 +260,10 @@ async fn main() -> std::io::Result<()> {
 
     let mut server = Server::new(Config::default());
 
+    db.seed(&[Box::new(Users)])
+        .await
+        .expect("Error: Failed to seed database");
+
     server.static_file("/", "index.html");
     server.with_datasource(db);
 
@@ -1,6 +1,9 @@
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
-use syn::{parse_macro_input, Data, DeriveInput, Fields, ItemFn, ItemStruct};
+use syn::{
+    parse_macro_input, punctuated::Punctuated, Data, DeriveInput, Expr, Fields, ItemFn, ItemStruct,
+    MetaNameValue, Token,
+};
 
 /// Enhances a struct with ORM functionality and common derives for use with the Oxide framework.
 ///
@@ -20,12 +23,22 @@ use syn::{parse_macro_input, Data, DeriveInput, Fields, ItemFn, ItemStruct};
 /// The `#[model]` macro modifies your struct to:
 /// 1. Automatically derive commonly needed traits:
 ///    - `Debug`, `Clone`, `serde::Serialize`, `serde::Deserialize`, `sqlx::FromRow`
-/// 2. Generate a companion `Columns` struct (e.g., `UserColumns`) for type-safe query building.
+/// 2. Generate a companion `Columns` struct (e.g., `UserColumns`) for type-safe query building,
+///    reachable from the model through `HasColumns`, so `Query<User>` names its query builder.
 /// 3. Implement the `Model` trait, including:
 ///    - A `table()` method for accessing the table name (`users` for the example above).
 ///    - A `columns()` method for accessing field metadata.
 /// 4. Add query-building methods for use with Oxide ORM:
-///    - `query()`, `insert()`, `update(id)`, etc.
+///    - `query()`, `insert()`, `update(id)`, `update_where()`, etc.
+///    - `find(&db, id)`, `find_by(&db, column, value)`, `all(&db)` and `first(&db)` for
+///      one-line lookups.
+///    - `to_insert()`, an insert of the struct's own field values.
+///    - `user.save(&db)` and `user.delete(&db)`, and with `#[model(new = "NewUser")]`
+///      `create(&db, NewUser { .. })`, for one-line writes.
+///    - `table_def()`, the table's columns and their SQL types, for generating migrations.
+/// 5. Implement `Validate` with the rules from its fields' `#[validate(...)]` attributes.
+/// 6. Implement `ModelHooks` with no hooks, unless `#[model(hooks = true)]` is given, writing
+///    outbox events with `#[model(outbox = true)]`.
 ///
 /// # Requirements
 /// - The struct must have named fields.
@@ -52,11 +65,339 @@ use syn::{parse_macro_input, Data, DeriveInput, Fields, ItemFn, ItemStruct};
 /// - The table name is automatically derived by pluralizing the struct name (e.g., `Product` -> `products`).
 /// - Column metadata is accessible through the generated `Columns` struct (e.g., `Product::columns().name`).
 /// - This macro eliminates the need to manually implement boilerplate for database operations.
+///
+/// # Column options
+/// Fields marked `#[column(default)]` (serials, `DEFAULT` expressions) or `#[column(generated)]`
+/// (`GENERATED ALWAYS` columns) are left out of `to_insert()` so the database fills them in:
+/// ```rust,ignore
+/// #[model]
+/// pub struct Order {
+///     #[column(default)]
+///     pub id: i32,
+///     pub quantity: i32,
+///     pub unit_price: i32,
+///     #[column(generated)]
+///     pub total: i32,
+/// }
+///
+/// order.to_insert().execute(&db).await?;
+/// ```
+///
+/// `#[column(flatten)]` embeds an `#[embeddable]` struct as columns prefixed with the field
+/// name, so `address.city` maps to `address_city` and is queried as
+/// `Customer::columns().address.city`:
+/// ```rust,ignore
+/// #[embeddable]
+/// pub struct Address {
+///     pub city: String,
+///     pub postcode: String,
+/// }
+///
+/// #[model]
+/// pub struct Customer {
+///     pub id: i32,
+///     #[column(flatten)]
+///     pub address: Address,
+/// }
+/// ```
+///
+/// `#[column(name = "...")]` maps a field to a column named differently, and
+/// `#[column(skip)]` keeps a field out of every query, filled with `Default::default()` when
+/// rows are read:
+/// ```rust,ignore
+/// #[model]
+/// pub struct Account {
+///     pub id: i32,
+///     #[column(name = "email_address")]
+///     pub email: String,
+///     #[column(skip)]
+///     pub display_name: String,
+/// }
+/// ```
+///
+/// # Nullable columns
+/// `Option` fields map to nullable columns and are compared with `Option` values; `None`
+/// inserts and sets `NULL`, and matches with `IS NULL`:
+/// ```rust,ignore
+/// #[model]
+/// pub struct Profile {
+///     pub id: i32,
+///     pub bio: Option<String>,
+/// }
+///
+/// let blank: Vec<Profile> = Profile::query()
+///     .and_where(Profile::columns().bio, None)
+///     .fetch_all(&db)
+///     .await?;
+/// Profile::update(id).set(Profile::columns().bio, None).execute(&db).await?;
+/// ```
+///
+/// # Scopes
+/// Conditions used across handlers can be kept on the model as scopes, functions taking and
+/// returning its query, and applied with `scope`. Each scope's conditions are grouped, so a
+/// scope using `or_where` can't loosen the others:
+/// ```rust,ignore
+/// impl User {
+///     pub fn active(query: Query<User>) -> Query<User> {
+///         query.and_where(Self::columns().active, true)
+///     }
+///
+///     pub fn adults(query: Query<User>) -> Query<User> {
+///         query.filter(Self::columns().age.gte(18))
+///     }
+/// }
+///
+/// let users = User::query().scope(User::active).scope(User::adults).fetch_all(&db).await?;
+/// ```
+///
+/// Scopes named in `default_scope`, separated by commas, apply to every query, update and
+/// delete of the model, including `find`, `save` and relations. Call `unscoped()` on a
+/// builder to leave them out. Only their conditions apply; an order or a limit is ignored:
+/// ```rust,ignore
+/// #[model(default_scope = "not_deleted")]
+/// pub struct Post {
+///     pub id: i32,
+///     pub deleted_at: Option<DateTime<Utc>>,
+/// }
+///
+/// impl Post {
+///     pub fn not_deleted(query: Query<Post>) -> Query<Post> {
+///         query.filter(Self::columns().deleted_at.is_null())
+///     }
+/// }
+///
+/// let visible = Post::all(&db).await?;
+/// let everything = Post::query().unscoped().fetch_all(&db).await?;
+/// ```
+///
+/// # JSON columns
+/// `serde_json::Value` fields map to `JSONB` columns. Their columns filter on what's inside
+/// with `json_get` (`->>`, as text), `json_field` (`->`, to reach nested objects),
+/// `json_contains` (`@>`) and `json_has_key` (`?`):
+/// ```rust,ignore
+/// #[model]
+/// pub struct Event {
+///     pub id: i32,
+///     pub payload: serde_json::Value,
+/// }
+///
+/// let payload = Event::columns().payload;
+/// let signups: Vec<Event> = Event::query()
+///     .filter(payload.json_get("kind").eq("signup".to_string()))
+///     .filter(payload.json_contains(json!({"tags": ["beta"]})))
+///     .order_by(payload.json_field("user").json_get("email"), Direction::Asc)
+///     .fetch_all(&db)
+///     .await?;
+/// ```
+///
+/// # Array columns
+/// `Vec` fields map to one-dimensional arrays of their element type, such as `text[]` for
+/// `Vec<String>`. Their columns filter with `any_eq` (`= ANY(...)`), `contains` (`@>`),
+/// `contained_by` (`<@`) and `overlaps` (`&&`):
+/// ```rust,ignore
+/// #[model]
+/// pub struct Post {
+///     pub id: i32,
+///     pub tags: Vec<String>,
+/// }
+///
+/// let tags = Post::columns().tags;
+/// let posts: Vec<Post> = Post::query()
+///     .filter(tags.any_eq("rust".to_string()))
+///     .filter(tags.overlaps(&["tokio".to_string(), "async".to_string()]))
+///     .fetch_all(&db)
+///     .await?;
+/// ```
+///
+/// # Enum columns
+/// Fields of enums deriving [`SqlEnum`](derive@SqlEnum) map to a Postgres enum type or a
+/// `TEXT` column, and compare with `eq`, `is_in` and the rest like any other value.
+///
+/// # Relations
+/// `#[has_many]` and `#[belongs_to]`, written below `#[model]`, generate accessors for related
+/// rows. `foreign_key` names the column holding the parent's primary key, on the child for
+/// `has_many` and on this model for `belongs_to`. Accessors are named after the related model
+/// (`posts()`, `user()`) unless given `name = "..."`:
+/// ```rust,ignore
+/// #[model]
+/// #[has_many(Post, foreign_key = "user_id")]
+/// pub struct User {
+///     pub id: i32,
+///     pub name: String,
+/// }
+///
+/// #[model]
+/// #[belongs_to(User, foreign_key = "user_id")]
+/// pub struct Post {
+///     pub id: i32,
+///     pub user_id: i32,
+///     pub title: String,
+/// }
+///
+/// let posts: Vec<Post> = user.posts(&db).await?;
+/// let author: User = post.user(&db).await?;
+/// ```
+/// An `Option` foreign key makes `belongs_to` return `Option<User>`. A `has_many` foreign key
+/// must have the same type as this model's primary key.
+///
+/// # Databases
+/// Apps spanning several databases register extra pools with
+/// `Server::with_named_datasource("analytics", db)` and point models at them. Builders given
+/// a handler's `Context` then run against that pool; given a `PgDatabase`, they use it as is.
+/// ```rust,ignore
+/// #[model(database = "analytics")]
+/// pub struct PageView {
+///     pub id: i32,
+///     pub path: String,
+/// }
+///
+/// let views: Vec<PageView> = PageView::query().fetch_all(ctx).await?;
+/// ```
+///
+/// Given a `Context`, selects go to a read replica of the model's datasource when one is
+/// registered with `Server::with_read_replica` or `with_named_read_replica`. Writes and
+/// transactions use the primary, as does a select marked `on_primary()`:
+/// ```rust,ignore
+/// let order = Order::query().key(id).on_primary().fetch_one(ctx).await?;
+/// ```
+///
+/// # Transactions
+/// Builders and relation accessors also accept `&mut tx`, running as part of the transaction
+/// so several operations commit or roll back together:
+/// ```rust,ignore
+/// let mut tx = db.begin().await?;
+/// let order: Order = Order::insert().value(Order::columns().total, 42).fetch_one(&mut tx).await?;
+/// Cart::delete().and_where(Cart::columns().user_id, order.user_id).execute(&mut tx).await?;
+/// tx.commit().await?;
+/// ```
+///
+/// # Primary keys
+/// The primary key is the `id` field unless declared with `pk`. Composite keys are written as a
+/// tuple and passed as one, in the same order:
+/// ```rust,ignore
+/// #[model(pk = (tenant_id, user_id))]
+/// pub struct Membership {
+///     pub tenant_id: i32,
+///     pub user_id: i32,
+///     pub role: String,
+/// }
+///
+/// Membership::update((tenant_id, user_id)).set(Membership::columns().role, role);
+/// Membership::query().key((tenant_id, user_id));
+/// ```
+///
+/// Models with neither, such as views, join tables and logs, have no primary key and so no
+/// `find()`, `update(key)`, `save()` or `delete(&db)`; query, insert and `update_where()` them
+/// instead.
+///
+/// # Saving
+/// `new = "NewUser"` generates a `NewUser` struct alongside the model, holding the fields
+/// `to_insert()` would write, and a `create()` that inserts one and returns the stored row.
+/// `save()` writes every column but the key and `#[column(generated)]` ones back to the row
+/// with the model's key, and `delete(&db)`, from the `DeleteRow` trait in the prelude, removes
+/// that row:
+/// ```rust,ignore
+/// #[model(new = "NewUser")]
+/// pub struct User { ... }
+///
+/// let mut user = User::create(&db, NewUser {
+///     name: "Ada".to_string(),
+///     email: "ada@example.com".to_string(),
+///     age: 36,
+///     active: true,
+/// })
+/// .await?;
+/// user.age += 1;
+/// let user = user.save(&db).await?;
+/// user.delete(&db).await?;
+/// ```
+///
+/// Keys can be any supported column type, such as `Uuid` or `String`. A single-column key
+/// left out of `insert()` can be filled in with `key_default`: `"uuid_v7"` generates a
+/// time-ordered UUID client-side, `"uuid_v4"` uses `gen_random_uuid()` in the database, and
+/// `"database"` (the default) relies on the column's own default.
+/// ```rust,ignore
+/// #[model(key_default = "uuid_v7")]
+/// pub struct Session {
+///     pub id: Uuid,
+///     pub user_id: i32,
+/// }
+/// ```
+///
+/// # Validation
+/// `#[validate(...)]` rules are checked by every insert and update of the model, including
+/// `create()` and `save()`, before any SQL is sent. Values that fail are reported as one
+/// `Error::Validation`, a 400, naming each failing column. `NULL` passes every rule:
+/// ```rust,ignore
+/// #[model]
+/// pub struct Member {
+///     pub id: i32,
+///     #[validate(email, length(max = 255))]
+///     pub email: String,
+///     #[validate(length(min = 1, max = 50))]
+///     pub nickname: Option<String>,
+///     #[validate(range(min = 0, max = 150))]
+///     pub age: i32,
+/// }
+///
+/// // Validation Error: email must be a valid email address; age must be at least 0
+/// Member::insert().value(Member::columns().email, "nope".into()).value(Member::columns().age, -1).execute(&db).await
+/// ```
+///
+/// # Hooks
+/// With `hooks = true` the model implements `ModelHooks` itself, and its builders await those
+/// methods around every insert, update and delete. `before_save` runs after validation and can
+/// change what gets written:
+/// ```rust,ignore
+/// #[model(hooks = true)]
+/// pub struct Account {
+///     pub id: i32,
+///     pub email: String,
+///     pub password: String,
+/// }
+///
+/// impl ModelHooks for Account {
+///     fn before_save(changes: &mut Changes) -> HookFuture<'_> {
+///         Box::pin(async move {
+///             if let Some(SqlValue::Text(password)) = changes.get("password") {
+///                 let hash = hash_password(password).await?;
+///                 changes.set("password", hash);
+///             }
+///             Ok(())
+///         })
+///     }
+///
+///     fn after_delete(rows: u64) -> HookFuture<'static> {
+///         Box::pin(async move { invalidate_account_cache(rows).await })
+///     }
+/// }
+/// ```
+///
+/// With `outbox = true`, or `const OUTBOX: bool = true` in a `ModelHooks` impl, every insert,
+/// update and delete also records a `<table>.created`, `.updated` or `.deleted` event per row
+/// in the transactional outbox, in the same statement as the write.
 
 #[proc_macro_attribute]
-pub fn model(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn model(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args =
+        parse_macro_input!(attr with Punctuated::<MetaNameValue, Token![,]>::parse_terminated);
     // Parse the input tokens as a struct definition
-    let input = parse_macro_input!(item as ItemStruct);
+    let mut input = parse_macro_input!(item as ItemStruct);
+    let options: Vec<ColumnOptions> = input.fields.iter_mut().map(column_options).collect();
+    let field_rules: Vec<_> = input.fields.iter_mut().map(validate_rules).collect();
+    // sqlx's own flatten has no column prefix, so models embedding structs map rows by hand;
+    // otherwise its derive is told about renamed and skipped fields.
+    let derive_from_row = !options.iter().any(|options| options.flatten);
+    if derive_from_row {
+        for (field, options) in input.fields.iter_mut().zip(&options) {
+            if options.skip {
+                field.attrs.push(syn::parse_quote!(#[sqlx(skip)]));
+            } else if let Some(name) = &options.name {
+                field.attrs.push(syn::parse_quote!(#[sqlx(rename = #name)]));
+            }
+        }
+    }
+    let relations = relations(&mut input);
     let name = &input.ident; // Struct name (e.g., `User`)
     let table_name = format!("{}s", name.to_string().to_lowercase());
     let columns_name = format_ident!("{}Columns", name);
@@ -67,39 +408,472 @@ pub fn model(_attr: TokenStream, item: TokenStream) -> TokenStream {
         _ => panic!("Only named fields are supported"),
     };
 
-    let field_idents: Vec<_> = fields.iter().map(|f| &f.ident).collect();
-    let field_types: Vec<_> = fields.iter().map(|f| &f.ty).collect();
+    // Skipped fields only exist on the struct; everything SQL is generated from the rest.
+    let columns: Vec<_> = fields
+        .iter()
+        .zip(&options)
+        .filter(|(_, options)| !options.skip)
+        .collect();
+    let column_name = |field: &syn::Field| {
+        let options = fields
+            .iter()
+            .zip(&options)
+            .find(|(f, _)| f.ident == field.ident)
+            .map(|(_, options)| options);
+        match options {
+            Some(ColumnOptions { skip: true, .. }) => panic!(
+                "`{}` is `#[column(skip)]` and has no column",
+                field.ident.as_ref().unwrap()
+            ),
+            Some(ColumnOptions {
+                name: Some(name), ..
+            }) => name.clone(),
+            _ => field.ident.as_ref().unwrap().to_string(),
+        }
+    };
+
+    let field_idents: Vec<_> = columns.iter().map(|(f, _)| &f.ident).collect();
+    let column_types: Vec<_> = columns
+        .iter()
+        .copied()
+        .map(|(field, options)| {
+            let ty = &field.ty;
+            if options.flatten {
+                quote! { <#ty as oxide_orm::Embedded>::Columns<#name> }
+            } else {
+                quote! { Column<#name, #ty> }
+            }
+        })
+        .collect();
+    let column_inits: Vec<_> = columns
+        .iter()
+        .map(|(field, options)| {
+            let (name, ty) = (column_name(field), &field.ty);
+            if options.flatten {
+                quote! { <#ty as oxide_orm::Embedded>::columns(concat!(#name, "_")) }
+            } else {
+                quote! { Column::new(#name) }
+            }
+        })
+        .collect();
+
+    let insert_fields: Vec<_> = columns
+        .iter()
+        .filter(|(_, options)| !options.default && !options.generated && !options.flatten)
+        .map(|(field, _)| *field)
+        .collect();
+    let insert_idents: Vec<_> = insert_fields.iter().map(|f| &f.ident).collect();
+    let insert_names: Vec<_> = insert_fields.iter().map(|f| column_name(f)).collect();
+    let insert_types: Vec<_> = insert_fields.iter().map(|f| &f.ty).collect();
+
+    let flatten_fields: Vec<_> = columns
+        .iter()
+        .filter(|(_, options)| options.flatten)
+        .map(|(field, _)| *field)
+        .collect();
+    let flatten_idents: Vec<_> = flatten_fields.iter().map(|f| &f.ident).collect();
+    let flatten_names: Vec<_> = flatten_fields.iter().map(|f| column_name(f)).collect();
+    let flatten_types: Vec<_> = flatten_fields.iter().map(|f| &f.ty).collect();
+
+    let key_idents = match primary_key(&args, fields) {
+        Ok(key_idents) => key_idents,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    // `save()` writes everything the database doesn't compute, including defaulted columns.
+    let save_fields: Vec<_> = columns
+        .iter()
+        .filter(|(field, options)| {
+            !options.generated
+                && !options.flatten
+                && !key_idents
+                    .iter()
+                    .any(|key| field.ident.as_ref() == Some(key))
+        })
+        .map(|(field, _)| *field)
+        .collect();
+    let save_idents: Vec<_> = save_fields.iter().map(|f| &f.ident).collect();
+    let save_names: Vec<_> = save_fields.iter().map(|f| column_name(f)).collect();
+    let save_types: Vec<_> = save_fields.iter().map(|f| &f.ty).collect();
+
+    // The `new = "..."` struct holds what `to_insert()` writes, less a key the model generates
+    // itself.
+    let new_fields: Vec<_> = insert_fields
+        .iter()
+        .filter(|field| {
+            key_default(&args).is_none()
+                || !key_idents
+                    .iter()
+                    .any(|key| field.ident.as_ref() == Some(key))
+        })
+        .copied()
+        .collect();
+    let new_idents: Vec<_> = new_fields.iter().map(|f| &f.ident).collect();
+    let new_names: Vec<_> = new_fields.iter().map(|f| column_name(f)).collect();
+    let new_types: Vec<_> = new_fields.iter().map(|f| &f.ty).collect();
+    let vis = &input.vis;
+    let new_name = string_arg(&args, "new").map(|new_name| format_ident!("{}", new_name));
+    let new_struct = new_name.as_ref().map(|new_name| {
+        quote! {
+            /// The fields `create()` inserts, leaving the rest to the database.
+            #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+            #vis struct #new_name {
+                #(pub #new_idents: #new_types,)*
+                #(pub #flatten_idents: #flatten_types,)*
+            }
+        }
+    });
+    let create_method = new_name.as_ref().map(|new_name| {
+        quote! {
+            /// Inserts `new` and returns the stored row, with the values the database filled in.
+            pub async fn create(
+                db: impl oxide_orm::IntoExecutor<'_>,
+                new: #new_name,
+            ) -> Result<Self, oxide_core::Error>
+            where
+                #(for<'a> &'a #new_types: oxide_orm::ToSql,)*
+                #(for<'a> &'a #flatten_types: oxide_orm::EmbeddedValues,)*
+            {
+                let insert = Self::insert()
+                    #(
+                        .column_value(
+                            #new_names,
+                            oxide_orm::ToSql::to_value(&&new.#new_idents),
+                        )
+                    )*;
+                #(
+                    let insert = oxide_orm::EmbeddedValues::column_values(
+                        &new.#flatten_idents,
+                        concat!(#flatten_names, "_"),
+                    )
+                    .into_iter()
+                    .fold(insert, |insert, (column, value)| insert.column_value(&column, value));
+                )*
+                insert.fetch_one(db).await
+            }
+        }
+    });
+
+    let rules: Vec<_> = fields
+        .iter()
+        .zip(&options)
+        .zip(&field_rules)
+        .flat_map(|((field, options), rules)| {
+            if !rules.is_empty() && (options.skip || options.flatten) {
+                panic!(
+                    "`{}` has no column of its own to validate",
+                    field.ident.as_ref().unwrap()
+                );
+            }
+            let name = column_name(field);
+            rules.iter().map(move |rule| quote! { (#name, #rule) })
+        })
+        .collect();
+
+    let scalar_types: Vec<_> = columns
+        .iter()
+        .filter(|(_, options)| !options.flatten)
+        .map(|(field, _)| option_inner(&field.ty).unwrap_or(&field.ty))
+        .collect();
+    let column_defs: Vec<_> = columns
+        .iter()
+        .map(|(field, options)| {
+            let (name, ty) = (column_name(field), &field.ty);
+            if options.flatten {
+                quote! {
+                    columns.extend(<&'a #ty as oxide_orm::EmbeddedSchema>::column_defs(
+                        concat!(#name, "_"),
+                    ));
+                }
+            } else {
+                let nullable = option_inner(ty).is_some();
+                let ty = option_inner(ty).unwrap_or(ty);
+                quote! {
+                    columns.push(oxide_orm::ColumnDef::new(
+                        #name,
+                        <&'a #ty as oxide_orm::ToSql>::sql_type(),
+                        #nullable,
+                    ));
+                }
+            }
+        })
+        .collect();
+
+    let (from_row_derive, from_row_impl) = if derive_from_row {
+        (quote! { sqlx::FromRow }, quote! {})
+    } else {
+        let row_values = fields.iter().zip(&options).map(|(field, options)| {
+            let ident = &field.ident;
+            if options.skip {
+                return quote! { #ident: Default::default() };
+            }
+            let (name, ty) = (column_name(field), &field.ty);
+            if options.flatten {
+                quote! {
+                    #ident: <#ty as oxide_orm::Embedded>::from_row(row, concat!(#name, "_"))?
+                }
+            } else {
+                quote! { #ident: sqlx::Row::try_get(row, #name)? }
+            }
+        });
+        (
+            quote! {},
+            quote! {
+                impl<'r> sqlx::FromRow<'r, sqlx::postgres::PgRow> for #name {
+                    fn from_row(row: &'r sqlx::postgres::PgRow) -> Result<Self, sqlx::Error> {
+                        Ok(Self {
+                            #(#row_values,)*
+                        })
+                    }
+                }
+            },
+        )
+    };
+
+    let key_fields: Vec<_> = key_idents
+        .iter()
+        .filter_map(|ident| fields.iter().find(|f| f.ident.as_ref() == Some(ident)))
+        .collect();
+    let key_names: Vec<_> = key_fields.iter().map(|f| column_name(f)).collect();
+    let key_types: Vec<_> = key_fields.iter().map(|f| &f.ty).collect();
+    let key_default = key_default(&args).map(|variant| {
+        if key_idents.len() != 1 {
+            panic!("`key_default` only applies to single-column primary keys");
+        }
+        quote! { const KEY_DEFAULT: oxide_orm::KeyDefault = oxide_orm::KeyDefault::#variant; }
+    });
+    // Models with `hooks = true` implement `ModelHooks` themselves.
+    let outbox = bool_arg(&args, "outbox");
+    let hooks_impl = if bool_arg(&args, "hooks") {
+        if outbox {
+            panic!("With `hooks = true`, set `const OUTBOX: bool = true` in the `ModelHooks` impl instead of `outbox = true`");
+        }
+        None
+    } else if outbox {
+        Some(quote! { impl oxide_orm::ModelHooks for #name { const OUTBOX: bool = true; } })
+    } else {
+        Some(quote! { impl oxide_orm::ModelHooks for #name {} })
+    };
+    let database = string_arg(&args, "database").map(|database| {
+        quote! { const DATABASE: Option<&'static str> = Some(#database); }
+    });
+    let default_scope = string_arg(&args, "default_scope").map(|scopes| {
+        let scopes = scopes
+            .split(',')
+            .map(|scope| format_ident!("{}", scope.trim()));
+        quote! {
+            fn default_scope(
+                query: oxide_orm::OxideQueryBuilder<Self, #columns_name>,
+            ) -> oxide_orm::OxideQueryBuilder<Self, #columns_name> {
+                query #(.scope(Self::#scopes))*
+            }
+        }
+    });
+    let (key_type, key_value) = if key_idents.is_empty() {
+        (quote! { () }, quote! {})
+    } else if key_idents.len() == 1 {
+        let (ident, ty) = (&key_idents[0], key_types[0]);
+        (quote! { #ty }, quote! { self.#ident.clone() })
+    } else {
+        (
+            quote! { (#(#key_types,)*) },
+            quote! { (#(self.#key_idents.clone(),)*) },
+        )
+    };
+
+    // Models without a primary key, such as views and log tables, get no key-based methods.
+    let key_methods = (!key_idents.is_empty()).then(|| {
+        quote! {
+        /// The row with primary key `key`, if there is one.
+        pub async fn find(
+            db: impl oxide_orm::IntoExecutor<'_>,
+            key: #key_type,
+        ) -> Result<Option<Self>, oxide_core::Error> {
+            Self::query().key(key).fetch_optional(db).await
+        }
+
+        /// An update of the row with primary key `key`.
+        pub fn update(key: #key_type) -> oxide_orm::OxideUpdateQueryBuilder<Self, #columns_name> {
+            oxide_orm::OxideUpdateQueryBuilder::new().key(key)
+        }
+
+        /// Writes this struct's field values to the row with its primary key and returns
+        /// the row as stored. Fails with `RowNotFound` if there is no such row.
+        pub async fn save<'a>(
+            &'a self,
+            db: impl oxide_orm::IntoExecutor<'_>,
+        ) -> Result<Self, oxide_core::Error>
+        where
+            #(&'a #save_types: oxide_orm::ToSql,)*
+            #(&'a #flatten_types: oxide_orm::EmbeddedValues,)*
+        {
+            let update = Self::update(#key_value)
+                #(
+                    .column_value(
+                        #save_names,
+                        oxide_orm::ToSql::to_value(&&self.#save_idents),
+                    )
+                )*;
+            #(
+                let update = oxide_orm::EmbeddedValues::column_values(
+                    &self.#flatten_idents,
+                    concat!(#flatten_names, "_"),
+                )
+                .into_iter()
+                .fold(update, |update, (column, value)| update.column_value(&column, value));
+            )*
+            update.fetch_one(db).await
+        }
+
+        }
+    });
+    // `user.delete(&db)` is a trait method so it can share its name with the `delete()`
+    // builder.
+    let delete_row_impl = (!key_idents.is_empty()).then(|| {
+        quote! {
+            impl oxide_orm::DeleteRow for #name {
+                fn delete<'a>(
+                    &'a self,
+                    db: impl oxide_orm::IntoExecutor<'a> + 'a,
+                ) -> oxide_orm::DeleteFuture<'a> {
+                    Box::pin(async move {
+                        let result = Self::delete_where()
+                            .key(#key_value)
+                            .execute(db)
+                            .await?;
+                        Ok(result.rows_affected() > 0)
+                    })
+                }
+            }
+        }
+    });
+
+    let relation_methods: Vec<_> = relations
+        .iter()
+        .map(|relation| {
+            let (target, foreign_key) = (&relation.target, &relation.foreign_key);
+            let target_name = snake_case(&target.segments.last().unwrap().ident.to_string());
+            match relation.kind {
+                RelationKind::HasMany => {
+                    if key_idents.len() != 1 {
+                        panic!("`has_many` needs a single-column primary key on `{}`", name);
+                    }
+                    let key = &key_idents[0];
+                    let method = relation
+                        .name
+                        .clone()
+                        .unwrap_or_else(|| format_ident!("{}s", target_name));
+                    quote! {
+                        /// The rows of the related model whose foreign key is this row's key.
+                        pub async fn #method(
+                            &self,
+                            db: impl oxide_orm::IntoExecutor<'_>,
+                        ) -> Result<Vec<#target>, oxide_core::Error> {
+                            #target::query()
+                                .and_where(#target::columns().#foreign_key, self.#key.clone())
+                                .fetch_all(db)
+                                .await
+                        }
+                    }
+                }
+                RelationKind::BelongsTo => {
+                    let method = relation
+                        .name
+                        .clone()
+                        .unwrap_or_else(|| format_ident!("{}", target_name));
+                    let field = fields
+                        .iter()
+                        .find(|f| f.ident.as_ref() == Some(foreign_key))
+                        .unwrap_or_else(|| {
+                            panic!("Foreign key `{}` is not a field of `{}`", foreign_key, name)
+                        });
+                    if option_inner(&field.ty).is_some() {
+                        quote! {
+                            /// The row this one refers to, or `None` when the foreign key is.
+                            pub async fn #method(
+                                &self,
+                                db: impl oxide_orm::IntoExecutor<'_>,
+                            ) -> Result<Option<#target>, oxide_core::Error> {
+                                match &self.#foreign_key {
+                                    Some(key) => #target::query().key(key.clone()).fetch_optional(db).await,
+                                    None => Ok(None),
+                                }
+                            }
+                        }
+                    } else {
+                        quote! {
+                            /// The row this one refers to.
+                            pub async fn #method(
+                                &self,
+                                db: impl oxide_orm::IntoExecutor<'_>,
+                            ) -> Result<#target, oxide_core::Error> {
+                                #target::query()
+                                    .key(self.#foreign_key.clone())
+                                    .fetch_one(db)
+                                    .await
+                            }
+                        }
+                    }
+                }
+            }
+        })
+        .collect();
 
     // Generate code to modify the struct definition and add implementations
     let output = quote! {
         #[derive(
-            Debug, Clone, serde::Serialize, serde::Deserialize, sqlx::FromRow
+            Debug, Clone, serde::Serialize, serde::Deserialize, #from_row_derive
         )]
         #input
 
+        #from_row_impl
+
+        #new_struct
+
         #[derive(Debug, Clone)]
         pub struct #columns_name {
             #(
-                pub #field_idents: Column<#name, #field_types>,
+                pub #field_idents: #column_types,
             )*
         }
 
+        impl oxide_orm::HasColumns for #name {
+            type Columns = #columns_name;
+        }
+
         impl ModelColumns for #columns_name {
             type Model = #name;
         }
 
         impl Model<#columns_name> for #name {
             const TABLE: &'static str = stringify!(#table_name);
+            const PRIMARY_KEY: &'static [&'static str] = &[#(#key_names),*];
+            type Key = #key_type;
+            #key_default
+            #database
+            #default_scope
 
             fn columns() -> #columns_name {
                 #columns_name {
                     #(
-                        #field_idents: Column::new(stringify!(#field_idents)),
+                        #field_idents: #column_inits,
                     )*
                 }
             }
+
+            fn primary_key(&self) -> #key_type {
+                #key_value
+            }
+        }
+
+        impl oxide_orm::Validate for #name {
+            const RULES: &'static [(&'static str, oxide_orm::Rule)] = &[#(#rules),*];
         }
 
+        #hooks_impl
+
+        #delete_row_impl
+
         impl #name {
             pub fn table() -> &'static str {
                 Self::TABLE
@@ -109,14 +883,108 @@ pub fn model(_attr: TokenStream, item: TokenStream) -> TokenStream {
                 oxide_orm::OxideQueryBuilder::new()
             }
 
+            /// The first row by primary key where `column` equals `value`, e.g.
+            /// `find_by(&db, Self::columns().email, email)`.
+            pub async fn find_by<T: oxide_orm::ToSql>(
+                db: impl oxide_orm::IntoExecutor<'_>,
+                column: oxide_orm::Column<Self, T>,
+                value: T,
+            ) -> Result<Option<Self>, oxide_core::Error> {
+                Self::query()
+                    .and_where(column, value)
+                    .order_by_key(oxide_orm::Direction::Asc)
+                    .limit(1)
+                    .fetch_optional(db)
+                    .await
+            }
+
+            /// Every row in the table, in no particular order.
+            pub async fn all(
+                db: impl oxide_orm::IntoExecutor<'_>,
+            ) -> Result<Vec<Self>, oxide_core::Error> {
+                Self::query().fetch_all(db).await
+            }
+
+            /// The row with the lowest primary key, if the table isn't empty.
+            pub async fn first(
+                db: impl oxide_orm::IntoExecutor<'_>,
+            ) -> Result<Option<Self>, oxide_core::Error> {
+                Self::query()
+                    .order_by_key(oxide_orm::Direction::Asc)
+                    .limit(1)
+                    .fetch_optional(db)
+                    .await
+            }
+
             pub fn insert() -> oxide_orm::OxideInsertQueryBuilder<Self, #columns_name> {
                 oxide_orm::OxideInsertQueryBuilder::new()
             }
 
-            pub fn update(id: i32) -> oxide_orm::OxideUpdateQueryBuilder<Self, #columns_name> {
-                oxide_orm::OxideUpdateQueryBuilder::new(id)
+            /// A multi-row insert, e.g. `insert_many().rows(items.iter().map(Self::to_insert))`.
+            pub fn insert_many() -> oxide_orm::OxideBulkInsertBuilder<Self, #columns_name> {
+                oxide_orm::OxideBulkInsertBuilder::new()
             }
 
+            /// An insert of this model's field values, leaving `#[column(default)]` and
+            /// `#[column(generated)]` fields to the database.
+            pub fn to_insert<'a>(&'a self) -> oxide_orm::OxideInsertQueryBuilder<Self, #columns_name>
+            where
+                #(&'a #insert_types: oxide_orm::ToSql,)*
+                #(&'a #flatten_types: oxide_orm::EmbeddedValues,)*
+            {
+                let insert = oxide_orm::OxideInsertQueryBuilder::new()
+                    #(
+                        .column_value(
+                            #insert_names,
+                            oxide_orm::ToSql::to_value(&&self.#insert_idents),
+                        )
+                    )*;
+                #(
+                    let insert = oxide_orm::EmbeddedValues::column_values(
+                        &self.#flatten_idents,
+                        concat!(#flatten_names, "_"),
+                    )
+                    .into_iter()
+                    .fold(insert, |insert, (column, value)| insert.column_value(&column, value));
+                )*
+                insert
+            }
+
+            #create_method
+
+            /// The table as this model expects it, compared with the database by
+            /// `migrate generate`. `Option` fields are nullable.
+            pub fn table_def<'a>() -> oxide_orm::TableDef
+            where
+                #(&'a #scalar_types: oxide_orm::ToSql,)*
+                #(&'a #flatten_types: oxide_orm::EmbeddedSchema,)*
+            {
+                let mut columns = vec![];
+                #(#column_defs)*
+                oxide_orm::TableDef::new(Self::TABLE, Self::PRIMARY_KEY, columns)
+            }
+
+            /// An update of every row matching the conditions added to it, e.g.
+            /// `update_where().filter(...).set(...)`.
+            pub fn update_where() -> oxide_orm::OxideUpdateQueryBuilder<Self, #columns_name> {
+                oxide_orm::OxideUpdateQueryBuilder::new()
+            }
+
+            /// A delete of every row matching the conditions added to it, e.g.
+            /// `delete().and_where(...)`.
+            pub fn delete() -> oxide_orm::OxideDeleteQueryBuilder<Self, #columns_name> {
+                oxide_orm::OxideDeleteQueryBuilder::new()
+            }
+
+            /// `delete()`, named to match `update_where()`.
+            pub fn delete_where() -> oxide_orm::OxideDeleteQueryBuilder<Self, #columns_name> {
+                oxide_orm::OxideDeleteQueryBuilder::new()
+            }
+
+            #key_methods
+
+            #(#relation_methods)*
+
             pub fn get_field<T: Clone>(&self, field: &Option<T>) -> Option<T> {
                 field.clone()
             }
@@ -130,6 +998,366 @@ pub fn model(_attr: TokenStream, item: TokenStream) -> TokenStream {
     output.into()
 }
 
+/// Declares a value object that models can embed with `#[column(flatten)]`, stored as columns
+/// prefixed with the embedding field's name.
+///
+/// # Usage
+/// ```rust,ignore
+/// #[embeddable]
+/// pub struct Address {
+///     pub city: String,
+///     pub postcode: String,
+/// }
+/// ```
+///
+/// # What it does
+/// 1. Derives `Debug`, `Clone`, `serde::Serialize` and `serde::Deserialize`.
+/// 2. Generates an `AddressColumns<M>` struct, reached through the embedding model's columns.
+/// 3. Implements `Embedded`, for reading the struct from prefixed columns of a row, and
+///    `EmbeddedValues` and `EmbeddedSchema` for `&Address`, for `to_insert()` and
+///    `table_def()`.
+#[proc_macro_attribute]
+pub fn embeddable(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as ItemStruct);
+    let name = &input.ident;
+    let columns_name = format_ident!("{}Columns", name);
+
+    let fields = match input.fields {
+        syn::Fields::Named(ref named) => &named.named,
+        _ => panic!("Only named fields are supported"),
+    };
+
+    let field_idents: Vec<_> = fields.iter().map(|f| &f.ident).collect();
+    let field_types: Vec<_> = fields.iter().map(|f| &f.ty).collect();
+    let scalar_types: Vec<_> = fields
+        .iter()
+        .map(|f| option_inner(&f.ty).unwrap_or(&f.ty))
+        .collect();
+    let nullable: Vec<_> = fields
+        .iter()
+        .map(|f| option_inner(&f.ty).is_some())
+        .collect();
+
+    let output = quote! {
+        #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+        #input
+
+        #[derive(Debug, Clone)]
+        pub struct #columns_name<M> {
+            #(
+                pub #field_idents: oxide_orm::Column<M, #field_types>,
+            )*
+        }
+
+        impl oxide_orm::Embedded for #name {
+            type Columns<M> = #columns_name<M>;
+
+            fn columns<M>(prefix: &str) -> #columns_name<M> {
+                #columns_name {
+                    #(
+                        #field_idents: oxide_orm::Column::prefixed(prefix, stringify!(#field_idents)),
+                    )*
+                }
+            }
+
+            fn from_row(row: &sqlx::postgres::PgRow, prefix: &str) -> Result<Self, sqlx::Error> {
+                Ok(Self {
+                    #(
+                        #field_idents: sqlx::Row::try_get(
+                            row,
+                            format!("{}{}", prefix, stringify!(#field_idents)).as_str(),
+                        )?,
+                    )*
+                })
+            }
+        }
+
+        impl<'a> oxide_orm::EmbeddedValues for &'a #name
+        where
+            #(&'a #field_types: oxide_orm::ToSql,)*
+        {
+            fn column_values(self, prefix: &str) -> Vec<(String, oxide_orm::SqlValue)> {
+                vec![
+                    #(
+                        (
+                            format!("{}{}", prefix, stringify!(#field_idents)),
+                            oxide_orm::ToSql::to_value(&&self.#field_idents),
+                        ),
+                    )*
+                ]
+            }
+        }
+
+        impl<'a> oxide_orm::EmbeddedSchema for &'a #name
+        where
+            #(&'a #scalar_types: oxide_orm::ToSql,)*
+        {
+            fn column_defs(prefix: &str) -> Vec<oxide_orm::ColumnDef> {
+                vec![
+                    #(
+                        oxide_orm::ColumnDef::new(
+                            format!("{}{}", prefix, stringify!(#field_idents)),
+                            <&'a #scalar_types as oxide_orm::ToSql>::sql_type(),
+                            #nullable,
+                        ),
+                    )*
+                ]
+            }
+        }
+    };
+
+    output.into()
+}
+
+/// Options from a field's `#[column(...)]` attributes.
+#[derive(Default)]
+struct ColumnOptions {
+    default: bool,
+    generated: bool,
+    flatten: bool,
+    skip: bool,
+    /// The database column's name, when it differs from the field's.
+    name: Option<String>,
+}
+
+/// Reads and removes the field's `#[column(...)]` attributes, which aren't real attributes.
+fn column_options(field: &mut syn::Field) -> ColumnOptions {
+    let mut options = ColumnOptions::default();
+    field.attrs.retain(|attr| {
+        if !attr.path().is_ident("column") {
+            return true;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("default") {
+                options.default = true;
+            } else if meta.path.is_ident("generated") {
+                options.generated = true;
+            } else if meta.path.is_ident("flatten") {
+                options.flatten = true;
+            } else if meta.path.is_ident("skip") {
+                options.skip = true;
+            } else if meta.path.is_ident("name") {
+                options.name = Some(meta.value()?.parse::<syn::LitStr>()?.value());
+            } else {
+                return Err(meta.error(
+                    "expected `default`, `generated`, `flatten`, `skip` or `name = \"...\"`",
+                ));
+            }
+            Ok(())
+        })
+        .unwrap_or_else(|e| panic!("Invalid #[column] attribute: {}", e));
+        false
+    });
+    options
+}
+
+/// Reads and removes the field's `#[validate(...)]` attributes, returning an
+/// `oxide_orm::Rule` expression for each rule.
+fn validate_rules(field: &mut syn::Field) -> Vec<proc_macro2::TokenStream> {
+    let mut rules = vec![];
+    field.attrs.retain(|attr| {
+        if !attr.path().is_ident("validate") {
+            return true;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("email") {
+                rules.push(quote! { oxide_orm::Rule::Email });
+                return Ok(());
+            }
+            let (kind, bound) = if meta.path.is_ident("length") {
+                ("Length", quote! { usize })
+            } else if meta.path.is_ident("range") {
+                ("Range", quote! { f64 })
+            } else {
+                return Err(meta.error("expected `email`, `length(...)` or `range(...)`"));
+            };
+            let (mut min, mut max) = (quote! { None }, quote! { None });
+            meta.parse_nested_meta(|bounds| {
+                let value = bounds.value()?.parse::<Expr>()?;
+                let value = quote! { Some((#value) as #bound) };
+                if bounds.path.is_ident("min") {
+                    min = value;
+                } else if bounds.path.is_ident("max") {
+                    max = value;
+                } else {
+                    return Err(bounds.error("expected `min = ...` or `max = ...`"));
+                }
+                Ok(())
+            })?;
+            let kind = format_ident!("{}", kind);
+            rules.push(quote! { oxide_orm::Rule::#kind { min: #min, max: #max } });
+            Ok(())
+        })
+        .unwrap_or_else(|e| panic!("Invalid #[validate] attribute: {}", e));
+        false
+    });
+    rules
+}
+
+enum RelationKind {
+    HasMany,
+    BelongsTo,
+}
+
+/// A `#[has_many(Target, foreign_key = "...")]` or `#[belongs_to(...)]` declaration.
+struct Relation {
+    kind: RelationKind,
+    target: syn::Path,
+    foreign_key: syn::Ident,
+    name: Option<syn::Ident>,
+}
+
+/// Reads and removes the struct's relation attributes, which aren't real attributes.
+fn relations(input: &mut ItemStruct) -> Vec<Relation> {
+    let mut relations = vec![];
+    input.attrs.retain(|attr| {
+        let kind = if attr.path().is_ident("has_many") {
+            RelationKind::HasMany
+        } else if attr.path().is_ident("belongs_to") {
+            RelationKind::BelongsTo
+        } else {
+            return true;
+        };
+        let args = attr
+            .parse_args_with(Punctuated::<syn::Meta, Token![,]>::parse_terminated)
+            .unwrap_or_else(|e| panic!("Invalid relation attribute: {}", e));
+        let mut args = args.into_iter();
+        let target = match args.next() {
+            Some(syn::Meta::Path(path)) => path,
+            _ => panic!("Relations start with the related model, e.g. `has_many(Post, ...)`"),
+        };
+        let (mut foreign_key, mut name) = (None, None);
+        for arg in args {
+            let syn::Meta::NameValue(arg) = arg else {
+                panic!("Relation options are `foreign_key = \"...\"` and `name = \"...\"`");
+            };
+            let value = match &arg.value {
+                Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(value),
+                    ..
+                }) => format_ident!("{}", value.value()),
+                _ => panic!("Relation options must be strings, e.g. `foreign_key = \"user_id\"`"),
+            };
+            if arg.path.is_ident("foreign_key") {
+                foreign_key = Some(value);
+            } else if arg.path.is_ident("name") {
+                name = Some(value);
+            } else {
+                panic!("Relation options are `foreign_key = \"...\"` and `name = \"...\"`");
+            }
+        }
+        relations.push(Relation {
+            kind,
+            target,
+            foreign_key: foreign_key.expect("Relations need `foreign_key = \"...\"`"),
+            name,
+        });
+        false
+    });
+    relations
+}
+
+/// `BlogPost` -> `blog_post`, for naming relation accessors.
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+/// `T` for an `Option<T>` field, which maps to a nullable column.
+fn option_inner(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first()? {
+            syn::GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The primary key fields named by `pk`, else the `id` field, else none.
+fn primary_key(
+    args: &Punctuated<MetaNameValue, Token![,]>,
+    fields: &Punctuated<syn::Field, Token![,]>,
+) -> syn::Result<Vec<syn::Ident>> {
+    let Some(arg) = args.iter().find(|arg| arg.path.is_ident("pk")) else {
+        let id = format_ident!("id");
+        let has_id = fields.iter().any(|f| f.ident.as_ref() == Some(&id));
+        return Ok(if has_id { vec![id] } else { vec![] });
+    };
+
+    let ident = |expr: &Expr| match expr {
+        Expr::Path(path) if path.path.get_ident().is_some() => path.path.segments[0].ident.clone(),
+        _ => panic!("`pk` must name fields, e.g. `pk = id` or `pk = (tenant_id, user_id)`"),
+    };
+
+    let key_idents: Vec<_> = match &arg.value {
+        Expr::Tuple(tuple) => tuple.elems.iter().map(ident).collect(),
+        Expr::Paren(paren) => vec![ident(&paren.expr)],
+        expr => vec![ident(expr)],
+    };
+    match key_idents
+        .iter()
+        .find(|ident| !fields.iter().any(|f| f.ident.as_ref() == Some(*ident)))
+    {
+        Some(missing) => Err(syn::Error::new(
+            missing.span(),
+            format!("Primary key `{}` is not a field of the model", missing),
+        )),
+        None => Ok(key_idents),
+    }
+}
+
+/// The `KeyDefault` variant named by `key_default = "..."`, if given.
+fn key_default(args: &Punctuated<MetaNameValue, Token![,]>) -> Option<syn::Ident> {
+    match string_arg(args, "key_default")?.as_str() {
+        "database" => Some(format_ident!("Database")),
+        "uuid_v4" => Some(format_ident!("UuidV4")),
+        "uuid_v7" => Some(format_ident!("UuidV7")),
+        other => panic!(
+            "Unknown `key_default` \"{}\", expected \"database\", \"uuid_v4\" or \"uuid_v7\"",
+            other
+        ),
+    }
+}
+
+/// The value of a `name = "..."` argument, if given.
+fn string_arg(args: &Punctuated<MetaNameValue, Token![,]>, name: &str) -> Option<String> {
+    let arg = args.iter().find(|arg| arg.path.is_ident(name))?;
+    match &arg.value {
+        Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(value),
+            ..
+        }) => Some(value.value()),
+        _ => panic!("`{}` must be a string, e.g. `{} = \"...\"`", name, name),
+    }
+}
+
+fn bool_arg(args: &Punctuated<MetaNameValue, Token![,]>, name: &str) -> bool {
+    let Some(arg) = args.iter().find(|arg| arg.path.is_ident(name)) else {
+        return false;
+    };
+    match &arg.value {
+        Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Bool(value),
+            ..
+        }) => value.value,
+        _ => panic!("`{}` must be `true` or `false`", name),
+    }
+}
+
 /// Converts an async function into a compatible HTTP request handler for the Oxide framework.
 ///
 /// # Usage
@@ -164,8 +1392,36 @@ pub fn model(_attr: TokenStream, item: TokenStream) -> TokenStream {
 /// // The macro creates a static `get_user_handler` that you can register
 /// app.route("/user", Method::GET, get_user_handler);
 /// ```
+///
+/// # Caching
+/// `cache(models(...), ttl = "...")` caches the handler's successful GET responses for the
+/// time to live (`"500ms"`, `"60s"`, `"5m"` or `"1h"`; 60 seconds if left out), and drops them
+/// as soon as one of the listed models is inserted, updated or deleted through the ORM. List
+/// every model the response is derived from.
+/// ```rust,ignore
+/// #[handler(cache(models(User, Post), ttl = "60s"))]
+/// async fn feed(ctx: &Context) -> OxideResponse {
+///     // Reads users and posts
+///     OxideResponse::json(OxideRes::Success, feed)
+/// }
+/// ```
+/// Responses are cached per path and query string, tenant, principal, and `Authorization`,
+/// `Cookie`, `Accept` and `Accept-Language` headers, in the cache `Server::with_response_cache`
+/// sizes; responses that set a cookie aren't cached. Writes inside a transaction invalidate
+/// once it commits. Writes made with raw SQL don't invalidate anything; follow them with
+/// `oxide_core::http::invalidate_responses(table)`.
 #[proc_macro_attribute]
-pub fn handler(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn handler(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut cache = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("cache") {
+            cache = Some(parse_handler_cache(meta)?);
+            Ok(())
+        } else {
+            Err(meta.error("expected `cache(models(...), ttl = \"...\")`"))
+        }
+    });
+    parse_macro_input!(attr with parser);
     let input_fn = parse_macro_input!(item as ItemFn);
     let fn_name = &input_fn.sig.ident;
     let handler_name = format!("{}_handler", fn_name);
@@ -173,6 +1429,19 @@ pub fn handler(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let fn_block = &input_fn.block;
     let fn_vis = &input_fn.vis;
     let fn_attrs = &input_fn.attrs;
+    let handle = match cache {
+        Some(HandlerCache { models, ttl_ms }) => quote! {
+            |ctx| Box::pin(async move {
+                ctx.cached(
+                    &[#(<#models as oxide_orm::Model<_>>::TABLE),*],
+                    ::std::time::Duration::from_millis(#ttl_ms),
+                    || #fn_name(ctx),
+                )
+                .await
+            })
+        },
+        None => quote! { |ctx| Box::pin(#fn_name(ctx)) },
+    };
 
     let output = quote! {
         #(#fn_attrs)*
@@ -180,7 +1449,259 @@ pub fn handler(_attr: TokenStream, item: TokenStream) -> TokenStream {
             #fn_block
 
         #[allow(non_upper_case_globals)]
-        pub static #handler_ident: fn(&Context) -> AsyncResponse<'_> = |ctx| Box::pin(#fn_name(ctx));
+        pub static #handler_ident: fn(&Context) -> AsyncResponse<'_> = #handle;
+    };
+
+    output.into()
+}
+
+/// A handler's `cache(...)` argument.
+struct HandlerCache {
+    models: Vec<syn::Path>,
+    ttl_ms: u64,
+}
+
+fn parse_handler_cache(meta: syn::meta::ParseNestedMeta) -> syn::Result<HandlerCache> {
+    let mut models = vec![];
+    let mut ttl_ms = 60_000;
+    meta.parse_nested_meta(|arg| {
+        if arg.path.is_ident("models") {
+            arg.parse_nested_meta(|model| {
+                models.push(model.path);
+                Ok(())
+            })
+        } else if arg.path.is_ident("ttl") {
+            let ttl = arg.value()?.parse::<syn::LitStr>()?;
+            ttl_ms = parse_ttl(&ttl.value()).ok_or_else(|| {
+                syn::Error::new(
+                    ttl.span(),
+                    "`ttl` must be like \"500ms\", \"60s\", \"5m\" or \"1h\"",
+                )
+            })?;
+            Ok(())
+        } else {
+            Err(arg.error("expected `models(...)` or `ttl = \"...\"`"))
+        }
+    })?;
+    if models.is_empty() {
+        return Err(meta.error(
+            "`cache` needs the models the response is derived from, e.g. `cache(models(User))`",
+        ));
+    }
+    Ok(HandlerCache { models, ttl_ms })
+}
+
+/// Milliseconds in a duration such as `"500ms"`, `"60s"`, `"5m"` or `"1h"`.
+fn parse_ttl(ttl: &str) -> Option<u64> {
+    let ttl = ttl.trim();
+    let split = ttl.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = ttl.split_at(split);
+    let amount: u64 = amount.parse().ok()?;
+    let unit_ms = match unit.trim() {
+        "ms" => 1,
+        "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        _ => return None,
+    };
+    amount.checked_mul(unit_ms)
+}
+
+/// Stores a Rust enum of unit variants in a Postgres enum type or a text column.
+///
+/// # Usage
+/// ```rust,ignore
+/// #[derive(Debug, Clone, Copy, PartialEq, SqlEnum)]
+/// #[sql_enum(type_name = "order_status")]
+/// pub enum OrderStatus {
+///     Pending,
+///     Paid,
+///     #[sql_enum(rename = "refunded_in_full")]
+///     Refunded,
+/// }
+///
+/// #[model]
+/// pub struct Order {
+///     pub id: i32,
+///     pub status: OrderStatus,
+/// }
+///
+/// let paid: Vec<Order> = Order::query()
+///     .and_where(Order::columns().status, OrderStatus::Paid)
+///     .fetch_all(&db)
+///     .await?;
+/// ```
+///
+/// # What it does
+/// Variants are stored as their names in snake case (`Pending` as `pending`) unless renamed.
+/// The macro implements:
+/// 1. `SqlEnum`, with the stored values and the DDL for them: `create_type()` and
+///    `drop_type()` for the Postgres enum type, or `check(column)` for a text column.
+/// 2. `ToSql`, so the enum can be a model field and a query value.
+/// 3. sqlx's `Type`, `Encode` and `Decode`, reading rows with an unknown value as an error,
+///    and `PgHasArrayType`, so `Vec<OrderStatus>` fields map to `order_status[]` columns.
+/// 4. `serde::Serialize` and `serde::Deserialize` as the stored values, so JSON bodies use
+///    them too. Don't derive these yourself.
+///
+/// Without `type_name` the values are stored as `TEXT`; pair the column with
+/// `OrderStatus::check("status")` to keep other values out.
+#[proc_macro_derive(SqlEnum, attributes(sql_enum))]
+pub fn sql_enum(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    let name = &input.ident;
+    let mut type_name = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("sql_enum")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("type_name") {
+                type_name = Some(meta.value()?.parse::<syn::LitStr>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error("expected `type_name = \"...\"`"))
+            }
+        })
+        .unwrap_or_else(|e| panic!("Invalid #[sql_enum] attribute: {}", e));
+    }
+
+    let Data::Enum(data) = &input.data else {
+        panic!("SqlEnum can only be derived for enums");
+    };
+    let mut variants = vec![];
+    let mut values = vec![];
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            panic!(
+                "SqlEnum variants can't have fields, but {} does",
+                variant.ident
+            );
+        }
+        let mut value = snake_case(&variant.ident.to_string());
+        for attr in variant
+            .attrs
+            .iter()
+            .filter(|a| a.path().is_ident("sql_enum"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    value = meta.value()?.parse::<syn::LitStr>()?.value();
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `rename = \"...\"`"))
+                }
+            })
+            .unwrap_or_else(|e| panic!("Invalid #[sql_enum] attribute: {}", e));
+        }
+        variants.push(&variant.ident);
+        values.push(value);
+    }
+
+    let (type_name_const, sql_type, to_value, type_info, compatible, array_type_info) =
+        match &type_name {
+            Some(type_name) => (
+                quote! { Some(#type_name) },
+                quote! { oxide_orm::SqlType::Enum(#type_name) },
+                quote! {
+                    oxide_orm::SqlValue::Enum(#type_name, oxide_orm::SqlEnum::as_sql_str(self))
+                },
+                quote! { sqlx::postgres::PgTypeInfo::with_name(#type_name) },
+                quote! { *ty == <Self as sqlx::Type<sqlx::Postgres>>::type_info() },
+                quote! { sqlx::postgres::PgTypeInfo::array_of(#type_name) },
+            ),
+            None => (
+                quote! { None },
+                quote! { oxide_orm::SqlType::Text },
+                quote! {
+                    oxide_orm::SqlValue::Text(oxide_orm::SqlEnum::as_sql_str(self).to_string())
+                },
+                quote! { <&str as sqlx::Type<sqlx::Postgres>>::type_info() },
+                quote! { <&str as sqlx::Type<sqlx::Postgres>>::compatible(ty) },
+                quote! { <&str as sqlx::postgres::PgHasArrayType>::array_type_info() },
+            ),
+        };
+    let enum_name = name.to_string();
+
+    let output = quote! {
+        impl oxide_orm::SqlEnum for #name {
+            const TYPE_NAME: Option<&'static str> = #type_name_const;
+            const VALUES: &'static [&'static str] = &[#(#values),*];
+
+            fn as_sql_str(&self) -> &'static str {
+                match self {
+                    #(Self::#variants => #values,)*
+                }
+            }
+
+            fn from_sql_str(value: &str) -> Option<Self> {
+                match value {
+                    #(#values => Some(Self::#variants),)*
+                    _ => None,
+                }
+            }
+        }
+
+        impl oxide_orm::ToSql for #name {
+            fn sql_type() -> oxide_orm::SqlType {
+                #sql_type
+            }
+            fn to_sql(&self) -> String {
+                oxide_orm::ToSql::to_value(self).to_sql()
+            }
+            fn to_value(&self) -> oxide_orm::SqlValue {
+                #to_value
+            }
+        }
+
+        impl sqlx::Type<sqlx::Postgres> for #name {
+            fn type_info() -> sqlx::postgres::PgTypeInfo {
+                #type_info
+            }
+            fn compatible(ty: &sqlx::postgres::PgTypeInfo) -> bool {
+                #compatible
+            }
+        }
+
+        impl sqlx::postgres::PgHasArrayType for #name {
+            fn array_type_info() -> sqlx::postgres::PgTypeInfo {
+                #array_type_info
+            }
+        }
+
+        impl sqlx::Encode<'_, sqlx::Postgres> for #name {
+            fn encode_by_ref(
+                &self,
+                buf: &mut sqlx::postgres::PgArgumentBuffer,
+            ) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+                <&str as sqlx::Encode<sqlx::Postgres>>::encode_by_ref(
+                    &oxide_orm::SqlEnum::as_sql_str(self),
+                    buf,
+                )
+            }
+        }
+
+        impl<'r> sqlx::Decode<'r, sqlx::Postgres> for #name {
+            fn decode(value: sqlx::postgres::PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+                let value = <&str as sqlx::Decode<sqlx::Postgres>>::decode(value)?;
+                <Self as oxide_orm::SqlEnum>::from_sql_str(value)
+                    .ok_or_else(|| format!("{:?} is not a {}", value, #enum_name).into())
+            }
+        }
+
+        impl serde::Serialize for #name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(oxide_orm::SqlEnum::as_sql_str(self))
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for #name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let value = <String as serde::Deserialize>::deserialize(deserializer)?;
+                <Self as oxide_orm::SqlEnum>::from_sql_str(&value).ok_or_else(|| {
+                    serde::de::Error::unknown_variant(
+                        &value,
+                        <Self as oxide_orm::SqlEnum>::VALUES,
+                    )
+                })
+            }
+        }
     };
 
     output.into()
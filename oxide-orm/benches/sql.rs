@@ -0,0 +1,68 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use oxide_orm::{model, prelude::*};
+
+#[model]
+pub struct User {
+    pub id: i32,
+    pub name: String,
+    pub email: String,
+    pub age: i32,
+    pub active: bool,
+}
+
+fn select(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sql/select");
+    group.bench_function("all", |b| b.iter(|| User::query().build()));
+    group.bench_function("where", |b| {
+        b.iter(|| {
+            User::query()
+                .select([User::columns().id, User::columns().age])
+                .and_where(User::columns().id, black_box(42))
+                .build()
+        })
+    });
+    group.bench_function("groups", |b| {
+        b.iter(|| {
+            User::query()
+                .and_where(User::columns().active, black_box(true))
+                .and_group(|q| {
+                    q.or_where(User::columns().name, black_box("Alice".to_string()))
+                        .or_where(User::columns().name, black_box("Bob".to_string()))
+                })
+                .build()
+        })
+    });
+    group.finish();
+}
+
+fn write(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sql/write");
+    group.bench_function("insert", |b| {
+        b.iter(|| {
+            User::insert()
+                .value(User::columns().name, black_box("Alice".to_string()))
+                .value(
+                    User::columns().email,
+                    black_box("alice@example.com".to_string()),
+                )
+                .value(User::columns().age, black_box(30))
+                .value(User::columns().active, black_box(true))
+                .build()
+        })
+    });
+    group.bench_function("update", |b| {
+        b.iter(|| {
+            User::update(black_box(42))
+                .set(
+                    User::columns().email,
+                    black_box("alice@example.org".to_string()),
+                )
+                .set(User::columns().active, black_box(false))
+                .build()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, select, write);
+criterion_main!(benches);
@@ -1 +1,309 @@
+#[cfg(feature = "fixtures")]
+use std::future::Future;
 
+#[cfg(feature = "fixtures")]
+use oxide_core::datasource::FixtureMode;
+use oxide_core::{
+    http::{invalidate_responses, Context},
+    Error, PgDatabase, PgTransaction, PgTransactionRef, RowStream,
+};
+#[cfg(feature = "fixtures")]
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{
+    postgres::{PgQueryResult, PgRow},
+    FromRow,
+};
+
+use crate::{types::bind_all, SqlValue};
+
+/// Where the query builders get their connection. A `PgDatabase` is used as given, while a
+/// handler's `Context` picks the pool named by the model's `#[model(database = "...")]`, or
+/// the server's main datasource for models without one, and sends selects to a read replica
+/// of it when one is registered.
+pub trait DatabaseSource {
+    fn database(&self, name: Option<&str>) -> Result<&PgDatabase, Error>;
+
+    /// The pool selects run against; the primary unless overridden.
+    fn read_database(&self, name: Option<&str>) -> Result<&PgDatabase, Error> {
+        self.database(name)
+    }
+}
+
+impl<T: DatabaseSource + ?Sized> DatabaseSource for &T {
+    fn database(&self, name: Option<&str>) -> Result<&PgDatabase, Error> {
+        (**self).database(name)
+    }
+
+    fn read_database(&self, name: Option<&str>) -> Result<&PgDatabase, Error> {
+        (**self).read_database(name)
+    }
+}
+
+impl DatabaseSource for PgDatabase {
+    fn database(&self, _name: Option<&str>) -> Result<&PgDatabase, Error> {
+        Ok(self)
+    }
+}
+
+impl DatabaseSource for Context {
+    fn database(&self, name: Option<&str>) -> Result<&PgDatabase, Error> {
+        match name {
+            Some(name) => self
+                .db_named(name)
+                .ok_or_else(|| Error::Config(format!("No datasource named '{}'", name))),
+            None => self
+                .db()
+                .ok_or_else(|| Error::Config("No datasource configured".to_string())),
+        }
+    }
+
+    fn read_database(&self, name: Option<&str>) -> Result<&PgDatabase, Error> {
+        match name {
+            Some(name) => self
+                .db_named_read(name)
+                .ok_or_else(|| Error::Config(format!("No datasource named '{}'", name))),
+            None => self
+                .db_read()
+                .ok_or_else(|| Error::Config("No datasource configured".to_string())),
+        }
+    }
+}
+
+/// What a query builder runs against: a pool, or the connection of an open transaction.
+/// Builders take anything that is [`IntoExecutor`], so the same query runs with `&db`,
+/// `&ctx` or `&mut tx`.
+pub enum Executor<'a> {
+    Pool(&'a PgDatabase),
+    Transaction(PgTransactionRef<'a>),
+}
+
+/// Converts a [`DatabaseSource`] reference or a `&mut PgTransaction` into an [`Executor`].
+/// `database` is the model's `#[model(database = "...")]`, which transactions ignore since
+/// they are already bound to a pool.
+pub trait IntoExecutor<'a>: Send {
+    fn executor(self, database: Option<&str>) -> Result<Executor<'a>, Error>;
+
+    /// The executor for a select, which may be a read replica. Transactions read from their
+    /// own connection, so they see their own writes.
+    fn read_executor(self, database: Option<&str>) -> Result<Executor<'a>, Error>
+    where
+        Self: Sized,
+    {
+        self.executor(database)
+    }
+}
+
+impl<'a, S: DatabaseSource + Sync + ?Sized> IntoExecutor<'a> for &'a S {
+    fn executor(self, database: Option<&str>) -> Result<Executor<'a>, Error> {
+        Ok(Executor::Pool(self.database(database)?))
+    }
+
+    fn read_executor(self, database: Option<&str>) -> Result<Executor<'a>, Error> {
+        Ok(Executor::Pool(self.read_database(database)?))
+    }
+}
+
+impl<'a> IntoExecutor<'a> for &'a mut PgTransaction<'_> {
+    fn executor(self, _database: Option<&str>) -> Result<Executor<'a>, Error> {
+        Ok(Executor::Transaction(self.reborrow()))
+    }
+}
+
+/// Types the query builders decode rows into.
+#[cfg(not(feature = "fixtures"))]
+pub trait FetchRow: for<'r> FromRow<'r, PgRow> + Send + Unpin {}
+
+#[cfg(not(feature = "fixtures"))]
+impl<T> FetchRow for T where T: for<'r> FromRow<'r, PgRow> + Send + Unpin {}
+
+/// Types the query builders decode rows into. With the `fixtures` feature they must also
+/// round-trip through serde, so results can be recorded to and replayed from
+/// [`Fixtures`](oxide_core::Fixtures); models and tuples of column types already do.
+#[cfg(feature = "fixtures")]
+pub trait FetchRow:
+    for<'r> FromRow<'r, PgRow> + Serialize + DeserializeOwned + Send + Unpin
+{
+}
+
+#[cfg(feature = "fixtures")]
+impl<T> FetchRow for T where
+    T: for<'r> FromRow<'r, PgRow> + Serialize + DeserializeOwned + Send + Unpin
+{
+}
+
+/// What a write reports, like sqlx's `PgQueryResult` but also built from
+/// [`Fixtures`](oxide_core::Fixtures) when a write is replayed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueryResult {
+    rows_affected: u64,
+}
+
+impl QueryResult {
+    pub fn rows_affected(&self) -> u64 {
+        self.rows_affected
+    }
+}
+
+impl From<PgQueryResult> for QueryResult {
+    fn from(result: PgQueryResult) -> Self {
+        Self {
+            rows_affected: result.rows_affected(),
+        }
+    }
+}
+
+impl<'a> Executor<'a> {
+    /// A shorter-lived executor on the same pool or connection, for running several
+    /// queries through one executor.
+    pub fn reborrow(&mut self) -> Executor<'_> {
+        match self {
+            Executor::Pool(db) => Executor::Pool(db),
+            Executor::Transaction(tx) => Executor::Transaction(tx.reborrow()),
+        }
+    }
+
+    /// Marks cached responses derived from `table` stale once a write through the executor is
+    /// visible to other connections: straight away on a pool, once a transaction commits.
+    pub fn invalidate_responses(&mut self, table: &'static str) {
+        match self {
+            Executor::Pool(_) => invalidate_responses(table),
+            Executor::Transaction(tx) => {
+                tx.after_commit(move || invalidate_responses(table));
+            }
+        }
+    }
+
+    pub fn fetch_stream<T>(self, query: String, values: Vec<SqlValue>) -> RowStream<'a, T>
+    where
+        T: FetchRow + 'a,
+    {
+        #[cfg(feature = "fixtures")]
+        let recorded = values.clone();
+        let args = match bind_all(values) {
+            Ok(args) => args,
+            Err(e) => return RowStream::from_rows(async { Err(e) }),
+        };
+        match self {
+            #[cfg(feature = "fixtures")]
+            Executor::Pool(db) if db.fixtures().is_some() => RowStream::from_rows(async move {
+                let rows = db.query_with(query.clone(), args);
+                replayable(db, &query, &recorded, rows).await
+            }),
+            Executor::Pool(db) => db.query_stream_with(query, args),
+            Executor::Transaction(tx) => tx.query_stream_with(query, args),
+        }
+    }
+
+    pub async fn fetch_all<T: FetchRow>(
+        self,
+        query: String,
+        values: Vec<SqlValue>,
+    ) -> Result<Vec<T>, Error> {
+        #[cfg(feature = "fixtures")]
+        let recorded = values.clone();
+        let args = bind_all(values)?;
+        match self {
+            #[cfg(feature = "fixtures")]
+            Executor::Pool(db) => {
+                let rows = db.query_with(query.clone(), args);
+                replayable(db, &query, &recorded, rows).await
+            }
+            #[cfg(not(feature = "fixtures"))]
+            Executor::Pool(db) => db.query_with(query, args).await,
+            Executor::Transaction(tx) => tx.query_with(query, args).await,
+        }
+    }
+
+    pub async fn fetch_one<T: FetchRow>(
+        self,
+        query: String,
+        values: Vec<SqlValue>,
+    ) -> Result<T, Error> {
+        #[cfg(feature = "fixtures")]
+        let recorded = values.clone();
+        let args = bind_all(values)?;
+        match self {
+            #[cfg(feature = "fixtures")]
+            Executor::Pool(db) => {
+                let row = db.query_one_with(query.clone(), args);
+                replayable(db, &query, &recorded, row).await
+            }
+            #[cfg(not(feature = "fixtures"))]
+            Executor::Pool(db) => db.query_one_with(query, args).await,
+            Executor::Transaction(tx) => tx.query_one_with(query, args).await,
+        }
+    }
+
+    pub async fn fetch_optional<T: FetchRow>(
+        self,
+        query: String,
+        values: Vec<SqlValue>,
+    ) -> Result<Option<T>, Error> {
+        #[cfg(feature = "fixtures")]
+        let recorded = values.clone();
+        let args = bind_all(values)?;
+        match self {
+            #[cfg(feature = "fixtures")]
+            Executor::Pool(db) => {
+                let row = db.query_optional_with(query.clone(), args);
+                replayable(db, &query, &recorded, row).await
+            }
+            #[cfg(not(feature = "fixtures"))]
+            Executor::Pool(db) => db.query_optional_with(query, args).await,
+            Executor::Transaction(tx) => tx.query_optional_with(query, args).await,
+        }
+    }
+
+    pub async fn execute(self, query: String, values: Vec<SqlValue>) -> Result<QueryResult, Error> {
+        #[cfg(feature = "fixtures")]
+        let recorded = values.clone();
+        let args = bind_all(values)?;
+        match self {
+            #[cfg(feature = "fixtures")]
+            Executor::Pool(db) => match db.fixtures() {
+                Some(fixtures) if fixtures.mode() == FixtureMode::Replay => {
+                    let rows_affected = fixtures.replay_result::<u64>(&query, params(&recorded))?;
+                    Ok(QueryResult { rows_affected })
+                }
+                Some(fixtures) => {
+                    let result = db.execute_with(query.clone(), args).await;
+                    let rows_affected = result.as_ref().map(PgQueryResult::rows_affected);
+                    fixtures.record_result(
+                        &query,
+                        params(&recorded),
+                        rows_affected.as_ref().map_err(|e| *e),
+                    )?;
+                    result.map(QueryResult::from)
+                }
+                None => db.execute_with(query, args).await.map(QueryResult::from),
+            },
+            #[cfg(not(feature = "fixtures"))]
+            Executor::Pool(db) => db.execute_with(query, args).await.map(QueryResult::from),
+            Executor::Transaction(tx) => tx.execute_with(query, args).await.map(QueryResult::from),
+        }
+    }
+}
+
+/// Runs `query` on the pool, through its fixtures if it has any.
+#[cfg(feature = "fixtures")]
+async fn replayable<T, F>(
+    db: &PgDatabase,
+    query: &str,
+    values: &[SqlValue],
+    run: F,
+) -> Result<T, Error>
+where
+    T: Serialize + DeserializeOwned,
+    F: Future<Output = Result<T, Error>>,
+{
+    match db.fixtures() {
+        Some(fixtures) => fixtures.run(query, params(values), run).await,
+        None => run.await,
+    }
+}
+
+/// Bound values as literals, identifying a query's results in fixtures.
+#[cfg(feature = "fixtures")]
+fn params(values: &[SqlValue]) -> Vec<String> {
+    values.iter().map(SqlValue::to_sql).collect()
+}
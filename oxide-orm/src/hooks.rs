@@ -0,0 +1,96 @@
+use std::{future::Future, pin::Pin};
+
+use oxide_core::Error;
+
+use crate::{SqlValue, ToSql};
+
+pub type HookFuture<'a> = Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+
+/// The columns an insert or update is about to write, or has written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Changes {
+    insert: bool,
+    pub(crate) values: Vec<(String, SqlValue)>,
+}
+
+impl Changes {
+    pub(crate) fn new(insert: bool, values: Vec<(String, SqlValue)>) -> Self {
+        Self { insert, values }
+    }
+
+    /// `true` for an insert, `false` for an update.
+    pub fn is_insert(&self) -> bool {
+        self.insert
+    }
+
+    pub fn get(&self, column: &str) -> Option<&SqlValue> {
+        self.values
+            .iter()
+            .find(|(c, _)| c == column)
+            .map(|(_, value)| value)
+    }
+
+    /// Sets `column` to `value`, replacing any value it already had.
+    pub fn set<T: ToSql>(&mut self, column: &str, value: T) {
+        self.set_value(column, value.to_value());
+    }
+
+    pub fn set_value(&mut self, column: &str, value: SqlValue) {
+        match self.values.iter_mut().find(|(c, _)| c == column) {
+            Some((_, existing)) => *existing = value,
+            None => self.values.push((column.to_string(), value)),
+        }
+    }
+
+    /// Stops `column` from being written.
+    pub fn remove(&mut self, column: &str) -> Option<SqlValue> {
+        let index = self.values.iter().position(|(c, _)| c == column)?;
+        Some(self.values.remove(index).1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &SqlValue)> {
+        self.values.iter().map(|(c, value)| (c.as_str(), value))
+    }
+}
+
+/// Code run by a model's builders around every write, including `create()`, `save()` and
+/// `delete()`, e.g. to hash passwords, invalidate caches or write an audit log. Every method
+/// does nothing unless overridden; `#[model(hooks = true)]` leaves the impl to the model.
+///
+/// `before_save` runs after validation and may change the values or refuse the write with an
+/// error. The `after_` hooks run once the statement succeeds; given a transaction, that is
+/// before it commits. An error from them is returned, but the write has already happened.
+///
+/// Whatever the hooks do, each write first marks responses cached from the model with
+/// `#[handler(cache(models(...)))]` as stale.
+pub trait ModelHooks {
+    /// Whether writes also record events in the [outbox](crate::outbox), set with
+    /// `#[model(outbox = true)]`. Each insert, update and delete through the builders then
+    /// enqueues a `<table>.created`, `<table>.updated` or `<table>.deleted` event per row, with
+    /// the row's columns as the payload, in the same statement as the write, so the events
+    /// are published exactly when the write commits. The outbox table must exist.
+    const OUTBOX: bool = false;
+
+    fn before_save(changes: &mut Changes) -> HookFuture<'_> {
+        let _ = changes;
+        Box::pin(async { Ok(()) })
+    }
+
+    /// After each inserted row, with the values inserted.
+    fn after_create(changes: &Changes) -> HookFuture<'_> {
+        let _ = changes;
+        Box::pin(async { Ok(()) })
+    }
+
+    /// After an update, with the values set and the number of rows they were set on.
+    fn after_update(changes: &Changes, rows: u64) -> HookFuture<'_> {
+        let _ = (changes, rows);
+        Box::pin(async { Ok(()) })
+    }
+
+    /// After a delete, with the number of rows deleted.
+    fn after_delete(rows: u64) -> HookFuture<'static> {
+        let _ = rows;
+        Box::pin(async { Ok(()) })
+    }
+}
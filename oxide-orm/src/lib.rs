@@ -1,19 +1,41 @@
-pub use oxide_macros::model;
+pub use oxide_macros::{embeddable, model, SqlEnum};
 
 mod database;
 mod error;
+mod hooks;
+pub mod migration;
+pub mod outbox;
 mod query;
 mod schema;
+pub mod seed;
+mod sql_enum;
+pub mod testing;
 mod types;
+mod validate;
 
-pub use query::{OxideInsertQueryBuilder, OxideQueryBuilder, OxideUpdateQueryBuilder};
-pub use schema::{Column, Model, ModelColumns};
-pub use types::{SqlType, ToSql};
+pub use database::{DatabaseSource, Executor, FetchRow, IntoExecutor, QueryResult};
+pub use hooks::{Changes, HookFuture, ModelHooks};
+pub use query::{
+    Aggregate, CursorPage, Direction, Expr, OxideAggregateQuery, OxideBulkInsertBuilder,
+    OxideDeleteQueryBuilder, OxideInsertQueryBuilder, OxideQueryBuilder, OxideUpdateQueryBuilder,
+    Page, Query, RawQuery, RawSql, Summable, CURSOR_PARAM, PAGE_PARAM, PER_PAGE_PARAM,
+};
+pub use schema::{
+    Column, ColumnDef, DeleteFuture, DeleteRow, Embedded, EmbeddedSchema, EmbeddedValues,
+    HasColumns, KeyDefault, Model, ModelColumns, PrimaryKey, TableDef,
+};
+pub use sql_enum::SqlEnum;
+pub use types::{SqlType, SqlValue, ToSql};
+pub use validate::{FieldError, Rule, Validate, ValidationErrors};
 
 // Create a prelude for easy imports
 pub mod prelude {
+    pub use super::migration::Migrate;
+    pub use super::seed::Seed;
     pub use super::{
-        Column, Model, ModelColumns, OxideInsertQueryBuilder, OxideQueryBuilder,
-        OxideUpdateQueryBuilder, SqlType, ToSql,
+        Aggregate, Changes, Column, CursorPage, DeleteRow, Direction, Expr, Model, ModelColumns,
+        ModelHooks, OxideBulkInsertBuilder, OxideDeleteQueryBuilder, OxideInsertQueryBuilder,
+        OxideQueryBuilder, OxideUpdateQueryBuilder, Page, PrimaryKey, Query, RawSql, SqlEnum,
+        SqlType, SqlValue, ToSql, Validate,
     };
 }
@@ -0,0 +1,237 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use oxide_core::{Error, PgDatabase};
+use sqlx::FromRow;
+
+use super::Schema;
+use crate::{types::bind_all, ColumnDef, SqlValue, TableDef};
+
+#[derive(FromRow)]
+struct ColumnRow {
+    name: String,
+    sql_type: String,
+    nullable: bool,
+}
+
+/// The table as it exists in the database, or `None` if it doesn't.
+pub async fn introspect(db: &PgDatabase, table: &str) -> Result<Option<TableDef>, Error> {
+    let exists: (bool,) = db
+        .query_one_with(
+            "SELECT to_regclass($1) IS NOT NULL".to_string(),
+            bind_all(vec![SqlValue::Text(table.to_string())])?,
+        )
+        .await?;
+    if !exists.0 {
+        return Ok(None);
+    }
+
+    let columns: Vec<ColumnRow> = db
+        .query_with(
+            "SELECT attname::TEXT AS name,
+                    format_type(atttypid, atttypmod) AS sql_type,
+                    NOT attnotnull AS nullable
+             FROM pg_attribute
+             WHERE attrelid = to_regclass($1) AND attnum > 0 AND NOT attisdropped
+             ORDER BY attnum"
+                .to_string(),
+            bind_all(vec![SqlValue::Text(table.to_string())])?,
+        )
+        .await?;
+    let primary_key: Vec<(String,)> = db
+        .query_with(
+            "SELECT a.attname::TEXT
+             FROM pg_index i
+             JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)
+             WHERE i.indrelid = to_regclass($1) AND i.indisprimary
+             ORDER BY array_position(i.indkey::SMALLINT[], a.attnum)"
+                .to_string(),
+            bind_all(vec![SqlValue::Text(table.to_string())])?,
+        )
+        .await?;
+
+    Ok(Some(TableDef {
+        table: table.to_string(),
+        primary_key: primary_key.into_iter().map(|(column,)| column).collect(),
+        columns: columns
+            .into_iter()
+            .map(|row| ColumnDef {
+                name: row.name,
+                sql_type: row.sql_type,
+                nullable: row.nullable,
+            })
+            .collect(),
+    }))
+}
+
+/// Whether a column of the database's type can hold the model's. `String` fields map to
+/// `text`, which `VARCHAR` columns are left alone for.
+fn same_type(model: &str, live: &str) -> bool {
+    model == live || (model == "text" && live.starts_with("character varying"))
+}
+
+/// The statements taking `live` to `model`, and the statements reverting them. Columns only in
+/// the database are dropped, and a dropped and an added column of the same type are assumed
+/// to be a rename when neither has another candidate; both are marked for review.
+pub fn diff(model: &TableDef, live: Option<&TableDef>) -> (Schema, Schema) {
+    let (mut up, mut down) = (Schema::new(), Schema::new());
+    let table = &model.table;
+    let Some(live) = live else {
+        up.create_table(table, |t| {
+            for column in &model.columns {
+                t.column(&column.name, &column.definition());
+            }
+            if !model.primary_key.is_empty() {
+                t.constraint(&format!("PRIMARY KEY ({})", model.primary_key.join(", ")));
+            }
+        });
+        down.drop_table(table);
+        return (up, down);
+    };
+
+    let added: Vec<&ColumnDef> = model
+        .columns
+        .iter()
+        .filter(|column| live.column(&column.name).is_none())
+        .collect();
+    let dropped: Vec<&ColumnDef> = live
+        .columns
+        .iter()
+        .filter(|column| model.column(&column.name).is_none())
+        .collect();
+    let renamed_from = |to: &ColumnDef| -> Option<&ColumnDef> {
+        let is_match = |to: &ColumnDef, from: &ColumnDef| {
+            to.sql_type == from.sql_type && to.nullable == from.nullable
+        };
+        let mut candidates = dropped.iter().filter(|from| is_match(to, from));
+        let from = *candidates.next()?;
+        let rivals = added.iter().filter(|other| is_match(other, from)).count();
+        (candidates.next().is_none() && rivals == 1).then_some(from)
+    };
+
+    let mut reverts = vec![];
+    let mut renamed = vec![];
+    for column in &added {
+        if let Some(from) = renamed_from(column) {
+            up.sql(format!(
+                "-- Review: assumed {} was renamed to {}\nALTER TABLE {} RENAME COLUMN {} TO {}",
+                from.name, column.name, table, from.name, column.name
+            ));
+            reverts.push(format!(
+                "ALTER TABLE {} RENAME COLUMN {} TO {}",
+                table, column.name, from.name
+            ));
+            renamed.push(from.name.as_str());
+        } else {
+            up.add_column(table, &column.name, &column.definition());
+            reverts.push(format!("ALTER TABLE {} DROP COLUMN {}", table, column.name));
+        }
+    }
+    for column in dropped
+        .iter()
+        .filter(|c| !renamed.contains(&c.name.as_str()))
+    {
+        up.sql(format!(
+            "-- Review: {} is not a field of the model, and dropping it loses its data\nALTER TABLE {} DROP COLUMN {}",
+            column.name, table, column.name
+        ));
+        reverts.push(format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table,
+            column.name,
+            column.definition()
+        ));
+    }
+
+    for column in &model.columns {
+        let Some(existing) = live.column(&column.name) else {
+            continue;
+        };
+        if !same_type(&column.sql_type, &existing.sql_type) {
+            up.sql(alter_type(table, &column.name, &column.sql_type));
+            reverts.push(alter_type(table, &column.name, &existing.sql_type));
+        }
+        if column.nullable != existing.nullable {
+            up.sql(alter_null(table, &column.name, column.nullable));
+            reverts.push(alter_null(table, &column.name, existing.nullable));
+        }
+    }
+
+    if !live.primary_key.is_empty() && model.primary_key != live.primary_key {
+        up.sql(format!(
+            "-- Review: the model's primary key ({}) differs from the table's ({}); not changed",
+            model.primary_key.join(", "),
+            live.primary_key.join(", ")
+        ));
+    }
+
+    for revert in reverts.into_iter().rev() {
+        down.sql(revert);
+    }
+    (up, down)
+}
+
+/// `ALTER COLUMN ... TYPE`, casting existing values explicitly.
+fn alter_type(table: &str, column: &str, sql_type: &str) -> String {
+    format!(
+        "ALTER TABLE {} ALTER COLUMN {} TYPE {} USING {}::{}",
+        table, column, sql_type, column, sql_type
+    )
+}
+
+fn alter_null(table: &str, column: &str, nullable: bool) -> String {
+    format!(
+        "ALTER TABLE {} ALTER COLUMN {} {} NOT NULL",
+        table,
+        column,
+        if nullable { "DROP" } else { "SET" }
+    )
+}
+
+/// Compares `tables` with the database and writes the difference to `dir` as
+/// `<version>_<name>.sql` and `<version>_<name>.down.sql`, numbered after the migrations
+/// already there. Returns the up file's path, or `None` if the database already matches.
+pub async fn generate(
+    db: &PgDatabase,
+    tables: &[TableDef],
+    dir: impl AsRef<Path>,
+    name: &str,
+) -> Result<Option<PathBuf>, Error> {
+    let (mut up, mut down) = (vec![], vec![]);
+    for table in tables {
+        let live = introspect(db, &table.table).await?;
+        let (table_up, table_down) = diff(table, live.as_ref());
+        up.extend(table_up.statements().iter().cloned());
+        down.splice(0..0, table_down.statements().iter().cloned());
+    }
+    if up.is_empty() {
+        return Ok(None);
+    }
+
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir).map_err(Error::Io)?;
+    let mut version = 0;
+    for entry in fs::read_dir(dir).map_err(Error::Io)? {
+        let file_name = entry.map_err(Error::Io)?.file_name();
+        let existing = file_name
+            .to_str()
+            .and_then(|file_name| file_name.split_once('_'))
+            .and_then(|(version, _)| version.parse::<i64>().ok());
+        version = version.max(existing.unwrap_or(0));
+    }
+
+    let stem = format!("{:04}_{}", version + 1, name);
+    let header = "-- Generated by `migrate generate`; review before applying.\n";
+    let up_path = dir.join(format!("{}.sql", stem));
+    fs::write(&up_path, format!("{}{};\n", header, up.join(";\n\n"))).map_err(Error::Io)?;
+    if !down.is_empty() {
+        fs::write(
+            dir.join(format!("{}.down.sql", stem)),
+            format!("{}{};\n", header, down.join(";\n\n")),
+        )
+        .map_err(Error::Io)?;
+    }
+    Ok(Some(up_path))
+}
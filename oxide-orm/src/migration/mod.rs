@@ -0,0 +1,819 @@
+//! Ordered, reversible schema changes. Applied migrations are recorded in
+//! `_oxide_migrations`, so each runs once per database.
+//!
+//! ```rust,ignore
+//! struct CreateUsers;
+//!
+//! impl Migration for CreateUsers {
+//!     fn version(&self) -> i64 {
+//!         1
+//!     }
+//!     fn name(&self) -> &str {
+//!         "create_users"
+//!     }
+//!     fn up(&self, schema: &mut Schema) {
+//!         schema.create_table("users", |t| {
+//!             t.column("id", "SERIAL PRIMARY KEY")
+//!                 .column("email", "TEXT NOT NULL UNIQUE");
+//!         });
+//!     }
+//!     fn down(&self, schema: &mut Schema) {
+//!         schema.drop_table("users");
+//!     }
+//! }
+//!
+//! db.migrate(&[Box::new(CreateUsers)]).await?;
+//! ```
+use std::{
+    collections::BTreeMap,
+    fs,
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+    time::Duration,
+};
+
+use oxide_core::{logger::LogLevel, Error, Logger, PgDatabase};
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgConnection};
+
+use crate::TableDef;
+
+mod generate;
+
+pub use generate::{diff, generate, introspect};
+
+pub const MIGRATIONS_TABLE: &str = "_oxide_migrations";
+
+/// Serializes migrators across processes, so two instances starting at once don't both
+/// apply the same migration.
+const LOCK_KEY: i64 = 0x006f_7869_6465;
+
+pub trait Migration: Send + Sync {
+    /// Orders migrations; must be unique, and usually a sequence number or timestamp.
+    fn version(&self) -> i64;
+    fn name(&self) -> &str;
+    fn up(&self, schema: &mut Schema);
+    /// Leaving this empty makes the migration irreversible.
+    fn down(&self, _schema: &mut Schema) {}
+
+    /// Whether the statements run in one transaction with the migration's record. Statements
+    /// that refuse to run in a transaction, like `CREATE INDEX CONCURRENTLY`, need `false`;
+    /// they then run one by one, and a failure leaves the earlier ones applied.
+    fn transactional(&self) -> bool {
+        true
+    }
+
+    /// How long a statement may wait for a lock before failing, so an `ALTER TABLE` stuck
+    /// behind a long query doesn't hold up every query queued behind it.
+    fn lock_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// How long a statement may run before it is cancelled.
+    fn statement_timeout(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// The statements a migration's `up` or `down` runs, in order.
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    statements: Vec<String>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn statements(&self) -> &[String] {
+        &self.statements
+    }
+
+    /// SHA-256 of the statements, recorded when a migration is applied so later edits to it
+    /// are caught.
+    pub fn checksum(&self) -> String {
+        let mut hasher = Sha256::new();
+        for statement in &self.statements {
+            hasher.update(statement.as_bytes());
+            hasher.update([0]);
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    pub fn sql(&mut self, sql: impl Into<String>) -> &mut Self {
+        self.statements.push(sql.into());
+        self
+    }
+
+    pub fn create_table(&mut self, table: &str, f: impl FnOnce(&mut TableBuilder)) -> &mut Self {
+        let mut builder = TableBuilder::default();
+        f(&mut builder);
+        self.sql(format!(
+            "CREATE TABLE {} (\n    {}\n)",
+            table,
+            builder.definitions.join(",\n    ")
+        ))
+    }
+
+    pub fn drop_table(&mut self, table: &str) -> &mut Self {
+        self.sql(format!("DROP TABLE {}", table))
+    }
+
+    pub fn rename_table(&mut self, from: &str, to: &str) -> &mut Self {
+        self.sql(format!("ALTER TABLE {} RENAME TO {}", from, to))
+    }
+
+    /// `definition` is the column's type and constraints, e.g. `"TEXT NOT NULL DEFAULT ''"`.
+    pub fn add_column(&mut self, table: &str, column: &str, definition: &str) -> &mut Self {
+        self.sql(format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, definition
+        ))
+    }
+
+    pub fn drop_column(&mut self, table: &str, column: &str) -> &mut Self {
+        self.sql(format!("ALTER TABLE {} DROP COLUMN {}", table, column))
+    }
+
+    pub fn rename_column(&mut self, table: &str, from: &str, to: &str) -> &mut Self {
+        self.sql(format!(
+            "ALTER TABLE {} RENAME COLUMN {} TO {}",
+            table, from, to
+        ))
+    }
+
+    pub fn create_index(&mut self, name: &str, table: &str, columns: &[&str]) -> &mut Self {
+        self.sql(format!(
+            "CREATE INDEX {} ON {} ({})",
+            name,
+            table,
+            columns.join(", ")
+        ))
+    }
+
+    pub fn drop_index(&mut self, name: &str) -> &mut Self {
+        self.sql(format!("DROP INDEX {}", name))
+    }
+}
+
+/// Columns and table constraints for [`Schema::create_table`].
+#[derive(Debug, Default)]
+pub struct TableBuilder {
+    definitions: Vec<String>,
+}
+
+impl TableBuilder {
+    pub fn column(&mut self, name: &str, definition: &str) -> &mut Self {
+        self.definitions.push(format!("{} {}", name, definition));
+        self
+    }
+
+    /// A table constraint, e.g. `"PRIMARY KEY (tenant_id, user_id)"`.
+    pub fn constraint(&mut self, constraint: &str) -> &mut Self {
+        self.definitions.push(constraint.to_string());
+        self
+    }
+}
+
+/// A migration written as plain SQL, possibly several `;`-separated statements.
+#[derive(Debug, Clone)]
+pub struct SqlMigration {
+    version: i64,
+    name: String,
+    up: String,
+    down: Option<String>,
+    transactional: bool,
+    lock_timeout: Option<Duration>,
+    statement_timeout: Option<Duration>,
+}
+
+impl SqlMigration {
+    pub fn new(version: i64, name: &str, up: &str) -> Self {
+        Self {
+            version,
+            name: name.to_string(),
+            up: up.to_string(),
+            down: None,
+            transactional: true,
+            lock_timeout: None,
+            statement_timeout: None,
+        }
+    }
+
+    pub fn with_down(mut self, down: &str) -> Self {
+        self.down = Some(down.to_string());
+        self
+    }
+
+    /// Runs the statements outside a transaction. They are split on lines ending in `;` and
+    /// executed one at a time.
+    pub fn without_transaction(mut self) -> Self {
+        self.transactional = false;
+        self
+    }
+
+    pub fn with_lock_timeout(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_statement_timeout(mut self, timeout: Duration) -> Self {
+        self.statement_timeout = Some(timeout);
+        self
+    }
+
+    /// Applies `-- oxide:no_transaction`, `-- oxide:lock_timeout <n>ms|s|min` and
+    /// `-- oxide:statement_timeout ...` lines from the top of a migration file.
+    fn with_directives(mut self, sql: &str) -> Result<Self, Error> {
+        for line in sql.lines().map(str::trim) {
+            let Some(comment) = line.strip_prefix("--") else {
+                if line.is_empty() {
+                    continue;
+                }
+                break;
+            };
+            let Some(directive) = comment.trim().strip_prefix("oxide:") else {
+                continue;
+            };
+            let (key, value) = directive
+                .split_once(char::is_whitespace)
+                .map_or((directive, ""), |(key, value)| (key, value.trim()));
+            self = match key {
+                "no_transaction" => self.without_transaction(),
+                "lock_timeout" => self.with_lock_timeout(parse_timeout(value)?),
+                "statement_timeout" => self.with_statement_timeout(parse_timeout(value)?),
+                _ => {
+                    return Err(Error::Config(format!(
+                        "Unknown directive `-- oxide:{}` in migration {} ({})",
+                        directive, self.version, self.name
+                    )))
+                }
+            };
+        }
+        Ok(self)
+    }
+
+    /// Loads `<version>_<name>.sql` files from `dir`, each with an optional
+    /// `<version>_<name>.down.sql` to reverse it, e.g. `0001_create_users.sql`. Directives in
+    /// the up file's leading comments apply to both directions:
+    ///
+    /// ```sql
+    /// -- oxide:no_transaction
+    /// -- oxide:lock_timeout 5s
+    /// CREATE INDEX CONCURRENTLY users_email ON users (email);
+    /// ```
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Vec<Box<dyn Migration>>, Error> {
+        let mut ups = BTreeMap::new();
+        let mut downs = BTreeMap::new();
+        for entry in fs::read_dir(dir.as_ref()).map_err(Error::Io)? {
+            let path = entry.map_err(Error::Io)?.path();
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let (stem, is_down) = match file_name.strip_suffix(".down.sql") {
+                Some(stem) => (stem.to_string(), true),
+                None => match file_name.strip_suffix(".sql") {
+                    Some(stem) => (stem.to_string(), false),
+                    None => continue,
+                },
+            };
+            let sql = fs::read_to_string(&path).map_err(Error::Io)?;
+            if is_down {
+                downs.insert(stem, sql);
+            } else {
+                ups.insert(stem, sql);
+            }
+        }
+
+        let mut migrations: Vec<Box<dyn Migration>> = vec![];
+        for (stem, up) in ups {
+            let (version, name) = stem
+                .split_once('_')
+                .and_then(|(version, name)| Some((version.parse::<i64>().ok()?, name)))
+                .ok_or_else(|| {
+                    Error::Config(format!(
+                        "Migration file {}.sql must be named <version>_<name>.sql",
+                        stem
+                    ))
+                })?;
+            let mut migration = SqlMigration::new(version, name, &up).with_directives(&up)?;
+            if let Some(down) = downs.remove(&stem) {
+                migration = migration.with_down(&down);
+            }
+            migrations.push(Box::new(migration));
+        }
+        if let Some(stem) = downs.keys().next() {
+            return Err(Error::Config(format!(
+                "Migration file {}.down.sql has no matching {}.sql",
+                stem, stem
+            )));
+        }
+        Ok(migrations)
+    }
+}
+
+impl Migration for SqlMigration {
+    fn version(&self) -> i64 {
+        self.version
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn up(&self, schema: &mut Schema) {
+        schema.sql(self.up.clone());
+    }
+
+    fn down(&self, schema: &mut Schema) {
+        if let Some(down) = &self.down {
+            schema.sql(down.clone());
+        }
+    }
+
+    fn transactional(&self) -> bool {
+        self.transactional
+    }
+
+    fn lock_timeout(&self) -> Option<Duration> {
+        self.lock_timeout
+    }
+
+    fn statement_timeout(&self) -> Option<Duration> {
+        self.statement_timeout
+    }
+}
+
+/// `500ms`, `5s` or `2min`.
+fn parse_timeout(value: &str) -> Result<Duration, Error> {
+    let digits = value.trim_end_matches(char::is_alphabetic);
+    let amount = digits.parse::<u64>().ok();
+    match (amount, &value[digits.len()..]) {
+        (Some(amount), "ms") => Ok(Duration::from_millis(amount)),
+        (Some(amount), "s") => Ok(Duration::from_secs(amount)),
+        (Some(amount), "min") => Ok(Duration::from_secs(amount * 60)),
+        _ => Err(Error::Config(format!(
+            "Invalid migration timeout `{}`, expected e.g. `500ms`, `5s` or `2min`",
+            value
+        ))),
+    }
+}
+
+/// Splits SQL on lines ending in `;`, for running statements outside a transaction, where a
+/// multi-statement string would still run as one implicit transaction.
+fn split_statements(sql: &str) -> Vec<String> {
+    let mut statements = vec![];
+    let mut current = String::new();
+    for line in sql.lines() {
+        current.push_str(line);
+        current.push('\n');
+        if line.trim_end().ends_with(';') {
+            statements.push(std::mem::take(&mut current));
+        }
+    }
+    statements.push(current);
+    statements
+        .into_iter()
+        .filter(|statement| {
+            statement
+                .lines()
+                .any(|line| !line.trim().is_empty() && !line.trim().starts_with("--"))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub name: String,
+    /// `None` for migrations applied before checksums were recorded.
+    pub checksum: Option<String>,
+}
+
+/// A pending migration and the statements [`Migrator::run`] would execute for it.
+#[derive(Debug, Clone)]
+pub struct PlannedMigration {
+    pub version: i64,
+    pub name: String,
+    pub statements: Vec<String>,
+}
+
+/// Applies and reverts migrations, each in its own transaction together with its row in
+/// `_oxide_migrations`, so a failed migration leaves no trace. Migrations that opt out of
+/// the transaction still hold the migration lock while they run.
+pub struct Migrator<'a> {
+    db: &'a PgDatabase,
+    logger: Logger,
+}
+
+impl<'a> Migrator<'a> {
+    pub fn new(db: &'a PgDatabase) -> Self {
+        Self {
+            db,
+            logger: Logger::for_target(module_path!()),
+        }
+    }
+
+    async fn create_table(&self) -> Result<(), Error> {
+        self.db
+            .execute(format!(
+                "CREATE TABLE IF NOT EXISTS {} (
+                    version BIGINT PRIMARY KEY,
+                    name TEXT NOT NULL,
+                    applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+                )",
+                MIGRATIONS_TABLE
+            ))
+            .await?;
+        self.db
+            .execute(format!(
+                "ALTER TABLE {} ADD COLUMN IF NOT EXISTS checksum TEXT",
+                MIGRATIONS_TABLE
+            ))
+            .await?;
+        Ok(())
+    }
+
+    /// Applied migrations, oldest first.
+    pub async fn applied(&self) -> Result<Vec<AppliedMigration>, Error> {
+        self.create_table().await?;
+        self.db
+            .query(format!(
+                "SELECT version, name, checksum FROM {} ORDER BY version",
+                MIGRATIONS_TABLE
+            ))
+            .await
+    }
+
+    /// Migrations not applied yet, in version order, with their `up` statements. Fails if an
+    /// applied migration no longer matches the checksum recorded for it.
+    async fn pending<'m>(
+        &self,
+        migrations: &'m [Box<dyn Migration>],
+    ) -> Result<Vec<(&'m dyn Migration, Schema)>, Error> {
+        let migrations = sorted(migrations)?;
+        let applied = self.applied().await?;
+
+        let mut pending = vec![];
+        for migration in migrations {
+            let mut schema = Schema::new();
+            migration.up(&mut schema);
+            match applied.iter().find(|m| m.version == migration.version()) {
+                Some(AppliedMigration {
+                    checksum: Some(checksum),
+                    ..
+                }) if *checksum != schema.checksum() => {
+                    return Err(Error::Config(format!(
+                        "Migration {} ({}) was modified after it was applied; add a new migration instead",
+                        migration.version(),
+                        migration.name()
+                    )));
+                }
+                Some(_) => {}
+                None => pending.push((migration, schema)),
+            }
+        }
+        Ok(pending)
+    }
+
+    /// What [`run`](Self::run) would execute, without executing it.
+    pub async fn plan(
+        &self,
+        migrations: &[Box<dyn Migration>],
+    ) -> Result<Vec<PlannedMigration>, Error> {
+        Ok(self
+            .pending(migrations)
+            .await?
+            .into_iter()
+            .map(|(migration, schema)| PlannedMigration {
+                version: migration.version(),
+                name: migration.name().to_string(),
+                statements: schema.statements,
+            })
+            .collect())
+    }
+
+    /// Applies every migration not applied yet, in version order, returning their versions.
+    /// Refuses to run anything if an applied migration has since been modified.
+    pub async fn run(&self, migrations: &[Box<dyn Migration>]) -> Result<Vec<i64>, Error> {
+        let mut versions = vec![];
+        for (migration, schema) in self.pending(migrations).await? {
+            if self.apply(migration, &schema, true).await? {
+                versions.push(migration.version());
+            }
+        }
+        Ok(versions)
+    }
+
+    /// Reverts the latest `steps` applied migrations, newest first, returning their versions.
+    /// Fails without reverting anything if one of them is irreversible or missing.
+    pub async fn rollback(
+        &self,
+        migrations: &[Box<dyn Migration>],
+        steps: usize,
+    ) -> Result<Vec<i64>, Error> {
+        let migrations = sorted(migrations)?;
+        let mut plan = vec![];
+        for applied in self.applied().await?.iter().rev().take(steps) {
+            let migration = migrations
+                .iter()
+                .find(|m| m.version() == applied.version)
+                .ok_or_else(|| {
+                    Error::Custom(format!(
+                        "Cannot roll back migration {} ({}): it is not in the given list",
+                        applied.version, applied.name
+                    ))
+                })?;
+            let mut schema = Schema::new();
+            migration.down(&mut schema);
+            if schema.statements().is_empty() {
+                return Err(Error::Custom(format!(
+                    "Cannot roll back migration {} ({}): it is irreversible",
+                    applied.version, applied.name
+                )));
+            }
+            plan.push((*migration, schema));
+        }
+
+        let mut versions = vec![];
+        for (migration, schema) in plan {
+            if self.apply(migration, &schema, false).await? {
+                versions.push(migration.version());
+            }
+        }
+        Ok(versions)
+    }
+
+    /// Runs `schema` and records (`up`) or forgets (`down`) the migration. Returns false if
+    /// another migrator got there first.
+    async fn apply(
+        &self,
+        migration: &dyn Migration,
+        schema: &Schema,
+        up: bool,
+    ) -> Result<bool, Error> {
+        let applied = if migration.transactional() {
+            let mut tx = self.db.begin().await?;
+            sqlx::query("SELECT pg_advisory_xact_lock($1)")
+                .bind(LOCK_KEY)
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::Database)?;
+            let applied = run_locked(&mut tx, migration, schema, up).await?;
+            if applied {
+                tx.commit().await?;
+            } else {
+                tx.rollback().await?;
+            }
+            applied
+        } else {
+            let mut conn = self.db.acquire().await?;
+            sqlx::query("SELECT pg_advisory_lock($1)")
+                .bind(LOCK_KEY)
+                .execute(&mut *conn)
+                .await
+                .map_err(Error::Database)?;
+            let result = run_locked(&mut conn, migration, schema, up).await;
+            // Session settings and locks outlive the migration on a pooled connection, so
+            // both are cleared whether or not it succeeded.
+            let reset = sqlx::Executor::execute(
+                &mut *conn,
+                sqlx::raw_sql("RESET lock_timeout; RESET statement_timeout"),
+            )
+            .await;
+            let unlock = sqlx::query("SELECT pg_advisory_unlock($1)")
+                .bind(LOCK_KEY)
+                .execute(&mut *conn)
+                .await;
+            if reset.is_err() || unlock.is_err() {
+                // Closing the session is the only other way to drop the lock and settings,
+                // and keeps the connection from going back to the pool with them.
+                let _ = sqlx::Connection::close(conn.detach()).await;
+            }
+            let applied = result?;
+            reset.map_err(Error::Database)?;
+            unlock.map_err(Error::Database)?;
+            applied
+        };
+
+        if applied {
+            self.logger.log(
+                LogLevel::Info,
+                &format!(
+                    "{} migration {} ({})",
+                    if up { "Applied" } else { "Rolled back" },
+                    migration.version(),
+                    migration.name()
+                ),
+            );
+        }
+        Ok(applied)
+    }
+}
+
+/// The part of [`Migrator::apply`] run while holding the migration lock, in a transaction
+/// unless the migration opts out.
+async fn run_locked(
+    conn: &mut PgConnection,
+    migration: &dyn Migration,
+    schema: &Schema,
+    up: bool,
+) -> Result<bool, Error> {
+    let recorded = sqlx::query(&format!(
+        "SELECT 1 FROM {} WHERE version = $1",
+        MIGRATIONS_TABLE
+    ))
+    .bind(migration.version())
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(Error::Database)?
+    .is_some();
+    if recorded == up {
+        return Ok(false);
+    }
+
+    // `SET LOCAL` ends with the transaction; outside one, `apply` resets the settings.
+    let scope = if migration.transactional() {
+        "SET LOCAL"
+    } else {
+        "SET"
+    };
+    for (setting, timeout) in [
+        ("lock_timeout", migration.lock_timeout()),
+        ("statement_timeout", migration.statement_timeout()),
+    ] {
+        if let Some(timeout) = timeout {
+            sqlx::Executor::execute(
+                &mut *conn,
+                sqlx::raw_sql(&format!(
+                    "{} {} = '{}ms'",
+                    scope,
+                    setting,
+                    timeout.as_millis()
+                )),
+            )
+            .await
+            .map_err(Error::Database)?;
+        }
+    }
+
+    let statements: Vec<String> = if migration.transactional() {
+        schema.statements().to_vec()
+    } else {
+        schema
+            .statements()
+            .iter()
+            .flat_map(|statement| split_statements(statement))
+            .collect()
+    };
+    for statement in &statements {
+        // Through `Executor` rather than `RawSql::execute`, whose future isn't `Send`.
+        sqlx::Executor::execute(&mut *conn, sqlx::raw_sql(statement))
+            .await
+            .map_err(|e| {
+                Error::Custom(format!(
+                    "Migration {} ({}) failed: {}",
+                    migration.version(),
+                    migration.name(),
+                    e
+                ))
+            })?;
+    }
+
+    let record = if up {
+        format!(
+            "INSERT INTO {} (version, name, checksum) VALUES ($1, $2, $3)",
+            MIGRATIONS_TABLE
+        )
+    } else {
+        format!("DELETE FROM {} WHERE version = $1", MIGRATIONS_TABLE)
+    };
+    let mut record = sqlx::query(&record).bind(migration.version());
+    if up {
+        record = record.bind(migration.name()).bind(schema.checksum());
+    }
+    record.execute(&mut *conn).await.map_err(Error::Database)?;
+    Ok(true)
+}
+
+/// `migrations` in version order, refusing duplicate versions.
+fn sorted(migrations: &[Box<dyn Migration>]) -> Result<Vec<&dyn Migration>, Error> {
+    let mut sorted: Vec<&dyn Migration> = migrations.iter().map(|m| m.as_ref()).collect();
+    sorted.sort_by_key(|m| m.version());
+    for pair in sorted.windows(2) {
+        if pair[0].version() == pair[1].version() {
+            return Err(Error::Config(format!(
+                "Migrations {} and {} share version {}",
+                pair[0].name(),
+                pair[1].name(),
+                pair[0].version()
+            )));
+        }
+    }
+    Ok(sorted)
+}
+
+pub type MigrateFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<i64>, Error>> + Send + 'a>>;
+
+/// `db.migrate(&migrations)`, shorthand for `Migrator::new(&db).run(&migrations)`.
+pub trait Migrate {
+    fn migrate<'a>(&'a self, migrations: &'a [Box<dyn Migration>]) -> MigrateFuture<'a>;
+}
+
+impl Migrate for PgDatabase {
+    fn migrate<'a>(&'a self, migrations: &'a [Box<dyn Migration>]) -> MigrateFuture<'a> {
+        Box::pin(async move { Migrator::new(self).run(migrations).await })
+    }
+}
+
+/// The `migrate` CLI command, connecting to `DATABASE_URL`. Plugin commands are plain
+/// functions, so the app wraps it in one and returns that from
+/// [`OxidePlugin::commands`](oxide_core::OxidePlugin::commands):
+///
+/// ```rust,ignore
+/// fn migrate(args: &[String]) -> Result<(), Error> {
+///     MigrateCommand::new("migrations")
+///         .with_table(User::table_def())
+///         .run(args)
+/// }
+/// ```
+///
+/// `./app migrate generate add_user_age` then writes `migrations/<version>_add_user_age.sql`
+/// and its `.down.sql` from the difference between the models and the database, and
+/// `./app migrate plan` prints the SQL the pending migrations in `migrations/` would run.
+#[derive(Debug, Clone)]
+pub struct MigrateCommand {
+    dir: PathBuf,
+    tables: Vec<TableDef>,
+}
+
+impl MigrateCommand {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            tables: vec![],
+        }
+    }
+
+    pub fn with_table(mut self, table: TableDef) -> Self {
+        self.tables.push(table);
+        self
+    }
+
+    pub fn run(&self, args: &[String]) -> Result<(), Error> {
+        if !matches!(args, [command, _] if command == "generate")
+            && !matches!(args, [command] if command == "plan")
+        {
+            return Err(Error::Config(
+                "Usage: migrate generate <name> | migrate plan".to_string(),
+            ));
+        }
+
+        // Commands run inside the server's runtime but can't await, so this one blocks on a
+        // runtime of its own on another thread.
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let runtime = tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .map_err(Error::Io)?;
+                    runtime.block_on(self.run_async(args))
+                })
+                .join()
+                .unwrap_or_else(|_| Err(Error::Custom("migrate command panicked".to_string())))
+        })
+    }
+
+    async fn run_async(&self, args: &[String]) -> Result<(), Error> {
+        let url = std::env::var("DATABASE_URL")
+            .map_err(|_| Error::Config("DATABASE_URL is not set".to_string()))?;
+        let db = PgDatabase::connect(&url).await?;
+        if let [_, name] = args {
+            match generate(&db, &self.tables, &self.dir, name).await? {
+                Some(path) => println!("Wrote {}", path.display()),
+                None => println!("The database already matches the models"),
+            }
+            return Ok(());
+        }
+
+        let migrations = SqlMigration::from_dir(&self.dir)?;
+        let plan = Migrator::new(&db).plan(&migrations).await?;
+        if plan.is_empty() {
+            println!("No pending migrations");
+        }
+        for migration in plan {
+            println!("-- {} ({})", migration.version, migration.name);
+            for statement in migration.statements {
+                println!("{};", statement.trim_end().trim_end_matches(';'));
+            }
+        }
+        Ok(())
+    }
+}
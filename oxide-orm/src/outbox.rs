@@ -0,0 +1,261 @@
+//! Transactional outbox: events are written to `oxide_outbox` in the same transaction as the
+//! data change they describe, and an [`OutboxWorker`] publishes them afterwards with
+//! at-least-once delivery.
+//!
+//! ```rust,ignore
+//! let mut tx = db.begin().await?;
+//! User::update(user.id).set(User::columns().active, false).execute_in(&mut tx).await?;
+//! Outbox::record(&mut tx, "deactivated", &user).await?;
+//! tx.commit().await?;
+//!
+//! OutboxWorker::new(db, MyBrokerPublisher::new()).spawn();
+//! ```
+//!
+//! Models declared with `#[model(outbox = true)]` record `<table>.created`, `.updated` and
+//! `.deleted` events for every write through their builders, without calling `Outbox::record`;
+//! see [`ModelHooks::OUTBOX`](crate::ModelHooks::OUTBOX).
+use std::{future::Future, pin::Pin, time::Duration};
+
+use oxide_core::{logger::LogLevel, server::Shutdown, Error, Logger, PgDatabase, PgTransaction};
+use serde::Serialize;
+use sqlx::FromRow;
+
+use crate::{Model, ModelColumns};
+
+pub const OUTBOX_TABLE: &str = "oxide_outbox";
+
+/// An event waiting in (or claimed from) the outbox.
+#[derive(Debug, Clone, FromRow)]
+pub struct OutboxEvent {
+    pub id: i64,
+    pub topic: String,
+    /// JSON-encoded payload.
+    pub payload: String,
+    pub attempts: i32,
+}
+
+pub type PublishFuture<'a> = Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+
+/// Delivers outbox events to an event bus, webhook or broker. Publishing must be idempotent
+/// on the receiving side: an event is retried if the worker fails before marking it sent.
+pub trait Publisher: Send + Sync {
+    fn publish<'a>(&'a self, event: &'a OutboxEvent) -> PublishFuture<'a>;
+}
+
+pub struct Outbox;
+
+impl Outbox {
+    pub async fn create_table(db: &PgDatabase) -> Result<(), Error> {
+        db.execute(format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                id BIGSERIAL PRIMARY KEY,
+                topic TEXT NOT NULL,
+                payload JSONB NOT NULL,
+                attempts INT NOT NULL DEFAULT 0,
+                last_error TEXT,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+                published_at TIMESTAMPTZ
+            )",
+            OUTBOX_TABLE
+        ))
+        .await?;
+        db.execute(format!(
+            "CREATE INDEX IF NOT EXISTS {0}_pending ON {0} (id) WHERE published_at IS NULL",
+            OUTBOX_TABLE
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Adds an event to the outbox as part of `tx`; it is only published if `tx` commits.
+    pub async fn enqueue<T: Serialize>(
+        tx: &mut PgTransaction<'_>,
+        topic: &str,
+        payload: &T,
+    ) -> Result<(), Error> {
+        let payload =
+            serde_json::to_string(payload).map_err(|e| Error::Serialization(e.to_string()))?;
+        sqlx::query(&format!(
+            "INSERT INTO {} (topic, payload) VALUES ($1, $2::jsonb)",
+            OUTBOX_TABLE
+        ))
+        .bind(topic)
+        .bind(payload)
+        .execute(&mut **tx)
+        .await
+        .map_err(Error::Database)?;
+        Ok(())
+    }
+
+    /// Records a change to `model` under the topic `<table>.<action>`, e.g. `users.created`.
+    pub async fn record<M, C>(
+        tx: &mut PgTransaction<'_>,
+        action: &str,
+        model: &M,
+    ) -> Result<(), Error>
+    where
+        M: Model<C> + Serialize,
+        C: ModelColumns<Model = M>,
+    {
+        let topic = format!("{}.{}", M::TABLE.trim_matches('"'), action);
+        Self::enqueue(tx, &topic, model).await
+    }
+}
+
+/// Polls the outbox and hands pending events to a [`Publisher`], oldest first. Several
+/// workers can run at once; rows are claimed with `FOR UPDATE SKIP LOCKED`.
+pub struct OutboxWorker<P: Publisher> {
+    db: PgDatabase,
+    publisher: P,
+    batch_size: i64,
+    poll_interval: Duration,
+    max_attempts: i32,
+    retention: Duration,
+    shutdown: Option<Shutdown>,
+    logger: Logger,
+}
+
+impl<P: Publisher + 'static> OutboxWorker<P> {
+    pub fn new(db: PgDatabase, publisher: P) -> Self {
+        Self {
+            db,
+            publisher,
+            batch_size: 100,
+            poll_interval: Duration::from_secs(1),
+            max_attempts: 10,
+            retention: Duration::from_secs(7 * 24 * 60 * 60),
+            shutdown: None,
+            logger: Logger::for_target(module_path!()),
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: i64) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Events failing this many times are left in the table for inspection and no longer retried.
+    pub fn with_max_attempts(mut self, max_attempts: i32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// How long published events are kept before being deleted.
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Stops the worker with the server, e.g. `with_shutdown(server.shutdown())`: no batch is
+    /// started once shutdown begins, and the one in progress is waited for.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    pub fn spawn(self) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+
+    pub async fn run(self) {
+        while !self.shutdown.as_ref().is_some_and(Shutdown::is_triggered) {
+            let job = self
+                .shutdown
+                .as_ref()
+                .map(|shutdown| shutdown.job("outbox batch"));
+            let published = self.publish_batch().await;
+            drop(job);
+            match published {
+                // A full batch published suggests a backlog, so go again without waiting. Any
+                // failure waits out the poll interval, so an unreachable broker doesn't burn
+                // through every event's attempts at once.
+                Ok(published) if published as i64 == self.batch_size => continue,
+                Ok(_) => {}
+                Err(e) => self
+                    .logger
+                    .log(LogLevel::Error, &format!("Outbox batch failed: {}", e)),
+            }
+            if let Err(e) = self.cleanup().await {
+                self.logger
+                    .log(LogLevel::Error, &format!("Outbox cleanup failed: {}", e));
+            }
+            match &self.shutdown {
+                Some(shutdown) => tokio::select! {
+                    _ = tokio::time::sleep(self.poll_interval) => {}
+                    _ = shutdown.triggered() => {}
+                },
+                None => tokio::time::sleep(self.poll_interval).await,
+            }
+        }
+        self.logger.log(LogLevel::Info, "Outbox worker stopped");
+    }
+
+    /// Publishes one batch of pending events, returning how many were published.
+    pub async fn publish_batch(&self) -> Result<usize, Error> {
+        let mut tx = self.db.begin().await?;
+        let events: Vec<OutboxEvent> = sqlx::query_as(&format!(
+            "SELECT id, topic, payload::text AS payload, attempts FROM {} \
+             WHERE published_at IS NULL AND attempts < $1 \
+             ORDER BY id LIMIT $2 FOR UPDATE SKIP LOCKED",
+            OUTBOX_TABLE
+        ))
+        .bind(self.max_attempts)
+        .bind(self.batch_size)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(Error::Database)?;
+
+        let mut published = 0;
+        for event in &events {
+            let update = match self.publisher.publish(event).await {
+                Ok(()) => {
+                    published += 1;
+                    sqlx::query(&format!(
+                        "UPDATE {} SET published_at = now(), attempts = attempts + 1 WHERE id = $1",
+                        OUTBOX_TABLE
+                    ))
+                    .bind(event.id)
+                    .execute(&mut *tx)
+                    .await
+                }
+                Err(e) => {
+                    self.logger.log(
+                        LogLevel::Warning,
+                        &format!(
+                            "Failed to publish outbox event {} ({}): {}",
+                            event.id, event.topic, e
+                        ),
+                    );
+                    sqlx::query(&format!(
+                        "UPDATE {} SET attempts = attempts + 1, last_error = $2 WHERE id = $1",
+                        OUTBOX_TABLE
+                    ))
+                    .bind(event.id)
+                    .bind(e.to_string())
+                    .execute(&mut *tx)
+                    .await
+                }
+            };
+            update.map_err(Error::Database)?;
+        }
+
+        tx.commit().await?;
+        Ok(published)
+    }
+
+    async fn cleanup(&self) -> Result<(), Error> {
+        self.db
+            .execute(format!(
+                "DELETE FROM {} WHERE published_at < now() - interval '{} seconds'",
+                OUTBOX_TABLE,
+                self.retention.as_secs()
+            ))
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+use std::marker::PhantomData;
+
+use oxide_core::Error;
+use sqlx::{Decode, Postgres, Type};
+
+use super::{builder::OxideQueryBuilder, expr::Expr, sql::SqlFragment};
+use crate::{
+    database::{FetchRow, IntoExecutor},
+    Column, Model, ModelColumns, SqlValue, ToSql,
+};
+
+/// An aggregate over rows of model `M` decoding to `R`, such as `COUNT(*)` or
+/// `User::columns().age.sum()`. Comparisons on it build `HAVING` conditions.
+#[derive(Debug, Clone)]
+pub struct Aggregate<M, R> {
+    sql: String,
+    _marker: PhantomData<(M, R)>,
+}
+
+impl<M, R> Aggregate<M, R> {
+    fn new(sql: String) -> Self {
+        Self {
+            sql,
+            _marker: PhantomData,
+        }
+    }
+
+    fn compare<V: ToSql>(&self, op: &str, value: V) -> Expr<M> {
+        Expr::new(
+            SqlFragment::new()
+                .sql(&format!("{} {} ", self.sql, op))
+                .value(value.to_value()),
+        )
+    }
+
+    pub fn eq<V: ToSql>(&self, value: V) -> Expr<M> {
+        self.compare("=", value)
+    }
+
+    pub fn ne<V: ToSql>(&self, value: V) -> Expr<M> {
+        self.compare("<>", value)
+    }
+
+    pub fn gt<V: ToSql>(&self, value: V) -> Expr<M> {
+        self.compare(">", value)
+    }
+
+    pub fn gte<V: ToSql>(&self, value: V) -> Expr<M> {
+        self.compare(">=", value)
+    }
+
+    pub fn lt<V: ToSql>(&self, value: V) -> Expr<M> {
+        self.compare("<", value)
+    }
+
+    pub fn lte<V: ToSql>(&self, value: V) -> Expr<M> {
+        self.compare("<=", value)
+    }
+}
+
+impl<M> Aggregate<M, i64> {
+    /// `COUNT(*)`.
+    pub fn count() -> Self {
+        Self::new("COUNT(*)".to_string())
+    }
+}
+
+/// Column types that can be summed, with the type their sum decodes to.
+pub trait Summable {
+    type Sum;
+    /// Postgres type the sum is cast to, so it decodes as `Sum` rather than `NUMERIC`.
+    const SUM_TYPE: &'static str;
+}
+
+impl Summable for i32 {
+    type Sum = i64;
+    const SUM_TYPE: &'static str = "BIGINT";
+}
+
+impl Summable for i16 {
+    type Sum = i64;
+    const SUM_TYPE: &'static str = "BIGINT";
+}
+
+/// Postgres sums `BIGINT` as `NUMERIC`; the cast back fails if the sum overflows.
+impl Summable for i64 {
+    type Sum = i64;
+    const SUM_TYPE: &'static str = "BIGINT";
+}
+
+impl Summable for f32 {
+    type Sum = f32;
+    const SUM_TYPE: &'static str = "REAL";
+}
+
+impl Summable for f64 {
+    type Sum = f64;
+    const SUM_TYPE: &'static str = "FLOAT8";
+}
+
+#[cfg(feature = "decimal")]
+impl Summable for rust_decimal::Decimal {
+    type Sum = rust_decimal::Decimal;
+    const SUM_TYPE: &'static str = "NUMERIC";
+}
+
+/// Nullable columns sum like their values; `SUM` skips NULLs.
+impl<T: Summable> Summable for Option<T> {
+    type Sum = T::Sum;
+    const SUM_TYPE: &'static str = T::SUM_TYPE;
+}
+
+impl<M, T> Column<M, T> {
+    /// `COUNT(column)`, which skips NULLs.
+    pub fn count(&self) -> Aggregate<M, i64> {
+        Aggregate::new(format!("COUNT({})", self.name))
+    }
+
+    /// `AVG(column)` as a double; `None` when there are no rows.
+    pub fn avg(&self) -> Aggregate<M, Option<f64>> {
+        Aggregate::new(format!("AVG({})::FLOAT8", self.name))
+    }
+
+    /// `MIN(column)`; `None` when there are no rows.
+    pub fn min(&self) -> Aggregate<M, Option<T>> {
+        Aggregate::new(format!("MIN({})", self.name))
+    }
+
+    /// `MAX(column)`; `None` when there are no rows.
+    pub fn max(&self) -> Aggregate<M, Option<T>> {
+        Aggregate::new(format!("MAX({})", self.name))
+    }
+}
+
+impl<M, T: Summable> Column<M, T> {
+    /// `SUM(column)`; `None` when there are no rows.
+    pub fn sum(&self) -> Aggregate<M, Option<T::Sum>> {
+        Aggregate::new(format!("SUM({})::{}", self.name, T::SUM_TYPE))
+    }
+}
+
+/// A query selecting one aggregate, created by `sum()`, `avg()`, `min()`, `max()` or
+/// `aggregate()` on [`OxideQueryBuilder`]. Filters, `group_by` and `having` from the
+/// builder all apply; ordering, `limit` and `offset` only apply to grouped queries, where
+/// they pick and order the groups.
+pub struct OxideAggregateQuery<M: Model<C>, C: ModelColumns<Model = M>, R> {
+    query: OxideQueryBuilder<M, C>,
+    aggregate: Aggregate<M, R>,
+}
+
+impl<M: Model<C>, C: ModelColumns<Model = M>, R> OxideAggregateQuery<M, C, R> {
+    pub(crate) fn new(query: OxideQueryBuilder<M, C>, aggregate: Aggregate<M, R>) -> Self {
+        Self { query, aggregate }
+    }
+
+    /// Grouped queries select the `group_by` columns first, then the aggregate.
+    fn columns(&self) -> String {
+        let mut columns = self.query.group_by_columns().to_vec();
+        columns.push(self.aggregate.sql.clone());
+        columns.join(", ")
+    }
+
+    /// The query with values inlined as literals, for logging and debugging.
+    pub fn build(&self) -> String {
+        self.query.build_aggregate(&self.columns())
+    }
+
+    /// The query with `$1, $2, ...` placeholders and the values to bind to them, in order.
+    pub fn build_params(&self) -> (String, Vec<SqlValue>) {
+        self.query.build_params_aggregate(&self.columns())
+    }
+
+    /// The aggregate over every matching row, for queries without `group_by`.
+    pub async fn fetch(self, db: impl IntoExecutor<'_>) -> Result<R, Error>
+    where
+        R: for<'r> Decode<'r, Postgres> + Type<Postgres> + Send + Unpin,
+        (R,): FetchRow,
+    {
+        let (query, values) = self.build_params();
+        let (value,): (R,) = self
+            .query
+            .read_executor(db)?
+            .fetch_one(query, values)
+            .await?;
+        Ok(value)
+    }
+
+    /// One row per group, decoded as the `group_by` columns followed by the aggregate, e.g.
+    /// `fetch_all::<(bool, i64)>(&db)` for a count grouped by a `bool` column.
+    pub async fn fetch_all<T>(self, db: impl IntoExecutor<'_>) -> Result<Vec<T>, Error>
+    where
+        T: FetchRow,
+    {
+        let (query, values) = self.build_params();
+        self.query.read_executor(db)?.fetch_all(query, values).await
+    }
+}
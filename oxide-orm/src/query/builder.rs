@@ -1,23 +1,81 @@
 use std::marker::PhantomData;
 
-use oxide_core::{Error, PgDatabase};
-use sqlx::{postgres::PgRow, FromRow};
+use futures::{
+    future::{self, Either},
+    stream::{self, Stream},
+};
+use oxide_core::{Error, PgTransaction};
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{PgConnection, Postgres};
 
-use crate::{Column, Model, ModelColumns, ToSql};
+use super::{
+    aggregate::{Aggregate, OxideAggregateQuery, Summable},
+    conditions::WhereClause,
+    expr::Expr,
+    page::{decode_cursor, encode_cursor, CursorPage, Keyed, Page, CURSOR_COLUMN},
+    sql::{SqlFragment, SqlWriter},
+};
+use crate::{
+    database::{Executor, FetchRow, IntoExecutor, QueryResult},
+    outbox::OUTBOX_TABLE,
+    types::{bind_all, quoted},
+    Changes, Column, HasColumns, KeyDefault, Model, ModelColumns, PrimaryKey, SqlValue, ToSql,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
+impl Direction {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            Direction::Asc => "ASC",
+            Direction::Desc => "DESC",
+        }
+    }
+}
+
+/// A model's query builder named by the model alone, e.g. for scopes:
+/// `fn active(query: Query<User>) -> Query<User>`.
+pub type Query<M> = OxideQueryBuilder<M, <M as HasColumns>::Columns>;
 
 pub struct OxideQueryBuilder<M: Model<C>, C: ModelColumns<Model = M>> {
-    conditions: ConditionExpression,
-    current_group: Option<ConditionExpression>,
+    filter: WhereClause,
+    /// Conditions of the model's default scope, cleared by `unscoped()`.
+    default_filter: WhereClause,
     selected: Vec<String>,
+    group_by: Vec<String>,
+    having: WhereClause,
+    order_by: Vec<(String, Direction)>,
+    limit: Option<u64>,
+    offset: Option<u64>,
+    primary: bool,
     _marker: PhantomData<(M, C)>,
 }
 
 impl<M: Model<C>, C: ModelColumns<Model = M>> OxideQueryBuilder<M, C> {
+    /// A query of every row in the model's default scope.
     pub fn new() -> Self {
         Self {
-            conditions: ConditionExpression::new(),
-            current_group: None,
+            default_filter: default_filter::<M, C>(),
+            ..Self::blank()
+        }
+    }
+
+    /// A query without the default scope, for collecting conditions.
+    fn blank() -> Self {
+        Self {
+            filter: WhereClause::new(),
+            default_filter: WhereClause::new(),
             selected: vec![],
+            group_by: vec![],
+            having: WhereClause::new(),
+            order_by: vec![],
+            limit: None,
+            offset: None,
+            primary: false,
             _marker: PhantomData,
         }
     }
@@ -34,29 +92,162 @@ impl<M: Model<C>, C: ModelColumns<Model = M>> OxideQueryBuilder<M, C> {
     }
 
     pub fn and_where<T: ToSql>(mut self, column: Column<M, T>, value: T) -> Self {
-        let condition = Condition::Raw(format!("{} = {}", column.name, value.to_sql()));
-        if let Some(group) = &mut self.current_group {
-            group.expressions.push(condition);
-        } else {
-            self.conditions.expressions.push(condition);
-        }
+        self.filter.and(equals(column, value));
         self
     }
 
     pub fn or_where<T: ToSql>(mut self, column: Column<M, T>, value: T) -> Self {
-        let mut or_group = ConditionExpression::new();
-        or_group.expressions.push(Condition::Raw(format!(
-            "{} = {}",
-            column.name,
-            value.to_sql()
-        )));
-        if let Some(group) = &mut self.current_group {
-            group.expressions.push(Condition::Or(Box::new(or_group)));
+        self.filter.or(equals(column, value));
+        self
+    }
+
+    /// Matches rows whose `column` is among those `query` selects, e.g.
+    /// `.and_where_in(User::columns().id, Post::query().select_one(Post::columns().user_id))`.
+    pub fn and_where_in<T, N, D>(
+        mut self,
+        column: Column<M, T>,
+        query: OxideQueryBuilder<N, D>,
+    ) -> Self
+    where
+        N: Model<D>,
+        D: ModelColumns<Model = N>,
+    {
+        self.filter.and(column.in_query(query).fragment);
+        self
+    }
+
+    pub fn or_where_in<T, N, D>(
+        mut self,
+        column: Column<M, T>,
+        query: OxideQueryBuilder<N, D>,
+    ) -> Self
+    where
+        N: Model<D>,
+        D: ModelColumns<Model = N>,
+    {
+        self.filter.or(column.in_query(query).fragment);
+        self
+    }
+
+    /// Adds a condition built from a column, e.g. `User::columns().age.gt(18)`.
+    pub fn filter(mut self, expr: Expr<M>) -> Self {
+        self.filter.and(expr.fragment);
+        self
+    }
+
+    pub fn or_filter(mut self, expr: Expr<M>) -> Self {
+        self.filter.or(expr.fragment);
+        self
+    }
+
+    /// Restricts the query to the row with primary key `key`, e.g. `.key((tenant_id, user_id))`.
+    pub fn key(mut self, key: M::Key) -> Self {
+        self.filter.and(key_equals::<M, C>(&key));
+        self
+    }
+
+    /// Applies a reusable part of a query kept on the model, e.g. `.scope(User::active)`.
+    /// The scope's conditions are grouped and added with AND, so one using `or_where` doesn't
+    /// loosen the conditions before it; anything else it sets, such as an order or a limit,
+    /// applies as usual.
+    pub fn scope<F>(mut self, scope: F) -> Self
+    where
+        F: FnOnce(Self) -> Self,
+    {
+        let filter = std::mem::replace(&mut self.filter, WhereClause::new());
+        let mut scoped = scope(self);
+        let conditions = std::mem::replace(&mut scoped.filter, filter);
+        scoped.filter.and_group(conditions);
+        scoped
+    }
+
+    /// Leaves out the model's default scope, e.g. to include soft-deleted rows.
+    pub fn unscoped(mut self) -> Self {
+        self.default_filter = WhereClause::new();
+        self
+    }
+
+    pub fn group_by<T>(mut self, column: Column<M, T>) -> Self {
+        self.group_by.push(column.name.to_string());
+        self
+    }
+
+    /// Adds a condition on an aggregate, e.g. `.having(Aggregate::count().gt(5))`.
+    pub fn having(mut self, expr: Expr<M>) -> Self {
+        self.having.and(expr.fragment);
+        self
+    }
+
+    /// Reads from the primary even when the datasource has read replicas, e.g. to see a
+    /// write made earlier in the same request.
+    pub fn on_primary(mut self) -> Self {
+        self.primary = true;
+        self
+    }
+
+    /// A read replica of the model's datasource, unless `on_primary()` was called.
+    pub(crate) fn read_executor<'a>(
+        &self,
+        db: impl IntoExecutor<'a>,
+    ) -> Result<Executor<'a>, Error> {
+        if self.primary {
+            db.executor(M::DATABASE)
         } else {
-            self.conditions
-                .expressions
-                .push(Condition::Or(Box::new(or_group)));
+            db.read_executor(M::DATABASE)
         }
+    }
+
+    pub(crate) fn group_by_columns(&self) -> &[String] {
+        &self.group_by
+    }
+
+    /// Selects `aggregate` instead of rows.
+    pub fn aggregate<R>(self, aggregate: Aggregate<M, R>) -> OxideAggregateQuery<M, C, R> {
+        OxideAggregateQuery::new(self, aggregate)
+    }
+
+    pub fn sum<T: Summable>(
+        self,
+        column: Column<M, T>,
+    ) -> OxideAggregateQuery<M, C, Option<T::Sum>> {
+        self.aggregate(column.sum())
+    }
+
+    pub fn avg<T>(self, column: Column<M, T>) -> OxideAggregateQuery<M, C, Option<f64>> {
+        self.aggregate(column.avg())
+    }
+
+    pub fn min<T>(self, column: Column<M, T>) -> OxideAggregateQuery<M, C, Option<T>> {
+        self.aggregate(column.min())
+    }
+
+    pub fn max<T>(self, column: Column<M, T>) -> OxideAggregateQuery<M, C, Option<T>> {
+        self.aggregate(column.max())
+    }
+
+    /// Sorts by `column`; repeated calls add tie-breakers in the order given.
+    pub fn order_by<T>(mut self, column: Column<M, T>, direction: Direction) -> Self {
+        self.order_by.push((column.name.to_string(), direction));
+        self
+    }
+
+    /// Orders by the primary key columns, in the order they were declared.
+    pub fn order_by_key(mut self, direction: Direction) -> Self {
+        self.order_by.extend(
+            M::PRIMARY_KEY
+                .iter()
+                .map(|column| (column.to_string(), direction)),
+        );
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.offset = Some(offset);
         self
     }
 
@@ -64,19 +255,7 @@ impl<M: Model<C>, C: ModelColumns<Model = M>> OxideQueryBuilder<M, C> {
     where
         F: FnOnce(OxideQueryBuilder<M, C>) -> OxideQueryBuilder<M, C>,
     {
-        let builder = f(OxideQueryBuilder::new());
-        let group_conditions = builder.conditions;
-        if !group_conditions.expressions.is_empty() {
-            if let Some(current) = &mut self.current_group {
-                current
-                    .expressions
-                    .push(Condition::And(Box::new(group_conditions)));
-            } else {
-                self.conditions
-                    .expressions
-                    .push(Condition::And(Box::new(group_conditions)));
-            }
-        }
+        self.filter.and_group(f(OxideQueryBuilder::blank()).filter);
         self
     }
 
@@ -84,195 +263,1202 @@ impl<M: Model<C>, C: ModelColumns<Model = M>> OxideQueryBuilder<M, C> {
     where
         F: FnOnce(OxideQueryBuilder<M, C>) -> OxideQueryBuilder<M, C>,
     {
-        let builder = f(OxideQueryBuilder::new());
-        let group_conditions = builder.conditions;
-        if !group_conditions.expressions.is_empty() {
-            if let Some(current) = &mut self.current_group {
-                current
-                    .expressions
-                    .push(Condition::Or(Box::new(group_conditions)));
-            } else {
-                self.conditions
-                    .expressions
-                    .push(Condition::Or(Box::new(group_conditions)));
-            }
-        }
+        self.filter.or_group(f(OxideQueryBuilder::blank()).filter);
         self
     }
 
-    pub fn build(&self) -> String {
-        let columns = if self.selected.is_empty() {
+    /// The statement as a fragment, for nesting in another as a subquery.
+    pub(crate) fn to_fragment(&self) -> SqlFragment {
+        let mut writer = SqlWriter::fragment();
+        self.write(&self.columns(), &mut writer);
+        writer.into_fragment()
+    }
+
+    fn write(&self, columns: &str, writer: &mut SqlWriter) {
+        self.write_filtered(columns, writer);
+
+        if !self.order_by.is_empty() {
+            let order_by = self
+                .order_by
+                .iter()
+                .map(|(column, direction)| format!("{} {}", column, direction.as_sql()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writer.push_sql(&format!(" ORDER BY {}", order_by));
+        }
+        if let Some(limit) = self.limit {
+            writer.push_sql(&format!(" LIMIT {}", limit));
+        }
+        if let Some(offset) = self.offset {
+            writer.push_sql(&format!(" OFFSET {}", offset));
+        }
+    }
+
+    fn write_filtered(&self, columns: &str, writer: &mut SqlWriter) {
+        writer.push_sql(&format!("SELECT {} FROM {}", columns, M::TABLE));
+
+        self.filter.write_scoped(&self.default_filter, writer);
+
+        if !self.group_by.is_empty() {
+            writer.push_sql(&format!(" GROUP BY {}", self.group_by.join(", ")));
+        }
+        self.having.write_as("HAVING", writer);
+    }
+
+    /// Selects an aggregate: over the filtered rows alone when ungrouped, where ordering,
+    /// `limit` and `offset` would only change which rows Postgres sums, or per group otherwise.
+    fn write_aggregate(&self, columns: &str, writer: &mut SqlWriter) {
+        if self.group_by.is_empty() {
+            self.write_filtered(columns, writer);
+        } else {
+            self.write(columns, writer);
+        }
+    }
+
+    /// Counts the rows the query would return: `COUNT(*)` in place of the columns, or over
+    /// the query as a subquery when grouping, `limit` or `offset` change what a row is.
+    fn write_count(&self, writer: &mut SqlWriter) {
+        if self.group_by.is_empty() && self.limit.is_none() && self.offset.is_none() {
+            self.write_filtered("COUNT(*)", writer);
+        } else {
+            writer.push_sql("SELECT COUNT(*) FROM (");
+            self.write("1", writer);
+            writer.push_sql(") AS counted");
+        }
+    }
+
+    fn columns(&self) -> String {
+        if self.selected.is_empty() {
             "*".to_string()
         } else {
             self.selected.join(", ")
-        };
+        }
+    }
+
+    /// The query with values inlined as literals, for logging and debugging.
+    pub fn build(&self) -> String {
+        self.build_with_columns(&self.columns())
+    }
 
-        let mut query = format!("SELECT {} FROM {}", columns, M::TABLE);
+    /// The query with `$1, $2, ...` placeholders and the values to bind to them, in order.
+    pub fn build_params(&self) -> (String, Vec<SqlValue>) {
+        self.build_params_with_columns(&self.columns())
+    }
 
-        let where_clause = self.conditions.build();
-        if !where_clause.is_empty() {
-            query.push_str(" WHERE ");
-            query.push_str(&where_clause);
-        }
+    pub(crate) fn build_with_columns(&self, columns: &str) -> String {
+        let mut writer = SqlWriter::inline();
+        self.write(columns, &mut writer);
+        writer.finish().0
+    }
+
+    pub(crate) fn build_params_with_columns(&self, columns: &str) -> (String, Vec<SqlValue>) {
+        let mut writer = SqlWriter::bound();
+        self.write(columns, &mut writer);
+        writer.finish()
+    }
 
-        query
+    pub(crate) fn build_aggregate(&self, columns: &str) -> String {
+        let mut writer = SqlWriter::inline();
+        self.write_aggregate(columns, &mut writer);
+        writer.finish().0
     }
 
-    pub async fn fetch_all<T>(self, db: &PgDatabase) -> Result<Vec<T>, Error>
+    pub(crate) fn build_params_aggregate(&self, columns: &str) -> (String, Vec<SqlValue>) {
+        let mut writer = SqlWriter::bound();
+        self.write_aggregate(columns, &mut writer);
+        writer.finish()
+    }
+
+    pub async fn fetch_all<T>(self, db: impl IntoExecutor<'_>) -> Result<Vec<T>, Error>
     where
-        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+        T: FetchRow,
     {
-        db.query(self.build()).await
+        let (query, values) = self.build_params();
+        self.read_executor(db)?.fetch_all(query, values).await
     }
 
-    pub async fn fetch_one<T>(self, db: &PgDatabase) -> Result<T, Error>
+    pub async fn fetch_one<T>(self, db: impl IntoExecutor<'_>) -> Result<T, Error>
     where
-        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+        T: FetchRow,
     {
-        db.query_one(self.build()).await
+        let (query, values) = self.build_params();
+        self.read_executor(db)?.fetch_one(query, values).await
     }
 
-    pub async fn fetch_optional<T>(self, db: &PgDatabase) -> Result<Option<T>, Error>
+    pub async fn fetch_optional<T>(self, db: impl IntoExecutor<'_>) -> Result<Option<T>, Error>
     where
-        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+        T: FetchRow,
     {
-        db.query_optional(self.build()).await
+        let (query, values) = self.build_params();
+        self.read_executor(db)?.fetch_optional(query, values).await
+    }
+
+    /// How many rows the query returns, e.g. the total behind a page of results. Selected
+    /// columns and ordering are ignored; for one count per group use
+    /// `.aggregate(Aggregate::count())`.
+    pub async fn count(self, db: impl IntoExecutor<'_>) -> Result<i64, Error> {
+        let mut writer = SqlWriter::bound();
+        self.write_count(&mut writer);
+        let (query, values) = writer.finish();
+        let (count,): (i64,) = self.read_executor(db)?.fetch_one(query, values).await?;
+        Ok(count)
     }
-}
 
-/* Example update User::update().value(User::columns().name, "John Doe").build();
-update will pass self and existing properties will be applied
-*/
+    /// Whether the query returns any rows, e.g. whether an email is already taken.
+    /// Postgres stops at the first match.
+    pub async fn exists(self, db: impl IntoExecutor<'_>) -> Result<bool, Error> {
+        let mut writer = SqlWriter::bound();
+        writer.push_sql("SELECT EXISTS (");
+        self.write("1", &mut writer);
+        writer.push_sql(")");
+        let (query, values) = writer.finish();
+        let (exists,): (bool,) = self.read_executor(db)?.fetch_one(query, values).await?;
+        Ok(exists)
+    }
 
-#[derive(Debug, Clone)]
+    /// The plan Postgres would use for the query, one line per plan node as `psql` shows it,
+    /// without running it.
+    ///
+    /// ```rust,ignore
+    /// let plan = User::query().and_where(User::columns().email, email).explain(&db).await?;
+    /// println!("{}", plan);
+    /// ```
+    pub async fn explain(&self, db: impl IntoExecutor<'_>) -> Result<String, Error> {
+        self.explain_as("EXPLAIN", db).await
+    }
+
+    /// Runs the query with `EXPLAIN (ANALYZE, BUFFERS)`, returning the plan with the time
+    /// and rows each step actually took instead of the rows themselves.
+    pub async fn explain_analyze(&self, db: impl IntoExecutor<'_>) -> Result<String, Error> {
+        self.explain_as("EXPLAIN (ANALYZE, BUFFERS)", db).await
+    }
+
+    async fn explain_as(&self, explain: &str, db: impl IntoExecutor<'_>) -> Result<String, Error> {
+        let (query, values) = self.build_params();
+        let lines: Vec<(String,)> = self
+            .read_executor(db)?
+            .fetch_all(format!("{} {}", explain, query), values)
+            .await?;
+        Ok(lines
+            .into_iter()
+            .map(|(line,)| line)
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    /// Fetches page `page`, counting from 1, of `per_page` rows along with the total across
+    /// all pages, which takes a second `COUNT(*)` query. Any `limit` or `offset` already set
+    /// is replaced. Order the query, e.g. with `order_by_key`, so pages don't overlap.
+    ///
+    /// ```rust,ignore
+    /// let page: Page<User> = User::query()
+    ///     .filter(User::columns().active.eq(true))
+    ///     .order_by_key(Direction::Asc)
+    ///     .paginate(&ctx, 2, 20)
+    ///     .await?;
+    /// ```
+    pub async fn paginate<T>(
+        mut self,
+        db: impl IntoExecutor<'_>,
+        page: u64,
+        per_page: u64,
+    ) -> Result<Page<T>, Error>
+    where
+        T: FetchRow,
+    {
+        let page = page.max(1);
+        let per_page = per_page.max(1);
+
+        self.limit = None;
+        self.offset = None;
+        let mut count = SqlWriter::bound();
+        self.write_count(&mut count);
+        let (count, count_values) = count.finish();
+
+        self.limit = Some(per_page);
+        self.offset = Some((page - 1).saturating_mul(per_page));
+        let (query, values) = self.build_params();
+
+        let mut db = self.read_executor(db)?;
+        let items = db.reborrow().fetch_all(query, values).await?;
+        let (total,): (i64,) = db.fetch_one(count, count_values).await?;
+        Ok(Page::new(items, total as u64, page, per_page))
+    }
+
+    /// Fetches the `per_page` rows following `cursor` in order of `column`, or the first
+    /// `per_page` without one, seeking with `column > $1` rather than skipping rows with
+    /// `OFFSET`, so later pages are as quick as the first. `column` must be unique and not
+    /// null, e.g. the primary key. It is sorted ascending unless the query already orders by
+    /// it descending; any other order, `limit` or `offset` is replaced.
+    ///
+    /// ```rust,ignore
+    /// let page: CursorPage<User> = User::query()
+    ///     .filter(User::columns().active.eq(true))
+    ///     .paginate_after(&ctx, User::columns().id, cursor.as_deref(), 20)
+    ///     .await?;
+    /// ```
+    ///
+    /// A cursor that wasn't returned by this method fails with `BadRequest`.
+    pub async fn paginate_after<T, V>(
+        mut self,
+        db: impl IntoExecutor<'_>,
+        column: Column<M, V>,
+        cursor: Option<&str>,
+        per_page: u64,
+    ) -> Result<CursorPage<T>, Error>
+    where
+        T: FetchRow,
+        V: ToSql + Serialize + DeserializeOwned + Send + Unpin,
+        V: for<'r> sqlx::Decode<'r, Postgres> + sqlx::Type<Postgres>,
+    {
+        let per_page = per_page.max(1);
+        let name = column.name.to_string();
+        let direction = match self.order_by.iter().find(|(c, _)| *c == name) {
+            Some((_, direction)) => *direction,
+            None => Direction::Asc,
+        };
+        if let Some(cursor) = cursor {
+            let after: V = decode_cursor(cursor)?;
+            let seek = match direction {
+                Direction::Asc => column.gt(after),
+                Direction::Desc => column.lt(after),
+            };
+            // Grouped so an OR in the query's own conditions can't skip the seek.
+            let filter = std::mem::replace(&mut self.filter, WhereClause::new());
+            self.filter.and_group(filter);
+            self.filter.and(seek.fragment);
+        }
+        self.order_by = vec![(name.clone(), direction)];
+        self.limit = Some(per_page + 1);
+        self.offset = None;
+
+        let columns = format!("{}, {} AS {}", self.columns(), name, CURSOR_COLUMN);
+        let (query, values) = self.build_params_with_columns(&columns);
+        let mut rows: Vec<Keyed<T, V>> = self.read_executor(db)?.fetch_all(query, values).await?;
+
+        let next_cursor = if rows.len() as u64 > per_page {
+            rows.truncate(per_page as usize);
+            rows.last().map(|row| encode_cursor(&row.key)).transpose()?
+        } else {
+            None
+        };
+        Ok(CursorPage {
+            items: rows.into_iter().map(|row| row.item).collect(),
+            next_cursor,
+            per_page,
+        })
+    }
+
+    /// Streams the matching rows instead of collecting them, so export endpoints can write
+    /// out millions of rows without holding them all in memory. Errors building the query
+    /// arrive as the stream's only item.
+    ///
+    /// ```rust,ignore
+    /// let mut orders = Order::query()
+    ///     .and_where(Order::columns().status, "shipped".to_string())
+    ///     .fetch_stream::<Order>(&ctx);
+    /// while let Some(order) = orders.try_next().await? {
+    ///     csv.serialize(&order)?;
+    /// }
+    /// ```
+    pub fn fetch_stream<'a, T>(
+        self,
+        db: impl IntoExecutor<'a>,
+    ) -> impl Stream<Item = Result<T, Error>> + Send + 'a
+    where
+        T: FetchRow + 'a,
+    {
+        let (query, values) = self.build_params();
+        let rows = self
+            .read_executor(db)
+            .map(|db| db.fetch_stream(query, values));
+        match rows {
+            Ok(rows) => Either::Left(rows),
+            Err(e) => Either::Right(stream::once(future::ready(Err(e)))),
+        }
+    }
+}
+
+/// An `UPDATE` of the rows matching its conditions, which are added like the select
+/// builder's. `User::update(id)` starts one for a single row; `User::update_where()` starts
+/// one without conditions:
+///
+/// ```rust,ignore
+/// User::update_where()
+///     .filter(User::columns().last_login.lt(cutoff))
+///     .and_where(User::columns().active, true)
+///     .set(User::columns().active, false)
+///     .set(User::columns().status, "dormant".to_string())
+///     .execute(&db)
+///     .await?;
+/// ```
+#[derive(Clone)]
 pub struct OxideUpdateQueryBuilder<M: Model<C>, C: ModelColumns<Model = M>> {
-    id: i32, // Just store the ID instead of the whole model
+    filter: WhereClause,
+    default_filter: WhereClause,
+    updates: Vec<(String, SqlValue)>,
+    returning: Vec<String>,
     _marker: PhantomData<(M, C)>,
-    updates: Vec<(String, String)>, // Store column-value pairs
 }
 
 impl<M: Model<C>, C: ModelColumns<Model = M>> OxideUpdateQueryBuilder<M, C> {
-    pub fn new(id: i32) -> Self {
+    /// An update of every row in the model's default scope.
+    pub fn new() -> Self {
         Self {
-            id,
-            _marker: PhantomData,
+            default_filter: default_filter::<M, C>(),
+            ..Self::blank()
+        }
+    }
+
+    fn blank() -> Self {
+        Self {
+            filter: WhereClause::new(),
+            default_filter: WhereClause::new(),
             updates: vec![],
+            returning: vec![],
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn and_where<T: ToSql>(mut self, column: Column<M, T>, value: T) -> Self {
+        self.filter.and(equals(column, value));
+        self
+    }
+
+    pub fn or_where<T: ToSql>(mut self, column: Column<M, T>, value: T) -> Self {
+        self.filter.or(equals(column, value));
+        self
+    }
+
+    /// Matches rows whose `column` is among those `query` selects, e.g.
+    /// `.and_where_in(User::columns().id, Post::query().select_one(Post::columns().user_id))`.
+    pub fn and_where_in<T, N, D>(
+        mut self,
+        column: Column<M, T>,
+        query: OxideQueryBuilder<N, D>,
+    ) -> Self
+    where
+        N: Model<D>,
+        D: ModelColumns<Model = N>,
+    {
+        self.filter.and(column.in_query(query).fragment);
+        self
+    }
+
+    pub fn or_where_in<T, N, D>(
+        mut self,
+        column: Column<M, T>,
+        query: OxideQueryBuilder<N, D>,
+    ) -> Self
+    where
+        N: Model<D>,
+        D: ModelColumns<Model = N>,
+    {
+        self.filter.or(column.in_query(query).fragment);
+        self
+    }
+
+    /// Adds a condition built from a column, e.g. `User::columns().age.gt(18)`.
+    pub fn filter(mut self, expr: Expr<M>) -> Self {
+        self.filter.and(expr.fragment);
+        self
+    }
+
+    pub fn or_filter(mut self, expr: Expr<M>) -> Self {
+        self.filter.or(expr.fragment);
+        self
+    }
+
+    /// Restricts the update to the row with primary key `key`.
+    pub fn key(mut self, key: M::Key) -> Self {
+        self.filter.and(key_equals::<M, C>(&key));
+        self
+    }
+
+    /// Leaves out the model's default scope, e.g. to restore a soft-deleted row.
+    pub fn unscoped(mut self) -> Self {
+        self.default_filter = WhereClause::new();
+        self
+    }
+
+    pub fn and_group<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(OxideUpdateQueryBuilder<M, C>) -> OxideUpdateQueryBuilder<M, C>,
+    {
+        self.filter
+            .and_group(f(OxideUpdateQueryBuilder::blank()).filter);
+        self
+    }
+
+    pub fn or_group<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(OxideUpdateQueryBuilder<M, C>) -> OxideUpdateQueryBuilder<M, C>,
+    {
+        self.filter
+            .or_group(f(OxideUpdateQueryBuilder::blank()).filter);
+        self
+    }
+
+    /// Sets `column` to `value`; setting a column again replaces the earlier value.
+    pub fn set<T: ToSql>(self, column: Column<M, T>, value: T) -> Self {
+        self.column_value(&column.name, value.to_value())
+    }
+
+    /// Untyped `set()`, for code generated by `#[model]`.
+    #[doc(hidden)]
+    pub fn column_value(mut self, column: &str, value: SqlValue) -> Self {
+        match self.updates.iter_mut().find(|(c, _)| c == column) {
+            Some((_, existing)) => *existing = value,
+            None => self.updates.push((column.to_string(), value)),
         }
+        self
     }
 
-    pub fn set<T: ToSql>(mut self, column: Column<M, T>, value: T) -> Self {
-        self.updates.push((column.name.to_string(), value.to_sql()));
+    /// Adds a `RETURNING` clause with `columns`, for `fetch_*`.
+    pub fn returning<T, const N: usize>(mut self, columns: [Column<M, T>; N]) -> Self {
+        self.returning
+            .extend(columns.iter().map(|c| c.name.to_string()));
         self
     }
 
+    /// `RETURNING *`, e.g. to fetch the affected rows as `M`.
+    pub fn returning_all(mut self) -> Self {
+        self.returning = vec!["*".to_string()];
+        self
+    }
+
+    /// Without any conditions this updates every row in the table.
+    fn write(&self, writer: &mut SqlWriter) {
+        write_publishing::<M, C>("updated", &self.returning, writer, |writer| {
+            writer.push_sql(&format!("UPDATE {} SET ", M::TABLE));
+            for (i, (col, val)) in self.updates.iter().enumerate() {
+                if i > 0 {
+                    writer.push_sql(", ");
+                }
+                writer.push_sql(&format!("{} = ", col));
+                writer.push_value(val);
+            }
+            self.filter.write_scoped(&self.default_filter, writer);
+        });
+    }
+
+    /// Refuses a statement that would set nothing or values that fail the model's rules,
+    /// then runs `M::before_save`, returning the values it left to be set.
+    async fn before_save(mut self) -> Result<(Self, Changes), Error> {
+        if self.updates.is_empty() {
+            return Err(Error::Custom(format!(
+                "Update of {} sets no columns",
+                M::TABLE
+            )));
+        }
+        M::validate(self.updates.iter().map(|(c, v)| (c.as_str(), v)))?;
+        let mut changes = Changes::new(false, std::mem::take(&mut self.updates));
+        M::before_save(&mut changes).await?;
+        self.updates = changes.values.clone();
+        Ok((self, changes))
+    }
+
+    /// The statement with values inlined as literals, for logging and debugging.
     pub fn build(&self) -> String {
-        let updates = self
-            .updates
-            .iter()
-            .map(|(col, val)| format!("{} = {}", col, val))
-            .collect::<Vec<_>>()
-            .join(", ");
+        let mut writer = SqlWriter::inline();
+        self.write(&mut writer);
+        writer.finish().0
+    }
+
+    /// The statement with `$1, $2, ...` placeholders and the values to bind to them.
+    pub fn build_params(&self) -> (String, Vec<SqlValue>) {
+        let mut writer = SqlWriter::bound();
+        self.write(&mut writer);
+        writer.finish()
+    }
+
+    pub async fn execute(self, db: impl IntoExecutor<'_>) -> Result<QueryResult, Error> {
+        let (builder, changes) = self.before_save().await?;
+        let (query, values) = builder.build_params();
+        let mut executor = db.executor(M::DATABASE)?;
+        let result = executor.reborrow().execute(query, values).await?;
+        executor.invalidate_responses(M::TABLE);
+        M::after_update(&changes, result.rows_affected()).await?;
+        Ok(result)
+    }
+
+    /// Runs the statement as part of `tx`.
+    pub async fn execute_in(self, tx: &mut PgTransaction<'_>) -> Result<QueryResult, Error> {
+        self.execute(tx).await
+    }
 
-        format!("UPDATE {} SET {} WHERE id = {}", M::TABLE, updates, self.id)
+    /// The updated row, as returned by `returning()`, or every column if it wasn't called.
+    pub async fn fetch_one<T>(self, db: impl IntoExecutor<'_>) -> Result<T, Error>
+    where
+        T: FetchRow,
+    {
+        let (builder, changes) = self.or_returning_all().before_save().await?;
+        let (query, values) = builder.build_params();
+        let mut executor = db.executor(M::DATABASE)?;
+        let row = executor.reborrow().fetch_one(query, values).await?;
+        executor.invalidate_responses(M::TABLE);
+        M::after_update(&changes, 1).await?;
+        Ok(row)
+    }
+
+    pub async fn fetch_optional<T>(self, db: impl IntoExecutor<'_>) -> Result<Option<T>, Error>
+    where
+        T: FetchRow,
+    {
+        let (builder, changes) = self.or_returning_all().before_save().await?;
+        let (query, values) = builder.build_params();
+        let mut executor = db.executor(M::DATABASE)?;
+        let row = executor.reborrow().fetch_optional(query, values).await?;
+        executor.invalidate_responses(M::TABLE);
+        M::after_update(&changes, row.is_some() as u64).await?;
+        Ok(row)
+    }
+
+    pub async fn fetch_all<T>(self, db: impl IntoExecutor<'_>) -> Result<Vec<T>, Error>
+    where
+        T: FetchRow,
+    {
+        let (builder, changes) = self.or_returning_all().before_save().await?;
+        let (query, values) = builder.build_params();
+        let mut executor = db.executor(M::DATABASE)?;
+        let rows: Vec<T> = executor.reborrow().fetch_all(query, values).await?;
+        executor.invalidate_responses(M::TABLE);
+        M::after_update(&changes, rows.len() as u64).await?;
+        Ok(rows)
+    }
+
+    fn or_returning_all(self) -> Self {
+        if self.returning.is_empty() {
+            self.returning_all()
+        } else {
+            self
+        }
+    }
+}
+
+impl<M: Model<C>, C: ModelColumns<Model = M>> Default for OxideUpdateQueryBuilder<M, C> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct OxideInsertQueryBuilder<M: Model<C>, C: ModelColumns<Model = M>> {
     columns: Vec<String>,
-    values: Vec<String>,
+    values: Vec<SqlValue>,
+    key_default: Option<SqlFragment>,
+    returning: Vec<String>,
     _marker: PhantomData<(M, C)>,
 }
 
 impl<M: Model<C>, C: ModelColumns<Model = M>> OxideInsertQueryBuilder<M, C> {
     pub fn new() -> Self {
+        // Generated up front so `build()` and `execute()` agree on a client-side key.
+        let key_default = match (M::KEY_DEFAULT, M::PRIMARY_KEY) {
+            (KeyDefault::UuidV4, [_]) => Some(SqlFragment::new().sql("gen_random_uuid()")),
+            (KeyDefault::UuidV7, [_]) => {
+                Some(SqlFragment::new().value(SqlValue::Uuid(uuid::Uuid::now_v7())))
+            }
+            _ => None,
+        };
         Self {
             columns: vec![],
             values: vec![],
+            key_default,
+            returning: vec![],
             _marker: PhantomData,
         }
     }
 
+    /// The primary key default, unless the key column was given a value.
+    fn key_default(&self) -> Option<(&str, &SqlFragment)> {
+        let column = M::PRIMARY_KEY.first()?;
+        if self.columns.iter().any(|c| c == column) {
+            return None;
+        }
+        self.key_default
+            .as_ref()
+            .map(|fragment| (*column, fragment))
+    }
+
     pub fn value<T: ToSql>(mut self, column: Column<M, T>, value: T) -> Self {
         self.columns.push(column.name.to_string());
-        self.values.push(value.to_sql());
+        self.values.push(value.to_value());
+        self
+    }
+
+    /// Untyped `value()`, for code generated by `#[model]`.
+    #[doc(hidden)]
+    pub fn column_value(mut self, column: &str, value: SqlValue) -> Self {
+        self.columns.push(column.to_string());
+        self.values.push(value);
+        self
+    }
+
+    /// Adds a `RETURNING` clause with `columns`, for `fetch_*`.
+    pub fn returning<T, const N: usize>(mut self, columns: [Column<M, T>; N]) -> Self {
+        self.returning
+            .extend(columns.iter().map(|c| c.name.to_string()));
         self
     }
 
+    /// `RETURNING *`, e.g. to fetch the affected rows as `M`.
+    pub fn returning_all(mut self) -> Self {
+        self.returning = vec!["*".to_string()];
+        self
+    }
+
+    /// The columns inserted, including a defaulted primary key.
+    fn insert_columns(&self) -> Vec<String> {
+        let mut columns = self.columns.clone();
+        if let Some((column, _)) = self.key_default() {
+            columns.push(column.to_string());
+        }
+        columns
+    }
+
+    /// The `(...)` tuple of values, in `insert_columns()` order.
+    fn write_row(&self, writer: &mut SqlWriter) {
+        writer.push_sql("(");
+        for (i, value) in self.values.iter().enumerate() {
+            if i > 0 {
+                writer.push_sql(", ");
+            }
+            writer.push_value(value);
+        }
+        if let Some((_, fragment)) = self.key_default() {
+            if !self.values.is_empty() {
+                writer.push_sql(", ");
+            }
+            fragment.write(writer);
+        }
+        writer.push_sql(")");
+    }
+
+    fn write(&self, writer: &mut SqlWriter) {
+        write_publishing::<M, C>("created", &self.returning, writer, |writer| {
+            writer.push_sql(&format!(
+                "INSERT INTO {} ({}) VALUES ",
+                M::TABLE,
+                self.insert_columns().join(", ")
+            ));
+            self.write_row(writer);
+        });
+    }
+
+    /// Refuses values that fail the model's rules, then runs `M::before_save`, returning the
+    /// values it left to be inserted.
+    async fn before_save(mut self) -> Result<(Self, Changes), Error> {
+        M::validate(self.columns.iter().map(String::as_str).zip(&self.values))?;
+        let values = std::mem::take(&mut self.columns)
+            .into_iter()
+            .zip(std::mem::take(&mut self.values))
+            .collect();
+        let mut changes = Changes::new(true, values);
+        M::before_save(&mut changes).await?;
+        (self.columns, self.values) = changes.values.iter().cloned().unzip();
+        Ok((self, changes))
+    }
+
+    /// The statement with values inlined as literals, for logging and debugging.
     pub fn build(&self) -> String {
-        let columns = self.columns.join(", ");
-        let values = self.values.join(", ");
-        format!("INSERT INTO {} ({}) VALUES ({})", M::TABLE, columns, values)
+        let mut writer = SqlWriter::inline();
+        self.write(&mut writer);
+        writer.finish().0
+    }
+
+    /// The statement with `$1, $2, ...` placeholders and the values to bind to them.
+    pub fn build_params(&self) -> (String, Vec<SqlValue>) {
+        let mut writer = SqlWriter::bound();
+        self.write(&mut writer);
+        writer.finish()
+    }
+
+    pub async fn execute(self, db: impl IntoExecutor<'_>) -> Result<QueryResult, Error> {
+        let (builder, changes) = self.before_save().await?;
+        let (query, values) = builder.build_params();
+        let mut executor = db.executor(M::DATABASE)?;
+        let result = executor.reborrow().execute(query, values).await?;
+        executor.invalidate_responses(M::TABLE);
+        M::after_create(&changes).await?;
+        Ok(result)
+    }
+
+    /// Runs the statement as part of `tx`.
+    pub async fn execute_in(self, tx: &mut PgTransaction<'_>) -> Result<QueryResult, Error> {
+        self.execute(tx).await
+    }
+
+    /// The inserted row, as returned by `returning()`, or every column if it wasn't called.
+    pub async fn fetch_one<T>(self, db: impl IntoExecutor<'_>) -> Result<T, Error>
+    where
+        T: FetchRow,
+    {
+        let (builder, changes) = self.or_returning_all().before_save().await?;
+        let (query, values) = builder.build_params();
+        let mut executor = db.executor(M::DATABASE)?;
+        let row = executor.reborrow().fetch_one(query, values).await?;
+        executor.invalidate_responses(M::TABLE);
+        M::after_create(&changes).await?;
+        Ok(row)
+    }
+
+    pub async fn fetch_optional<T>(self, db: impl IntoExecutor<'_>) -> Result<Option<T>, Error>
+    where
+        T: FetchRow,
+    {
+        let (builder, changes) = self.or_returning_all().before_save().await?;
+        let (query, values) = builder.build_params();
+        let mut executor = db.executor(M::DATABASE)?;
+        let row = executor.reborrow().fetch_optional(query, values).await?;
+        executor.invalidate_responses(M::TABLE);
+        M::after_create(&changes).await?;
+        Ok(row)
+    }
+
+    pub async fn fetch_all<T>(self, db: impl IntoExecutor<'_>) -> Result<Vec<T>, Error>
+    where
+        T: FetchRow,
+    {
+        let (builder, changes) = self.or_returning_all().before_save().await?;
+        let (query, values) = builder.build_params();
+        let mut executor = db.executor(M::DATABASE)?;
+        let rows = executor.reborrow().fetch_all(query, values).await?;
+        executor.invalidate_responses(M::TABLE);
+        M::after_create(&changes).await?;
+        Ok(rows)
+    }
+
+    fn or_returning_all(self) -> Self {
+        if self.returning.is_empty() {
+            self.returning_all()
+        } else {
+            self
+        }
     }
 }
 
-#[derive(Clone)]
-enum Condition {
-    And(Box<ConditionExpression>),
-    Or(Box<ConditionExpression>),
-    Raw(String),
+/// Postgres accepts at most this many bind parameters in one statement.
+const MAX_PARAMETERS: usize = 65535;
+
+/// Inserts many rows with multi-row `INSERT ... VALUES (...), (...)` statements, built from
+/// one [`OxideInsertQueryBuilder`] per row, e.g.
+/// `User::insert_many().rows(users.iter().map(User::to_insert))`. Every row must set the same
+/// columns in the same order.
+#[derive(Debug, Clone)]
+pub struct OxideBulkInsertBuilder<M: Model<C>, C: ModelColumns<Model = M>> {
+    rows: Vec<OxideInsertQueryBuilder<M, C>>,
+    chunk_size: usize,
+}
+
+impl<M: Model<C>, C: ModelColumns<Model = M>> OxideBulkInsertBuilder<M, C> {
+    pub fn new() -> Self {
+        Self {
+            rows: vec![],
+            chunk_size: 1000,
+        }
+    }
+
+    pub fn row(mut self, row: OxideInsertQueryBuilder<M, C>) -> Self {
+        self.rows.push(row);
+        self
+    }
+
+    pub fn rows(mut self, rows: impl IntoIterator<Item = OxideInsertQueryBuilder<M, C>>) -> Self {
+        self.rows.extend(rows);
+        self
+    }
+
+    /// Rows per statement, 1000 by default. Lowered when needed to stay within Postgres's
+    /// limit on bind parameters.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    fn chunks(&self) -> Result<std::slice::Chunks<'_, OxideInsertQueryBuilder<M, C>>, Error> {
+        let columns = self
+            .rows
+            .first()
+            .map(|row| row.insert_columns())
+            .unwrap_or_default();
+        if let Some(row) = self.rows.iter().find(|row| row.insert_columns() != columns) {
+            return Err(Error::Custom(format!(
+                "Bulk insert into {} mixes columns ({}) and ({})",
+                M::TABLE,
+                columns.join(", "),
+                row.insert_columns().join(", ")
+            )));
+        }
+        let chunk_size = self
+            .chunk_size
+            .min(MAX_PARAMETERS / columns.len().max(1))
+            .max(1);
+        Ok(self.rows.chunks(chunk_size))
+    }
+
+    fn write(rows: &[OxideInsertQueryBuilder<M, C>], writer: &mut SqlWriter) {
+        write_publishing::<M, C>("created", &[], writer, |writer| {
+            writer.push_sql(&format!(
+                "INSERT INTO {} ({}) VALUES ",
+                M::TABLE,
+                rows[0].insert_columns().join(", ")
+            ));
+            for (i, row) in rows.iter().enumerate() {
+                if i > 0 {
+                    writer.push_sql(", ");
+                }
+                row.write_row(writer);
+            }
+        });
+    }
+
+    /// One statement per chunk, with values inlined as literals, for logging and debugging.
+    pub fn build(&self) -> Result<Vec<String>, Error> {
+        Ok(self
+            .chunks()?
+            .map(|rows| {
+                let mut writer = SqlWriter::inline();
+                Self::write(rows, &mut writer);
+                writer.finish().0
+            })
+            .collect())
+    }
+
+    /// One statement per chunk, with `$1, $2, ...` placeholders and the values to bind.
+    pub fn build_params(&self) -> Result<Vec<(String, Vec<SqlValue>)>, Error> {
+        Ok(self
+            .chunks()?
+            .map(|rows| {
+                let mut writer = SqlWriter::bound();
+                Self::write(rows, &mut writer);
+                writer.finish()
+            })
+            .collect())
+    }
+
+    /// Inserts every row in one transaction, so either all of them are inserted or none is;
+    /// given a transaction, as part of it. Returns the number of rows inserted. Nothing is
+    /// sent if any row fails the model's rules or `before_save`, and `after_create` runs for
+    /// each row once all are inserted.
+    pub async fn execute(mut self, db: impl IntoExecutor<'_>) -> Result<u64, Error> {
+        let mut changes = Vec::with_capacity(self.rows.len());
+        for row in std::mem::take(&mut self.rows) {
+            let (row, row_changes) = row.before_save().await?;
+            self.rows.push(row);
+            changes.push(row_changes);
+        }
+        let mut executor = db.executor(M::DATABASE)?;
+        let inserted = match executor.reborrow() {
+            Executor::Pool(db) => {
+                let mut tx = db.begin().await?;
+                let inserted = self.insert_all(&mut tx).await?;
+                tx.commit().await?;
+                inserted
+            }
+            Executor::Transaction(tx) => self.insert_all(tx.into_connection()).await?,
+        };
+        executor.invalidate_responses(M::TABLE);
+        for changes in &changes {
+            M::after_create(changes).await?;
+        }
+        Ok(inserted)
+    }
+
+    /// Inserts every row as part of `tx`, returning the number of rows inserted.
+    pub async fn execute_in(self, tx: &mut PgTransaction<'_>) -> Result<u64, Error> {
+        self.execute(tx).await
+    }
+
+    async fn insert_all(self, conn: &mut PgConnection) -> Result<u64, Error> {
+        let mut inserted = 0;
+        for (query, values) in self.build_params()? {
+            inserted += sqlx::query_with(&query, bind_all(values)?)
+                .execute(&mut *conn)
+                .await
+                .map_err(Error::Database)?
+                .rows_affected();
+        }
+        Ok(inserted)
+    }
+}
+
+impl<M: Model<C>, C: ModelColumns<Model = M>> Default for OxideBulkInsertBuilder<M, C> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[derive(Clone)]
-struct ConditionExpression {
-    expressions: Vec<Condition>,
+pub struct OxideDeleteQueryBuilder<M: Model<C>, C: ModelColumns<Model = M>> {
+    filter: WhereClause,
+    default_filter: WhereClause,
+    returning: Vec<String>,
+    _marker: PhantomData<(M, C)>,
 }
 
-impl ConditionExpression {
-    fn new() -> Self {
+impl<M: Model<C>, C: ModelColumns<Model = M>> OxideDeleteQueryBuilder<M, C> {
+    /// A delete of every row in the model's default scope.
+    pub fn new() -> Self {
         Self {
-            expressions: vec![],
+            default_filter: default_filter::<M, C>(),
+            ..Self::blank()
         }
     }
 
-    fn build(&self) -> String {
-        if self.expressions.is_empty() {
-            return String::new();
+    fn blank() -> Self {
+        Self {
+            filter: WhereClause::new(),
+            default_filter: WhereClause::new(),
+            returning: vec![],
+            _marker: PhantomData,
         }
+    }
 
-        let mut parts = Vec::new();
-        let mut is_first = true;
+    pub fn and_where<T: ToSql>(mut self, column: Column<M, T>, value: T) -> Self {
+        self.filter.and(equals(column, value));
+        self
+    }
 
-        for condition in &self.expressions {
-            match condition {
-                Condition::And(expr) => {
-                    if expr.expressions.is_empty() {
-                        continue;
-                    }
-                    if !is_first {
-                        parts.push("AND".to_string());
-                    }
-                    let expr_str = expr.build();
-                    if expr.expressions.len() > 1 {
-                        parts.push(format!("({})", expr_str));
-                    } else {
-                        parts.push(expr_str);
-                    }
-                }
-                Condition::Or(expr) => {
-                    if expr.expressions.is_empty() {
-                        continue;
-                    }
-                    if !is_first {
-                        parts.push("OR".to_string());
-                    }
-                    let expr_str = expr.build();
-                    if expr.expressions.len() > 1 {
-                        parts.push(format!("({})", expr_str));
-                    } else {
-                        parts.push(expr_str);
-                    }
-                }
-                Condition::Raw(expr) => {
-                    if !is_first {
-                        parts.push("AND".to_string());
-                    }
-                    parts.push(expr.clone());
-                }
-            }
-            is_first = false;
+    pub fn or_where<T: ToSql>(mut self, column: Column<M, T>, value: T) -> Self {
+        self.filter.or(equals(column, value));
+        self
+    }
+
+    /// Matches rows whose `column` is among those `query` selects, e.g.
+    /// `.and_where_in(User::columns().id, Post::query().select_one(Post::columns().user_id))`.
+    pub fn and_where_in<T, N, D>(
+        mut self,
+        column: Column<M, T>,
+        query: OxideQueryBuilder<N, D>,
+    ) -> Self
+    where
+        N: Model<D>,
+        D: ModelColumns<Model = N>,
+    {
+        self.filter.and(column.in_query(query).fragment);
+        self
+    }
+
+    pub fn or_where_in<T, N, D>(
+        mut self,
+        column: Column<M, T>,
+        query: OxideQueryBuilder<N, D>,
+    ) -> Self
+    where
+        N: Model<D>,
+        D: ModelColumns<Model = N>,
+    {
+        self.filter.or(column.in_query(query).fragment);
+        self
+    }
+
+    /// Adds a condition built from a column, e.g. `User::columns().age.gt(18)`.
+    pub fn filter(mut self, expr: Expr<M>) -> Self {
+        self.filter.and(expr.fragment);
+        self
+    }
+
+    pub fn or_filter(mut self, expr: Expr<M>) -> Self {
+        self.filter.or(expr.fragment);
+        self
+    }
+
+    /// Restricts the delete to the row with primary key `key`.
+    pub fn key(mut self, key: M::Key) -> Self {
+        self.filter.and(key_equals::<M, C>(&key));
+        self
+    }
+
+    /// Leaves out the model's default scope, e.g. to purge soft-deleted rows.
+    pub fn unscoped(mut self) -> Self {
+        self.default_filter = WhereClause::new();
+        self
+    }
+
+    pub fn and_group<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(OxideDeleteQueryBuilder<M, C>) -> OxideDeleteQueryBuilder<M, C>,
+    {
+        self.filter
+            .and_group(f(OxideDeleteQueryBuilder::blank()).filter);
+        self
+    }
+
+    pub fn or_group<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(OxideDeleteQueryBuilder<M, C>) -> OxideDeleteQueryBuilder<M, C>,
+    {
+        self.filter
+            .or_group(f(OxideDeleteQueryBuilder::blank()).filter);
+        self
+    }
+
+    /// Adds a `RETURNING` clause with `columns`, for `fetch_*`.
+    pub fn returning<T, const N: usize>(mut self, columns: [Column<M, T>; N]) -> Self {
+        self.returning
+            .extend(columns.iter().map(|c| c.name.to_string()));
+        self
+    }
+
+    /// `RETURNING *`, e.g. to fetch the affected rows as `M`.
+    pub fn returning_all(mut self) -> Self {
+        self.returning = vec!["*".to_string()];
+        self
+    }
+
+    /// Without any conditions this deletes every row in the table.
+    fn write(&self, writer: &mut SqlWriter) {
+        write_publishing::<M, C>("deleted", &self.returning, writer, |writer| {
+            writer.push_sql(&format!("DELETE FROM {}", M::TABLE));
+            self.filter.write_scoped(&self.default_filter, writer);
+        });
+    }
+
+    /// The statement with values inlined as literals, for logging and debugging.
+    pub fn build(&self) -> String {
+        let mut writer = SqlWriter::inline();
+        self.write(&mut writer);
+        writer.finish().0
+    }
+
+    /// The statement with `$1, $2, ...` placeholders and the values to bind to them.
+    pub fn build_params(&self) -> (String, Vec<SqlValue>) {
+        let mut writer = SqlWriter::bound();
+        self.write(&mut writer);
+        writer.finish()
+    }
+
+    pub async fn execute(self, db: impl IntoExecutor<'_>) -> Result<QueryResult, Error> {
+        let (query, values) = self.build_params();
+        let mut executor = db.executor(M::DATABASE)?;
+        let result = executor.reborrow().execute(query, values).await?;
+        executor.invalidate_responses(M::TABLE);
+        M::after_delete(result.rows_affected()).await?;
+        Ok(result)
+    }
+
+    /// Runs the statement as part of `tx`.
+    pub async fn execute_in(self, tx: &mut PgTransaction<'_>) -> Result<QueryResult, Error> {
+        self.execute(tx).await
+    }
+
+    /// The deleted row, as returned by `returning()`, or every column if it wasn't called.
+    pub async fn fetch_one<T>(self, db: impl IntoExecutor<'_>) -> Result<T, Error>
+    where
+        T: FetchRow,
+    {
+        let (query, values) = self.or_returning_all().build_params();
+        let mut executor = db.executor(M::DATABASE)?;
+        let row = executor.reborrow().fetch_one(query, values).await?;
+        executor.invalidate_responses(M::TABLE);
+        M::after_delete(1).await?;
+        Ok(row)
+    }
+
+    pub async fn fetch_optional<T>(self, db: impl IntoExecutor<'_>) -> Result<Option<T>, Error>
+    where
+        T: FetchRow,
+    {
+        let (query, values) = self.or_returning_all().build_params();
+        let mut executor = db.executor(M::DATABASE)?;
+        let row = executor.reborrow().fetch_optional(query, values).await?;
+        executor.invalidate_responses(M::TABLE);
+        M::after_delete(row.is_some() as u64).await?;
+        Ok(row)
+    }
+
+    pub async fn fetch_all<T>(self, db: impl IntoExecutor<'_>) -> Result<Vec<T>, Error>
+    where
+        T: FetchRow,
+    {
+        let (query, values) = self.or_returning_all().build_params();
+        let mut executor = db.executor(M::DATABASE)?;
+        let rows: Vec<T> = executor.reborrow().fetch_all(query, values).await?;
+        executor.invalidate_responses(M::TABLE);
+        M::after_delete(rows.len() as u64).await?;
+        Ok(rows)
+    }
+
+    fn or_returning_all(self) -> Self {
+        if self.returning.is_empty() {
+            self.returning_all()
+        } else {
+            self
         }
+    }
+}
+
+impl<M: Model<C>, C: ModelColumns<Model = M>> Default for OxideDeleteQueryBuilder<M, C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        parts.join(" ")
+/// The conditions of `M`'s default scope. Anything else the scope sets, such as an order,
+/// is dropped.
+fn default_filter<M: Model<C>, C: ModelColumns<Model = M>>() -> WhereClause {
+    M::default_scope(OxideQueryBuilder::blank()).filter
+}
+
+fn write_returning(columns: &[String], writer: &mut SqlWriter) {
+    if !columns.is_empty() {
+        writer.push_sql(&format!(" RETURNING {}", columns.join(", ")));
+    }
+}
+
+/// Writes the statement `write` produces followed by `returning`. For models with
+/// `ModelHooks::OUTBOX` the statement is wrapped so the same statement also records each row
+/// it wrote in the outbox as a `<table>.<action>` event, then selects `returning` from those
+/// rows; counted the same, since Postgres reports the rows a `SELECT` returned.
+fn write_publishing<M: Model<C>, C: ModelColumns<Model = M>>(
+    action: &str,
+    returning: &[String],
+    writer: &mut SqlWriter,
+    write: impl FnOnce(&mut SqlWriter),
+) {
+    if !M::OUTBOX {
+        write(writer);
+        write_returning(returning, writer);
+        return;
+    }
+    writer.push_sql("WITH _oxide_written AS (");
+    write(writer);
+    let topic = format!("{}.{}", M::TABLE.trim_matches('"'), action);
+    let selected = if returning.is_empty() {
+        "1".to_string()
+    } else {
+        returning.join(", ")
+    };
+    writer.push_sql(&format!(
+        " RETURNING *), _oxide_events AS (INSERT INTO {} (topic, payload) \
+         SELECT {}, to_jsonb(_oxide_written) FROM _oxide_written) \
+         SELECT {} FROM _oxide_written",
+        OUTBOX_TABLE,
+        quoted(&topic),
+        selected
+    ));
+}
+
+fn equals<M, T: ToSql>(column: Column<M, T>, value: T) -> SqlFragment {
+    column.eq(value).fragment
+}
+
+/// `pk = <value>`, or `(a = <value> AND b = <value>)` for a composite key.
+fn key_equals<M: Model<C>, C: ModelColumns<Model = M>>(key: &M::Key) -> SqlFragment {
+    let composite = M::PRIMARY_KEY.len() > 1;
+    let mut fragment = SqlFragment::new();
+    if composite {
+        fragment = fragment.sql("(");
+    }
+    for (i, (column, value)) in M::PRIMARY_KEY.iter().zip(key.values()).enumerate() {
+        if i > 0 {
+            fragment = fragment.sql(" AND ");
+        }
+        fragment = fragment.sql(&format!("{} = ", column)).value(value);
+    }
+    if composite {
+        fragment = fragment.sql(")");
     }
+    fragment
 }
@@ -0,0 +1,130 @@
+use super::sql::{SqlFragment, SqlWriter};
+
+#[derive(Clone)]
+pub(crate) enum Condition {
+    And(Box<ConditionExpression>),
+    Or(Box<ConditionExpression>),
+    Raw(SqlFragment),
+}
+
+#[derive(Clone)]
+pub(crate) struct ConditionExpression {
+    pub(crate) expressions: Vec<Condition>,
+}
+
+impl ConditionExpression {
+    pub(crate) fn new() -> Self {
+        Self {
+            expressions: vec![],
+        }
+    }
+
+    pub(crate) fn write(&self, writer: &mut SqlWriter) {
+        let mut is_first = true;
+
+        for condition in &self.expressions {
+            match condition {
+                Condition::And(expr) | Condition::Or(expr) => {
+                    if expr.expressions.is_empty() {
+                        continue;
+                    }
+                    if !is_first {
+                        writer.push_sql(match condition {
+                            Condition::Or(_) => " OR ",
+                            _ => " AND ",
+                        });
+                    }
+                    let grouped = expr.expressions.len() > 1;
+                    if grouped {
+                        writer.push_sql("(");
+                    }
+                    expr.write(writer);
+                    if grouped {
+                        writer.push_sql(")");
+                    }
+                }
+                Condition::Raw(fragment) => {
+                    if !is_first {
+                        writer.push_sql(" AND ");
+                    }
+                    fragment.write(writer);
+                }
+            }
+            is_first = false;
+        }
+    }
+}
+
+/// The conditions of a WHERE clause, shared by the SELECT, UPDATE and DELETE builders.
+#[derive(Clone)]
+pub(crate) struct WhereClause {
+    conditions: ConditionExpression,
+    current_group: Option<ConditionExpression>,
+}
+
+impl WhereClause {
+    pub(crate) fn new() -> Self {
+        Self {
+            conditions: ConditionExpression::new(),
+            current_group: None,
+        }
+    }
+
+    fn push(&mut self, condition: Condition) {
+        if let Some(group) = &mut self.current_group {
+            group.expressions.push(condition);
+        } else {
+            self.conditions.expressions.push(condition);
+        }
+    }
+
+    pub(crate) fn and(&mut self, fragment: SqlFragment) {
+        self.push(Condition::Raw(fragment));
+    }
+
+    pub(crate) fn or(&mut self, fragment: SqlFragment) {
+        let mut or_group = ConditionExpression::new();
+        or_group.expressions.push(Condition::Raw(fragment));
+        self.push(Condition::Or(Box::new(or_group)));
+    }
+
+    pub(crate) fn and_group(&mut self, group: WhereClause) {
+        if !group.conditions.expressions.is_empty() {
+            self.push(Condition::And(Box::new(group.conditions)));
+        }
+    }
+
+    pub(crate) fn or_group(&mut self, group: WhereClause) {
+        if !group.conditions.expressions.is_empty() {
+            self.push(Condition::Or(Box::new(group.conditions)));
+        }
+    }
+
+    /// Writes ` WHERE ...`, or nothing if there are no conditions.
+    pub(crate) fn write(&self, writer: &mut SqlWriter) {
+        self.write_as("WHERE", writer);
+    }
+
+    /// Writes ` WHERE ...` with the conditions of a model's default scope ahead of these,
+    /// each side grouped so an OR in one can't loosen the other.
+    pub(crate) fn write_scoped(&self, default: &WhereClause, writer: &mut SqlWriter) {
+        if default.conditions.expressions.is_empty() {
+            return self.write(writer);
+        }
+        if self.conditions.expressions.is_empty() {
+            return default.write(writer);
+        }
+        let mut scoped = WhereClause::new();
+        scoped.and_group(default.clone());
+        scoped.and_group(self.clone());
+        scoped.write(writer);
+    }
+
+    /// Writes the conditions after `keyword`, e.g. ` HAVING ...`, or nothing if there are none.
+    pub(crate) fn write_as(&self, keyword: &str, writer: &mut SqlWriter) {
+        if !self.conditions.expressions.is_empty() {
+            writer.push_sql(&format!(" {} ", keyword));
+            self.conditions.write(writer);
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::marker::PhantomData;
+
+use super::{builder::OxideQueryBuilder, sql::SqlFragment};
+use crate::{Column, Model, ModelColumns, SqlValue, ToSql};
+
+/// A condition on a column of model `M`, built from the comparison methods on [`Column`]
+/// and passed to `filter`/`or_filter`, e.g. `User::query().filter(User::columns().age.gte(18))`.
+#[derive(Debug, Clone)]
+pub struct Expr<M> {
+    pub(crate) fragment: SqlFragment,
+    _marker: PhantomData<M>,
+}
+
+impl<M> Expr<M> {
+    pub(crate) fn new(fragment: SqlFragment) -> Self {
+        Self {
+            fragment,
+            _marker: PhantomData,
+        }
+    }
+
+    /// `EXISTS (SELECT ...)`: whether `query` matches any row.
+    pub fn exists<N: Model<C>, C: ModelColumns<Model = N>>(query: OxideQueryBuilder<N, C>) -> Self {
+        Self::new(subquery("EXISTS", query))
+    }
+
+    /// `NOT EXISTS (SELECT ...)`: whether `query` matches no rows.
+    pub fn not_exists<N: Model<C>, C: ModelColumns<Model = N>>(
+        query: OxideQueryBuilder<N, C>,
+    ) -> Self {
+        Self::new(subquery("NOT EXISTS", query))
+    }
+}
+
+/// `<op> (SELECT ...)`, with the subquery's values numbered along with the outer statement's.
+fn subquery<N: Model<C>, C: ModelColumns<Model = N>>(
+    op: &str,
+    query: OxideQueryBuilder<N, C>,
+) -> SqlFragment {
+    SqlFragment::new()
+        .sql(&format!("{} (", op))
+        .fragment(query.to_fragment())
+        .sql(")")
+}
+
+impl<M, T: ToSql> Column<M, T> {
+    fn compare(&self, op: &str, value: T) -> Expr<M> {
+        Expr::new(
+            SqlFragment::new()
+                .sql(&format!("{} {} ", self.name, op))
+                .value(value.to_value()),
+        )
+    }
+
+    /// `column = value`, or `column IS NULL` for `None`.
+    pub fn eq(&self, value: T) -> Expr<M> {
+        match value.to_value() {
+            SqlValue::Null(_) => self.is_null(),
+            _ => self.compare("=", value),
+        }
+    }
+
+    /// `column <> value`, or `column IS NOT NULL` for `None`.
+    pub fn ne(&self, value: T) -> Expr<M> {
+        match value.to_value() {
+            SqlValue::Null(_) => self.is_not_null(),
+            _ => self.compare("<>", value),
+        }
+    }
+
+    pub fn gt(&self, value: T) -> Expr<M> {
+        self.compare(">", value)
+    }
+
+    pub fn gte(&self, value: T) -> Expr<M> {
+        self.compare(">=", value)
+    }
+
+    pub fn lt(&self, value: T) -> Expr<M> {
+        self.compare("<", value)
+    }
+
+    pub fn lte(&self, value: T) -> Expr<M> {
+        self.compare("<=", value)
+    }
+
+    /// `column IN (...)`; an empty list matches nothing.
+    pub fn is_in(&self, values: impl IntoIterator<Item = T>) -> Expr<M> {
+        self.list("IN", "FALSE", values)
+    }
+
+    /// `column NOT IN (...)`; an empty list matches everything.
+    pub fn not_in(&self, values: impl IntoIterator<Item = T>) -> Expr<M> {
+        self.list("NOT IN", "TRUE", values)
+    }
+
+    fn list(&self, op: &str, if_empty: &str, values: impl IntoIterator<Item = T>) -> Expr<M> {
+        let mut fragment = SqlFragment::new().sql(&format!("{} {} (", self.name, op));
+        let mut empty = true;
+        for value in values {
+            if !empty {
+                fragment = fragment.sql(", ");
+            }
+            fragment = fragment.value(value.to_value());
+            empty = false;
+        }
+        if empty {
+            return Expr::new(SqlFragment::new().sql(if_empty));
+        }
+        Expr::new(fragment.sql(")"))
+    }
+
+    /// `column BETWEEN low AND high`, inclusive at both ends.
+    pub fn between(&self, low: T, high: T) -> Expr<M> {
+        Expr::new(
+            SqlFragment::new()
+                .sql(&format!("{} BETWEEN ", self.name))
+                .value(low.to_value())
+                .sql(" AND ")
+                .value(high.to_value()),
+        )
+    }
+}
+
+impl<M, T> Column<M, T> {
+    /// `column IN (SELECT ...)`. `query` should select a single column of the same type,
+    /// e.g. with `select_one`.
+    pub fn in_query<N: Model<C>, C: ModelColumns<Model = N>>(
+        &self,
+        query: OxideQueryBuilder<N, C>,
+    ) -> Expr<M> {
+        Expr::new(subquery(&format!("{} IN", self.name), query))
+    }
+
+    /// `column NOT IN (SELECT ...)`. Like SQL's, this matches nothing if the subquery returns
+    /// a `NULL`; filter those out of `query` or use [`Expr::not_exists`].
+    pub fn not_in_query<N: Model<C>, C: ModelColumns<Model = N>>(
+        &self,
+        query: OxideQueryBuilder<N, C>,
+    ) -> Expr<M> {
+        Expr::new(subquery(&format!("{} NOT IN", self.name), query))
+    }
+
+    pub fn is_null(&self) -> Expr<M> {
+        Expr::new(SqlFragment::new().sql(&format!("{} IS NULL", self.name)))
+    }
+
+    pub fn is_not_null(&self) -> Expr<M> {
+        Expr::new(SqlFragment::new().sql(&format!("{} IS NOT NULL", self.name)))
+    }
+}
+
+impl<M> Column<M, String> {
+    /// Case-sensitive pattern match using `%` and `_` wildcards.
+    pub fn like(&self, pattern: impl Into<String>) -> Expr<M> {
+        self.compare("LIKE", pattern.into())
+    }
+
+    /// Case-insensitive `LIKE`.
+    pub fn ilike(&self, pattern: impl Into<String>) -> Expr<M> {
+        self.compare("ILIKE", pattern.into())
+    }
+}
+
+impl<M, T: ToSql> Column<M, Vec<T>> {
+    /// `value = ANY(column)`: whether `value` is one of the array's elements, e.g.
+    /// `Post::columns().tags.any_eq("rust".to_string())`.
+    pub fn any_eq(&self, value: T) -> Expr<M> {
+        Expr::new(
+            SqlFragment::new()
+                .value(value.to_value())
+                .sql(&format!(" = ANY({})", self.name)),
+        )
+    }
+
+    /// `column @> values`: whether the array has every one of `values`.
+    pub fn contains(&self, values: &[T]) -> Expr<M> {
+        self.compare_array("@>", values)
+    }
+
+    /// `column <@ values`: whether every element of the array is one of `values`.
+    pub fn contained_by(&self, values: &[T]) -> Expr<M> {
+        self.compare_array("<@", values)
+    }
+
+    /// `column && values`: whether the array has any of `values`.
+    pub fn overlaps(&self, values: &[T]) -> Expr<M> {
+        self.compare_array("&&", values)
+    }
+
+    fn compare_array(&self, op: &str, values: &[T]) -> Expr<M> {
+        Expr::new(
+            SqlFragment::new()
+                .sql(&format!("{} {} ", self.name, op))
+                .value(values.to_value()),
+        )
+    }
+}
+
+impl<M> Column<M, serde_json::Value> {
+    /// `column ->> 'key'`: the value under `key` as text, to compare or match like any text
+    /// column, e.g. `Event::columns().payload.json_get("kind").eq("signup".to_string())`.
+    /// Numbers come back as their text, and a missing key as `NULL`.
+    pub fn json_get(&self, key: &str) -> Column<M, String> {
+        Column::expression(format!("({} ->> {})", self.name, key.to_string().to_sql()))
+    }
+
+    /// `column -> 'key'`: the JSON under `key`, for reaching into nested objects, e.g.
+    /// `payload.json_field("user").json_get("email")`.
+    pub fn json_field(&self, key: &str) -> Column<M, serde_json::Value> {
+        Column::expression(format!("({} -> {})", self.name, key.to_string().to_sql()))
+    }
+
+    /// `column @> value`: whether the column contains `value`, e.g.
+    /// `json!({"tags": ["rust"]})` matches documents whose `tags` array includes `"rust"`.
+    pub fn json_contains(&self, value: serde_json::Value) -> Expr<M> {
+        self.compare("@>", value)
+    }
+
+    /// `column ? key`: whether the column is an object with `key` at its top level, or an
+    /// array with `key` as one of its strings.
+    pub fn json_has_key(&self, key: &str) -> Expr<M> {
+        Expr::new(
+            SqlFragment::new()
+                .sql(&format!("{} ? ", self.name))
+                .value(key.to_string().to_value()),
+        )
+    }
+}
@@ -1,7 +1,20 @@
+mod aggregate;
 mod builder;
+mod conditions;
+mod expr;
+mod page;
+mod raw;
+mod sql;
 // mod clauses;
 // mod execute;
 
-pub use builder::{OxideInsertQueryBuilder, OxideQueryBuilder, OxideUpdateQueryBuilder};
+pub use aggregate::{Aggregate, OxideAggregateQuery, Summable};
+pub use builder::{
+    Direction, OxideBulkInsertBuilder, OxideDeleteQueryBuilder, OxideInsertQueryBuilder,
+    OxideQueryBuilder, OxideUpdateQueryBuilder, Query,
+};
+pub use expr::Expr;
+pub use page::{CursorPage, Page, CURSOR_PARAM, PAGE_PARAM, PER_PAGE_PARAM};
+pub use raw::{RawQuery, RawSql};
 // pub use clauses::{Limit, OrderBy, Where};
 // pub use execute::Execute;
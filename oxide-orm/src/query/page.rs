@@ -0,0 +1,204 @@
+use oxide_core::{
+    http::{Context, OxideRes, OxideResponse},
+    Error,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{postgres::PgRow, FromRow, Postgres, Row};
+
+/// The query parameter page links set to the page number.
+pub const PAGE_PARAM: &str = "page";
+/// The query parameter page links set to the page size.
+pub const PER_PAGE_PARAM: &str = "per_page";
+/// The query parameter cursor page links set to the cursor.
+pub const CURSOR_PARAM: &str = "cursor";
+
+/// One page of query results with the totals needed to render pagination, returned by
+/// [`OxideQueryBuilder::paginate`](super::OxideQueryBuilder::paginate). Serializes as
+/// `{ "items": [...], "total": 42, "page": 1, "per_page": 20, "total_pages": 3 }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Rows matching the query across every page.
+    pub total: u64,
+    /// 1-based.
+    pub page: u64,
+    pub per_page: u64,
+    pub total_pages: u64,
+}
+
+impl<T> Page<T> {
+    pub(crate) fn new(items: Vec<T>, total: u64, page: u64, per_page: u64) -> Self {
+        Self {
+            items,
+            total,
+            page,
+            per_page,
+            total_pages: total.div_ceil(per_page),
+        }
+    }
+
+    pub fn has_next(&self) -> bool {
+        self.page < self.total_pages
+    }
+
+    pub fn has_previous(&self) -> bool {
+        self.page > 1
+    }
+
+    /// Converts the items, e.g. from rows into response types, keeping the totals.
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            total: self.total,
+            page: self.page,
+            per_page: self.per_page,
+            total_pages: self.total_pages,
+        }
+    }
+
+    /// `Link` (RFC 8288) to the first, previous, next and last pages, leaving out those that
+    /// don't exist, and `X-Total-Count`. Links are `path`, the request's path and query
+    /// string, with `page` and `per_page` set.
+    pub fn headers(&self, path: &str) -> Vec<(String, String)> {
+        let link = |page: u64, rel: &str| {
+            let url = with_query_param(path, PAGE_PARAM, &page.to_string());
+            let url = with_query_param(&url, PER_PAGE_PARAM, &self.per_page.to_string());
+            format!("<{}>; rel=\"{}\"", url, rel)
+        };
+        let mut links = vec![link(1, "first")];
+        if self.has_previous() {
+            links.push(link((self.page - 1).min(self.total_pages.max(1)), "prev"));
+        }
+        if self.has_next() {
+            links.push(link(self.page + 1, "next"));
+        }
+        links.push(link(self.total_pages.max(1), "last"));
+        vec![
+            ("Link".to_string(), links.join(", ")),
+            ("X-Total-Count".to_string(), self.total.to_string()),
+        ]
+    }
+}
+
+impl<T: Serialize> Page<T> {
+    /// Responds with the page as JSON along with its [`headers`](Page::headers), linking
+    /// from the request in `ctx`.
+    ///
+    /// ```rust,ignore
+    /// let page = User::query().order_by_key(Direction::Asc).paginate(ctx, page, 20).await?;
+    /// page.into_response(ctx)
+    /// ```
+    pub fn into_response(self, ctx: &Context) -> OxideResponse {
+        let headers = self.headers(&ctx.request.path);
+        headers.iter().fold(
+            OxideResponse::json(OxideRes::Success, self),
+            |res, (name, value)| res.with_header(name, value),
+        )
+    }
+}
+
+/// One page of results fetched after a cursor, returned by
+/// [`OxideQueryBuilder::paginate_after`](super::OxideQueryBuilder::paginate_after).
+/// Serializes as `{ "items": [...], "next_cursor": "...", "per_page": 20 }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CursorPage<T> {
+    pub items: Vec<T>,
+    /// Pass to `paginate_after` for the page after this one; `None` on the last page.
+    pub next_cursor: Option<String>,
+    pub per_page: u64,
+}
+
+impl<T> CursorPage<T> {
+    pub fn has_next(&self) -> bool {
+        self.next_cursor.is_some()
+    }
+
+    /// Converts the items, e.g. from rows into response types, keeping the cursor.
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> CursorPage<U> {
+        CursorPage {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+            per_page: self.per_page,
+        }
+    }
+
+    /// `Link` (RFC 8288) to the next page, if there is one: `path`, the request's path and
+    /// query string, with `cursor` and `per_page` set. Cursors only lead forward, so there
+    /// is no previous link.
+    pub fn headers(&self, path: &str) -> Vec<(String, String)> {
+        let Some(cursor) = &self.next_cursor else {
+            return vec![];
+        };
+        let url = with_query_param(path, CURSOR_PARAM, cursor);
+        let url = with_query_param(&url, PER_PAGE_PARAM, &self.per_page.to_string());
+        vec![("Link".to_string(), format!("<{}>; rel=\"next\"", url))]
+    }
+}
+
+impl<T: Serialize> CursorPage<T> {
+    /// Responds with the page as JSON along with its [`headers`](CursorPage::headers),
+    /// linking from the request in `ctx`.
+    pub fn into_response(self, ctx: &Context) -> OxideResponse {
+        let headers = self.headers(&ctx.request.path);
+        headers.iter().fold(
+            OxideResponse::json(OxideRes::Success, self),
+            |res, (name, value)| res.with_header(name, value),
+        )
+    }
+}
+
+/// `path` with the query parameter `key` set to `value`, replacing any value it had and
+/// keeping the other parameters in order. `value` must not need percent-encoding.
+fn with_query_param(path: &str, key: &str, value: &str) -> String {
+    let (path, query) = path.split_once('?').unwrap_or((path, ""));
+    let mut params: Vec<String> = query
+        .split('&')
+        .filter(|pair| !pair.is_empty() && pair.split('=').next() != Some(key))
+        .map(str::to_string)
+        .collect();
+    params.push(format!("{}={}", key, value));
+    format!("{}?{}", path, params.join("&"))
+}
+
+/// The alias the cursor column is selected under, next to the row's own columns.
+pub(crate) const CURSOR_COLUMN: &str = "_oxide_cursor";
+
+/// A row along with the value of its cursor column.
+#[cfg_attr(feature = "fixtures", derive(Serialize, serde::Deserialize))]
+pub(crate) struct Keyed<T, V> {
+    pub(crate) item: T,
+    pub(crate) key: V,
+}
+
+impl<'r, T, V> FromRow<'r, PgRow> for Keyed<T, V>
+where
+    T: FromRow<'r, PgRow>,
+    V: sqlx::Decode<'r, Postgres> + sqlx::Type<Postgres>,
+{
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            item: T::from_row(row)?,
+            key: row.try_get(CURSOR_COLUMN)?,
+        })
+    }
+}
+
+/// The cursor pointing after `key`: its JSON, hex encoded so it can go in a query string.
+pub(crate) fn encode_cursor<V: Serialize>(key: &V) -> Result<String, Error> {
+    let json = serde_json::to_vec(key).map_err(|e| Error::Serialization(e.to_string()))?;
+    Ok(json.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// The key a cursor from [`encode_cursor`] points after.
+pub(crate) fn decode_cursor<V: DeserializeOwned>(cursor: &str) -> Result<V, Error> {
+    let invalid = || Error::BadRequest(format!("Invalid cursor: {}", cursor));
+    if !cursor.len().is_multiple_of(2) || !cursor.is_ascii() {
+        return Err(invalid());
+    }
+    let json = (0..cursor.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&cursor[i..i + 2], 16))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| invalid())?;
+    serde_json::from_slice(&json).map_err(|_| invalid())
+}
@@ -0,0 +1,137 @@
+use futures::{
+    future::{self, Either},
+    stream::{self, Stream},
+};
+use oxide_core::Error;
+
+use crate::{
+    database::{FetchRow, IntoExecutor, QueryResult},
+    SqlValue, ToSql,
+};
+
+/// SQL the query builders can't express, still sent with bound parameters and decoded
+/// into typed rows rather than built with `format!()`. Values are bound to `$1, $2, ...`
+/// in the order of the `bind` calls.
+///
+/// ```rust,ignore
+/// let users: Vec<User> = db
+///     .raw("SELECT u.* FROM users u JOIN teams t ON t.id = u.team_id WHERE t.slug = $1 AND u.age > $2")
+///     .bind("core".to_string())
+///     .bind(30)
+///     .fetch_all::<User>()
+///     .await?;
+/// ```
+pub struct RawQuery<E> {
+    source: E,
+    database: Option<String>,
+    sql: String,
+    values: Vec<SqlValue>,
+}
+
+/// Starts a [`RawQuery`] on anything the query builders run against: `&db`, `&ctx` or
+/// `&mut tx`.
+pub trait RawSql: Sized {
+    fn raw(self, sql: impl Into<String>) -> RawQuery<Self>;
+}
+
+impl<'a, E: IntoExecutor<'a>> RawSql for E {
+    fn raw(self, sql: impl Into<String>) -> RawQuery<Self> {
+        RawQuery {
+            source: self,
+            database: None,
+            sql: sql.into(),
+            values: vec![],
+        }
+    }
+}
+
+impl<E> RawQuery<E> {
+    pub(crate) fn sql_and_values(&self) -> (&str, &[SqlValue]) {
+        (&self.sql, &self.values)
+    }
+
+    /// Binds `value` to the next `$n` placeholder.
+    pub fn bind<T: ToSql>(mut self, value: T) -> Self {
+        self.values.push(value.to_value());
+        self
+    }
+
+    /// Runs on the named datasource when started from a `Context`, instead of the main one.
+    pub fn with_database(mut self, name: &str) -> Self {
+        self.database = Some(name.to_string());
+        self
+    }
+
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    pub fn values(&self) -> &[SqlValue] {
+        &self.values
+    }
+
+    pub async fn fetch_all<'a, T>(self) -> Result<Vec<T>, Error>
+    where
+        E: IntoExecutor<'a>,
+        T: FetchRow,
+    {
+        self.source
+            .executor(self.database.as_deref())?
+            .fetch_all(self.sql, self.values)
+            .await
+    }
+
+    pub async fn fetch_one<'a, T>(self) -> Result<T, Error>
+    where
+        E: IntoExecutor<'a>,
+        T: FetchRow,
+    {
+        self.source
+            .executor(self.database.as_deref())?
+            .fetch_one(self.sql, self.values)
+            .await
+    }
+
+    pub async fn fetch_optional<'a, T>(self) -> Result<Option<T>, Error>
+    where
+        E: IntoExecutor<'a>,
+        T: FetchRow,
+    {
+        self.source
+            .executor(self.database.as_deref())?
+            .fetch_optional(self.sql, self.values)
+            .await
+    }
+
+    /// Streams the rows instead of collecting them, like
+    /// [`OxideQueryBuilder::fetch_stream`](crate::OxideQueryBuilder::fetch_stream).
+    pub fn fetch_stream<'a, T>(self) -> impl Stream<Item = Result<T, Error>> + Send + 'a
+    where
+        E: IntoExecutor<'a>,
+        T: FetchRow + 'a,
+    {
+        let Self {
+            source,
+            database,
+            sql,
+            values,
+        } = self;
+        let rows = source
+            .executor(database.as_deref())
+            .map(|db| db.fetch_stream(sql, values));
+        match rows {
+            Ok(rows) => Either::Left(rows),
+            Err(e) => Either::Right(stream::once(future::ready(Err(e)))),
+        }
+    }
+
+    pub async fn execute<'a>(self) -> Result<QueryResult, Error>
+    where
+        E: IntoExecutor<'a>,
+    {
+        self.source
+            .executor(self.database.as_deref())?
+            .execute(self.sql, self.values)
+            .await
+    }
+}
@@ -0,0 +1,116 @@
+use crate::SqlValue;
+
+/// Accumulates SQL text, rendering values either inline as literals or as `$n` placeholders
+/// collected for binding, or keeping them apart as a [`SqlFragment`] to nest in another
+/// statement.
+pub(crate) struct SqlWriter {
+    sql: String,
+    output: Output,
+}
+
+enum Output {
+    Inline,
+    Bound(Vec<SqlValue>),
+    Fragment(Vec<Part>),
+}
+
+impl SqlWriter {
+    pub(crate) fn inline() -> Self {
+        Self {
+            sql: String::new(),
+            output: Output::Inline,
+        }
+    }
+
+    pub(crate) fn bound() -> Self {
+        Self {
+            sql: String::new(),
+            output: Output::Bound(vec![]),
+        }
+    }
+
+    /// Writes into a fragment, so a statement can be nested as a subquery and numbered
+    /// along with the statement it ends up in.
+    pub(crate) fn fragment() -> Self {
+        Self {
+            sql: String::new(),
+            output: Output::Fragment(vec![]),
+        }
+    }
+
+    pub(crate) fn push_sql(&mut self, sql: &str) {
+        self.sql.push_str(sql);
+    }
+
+    pub(crate) fn push_value(&mut self, value: &SqlValue) {
+        match &mut self.output {
+            Output::Bound(params) => {
+                params.push(value.clone());
+                self.sql.push_str(&format!("${}", params.len()));
+            }
+            Output::Inline => self.sql.push_str(&value.to_sql()),
+            Output::Fragment(parts) => {
+                parts.push(Part::Sql(std::mem::take(&mut self.sql)));
+                parts.push(Part::Value(value.clone()));
+            }
+        }
+    }
+
+    pub(crate) fn finish(self) -> (String, Vec<SqlValue>) {
+        match self.output {
+            Output::Bound(params) => (self.sql, params),
+            _ => (self.sql, vec![]),
+        }
+    }
+
+    pub(crate) fn into_fragment(self) -> SqlFragment {
+        let mut parts = match self.output {
+            Output::Fragment(parts) => parts,
+            _ => vec![],
+        };
+        parts.push(Part::Sql(self.sql));
+        SqlFragment { parts }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Sql(String),
+    Value(SqlValue),
+}
+
+/// A piece of SQL with values kept apart from the text, such as `age = <value>`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct SqlFragment {
+    parts: Vec<Part>,
+}
+
+impl SqlFragment {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn sql(mut self, sql: &str) -> Self {
+        self.parts.push(Part::Sql(sql.to_string()));
+        self
+    }
+
+    pub(crate) fn value(mut self, value: SqlValue) -> Self {
+        self.parts.push(Part::Value(value));
+        self
+    }
+
+    pub(crate) fn fragment(mut self, fragment: SqlFragment) -> Self {
+        self.parts.extend(fragment.parts);
+        self
+    }
+
+    pub(crate) fn write(&self, writer: &mut SqlWriter) {
+        for part in &self.parts {
+            match part {
+                Part::Sql(sql) => writer.push_sql(sql),
+                Part::Value(value) => writer.push_value(value),
+            }
+        }
+    }
+}
@@ -1,6 +1,9 @@
-use std::marker::PhantomData;
+use std::{borrow::Cow, future::Future, marker::PhantomData, pin::Pin};
 
-use sqlx::FromRow;
+use oxide_core::Error;
+use sqlx::{postgres::PgRow, FromRow};
+
+use crate::{IntoExecutor, ModelHooks, OxideQueryBuilder, SqlType, SqlValue, ToSql, Validate};
 
 // pub trait Table: Sized {
 //     const NAME: &'static str;
@@ -15,21 +18,193 @@ pub trait ModelColumns: Sized {
     type Model: Model<Self>;
 }
 
-pub trait Model<C: ModelColumns>: for<'r> FromRow<'r, sqlx::postgres::PgRow> + Send + Sync {
+/// The way back from a model to its columns struct, implemented by `#[model]`, so
+/// [`Query<User>`](crate::Query) can name a model's query builder.
+pub trait HasColumns: Sized {
+    type Columns: ModelColumns<Model = Self>;
+}
+
+pub trait Model<C: ModelColumns>:
+    for<'r> FromRow<'r, sqlx::postgres::PgRow> + Validate + ModelHooks + Send + Sync
+{
     const TABLE: &'static str;
+    /// Primary key columns, in the order their values appear in `Key`.
+    const PRIMARY_KEY: &'static [&'static str];
+    type Key: PrimaryKey;
+    /// The named datasource this model lives in, set with `#[model(database = "analytics")]`.
+    /// Only consulted when the builders are given a `Context`.
+    const DATABASE: Option<&'static str> = None;
+    /// How `insert()` fills in the primary key when it isn't set.
+    const KEY_DEFAULT: KeyDefault = KeyDefault::Database;
     fn columns() -> C;
+    fn primary_key(&self) -> Self::Key;
+
+    /// Narrows every query, update and delete of the model unless they call `unscoped()`,
+    /// e.g. to leave out soft-deleted rows. Set with `#[model(default_scope = "...")]`. Only
+    /// the conditions are kept; an order or a limit set here is ignored.
+    fn default_scope(query: OxideQueryBuilder<Self, C>) -> OxideQueryBuilder<Self, C>
+    where
+        C: ModelColumns<Model = Self>,
+    {
+        query
+    }
+}
+
+pub type DeleteFuture<'a> = Pin<Box<dyn Future<Output = Result<bool, Error>> + Send + 'a>>;
+
+/// `model.delete(&db)`, implemented by `#[model]` for models with a primary key. A trait
+/// rather than an inherent method so it doesn't clash with the `Model::delete()` builder.
+pub trait DeleteRow {
+    /// Deletes the row with this value's primary key, returning whether there was one.
+    fn delete<'a>(&'a self, db: impl IntoExecutor<'a> + 'a) -> DeleteFuture<'a>;
+}
+
+/// Set with `#[model(key_default = "uuid_v7")]`; only applies to single-column keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyDefault {
+    /// Leave the column out and let its own default (serial, identity, ...) fill it in.
+    Database,
+    /// `gen_random_uuid()`, built into Postgres 13 and later.
+    UuidV4,
+    /// A time-ordered v7 UUID generated client-side, so the key is known before the insert.
+    UuidV7,
+}
+
+/// The value of a model's primary key: the column's own type, or a tuple for composite keys
+/// declared with `#[model(pk = (tenant_id, user_id))]`.
+pub trait PrimaryKey {
+    fn values(&self) -> Vec<SqlValue>;
+}
+
+impl<T: ToSql> PrimaryKey for T {
+    fn values(&self) -> Vec<SqlValue> {
+        vec![self.to_value()]
+    }
+}
+
+macro_rules! tuple_primary_key {
+    ($($name:ident: $index:tt),+) => {
+        impl<$($name: ToSql),+> PrimaryKey for ($($name,)+) {
+            fn values(&self) -> Vec<SqlValue> {
+                vec![$(self.$index.to_value()),+]
+            }
+        }
+    };
+}
+
+/// The key of models without one, which get no key-based methods.
+impl PrimaryKey for () {
+    fn values(&self) -> Vec<SqlValue> {
+        vec![]
+    }
+}
+
+tuple_primary_key!(A: 0, B: 1);
+tuple_primary_key!(A: 0, B: 1, C: 2);
+tuple_primary_key!(A: 0, B: 1, C: 2, D: 3);
+
+/// A value object stored as prefixed columns of the model it's embedded in, declared with
+/// `#[embeddable]` and embedded with `#[column(flatten)]`. An `address: Address` field maps
+/// `Address::city` to the `address_city` column.
+pub trait Embedded: Sized {
+    type Columns<M>;
+    fn columns<M>(prefix: &str) -> Self::Columns<M>;
+    fn from_row(row: &PgRow, prefix: &str) -> Result<Self, sqlx::Error>;
+}
+
+/// The column values of an embedded struct, for `to_insert()`. `#[embeddable]` implements it
+/// for `&Struct` when every field is `ToSql`.
+pub trait EmbeddedValues {
+    fn column_values(self, prefix: &str) -> Vec<(String, SqlValue)>;
+}
+
+/// The column definitions of an embedded struct, for `table_def()`. `#[embeddable]`
+/// implements it for `&Struct` when every field is `ToSql`.
+pub trait EmbeddedSchema {
+    fn column_defs(prefix: &str) -> Vec<ColumnDef>;
+}
+
+/// A column as a model declares it or as introspection finds it in the database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDef {
+    pub name: String,
+    /// The type as `format_type()` spells it, e.g. `integer` or `character varying(255)`.
+    pub sql_type: String,
+    pub nullable: bool,
+}
+
+impl ColumnDef {
+    pub fn new(name: impl Into<String>, sql_type: SqlType, nullable: bool) -> Self {
+        Self {
+            name: name.into(),
+            sql_type: sql_type.type_name(),
+            nullable,
+        }
+    }
+
+    /// The type and nullability, as written after the name in `ADD COLUMN` or `CREATE TABLE`.
+    pub fn definition(&self) -> String {
+        if self.nullable {
+            self.sql_type.clone()
+        } else {
+            format!("{} NOT NULL", self.sql_type)
+        }
+    }
+}
+
+/// A table's columns and primary key, from a model's generated `table_def()` or from
+/// [`introspect`](crate::migration::introspect).
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableDef {
+    pub table: String,
+    pub primary_key: Vec<String>,
+    pub columns: Vec<ColumnDef>,
+}
+
+impl TableDef {
+    pub fn new(table: &str, primary_key: &[&str], columns: Vec<ColumnDef>) -> Self {
+        Self {
+            table: table.to_string(),
+            primary_key: primary_key
+                .iter()
+                .map(|column| column.to_string())
+                .collect(),
+            columns,
+        }
+    }
+
+    pub fn column(&self, name: &str) -> Option<&ColumnDef> {
+        self.columns.iter().find(|column| column.name == name)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Column<M, T> {
-    pub name: &'static str,
+    pub name: Cow<'static, str>,
     _marker: PhantomData<(M, T)>,
 }
 
 impl<M, T> Column<M, T> {
     pub fn new(name: &'static str) -> Self {
         Self {
-            name,
+            name: Cow::Borrowed(name),
+            _marker: PhantomData,
+        }
+    }
+
+    /// A column of an embedded struct, e.g. `address_` + `city`.
+    pub fn prefixed(prefix: &str, name: &str) -> Self {
+        Self {
+            name: Cow::Owned(format!("{}{}", prefix, name)),
+            _marker: PhantomData,
+        }
+    }
+
+    /// An expression over a column that can be filtered and ordered by like one, such as
+    /// `(payload ->> 'kind')`.
+    pub(crate) fn expression(sql: String) -> Self {
+        Self {
+            name: Cow::Owned(sql),
             _marker: PhantomData,
         }
     }
@@ -0,0 +1,162 @@
+//! Example and test data, inserted in order by seeders that each run once per database.
+//! Finished seeders are recorded in `_oxide_seeds`; rename one to run it again.
+//!
+//! ```rust,ignore
+//! struct Users;
+//!
+//! impl Seeder for Users {
+//!     fn name(&self) -> &str {
+//!         "users"
+//!     }
+//!     fn run<'a>(&'a self, tx: &'a mut PgTransaction<'_>) -> SeedFuture<'a> {
+//!         Box::pin(async move {
+//!             User::insert_many()
+//!                 .row(User::insert().value(User::columns().name, "Ada".to_string()))
+//!                 .execute(tx)
+//!                 .await?;
+//!             Ok(())
+//!         })
+//!     }
+//! }
+//!
+//! db.seed(&[Box::new(Users), Box::new(Posts)]).await?;
+//! ```
+use std::{collections::HashSet, future::Future, pin::Pin};
+
+use oxide_core::{logger::LogLevel, Environment, Error, Logger, PgDatabase, PgTransaction};
+
+pub const SEEDS_TABLE: &str = "_oxide_seeds";
+
+/// Serializes seeding across processes, so two instances starting at once don't both run
+/// the same seeder.
+const LOCK_KEY: i64 = 0x006f_7869_6465_5eed;
+
+pub type SeedFuture<'a> = Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+
+pub trait Seeder: Send + Sync {
+    /// Identifies the seeder in `_oxide_seeds`; must be unique.
+    fn name(&self) -> &str;
+
+    /// Environments the seeder runs in; it is skipped in the others. Development and test
+    /// by default, so seed data never reaches production unless a seeder asks for it.
+    fn environments(&self) -> &[Environment] {
+        &[Environment::Development, Environment::Test]
+    }
+
+    /// Inserts the data as part of `tx`, which also records the seeder, so a seeder that
+    /// fails leaves nothing behind and runs again next time.
+    fn run<'a>(&'a self, tx: &'a mut PgTransaction<'_>) -> SeedFuture<'a>;
+}
+
+/// Runs seeders in the order given, each in its own transaction together with its row in
+/// `_oxide_seeds`, skipping those already recorded or meant for other environments.
+pub struct SeedRunner<'a> {
+    db: &'a PgDatabase,
+    environment: Environment,
+    logger: Logger,
+}
+
+impl<'a> SeedRunner<'a> {
+    pub fn new(db: &'a PgDatabase) -> Self {
+        Self {
+            db,
+            environment: Environment::current(),
+            logger: Logger::for_target(module_path!()),
+        }
+    }
+
+    /// Seeds as if running in `environment` instead of the process's own.
+    pub fn with_environment(mut self, environment: Environment) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    async fn create_table(&self) -> Result<(), Error> {
+        self.db
+            .execute(format!(
+                "CREATE TABLE IF NOT EXISTS {} (
+                    name TEXT PRIMARY KEY,
+                    seeded_at TIMESTAMPTZ NOT NULL DEFAULT now()
+                )",
+                SEEDS_TABLE
+            ))
+            .await?;
+        Ok(())
+    }
+
+    /// Runs every seeder not run yet, returning the names of those that ran. Refuses to run
+    /// anything if two seeders share a name.
+    pub async fn run(&self, seeders: &[Box<dyn Seeder>]) -> Result<Vec<String>, Error> {
+        let mut names = HashSet::new();
+        if let Some(seeder) = seeders.iter().find(|s| !names.insert(s.name())) {
+            return Err(Error::Config(format!(
+                "Two seeders are named {}",
+                seeder.name()
+            )));
+        }
+        self.create_table().await?;
+
+        let mut seeded = vec![];
+        for seeder in seeders {
+            if !seeder.environments().contains(&self.environment) {
+                self.logger.log(
+                    LogLevel::Debug,
+                    &format!("Skipped seeder {} in {}", seeder.name(), self.environment),
+                );
+                continue;
+            }
+            if self.apply(seeder.as_ref()).await? {
+                seeded.push(seeder.name().to_string());
+            }
+        }
+        Ok(seeded)
+    }
+
+    /// Runs `seeder` unless it is already recorded, returning whether it ran.
+    async fn apply(&self, seeder: &dyn Seeder) -> Result<bool, Error> {
+        let mut tx = self.db.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(LOCK_KEY)
+            .execute(&mut *tx)
+            .await
+            .map_err(Error::Database)?;
+        let recorded = sqlx::query(&format!("SELECT 1 FROM {} WHERE name = $1", SEEDS_TABLE))
+            .bind(seeder.name())
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(Error::Database)?
+            .is_some();
+        if recorded {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+
+        seeder
+            .run(&mut tx)
+            .await
+            .map_err(|e| Error::Custom(format!("Seeder {} failed: {}", seeder.name(), e)))?;
+        sqlx::query(&format!("INSERT INTO {} (name) VALUES ($1)", SEEDS_TABLE))
+            .bind(seeder.name())
+            .execute(&mut *tx)
+            .await
+            .map_err(Error::Database)?;
+        tx.commit().await?;
+
+        self.logger
+            .log(LogLevel::Info, &format!("Seeded {}", seeder.name()));
+        Ok(true)
+    }
+}
+
+pub type SeedAllFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<String>, Error>> + Send + 'a>>;
+
+/// `db.seed(&seeders)`, shorthand for `SeedRunner::new(&db).run(&seeders)`.
+pub trait Seed {
+    fn seed<'a>(&'a self, seeders: &'a [Box<dyn Seeder>]) -> SeedAllFuture<'a>;
+}
+
+impl Seed for PgDatabase {
+    fn seed<'a>(&'a self, seeders: &'a [Box<dyn Seeder>]) -> SeedAllFuture<'a> {
+        Box::pin(async move { SeedRunner::new(self).run(seeders).await })
+    }
+}
@@ -0,0 +1,58 @@
+use crate::types::quoted;
+
+/// A Rust enum stored as a Postgres enum type or as text, implemented with
+/// `#[derive(SqlEnum)]` along with `ToSql` and sqlx's `Type`, `Encode` and `Decode`, so the
+/// enum can be a model field.
+///
+/// ```rust,ignore
+/// #[derive(Debug, Clone, Copy, PartialEq, SqlEnum)]
+/// #[sql_enum(type_name = "order_status")]
+/// pub enum OrderStatus {
+///     Pending,
+///     Paid,
+///     #[sql_enum(rename = "refunded_in_full")]
+///     Refunded,
+/// }
+///
+/// // In a migration, before the table using it:
+/// schema.sql(OrderStatus::create_type().unwrap());
+/// ```
+pub trait SqlEnum: Sized + 'static {
+    /// The Postgres enum type the values are stored as, or `None` to store them as text.
+    const TYPE_NAME: Option<&'static str>;
+    /// What each variant is stored as, in declaration order.
+    const VALUES: &'static [&'static str];
+
+    fn as_sql_str(&self) -> &'static str;
+
+    /// The variant stored as `value`, if any.
+    fn from_sql_str(value: &str) -> Option<Self>;
+
+    /// `CREATE TYPE ... AS ENUM (...)`, or `None` for enums stored as text.
+    fn create_type() -> Option<String> {
+        Some(format!(
+            "CREATE TYPE {} AS ENUM ({})",
+            Self::TYPE_NAME?,
+            quoted_values(Self::VALUES)
+        ))
+    }
+
+    /// `DROP TYPE ...`, reverting [`create_type`](SqlEnum::create_type).
+    fn drop_type() -> Option<String> {
+        Some(format!("DROP TYPE {}", Self::TYPE_NAME?))
+    }
+
+    /// `CHECK (column IN (...))`, limiting a text column to the enum's values, e.g. for
+    /// `TableBuilder::constraint`.
+    fn check(column: &str) -> String {
+        format!("CHECK ({} IN ({}))", column, quoted_values(Self::VALUES))
+    }
+}
+
+fn quoted_values(values: &[&str]) -> String {
+    values
+        .iter()
+        .map(|value| quoted(value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,246 @@
+//! Locking in the SQL the builders generate, so a refactor or an oxide-orm upgrade that
+//! changes a query fails a test instead of going unnoticed.
+//!
+//! A snapshot is the statement with `$n` placeholders, followed by the values bound to them
+//! as a `--` comment. Whitespace is normalized on both sides, so snapshots can be laid out
+//! over several lines, and placeholders are numbered in the order they appear.
+//!
+//! ```rust,ignore
+//! assert_sql!(
+//!     User::query().and_where(User::columns().age, 18),
+//!     @r#"SELECT * FROM "users" WHERE age = $1 -- $1 = 18"#
+//! );
+//!
+//! // Compared with tests/snapshots/active_users.sql, which is written if it doesn't exist.
+//! assert_sql!(active_users(), "tests/snapshots/active_users.sql");
+//! ```
+//!
+//! Run the tests with `OXIDE_UPDATE_SNAPSHOTS=1` to rewrite snapshot files that no longer
+//! match, then review the diff.
+use std::{fs, path::Path};
+
+use crate::{
+    Model, ModelColumns, OxideAggregateQuery, OxideBulkInsertBuilder, OxideDeleteQueryBuilder,
+    OxideInsertQueryBuilder, OxideQueryBuilder, OxideUpdateQueryBuilder, RawQuery, SqlValue,
+};
+
+/// Set to `1` to overwrite snapshot files that don't match instead of failing.
+pub const UPDATE_SNAPSHOTS: &str = "OXIDE_UPDATE_SNAPSHOTS";
+
+/// Anything that generates SQL to snapshot.
+pub trait SqlSnapshot {
+    /// The normalized statement and its values, as compared with snapshots.
+    fn snapshot(&self) -> String;
+}
+
+impl<T: SqlSnapshot + ?Sized> SqlSnapshot for &T {
+    fn snapshot(&self) -> String {
+        (**self).snapshot()
+    }
+}
+
+/// `sql` with placeholders renumbered in order of appearance and the values bound to them
+/// appended, with whitespace normalized.
+pub fn render(sql: &str, values: &[SqlValue]) -> String {
+    let mut order: Vec<usize> = vec![];
+    let renumbered = map_unquoted(sql, |rest, out| {
+        let Some(number) = rest.strip_prefix('$') else {
+            return 0;
+        };
+        let digits = number.chars().take_while(char::is_ascii_digit).count();
+        if digits == 0 {
+            return 0;
+        }
+        let original: usize = number[..digits].parse().unwrap_or(0);
+        let position = match order.iter().position(|&n| n == original) {
+            Some(position) => position,
+            None => {
+                order.push(original);
+                order.len() - 1
+            }
+        };
+        out.push_str(&format!("${}", position + 1));
+        digits + 1
+    });
+
+    let mut rendered = renumbered;
+    if !order.is_empty() {
+        let bound: Vec<_> = order
+            .iter()
+            .enumerate()
+            .map(|(position, original)| {
+                let value = original
+                    .checked_sub(1)
+                    .and_then(|index| values.get(index))
+                    .map_or("?".to_string(), SqlValue::to_sql);
+                format!("${} = {}", position + 1, value)
+            })
+            .collect();
+        rendered.push_str(&format!(" -- {}", bound.join(", ")));
+    }
+    normalize(&rendered)
+}
+
+/// Collapses whitespace outside quotes to single spaces, dropping it just inside
+/// parentheses and at either end, so differently laid out SQL compares equal.
+pub fn normalize(sql: &str) -> String {
+    let mut pending_space = false;
+    let collapsed = map_unquoted(sql, |rest, out| {
+        let Some(c) = rest.chars().next() else {
+            return 0;
+        };
+        if c.is_whitespace() {
+            pending_space = true;
+            return c.len_utf8();
+        }
+        if pending_space && c != ')' && !out.is_empty() && !out.ends_with('(') {
+            out.push(' ');
+        }
+        pending_space = false;
+        0
+    });
+    collapsed.trim().to_string()
+}
+
+/// Copies `sql`, letting `visit` rewrite text outside quoted strings and identifiers. `visit`
+/// gets the remaining input and returns how many bytes it consumed, or 0 to have the next
+/// character copied as is.
+fn map_unquoted(sql: &str, mut visit: impl FnMut(&str, &mut String) -> usize) -> String {
+    let mut out = String::with_capacity(sql.len());
+    let mut quote: Option<char> = None;
+    let mut index = 0;
+    while let Some(c) = sql[index..].chars().next() {
+        if quote.is_none() {
+            let consumed = visit(&sql[index..], &mut out);
+            if consumed > 0 {
+                index += consumed;
+                continue;
+            }
+            if c == '\'' || c == '"' {
+                quote = Some(c);
+            }
+        } else if quote == Some(c) {
+            quote = None;
+        }
+        out.push(c);
+        index += c.len_utf8();
+    }
+    out
+}
+
+/// Panics unless `actual` renders the same as `expected` once both are normalized.
+#[track_caller]
+pub fn assert_sql(actual: &impl SqlSnapshot, expected: &str) {
+    let actual = actual.snapshot();
+    let expected = normalize(expected);
+    if actual != expected {
+        panic!(
+            "SQL doesn't match the snapshot\nexpected: {}\n  actual: {}",
+            expected, actual
+        );
+    }
+}
+
+/// Like [`assert_sql`], against the snapshot file at `path`, which is written if it doesn't
+/// exist yet, and rewritten instead of failing when `OXIDE_UPDATE_SNAPSHOTS=1`.
+#[track_caller]
+pub fn assert_sql_file(actual: &impl SqlSnapshot, path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    let actual = actual.snapshot();
+    let update = std::env::var(UPDATE_SNAPSHOTS).is_ok_and(|v| v == "1");
+    match fs::read_to_string(path) {
+        Ok(expected) if normalize(&expected) == actual => {}
+        Ok(expected) if !update => panic!(
+            "SQL doesn't match the snapshot in {}\nexpected: {}\n  actual: {}\nRun with {}=1 to update it.",
+            path.display(),
+            normalize(&expected),
+            actual,
+            UPDATE_SNAPSHOTS
+        ),
+        _ => {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).unwrap_or_else(|e| {
+                    panic!("Couldn't create {}: {}", dir.display(), e)
+                });
+            }
+            fs::write(path, format!("{}\n", actual))
+                .unwrap_or_else(|e| panic!("Couldn't write {}: {}", path.display(), e));
+        }
+    }
+}
+
+/// Asserts that a builder generates the SQL in a snapshot, either given inline after `@` or
+/// stored in a file at a path relative to the crate's manifest. See [`testing`](crate::testing).
+///
+/// ```rust,ignore
+/// assert_sql!(User::query().limit(10), @r#"SELECT * FROM "users" LIMIT 10"#);
+/// assert_sql!(User::query().limit(10), "tests/snapshots/first_users.sql");
+/// ```
+#[macro_export]
+macro_rules! assert_sql {
+    ($query:expr, @$expected:literal $(,)?) => {
+        $crate::testing::assert_sql(&$query, $expected)
+    };
+    ($query:expr, $path:expr $(,)?) => {
+        $crate::testing::assert_sql_file(
+            &$query,
+            ::std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join($path),
+        )
+    };
+}
+
+impl<M: Model<C>, C: ModelColumns<Model = M>> SqlSnapshot for OxideQueryBuilder<M, C> {
+    fn snapshot(&self) -> String {
+        let (sql, values) = self.build_params();
+        render(&sql, &values)
+    }
+}
+
+impl<M: Model<C>, C: ModelColumns<Model = M>> SqlSnapshot for OxideUpdateQueryBuilder<M, C> {
+    fn snapshot(&self) -> String {
+        let (sql, values) = self.build_params();
+        render(&sql, &values)
+    }
+}
+
+impl<M: Model<C>, C: ModelColumns<Model = M>> SqlSnapshot for OxideInsertQueryBuilder<M, C> {
+    fn snapshot(&self) -> String {
+        let (sql, values) = self.build_params();
+        render(&sql, &values)
+    }
+}
+
+impl<M: Model<C>, C: ModelColumns<Model = M>> SqlSnapshot for OxideDeleteQueryBuilder<M, C> {
+    fn snapshot(&self) -> String {
+        let (sql, values) = self.build_params();
+        render(&sql, &values)
+    }
+}
+
+impl<M: Model<C>, C: ModelColumns<Model = M>, R> SqlSnapshot for OxideAggregateQuery<M, C, R> {
+    fn snapshot(&self) -> String {
+        let (sql, values) = self.build_params();
+        render(&sql, &values)
+    }
+}
+
+/// Every statement the insert is split into, separated by `;`, or the error building them.
+impl<M: Model<C>, C: ModelColumns<Model = M>> SqlSnapshot for OxideBulkInsertBuilder<M, C> {
+    fn snapshot(&self) -> String {
+        match self.build_params() {
+            Ok(statements) => statements
+                .iter()
+                .map(|(sql, values)| render(sql, values))
+                .collect::<Vec<_>>()
+                .join("; "),
+            Err(e) => format!("error: {}", e),
+        }
+    }
+}
+
+impl<E> SqlSnapshot for RawQuery<E> {
+    fn snapshot(&self) -> String {
+        let (sql, values) = self.sql_and_values();
+        render(sql, values)
+    }
+}
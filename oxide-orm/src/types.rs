@@ -1,4 +1,4 @@
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SqlType {
     Int,
     BigInt,
@@ -7,19 +7,356 @@ pub enum SqlType {
     VarChar(usize),
     Bool,
     Timestamp,
+    TimestampTz,
     Date,
     Time,
     Float,
     Double,
     Decimal(u8, u8),
+    /// `NUMERIC` without a declared precision.
+    Numeric,
     Uuid,
     Json,
     JsonB,
+    /// A one-dimensional array of the element type, e.g. `text[]`. The element type is boxed,
+    /// so `SqlType` is `Clone` but not `Copy`.
+    Array(Box<SqlType>),
+    /// A Postgres enum type, by name, as declared with `#[derive(SqlEnum)]`.
+    Enum(&'static str),
 }
 
-pub trait ToSql: std::fmt::Display {
+impl SqlType {
+    /// The type as Postgres's `format_type()` spells it, e.g. `character varying(255)`, so it
+    /// compares equal to an introspected column's type.
+    pub fn type_name(&self) -> String {
+        match self {
+            SqlType::Int => "integer".to_string(),
+            SqlType::BigInt => "bigint".to_string(),
+            SqlType::SmallInt => "smallint".to_string(),
+            SqlType::Text => "text".to_string(),
+            SqlType::VarChar(length) => format!("character varying({})", length),
+            SqlType::Bool => "boolean".to_string(),
+            SqlType::Timestamp => "timestamp without time zone".to_string(),
+            SqlType::TimestampTz => "timestamp with time zone".to_string(),
+            SqlType::Date => "date".to_string(),
+            SqlType::Time => "time without time zone".to_string(),
+            SqlType::Float => "real".to_string(),
+            SqlType::Double => "double precision".to_string(),
+            SqlType::Decimal(precision, scale) => format!("numeric({},{})", precision, scale),
+            SqlType::Numeric => "numeric".to_string(),
+            SqlType::Uuid => "uuid".to_string(),
+            SqlType::Json => "json".to_string(),
+            SqlType::JsonB => "jsonb".to_string(),
+            SqlType::Array(element) => format!("{}[]", element.type_name()),
+            SqlType::Enum(name) => name.to_string(),
+        }
+    }
+}
+
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgArguments, PgTypeInfo},
+    Arguments, Postgres,
+};
+
+pub trait ToSql {
     fn sql_type() -> SqlType;
     fn to_sql(&self) -> String;
+    fn to_value(&self) -> SqlValue;
+}
+
+/// A value collected by the query builders, either bound as a `$n` parameter or rendered
+/// inline as a literal by `build()`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlValue {
+    Int(i32),
+    BigInt(i64),
+    SmallInt(i16),
+    Float(f32),
+    Double(f64),
+    Text(String),
+    Bool(bool),
+    Uuid(uuid::Uuid),
+    Json(serde_json::Value),
+    /// Elements of the given type; the type is kept so empty arrays and `NULL` elements bind
+    /// as the column's array type.
+    Array(SqlType, Vec<SqlValue>),
+    /// A value of the Postgres enum type named first.
+    Enum(&'static str, &'static str),
+    #[cfg(feature = "chrono")]
+    Timestamp(chrono::NaiveDateTime),
+    #[cfg(feature = "chrono")]
+    TimestampTz(chrono::DateTime<chrono::Utc>),
+    #[cfg(feature = "chrono")]
+    Date(chrono::NaiveDate),
+    #[cfg(feature = "chrono")]
+    Time(chrono::NaiveTime),
+    #[cfg(feature = "decimal")]
+    Decimal(rust_decimal::Decimal),
+    /// `NULL`, bound as the column type so Postgres doesn't have to infer it.
+    Null(SqlType),
+}
+
+impl SqlValue {
+    /// The value as an inline SQL literal.
+    pub fn to_sql(&self) -> String {
+        match self {
+            SqlValue::Int(v) => v.to_sql(),
+            SqlValue::BigInt(v) => v.to_sql(),
+            SqlValue::SmallInt(v) => v.to_sql(),
+            SqlValue::Float(v) => v.to_sql(),
+            SqlValue::Double(v) => v.to_sql(),
+            SqlValue::Text(v) => v.to_sql(),
+            SqlValue::Bool(v) => v.to_sql(),
+            SqlValue::Uuid(v) => v.to_sql(),
+            SqlValue::Json(v) => v.to_sql(),
+            SqlValue::Array(element, values) => {
+                let values: Vec<_> = values.iter().map(SqlValue::to_sql).collect();
+                format!("ARRAY[{}]::{}[]", values.join(", "), element.type_name())
+            }
+            SqlValue::Enum(_, value) => quoted(value),
+            #[cfg(feature = "chrono")]
+            SqlValue::Timestamp(v) => v.to_sql(),
+            #[cfg(feature = "chrono")]
+            SqlValue::TimestampTz(v) => v.to_sql(),
+            #[cfg(feature = "chrono")]
+            SqlValue::Date(v) => v.to_sql(),
+            #[cfg(feature = "chrono")]
+            SqlValue::Time(v) => v.to_sql(),
+            #[cfg(feature = "decimal")]
+            SqlValue::Decimal(v) => v.to_sql(),
+            SqlValue::Null(_) => "NULL".to_string(),
+        }
+    }
+
+    pub fn bind(self, args: &mut PgArguments) -> Result<(), oxide_core::Error> {
+        let result = match self {
+            SqlValue::Int(v) => args.add(v),
+            SqlValue::BigInt(v) => args.add(v),
+            SqlValue::SmallInt(v) => args.add(v),
+            SqlValue::Float(v) => args.add(v),
+            SqlValue::Double(v) => args.add(v),
+            SqlValue::Text(v) => args.add(v),
+            SqlValue::Bool(v) => args.add(v),
+            SqlValue::Uuid(v) => args.add(v),
+            SqlValue::Json(v) => args.add(v),
+            SqlValue::Array(element, values) => bind_array(args, &element, Some(values)),
+            SqlValue::Enum(type_name, value) => args.add(EnumArg {
+                type_name,
+                value: Some(value),
+            }),
+            #[cfg(feature = "chrono")]
+            SqlValue::Timestamp(v) => args.add(v),
+            #[cfg(feature = "chrono")]
+            SqlValue::TimestampTz(v) => args.add(v),
+            #[cfg(feature = "chrono")]
+            SqlValue::Date(v) => args.add(v),
+            #[cfg(feature = "chrono")]
+            SqlValue::Time(v) => args.add(v),
+            #[cfg(feature = "decimal")]
+            SqlValue::Decimal(v) => args.add(v),
+            SqlValue::Null(sql_type) => match sql_type {
+                SqlType::Int => args.add(None::<i32>),
+                SqlType::BigInt => args.add(None::<i64>),
+                SqlType::SmallInt => args.add(None::<i16>),
+                SqlType::Bool => args.add(None::<bool>),
+                SqlType::Float => args.add(None::<f32>),
+                SqlType::Double => args.add(None::<f64>),
+                SqlType::Uuid => args.add(None::<uuid::Uuid>),
+                SqlType::Json | SqlType::JsonB => args.add(None::<serde_json::Value>),
+                SqlType::Array(element) => bind_array(args, &element, None),
+                SqlType::Enum(type_name) => args.add(EnumArg {
+                    type_name,
+                    value: None,
+                }),
+                #[cfg(feature = "chrono")]
+                SqlType::Timestamp => args.add(None::<chrono::NaiveDateTime>),
+                #[cfg(feature = "chrono")]
+                SqlType::TimestampTz => args.add(None::<chrono::DateTime<chrono::Utc>>),
+                #[cfg(feature = "chrono")]
+                SqlType::Date => args.add(None::<chrono::NaiveDate>),
+                #[cfg(feature = "chrono")]
+                SqlType::Time => args.add(None::<chrono::NaiveTime>),
+                #[cfg(feature = "decimal")]
+                SqlType::Decimal(..) | SqlType::Numeric => args.add(None::<rust_decimal::Decimal>),
+                // Without the feature for the column's type no value of it can be built, and
+                // text is the closest Postgres will accept from a NULL.
+                _ => args.add(None::<String>),
+            },
+        };
+        result.map_err(|e| oxide_core::Error::Database(sqlx::Error::Encode(e)))
+    }
+}
+
+/// An enum value or `NULL`, bound as the enum type, whose oid is only known by name.
+struct EnumArg {
+    type_name: &'static str,
+    value: Option<&'static str>,
+}
+
+impl sqlx::Type<Postgres> for EnumArg {
+    fn type_info() -> PgTypeInfo {
+        <&str as sqlx::Type<Postgres>>::type_info()
+    }
+}
+
+impl sqlx::Encode<'_, Postgres> for EnumArg {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        match self.value {
+            Some(value) => <&str as sqlx::Encode<Postgres>>::encode_by_ref(&value, buf),
+            None => Ok(IsNull::Yes),
+        }
+    }
+
+    fn produces(&self) -> Option<PgTypeInfo> {
+        Some(PgTypeInfo::with_name(self.type_name))
+    }
+}
+
+/// An array of enum values or `NULL`s, or a `NULL` array, bound as the enum's array type.
+struct EnumArrayArg {
+    type_name: &'static str,
+    values: Option<Vec<Option<&'static str>>>,
+}
+
+impl sqlx::Type<Postgres> for EnumArrayArg {
+    fn type_info() -> PgTypeInfo {
+        <Vec<&str> as sqlx::Type<Postgres>>::type_info()
+    }
+}
+
+impl sqlx::Encode<'_, Postgres> for EnumArrayArg {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        let Some(values) = &self.values else {
+            return Ok(IsNull::Yes);
+        };
+        let element = |value| EnumArg {
+            type_name: self.type_name,
+            value,
+        };
+        if !values.is_empty() {
+            let elements: Vec<_> = values.iter().copied().map(element).collect();
+            return <&[EnumArg] as sqlx::Encode<Postgres>>::encode_by_ref(&&elements[..], buf);
+        }
+        // sqlx takes the element type from the first element, so an empty array is encoded
+        // with one NULL element that is then dropped: the header is the dimensions, flags,
+        // element type, length and lower bound, followed by the element's -1 length.
+        let start = buf.len();
+        let is_null =
+            <&[EnumArg] as sqlx::Encode<Postgres>>::encode_by_ref(&&[element(None)][..], buf)?;
+        buf.truncate(start + 20);
+        buf[start + 12..start + 16].copy_from_slice(&0_i32.to_be_bytes());
+        Ok(is_null)
+    }
+
+    fn produces(&self) -> Option<PgTypeInfo> {
+        Some(PgTypeInfo::array_of(self.type_name))
+    }
+}
+
+/// Binds `values` as an array of `element`, or a `NULL` array for `None`. Fails if an element
+/// is neither `NULL` nor of the element type.
+fn bind_array(
+    args: &mut PgArguments,
+    element: &SqlType,
+    values: Option<Vec<SqlValue>>,
+) -> Result<(), BoxDynError> {
+    macro_rules! elements {
+        ($pattern:pat $(if $guard:expr)? => $value:expr) => {
+            values
+                .map(|values| {
+                    values
+                        .into_iter()
+                        .map(|value| match value {
+                            $pattern $(if $guard)? => Ok(Some($value)),
+                            SqlValue::Null(_) => Ok(None),
+                            other => Err(format!(
+                                "Cannot bind {} in an array of {}",
+                                other.to_sql(),
+                                element.type_name()
+                            )),
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .transpose()?
+        };
+    }
+    macro_rules! bind_as {
+        ($variant:ident, $ty:ty) => {{
+            let elements: Option<Vec<Option<$ty>>> = elements!(SqlValue::$variant(v) => v);
+            args.add(elements)
+        }};
+    }
+    match element {
+        SqlType::Int => bind_as!(Int, i32),
+        SqlType::BigInt => bind_as!(BigInt, i64),
+        SqlType::SmallInt => bind_as!(SmallInt, i16),
+        SqlType::Bool => bind_as!(Bool, bool),
+        SqlType::Float => bind_as!(Float, f32),
+        SqlType::Double => bind_as!(Double, f64),
+        SqlType::Uuid => bind_as!(Uuid, uuid::Uuid),
+        SqlType::Json | SqlType::JsonB => bind_as!(Json, serde_json::Value),
+        #[cfg(feature = "chrono")]
+        SqlType::Timestamp => bind_as!(Timestamp, chrono::NaiveDateTime),
+        #[cfg(feature = "chrono")]
+        SqlType::TimestampTz => bind_as!(TimestampTz, chrono::DateTime<chrono::Utc>),
+        #[cfg(feature = "chrono")]
+        SqlType::Date => bind_as!(Date, chrono::NaiveDate),
+        #[cfg(feature = "chrono")]
+        SqlType::Time => bind_as!(Time, chrono::NaiveTime),
+        #[cfg(feature = "decimal")]
+        SqlType::Decimal(..) | SqlType::Numeric => bind_as!(Decimal, rust_decimal::Decimal),
+        SqlType::Enum(type_name) => args.add(EnumArrayArg {
+            type_name,
+            values: elements!(SqlValue::Enum(name, v) if name == *type_name => v),
+        }),
+        SqlType::Array(_) => Err("Arrays of arrays can't be bound".into()),
+        // Without the feature for the element type only NULL elements can be built, and text
+        // is the closest Postgres will accept for them.
+        _ => bind_as!(Text, String),
+    }
+}
+
+/// Binds `values` in order, for a statement using `$1, $2, ...` placeholders.
+pub fn bind_all(values: Vec<SqlValue>) -> Result<PgArguments, oxide_core::Error> {
+    let mut args = PgArguments::default();
+    for value in values {
+        value.bind(&mut args)?;
+    }
+    Ok(args)
+}
+
+impl<T: ToSql + ?Sized> ToSql for &T {
+    fn sql_type() -> SqlType {
+        T::sql_type()
+    }
+    fn to_sql(&self) -> String {
+        (**self).to_sql()
+    }
+    fn to_value(&self) -> SqlValue {
+        (**self).to_value()
+    }
+}
+
+/// `None` is `NULL`. Comparing a column with `None` through `eq`/`ne` or `and_where`
+/// becomes `IS NULL`/`IS NOT NULL`, since `= NULL` never matches.
+impl<T: ToSql> ToSql for Option<T> {
+    fn sql_type() -> SqlType {
+        T::sql_type()
+    }
+    fn to_sql(&self) -> String {
+        match self {
+            Some(value) => value.to_sql(),
+            None => "NULL".to_string(),
+        }
+    }
+    fn to_value(&self) -> SqlValue {
+        match self {
+            Some(value) => value.to_value(),
+            None => SqlValue::Null(T::sql_type()),
+        }
+    }
 }
 
 impl ToSql for i32 {
@@ -29,6 +366,102 @@ impl ToSql for i32 {
     fn to_sql(&self) -> String {
         self.to_string()
     }
+    fn to_value(&self) -> SqlValue {
+        SqlValue::Int(*self)
+    }
+}
+
+impl ToSql for i64 {
+    fn sql_type() -> SqlType {
+        SqlType::BigInt
+    }
+    fn to_sql(&self) -> String {
+        self.to_string()
+    }
+    fn to_value(&self) -> SqlValue {
+        SqlValue::BigInt(*self)
+    }
+}
+
+impl ToSql for i16 {
+    fn sql_type() -> SqlType {
+        SqlType::SmallInt
+    }
+    fn to_sql(&self) -> String {
+        self.to_string()
+    }
+    fn to_value(&self) -> SqlValue {
+        SqlValue::SmallInt(*self)
+    }
+}
+
+/// Finite floats as plain numbers; `NaN` and the infinities as the quoted words Postgres
+/// parses them from.
+fn float_literal(value: f64, sql_type: &str) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else if value.is_nan() {
+        format!("'NaN'::{}", sql_type)
+    } else if value > 0.0 {
+        format!("'Infinity'::{}", sql_type)
+    } else {
+        format!("'-Infinity'::{}", sql_type)
+    }
+}
+
+impl ToSql for f32 {
+    fn sql_type() -> SqlType {
+        SqlType::Float
+    }
+    fn to_sql(&self) -> String {
+        float_literal(*self as f64, "REAL")
+    }
+    fn to_value(&self) -> SqlValue {
+        SqlValue::Float(*self)
+    }
+}
+
+impl ToSql for f64 {
+    fn sql_type() -> SqlType {
+        SqlType::Double
+    }
+    fn to_sql(&self) -> String {
+        float_literal(*self, "FLOAT8")
+    }
+    fn to_value(&self) -> SqlValue {
+        SqlValue::Double(*self)
+    }
+}
+
+/// A one-dimensional array; `Option` elements may be `NULL`. Decoded from the row like any
+/// other `Vec` field.
+impl<T: ToSql> ToSql for [T] {
+    fn sql_type() -> SqlType {
+        SqlType::Array(Box::new(T::sql_type()))
+    }
+    fn to_sql(&self) -> String {
+        self.to_value().to_sql()
+    }
+    fn to_value(&self) -> SqlValue {
+        SqlValue::Array(T::sql_type(), self.iter().map(ToSql::to_value).collect())
+    }
+}
+
+impl<T: ToSql> ToSql for Vec<T> {
+    fn sql_type() -> SqlType {
+        <[T]>::sql_type()
+    }
+    fn to_sql(&self) -> String {
+        self.as_slice().to_sql()
+    }
+    fn to_value(&self) -> SqlValue {
+        self.as_slice().to_value()
+    }
+}
+
+/// A string literal; quotes are doubled so values can't terminate it early.
+pub(crate) fn quoted(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
 }
 
 impl ToSql for String {
@@ -36,7 +469,10 @@ impl ToSql for String {
         SqlType::Text
     }
     fn to_sql(&self) -> String {
-        format!("'{}'", self)
+        quoted(self)
+    }
+    fn to_value(&self) -> SqlValue {
+        SqlValue::Text(self.clone())
     }
 }
 
@@ -47,6 +483,9 @@ impl ToSql for bool {
     fn to_sql(&self) -> String {
         if *self { "TRUE" } else { "FALSE" }.to_string()
     }
+    fn to_value(&self) -> SqlValue {
+        SqlValue::Bool(*self)
+    }
 }
 
 impl ToSql for uuid::Uuid {
@@ -56,4 +495,84 @@ impl ToSql for uuid::Uuid {
     fn to_sql(&self) -> String {
         format!("'{}'", self)
     }
+    fn to_value(&self) -> SqlValue {
+        SqlValue::Uuid(*self)
+    }
+}
+
+impl ToSql for serde_json::Value {
+    fn sql_type() -> SqlType {
+        SqlType::JsonB
+    }
+    fn to_sql(&self) -> String {
+        format!("{}::JSONB", quoted(&self.to_string()))
+    }
+    fn to_value(&self) -> SqlValue {
+        SqlValue::Json(self.clone())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl ToSql for chrono::NaiveDateTime {
+    fn sql_type() -> SqlType {
+        SqlType::Timestamp
+    }
+    fn to_sql(&self) -> String {
+        format!("'{}'::TIMESTAMP", self)
+    }
+    fn to_value(&self) -> SqlValue {
+        SqlValue::Timestamp(*self)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl ToSql for chrono::DateTime<chrono::Utc> {
+    fn sql_type() -> SqlType {
+        SqlType::TimestampTz
+    }
+    fn to_sql(&self) -> String {
+        format!("'{}'::TIMESTAMPTZ", self.to_rfc3339())
+    }
+    fn to_value(&self) -> SqlValue {
+        SqlValue::TimestampTz(*self)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl ToSql for chrono::NaiveDate {
+    fn sql_type() -> SqlType {
+        SqlType::Date
+    }
+    fn to_sql(&self) -> String {
+        format!("'{}'::DATE", self)
+    }
+    fn to_value(&self) -> SqlValue {
+        SqlValue::Date(*self)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl ToSql for chrono::NaiveTime {
+    fn sql_type() -> SqlType {
+        SqlType::Time
+    }
+    fn to_sql(&self) -> String {
+        format!("'{}'::TIME", self)
+    }
+    fn to_value(&self) -> SqlValue {
+        SqlValue::Time(*self)
+    }
+}
+
+#[cfg(feature = "decimal")]
+impl ToSql for rust_decimal::Decimal {
+    fn sql_type() -> SqlType {
+        SqlType::Numeric
+    }
+    fn to_sql(&self) -> String {
+        self.to_string()
+    }
+    fn to_value(&self) -> SqlValue {
+        SqlValue::Decimal(*self)
+    }
 }
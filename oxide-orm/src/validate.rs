@@ -0,0 +1,177 @@
+use std::fmt;
+
+use oxide_core::Error;
+use serde::Serialize;
+
+use crate::SqlValue;
+
+/// A check a column's value must pass before it's written, declared on a model field with
+/// `#[validate(...)]`. `NULL` passes every rule; whether a column may be `NULL` is up to its
+/// type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rule {
+    /// `#[validate(email)]`: text shaped like an email address.
+    Email,
+    /// `#[validate(length(min = 1, max = 100))]`: text with this many characters.
+    Length {
+        min: Option<usize>,
+        max: Option<usize>,
+    },
+    /// `#[validate(range(min = 0, max = 150))]`: a number within these bounds, inclusive.
+    Range { min: Option<f64>, max: Option<f64> },
+}
+
+impl Rule {
+    /// Checks `value`, returning why it fails.
+    pub fn check(&self, value: &SqlValue) -> Result<(), String> {
+        if let SqlValue::Null(_) = value {
+            return Ok(());
+        }
+        match self {
+            Rule::Email => match value {
+                SqlValue::Text(text) if is_email(text) => Ok(()),
+                SqlValue::Text(_) => Err("must be a valid email address".to_string()),
+                _ => Err("must be text".to_string()),
+            },
+            Rule::Length { min, max } => match value {
+                SqlValue::Text(text) => {
+                    let length = text.chars().count();
+                    if min.is_some_and(|min| length < min) || max.is_some_and(|max| length > max) {
+                        Err(format!("must be {} characters", bounds(min, max)))
+                    } else {
+                        Ok(())
+                    }
+                }
+                _ => Err("must be text".to_string()),
+            },
+            Rule::Range { min, max } => match number(value) {
+                Some(n) if min.is_some_and(|min| n < min) || max.is_some_and(|max| n > max) => {
+                    Err(format!("must be {}", bounds(min, max)))
+                }
+                Some(_) => Ok(()),
+                None => Err("must be a number".to_string()),
+            },
+        }
+    }
+}
+
+/// "at least 1", "at most 100" or "between 1 and 100".
+fn bounds<T: fmt::Display>(min: &Option<T>, max: &Option<T>) -> String {
+    match (min, max) {
+        (Some(min), Some(max)) => format!("between {} and {}", min, max),
+        (Some(min), None) => format!("at least {}", min),
+        (None, Some(max)) => format!("at most {}", max),
+        (None, None) => "any".to_string(),
+    }
+}
+
+fn number(value: &SqlValue) -> Option<f64> {
+    match value {
+        SqlValue::Int(n) => Some(*n as f64),
+        SqlValue::BigInt(n) => Some(*n as f64),
+        SqlValue::SmallInt(n) => Some(*n as f64),
+        SqlValue::Float(n) => Some(*n as f64),
+        SqlValue::Double(n) => Some(*n),
+        #[cfg(feature = "decimal")]
+        SqlValue::Decimal(n) => rust_decimal::prelude::ToPrimitive::to_f64(n),
+        _ => None,
+    }
+}
+
+/// One `@` between a local part and a dotted domain, without whitespace. Deliberately loose;
+/// the only real check is sending it mail.
+fn is_email(text: &str) -> bool {
+    let Some((local, domain)) = text.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !domain.contains('@')
+        && !text.chars().any(char::is_whitespace)
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !domain.contains("..")
+}
+
+/// A column whose value failed one of its rules.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// Every rule a write failed, reported as [`Error::Validation`] with one message per field.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ValidationErrors {
+    errors: Vec<FieldError>,
+}
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            message: message.into(),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn errors(&self) -> &[FieldError] {
+        &self.errors
+    }
+
+    /// `Ok` when nothing failed.
+    pub fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, error) in self.errors.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{} {}", error.field, error.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<ValidationErrors> for Error {
+    fn from(errors: ValidationErrors) -> Self {
+        Error::Validation(errors.to_string())
+    }
+}
+
+/// Checks the values a model's builders are about to write, before any SQL is sent.
+/// `#[model]` implements it with the rules from its fields' `#[validate(...)]` attributes.
+pub trait Validate {
+    /// Rules by column name.
+    const RULES: &'static [(&'static str, Rule)] = &[];
+
+    /// Checks `(column, value)` pairs against `RULES`, collecting every failure.
+    fn validate<'a>(
+        values: impl IntoIterator<Item = (&'a str, &'a SqlValue)>,
+    ) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        for (column, value) in values {
+            for (_, rule) in Self::RULES.iter().filter(|(c, _)| *c == column) {
+                if let Err(message) = rule.check(value) {
+                    errors.add(column, message);
+                }
+            }
+        }
+        errors.into_result()
+    }
+}